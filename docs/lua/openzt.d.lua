@@ -0,0 +1,557 @@
+---@meta openzt
+-- Generated by `cargo test` from the registered Lua functions, do not edit
+
+---@class ZTEntity
+
+---@class ZTHabitat
+
+---@class ZooState
+
+events = {}
+
+world = {}
+
+---Adds cash to player's budget
+---
+---Capability: modify_economy
+---@param amount number
+---@return string?
+---@return string?
+function add_cash(amount) end
+
+---Show resource cache statistics
+---
+---Capability: read_world
+---@return string?
+---@return string?
+function cache_stats() end
+
+---Cancels a waiting job, returns false if there is none with that id
+---
+---Capability: core
+---@param id integer
+---@return boolean
+function cancel(id) end
+
+---Clicks the continue button
+---
+---Capability: modify_world
+function click_continue() end
+
+---Decrement reference count for a resource
+---
+---Capability: debug_memory
+---@param file_name string
+---@return string?
+---@return string?
+function decrement_ref(file_name) end
+
+---Enables/disables developer mode
+---
+---Capability: modify_economy
+---@param enabled boolean
+---@return string?
+---@return string?
+function enable_dev_mode(enabled) end
+
+---Calls handler for every event of a kind, such as "entity_placed"; higher priorities run first and returning false cancels
+---
+---Capability: read_world
+---@param name string
+---@param handler function
+---@param priority? integer
+---@return integer
+function events.subscribe(name, handler, priority) end
+
+---Removes a handler added by events.subscribe
+---
+---Capability: read_world
+---@param id integer
+---@return boolean
+function events.unsubscribe(id) end
+
+---Check if an extension has a specific tag
+---
+---Capability: read_world
+---@param extension_key string
+---@param tag string
+---@return string
+function extension_has_tag(extension_key, tag) end
+
+---Generates [patches] TOML that turns a loaded INI resource into an edited copy on disk
+---
+---Capability: modify_config
+---@param target string
+---@param modified_path string
+---@param output_path? string
+---@return string?
+---@return string?
+function generate_patches(target, modified_path, output_path) end
+
+---Returns BF resource manager details
+---
+---Capability: debug_memory
+---@return string?
+---@return string?
+function get_bfresourcemgr() end
+
+---Returns the currently active buy tab
+---
+---Capability: read_world
+---@return string?
+---@return string?
+function get_buy_tab() end
+
+---Returns current active expansion
+---
+---Capability: read_world
+---@return string?
+---@return string?
+function get_current_expansion() end
+
+---Returns current in-game date/time
+---
+---Capability: read_world
+---@return string?
+---@return string?
+function get_date() end
+
+---Returns UI element details by ID
+---
+---Capability: read_world
+---@param id integer
+---@return string?
+---@return string?
+function get_element(id) end
+
+---Returns unique entity type vtable entries at offset
+---
+---Capability: debug_memory
+---@param offset string
+---@return string?
+---@return string?
+function get_entity_type_vtable_entry(offset) end
+
+---Returns unique entity vtable entries at offset
+---
+---Capability: debug_memory
+---@param offset string
+---@return string?
+---@return string?
+function get_entity_vtable_entry(offset) end
+
+---Get extension data by extension key (e.g., 'animals.elephant')
+---
+---Capability: read_world
+---@param extension_key string
+---@return string
+---@return string
+function get_extension(extension_key) end
+
+---Get a specific attribute for an extension
+---
+---Capability: read_world
+---@param extension_key string
+---@param attribute_key string
+---@return string
+function get_extension_attribute(extension_key, attribute_key) end
+
+---Get extension data by base entity (e.g., 'legacy.animals.elephant')
+---
+---Capability: read_world
+---@param base string
+---@return string
+---@return string
+function get_extension_by_base(base) end
+
+---Get tags for an extension by key
+---
+---Capability: read_world
+---@param extension_key string
+---@return string
+function get_extension_tags(extension_key) end
+
+---Get a legacy entity attribute (name_id currently supported)
+---
+---Capability: read_world
+---@param entity_type string
+---@param entity_name string
+---@param ... string
+---@return string
+---@return string
+function get_legacy_attribute(entity_type, entity_name, ...) end
+
+---Lists expansion member sets
+---
+---Capability: read_world
+---@return string?
+---@return string?
+function get_members() end
+
+---Gets a mod option's current value, or lists all of a mod's options
+---
+---Capability: read_world
+---@param mod_id string
+---@param name? string
+---@return string?
+---@return string?
+function get_mod_option(mod_id, name) end
+
+---Get reference count for a resource
+---
+---Capability: read_world
+---@param file_name string
+---@return string?
+---@return string?
+function get_ref_count(file_name) end
+
+---Returns details of the currently selected entity
+---
+---Capability: read_world
+---@return string?
+---@return string?
+function get_selected_entity() end
+
+---Gets a setting value
+---
+---Capability: read_world
+---@param section string
+---@param key string
+---@return string?
+---@return string?
+function get_setting(section, key) end
+
+---Retrieves game string by ID (from OpenZT registry or game)
+---
+---Capability: read_world
+---@param id integer
+---@return string?
+---@return string?
+function get_string(id) end
+
+---Returns summary of all entity types
+---
+---Capability: read_world
+---@return string?
+---@return string?
+function get_types_summary() end
+
+---Returns world manager details
+---
+---Capability: debug_memory
+---@return string?
+---@return string?
+function get_zt_world_mgr() end
+
+---Returns ZTHabitatMgr debug info
+---
+---Capability: debug_memory
+---@return string?
+---@return string?
+function get_zthabitatmgr() end
+
+---Lists available Lua functions or searches by keyword
+---
+---Capability: core
+---@param search? string
+---@return string
+function help(search) end
+
+---Hide all entities tagged with 'roof'
+---
+---Capability: modify_world
+---@return string
+---@return string?
+function hide_roofs() end
+
+---Increment reference count for a resource
+---
+---Capability: debug_memory
+---@param file_name string
+---@return string?
+---@return string?
+function increment_ref(file_name) end
+
+---Lists the jobs waiting to be resumed
+---
+---Capability: core
+---@return string
+function jobs() end
+
+---Lists terrain type info
+---
+---Capability: read_world
+---@return string?
+---@return string?
+function list_bfterraintypeinfo() end
+
+---Lists the game functions OpenZT detours, optionally only those whose name or hook contains filter
+---
+---Capability: debug_memory
+---@param filter? string
+---@return string
+function list_detours(filter) end
+
+---Lists all entities in the world
+---
+---Capability: read_world
+---@return string?
+---@return string?
+function list_entities() end
+
+---Lists all entities in the world (alternate format)
+---
+---Capability: read_world
+---@return string?
+---@return string?
+function list_entities_2() end
+
+---Lists all zoo exhibits/habitats
+---
+---Capability: read_world
+---@return string?
+---@return string?
+function list_exhibits() end
+
+---Lists all loaded expansions
+---
+---Capability: read_world
+---@return string?
+---@return string?
+function list_expansion() end
+
+---List all extensions that have a specific tag
+---
+---Capability: read_world
+---@param tag string
+---@return string
+function list_extensions_with_tag(tag) end
+
+---List all legacy entities (optionally filtered by type)
+---
+---Capability: read_world
+---@param entity_type? string
+---@return string
+function list_legacy_entities(entity_type) end
+
+---List all available legacy entity types with counts
+---
+---Capability: read_world
+---@return string
+function list_legacy_types() end
+
+---Returns a table of registered Lua functions with their signatures
+---
+---Capability: core
+---@return table<string, string>[]
+function list_lua_functions() end
+
+---Lists all OpenZT location and habitat IDs
+---
+---Capability: read_world
+---@return string?
+---@return string?
+function list_openzt_locations_habitats() end
+
+---Lists all OpenZT mod IDs
+---
+---Capability: read_world
+---@return string?
+---@return string?
+function list_openzt_mods() end
+
+---Lists all OpenZT resource strings
+---
+---Capability: read_world
+---@return string?
+---@return string?
+function list_openzt_resource_strings() end
+
+---Lists the patches OpenZT makes to the game's code and whether each is applied
+---
+---Capability: debug_memory
+---@return string
+function list_patches() end
+
+---List all registered attributes (optionally filtered by entity type)
+---
+---Capability: read_world
+---@param entity_type? string
+---@return string
+function list_registered_attributes(entity_type) end
+
+---List all registered tags (optionally filtered by entity type)
+---
+---Capability: read_world
+---@param entity_type? string
+---@return string
+function list_registered_tags(entity_type) end
+
+---Lists resource strings, optionally filtered by prefix
+---
+---Capability: read_world
+---@param prefix? string
+---@return string?
+---@return string?
+function list_resource_strings(prefix) end
+
+---Lists all BF resource directories and files
+---
+---Capability: read_world
+---@return string?
+---@return string?
+function list_resources() end
+
+---Lists available settings, optionally filtered by category
+---
+---Capability: read_world
+---@param category? string
+---@return string?
+---@return string?
+function list_settings(category) end
+
+---Lists all entity types in the world
+---
+---Capability: read_world
+---@return string?
+---@return string?
+function list_types() end
+
+---Makes entity type selectable
+---
+---Capability: modify_world
+---@param id integer
+---@return string?
+---@return string?
+function make_sel(id) end
+
+---Shows which patches a mod applied, skipped or failed
+---
+---Capability: read_world
+---@param mod_id string
+---@return string?
+---@return string?
+function patch_report(mod_id) end
+
+---Saves ranges of game memory, given as {address, length} pairs, to a snapshot file for tests
+---
+---Capability: debug_memory
+---@param path string
+---@param ranges integer[][]
+---@return string?
+---@return string?
+function save_memory_snapshot(path, ranges) end
+
+---Gets selected entity type config, with optional key/value to set
+---
+---Capability: modify_world
+---@param ... string
+---@return string?
+---@return string?
+function sel_type(...) end
+
+---Enables or disables a detour, found by address (0x...), function name or hook path
+---
+---Capability: debug_memory
+---@param detour string
+---@param enabled boolean
+---@return string?
+---@return string?
+function set_detour_enabled(detour, enabled) end
+
+---Sets a mod option and saves it to openzt.toml (takes effect on next load)
+---
+---Capability: modify_config
+---@param mod_id string
+---@param name string
+---@param value string
+---@return string?
+---@return string?
+function set_mod_option(mod_id, name, value) end
+
+---Applies or restores the game's original code for a patch, by the name list_patches() shows
+---
+---Capability: debug_memory
+---@param name string
+---@param enabled boolean
+---@return string?
+---@return string?
+function set_patch_enabled(name, enabled) end
+
+---Sets a setting value
+---
+---Capability: modify_config
+---@param section string
+---@param key string
+---@param value string
+---@return string?
+---@return string?
+function set_setting(section, key, value) end
+
+---Runs a function as a job that can wait, returns the job id
+---
+---Capability: core
+---@param fn function
+---@param ... any
+---@return integer?
+function spawn(fn, ...) end
+
+---Calls a UI callback function
+---
+---Capability: modify_world
+---@param callback string
+---@return string?
+---@return string?
+function ui(callback) end
+
+---Unload all loaded resources to free memory
+---
+---Capability: modify_world
+---@return string?
+---@return string?
+function unload_resources() end
+
+---Suspends the running job for a number of in-game days
+---
+---Capability: core
+---@param n number
+function wait_game_days(n) end
+
+---Suspends the running job for a number of sim ticks
+---
+---Capability: core
+---@param n number
+function wait_ticks(n) end
+
+---Suspends the running job until predicate() returns true, checked every tick
+---
+---Capability: core
+---@param predicate function
+function wait_until(predicate) end
+
+---Iterates over entities in the world, optionally only those of a class such as "Animal"
+---
+---Capability: read_world
+---@param class? string
+---@return fun(): ZTEntity?
+function world.entities(class) end
+
+---Iterates over the zoo's exhibits and habitats
+---
+---Capability: read_world
+---@return fun(): ZTHabitat?
+function world.habitats() end
+
+---Returns the state saved with the zoo for a namespace (mod id)
+---
+---Capability: read_world
+---@param namespace string
+---@return ZooState
+function zoo_state(namespace) end
+
+---Returns zoo statistics
+---
+---Capability: read_world
+---@return string?
+---@return string?
+function zoostats() end
@@ -0,0 +1,145 @@
+//! `#[game_struct]` lays out structs that mirror game memory from declared offsets
+//!
+//! ```ignore
+//! #[game_struct]
+//! #[size(0x14)]
+//! #[derive(Debug)]
+//! #[repr(C)]
+//! pub struct Example {
+//!     vtable: u32,
+//!     #[offset(0x8)]
+//!     cash: f32,
+//!     flags: u32, // 0xc, follows cash
+//! }
+//! ```
+//!
+//! Padding is generated before every field with an `#[offset]` and after the last field up to
+//! the `#[size]`, and each of them is checked with a `const` assertion on `offset_of!` or
+//! `size_of`, so a wrong offset or field type is a compile error instead of corrupted reads.
+//! Fields without an `#[offset]` follow the previous field. `#[game_struct]` must come before
+//! the struct's derives so they see the generated padding.
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote, quote_spanned};
+use syn::{parse_macro_input, parse_quote, Attribute, Error, Field, Fields, ItemStruct, LitInt};
+
+#[proc_macro_attribute]
+pub fn game_struct(args: TokenStream, input: TokenStream) -> TokenStream {
+    if !args.is_empty() {
+        return Error::new(Span::call_site(), "game_struct takes no arguments, use #[size(...)] for the size")
+            .to_compile_error()
+            .into();
+    }
+    let item = parse_macro_input!(input as ItemStruct);
+    match layout(item) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+/// Removes the attribute `name` from `attrs` and parses its argument as an integer
+fn take_int_attr(attrs: &mut Vec<Attribute>, name: &str) -> syn::Result<Option<(LitInt, u64)>> {
+    let Some(index) = attrs.iter().position(|attr| attr.path().is_ident(name)) else {
+        return Ok(None);
+    };
+    let attr = attrs.remove(index);
+    let lit: LitInt = attr.parse_args()?;
+    let value = lit.base10_parse::<u64>()?;
+    if attrs.iter().any(|attr| attr.path().is_ident(name)) {
+        return Err(Error::new_spanned(attr, format!("duplicate #[{}]", name)));
+    }
+    Ok(Some((lit, value)))
+}
+
+fn layout(mut item: ItemStruct) -> syn::Result<TokenStream2> {
+    if !item.generics.params.is_empty() {
+        return Err(Error::new_spanned(&item.generics, "game_struct does not support generic structs"));
+    }
+    let mut is_repr_c = false;
+    for attr in item.attrs.iter().filter(|attr| attr.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            is_repr_c |= meta.path.is_ident("C");
+            // Skip arguments such as align(8)
+            if meta.input.peek(syn::token::Paren) {
+                let arguments;
+                syn::parenthesized!(arguments in meta.input);
+                arguments.parse::<TokenStream2>()?;
+            }
+            Ok(())
+        })?;
+    }
+    if !is_repr_c {
+        return Err(Error::new_spanned(&item.ident, "game_struct requires #[repr(C)]"));
+    }
+    let size = take_int_attr(&mut item.attrs, "size")?;
+
+    let Fields::Named(named) = &mut item.fields else {
+        return Err(Error::new_spanned(&item.ident, "game_struct requires a struct with named fields"));
+    };
+
+    let name = item.ident.clone();
+    let mut fields: Vec<Field> = Vec::new();
+    let mut assertions = Vec::new();
+    // End of the previous field, as an expression (None before the first field), and the last offset seen
+    let mut end: Option<TokenStream2> = None;
+    let mut last_offset: Option<u64> = None;
+    let mut pad_count = 0usize;
+
+    let mut add_padding = |fields: &mut Vec<Field>, to: &LitInt, end: &Option<TokenStream2>| {
+        let pad = format_ident!("_pad_{}", pad_count);
+        pad_count += 1;
+        let length = match end {
+            Some(end) => quote_spanned! { to.span()=> #to - (#end) },
+            None => quote_spanned! { to.span()=> #to },
+        };
+        fields.push(parse_quote! { #pad: [u8; #length] });
+    };
+
+    for mut field in std::mem::take(&mut named.named) {
+        let ident = field.ident.clone().expect("named field");
+        let ty = field.ty.clone();
+        match take_int_attr(&mut field.attrs, "offset")? {
+            Some((lit, offset)) => {
+                if last_offset.is_some_and(|last| offset <= last) {
+                    return Err(Error::new_spanned(&lit, "offsets must increase from field to field"));
+                }
+                // No padding before a first field at 0
+                if !(fields.is_empty() && offset == 0) {
+                    add_padding(&mut fields, &lit, &end);
+                }
+                let message = format!("{}::{} is not at offset {:#x}", name, ident, offset);
+                assertions.push(quote_spanned! { lit.span()=> assert!(::core::mem::offset_of!(#name, #ident) == #lit, #message); });
+                end = Some(quote! { #lit + ::core::mem::size_of::<#ty>() });
+                last_offset = Some(offset);
+            }
+            None => {
+                end = Some(match end {
+                    Some(end) => quote! { #end + ::core::mem::size_of::<#ty>() },
+                    None => quote! { ::core::mem::size_of::<#ty>() },
+                });
+            }
+        }
+        fields.push(field);
+    }
+
+    if let Some((lit, size)) = &size {
+        if last_offset.is_some_and(|last| *size <= last) {
+            return Err(Error::new_spanned(lit, "size must be past the last offset"));
+        }
+        add_padding(&mut fields, lit, &end);
+        let message = format!("{} is not {:#x} bytes", name, size);
+        assertions.push(quote_spanned! { lit.span()=> assert!(::core::mem::size_of::<#name>() == #lit, #message); });
+    }
+
+    named.named = fields.into_iter().collect();
+
+    Ok(quote! {
+        #item
+
+        const _: () = {
+            #(#assertions)*
+        };
+    })
+}
@@ -0,0 +1,62 @@
+#[cfg(test)]
+mod tests {
+    use std::mem::{offset_of, size_of};
+
+    use field_accessor_as_string::FieldAccessorAsString;
+    use field_accessor_as_string_trait::FieldAccessorAsStringTrait;
+    use game_struct_layout::game_struct;
+
+    #[game_struct]
+    #[size(0x20)]
+    #[derive(Debug, Default, FieldAccessorAsString)]
+    #[repr(C)]
+    struct Mirror {
+        vtable: u32,
+        #[offset(0x8)]
+        cash: f32,
+        flags: u32,
+        #[offset(0x14)]
+        id: u16,
+        byte: u8,
+    }
+
+    #[game_struct]
+    #[derive(Default)]
+    #[repr(C, align(4))]
+    struct Unsized {
+        #[offset(0x4)]
+        value: u32,
+        #[offset(0x10)]
+        array: [u8; 3],
+    }
+
+    #[test]
+    fn test_offsets_and_size() {
+        assert_eq!(offset_of!(Mirror, vtable), 0);
+        assert_eq!(offset_of!(Mirror, cash), 0x8);
+        assert_eq!(offset_of!(Mirror, flags), 0xc);
+        assert_eq!(offset_of!(Mirror, id), 0x14);
+        assert_eq!(offset_of!(Mirror, byte), 0x16);
+        assert_eq!(size_of::<Mirror>(), 0x20);
+    }
+
+    #[test]
+    fn test_without_size() {
+        assert_eq!(offset_of!(Unsized, value), 0x4);
+        assert_eq!(offset_of!(Unsized, array), 0x10);
+        // Only rounded up to the alignment
+        assert_eq!(size_of::<Unsized>(), 0x14);
+        let unsized_value = Unsized { value: 1, array: [2; 3], ..Default::default() };
+        assert_eq!((unsized_value.value, unsized_value.array), (1, [2; 3]));
+    }
+
+    #[test]
+    fn test_derives_see_the_fields() {
+        let mut mirror = Mirror::default();
+        mirror.set_field("cash", "12.5").unwrap();
+        assert_eq!(mirror.cash, 12.5);
+        assert_eq!(mirror.get_field("id"), Ok("0".to_string()));
+        assert!(!mirror.is_field("_pad_0"));
+        assert!(format!("{:?}", mirror).contains("cash: 12.5"));
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    io::{self, BufReader},
+    net::{TcpStream, ToSocketAddrs},
+};
+
+use serde_json::Value;
+
+use crate::{
+    frame::{read_frame, write_frame, Request, Response},
+    jsonrpc::{RpcError, RpcRequest, RpcResponse},
+};
+
+/// A connection to the game's Lua console
+pub struct ConsoleClient {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    next_id: u64,
+}
+
+impl ConsoleClient {
+    pub fn connect(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let writer = TcpStream::connect(address)?;
+        let reader = BufReader::new(writer.try_clone()?);
+        Ok(ConsoleClient { reader, writer, next_id: 1 })
+    }
+
+    /// Send Lua code without waiting for the result
+    ///
+    /// # Returns
+    /// * `Ok(u64)` - The request ID its responses will carry
+    pub fn send(&mut self, code: &str) -> io::Result<u64> {
+        let id = self.next_id;
+        self.next_id += 1;
+        write_frame(&mut self.writer, &Request { id, code: code.to_string() })?;
+        Ok(id)
+    }
+
+    /// Read the next response frame, `Ok(None)` if the server closed the connection
+    pub fn read_response(&mut self) -> io::Result<Option<Response>> {
+        read_frame(&mut self.reader)
+    }
+
+    /// Execute Lua code and wait for its complete result
+    ///
+    /// # Returns
+    /// * `Ok(Ok(String))` - The full output, with any streamed parts joined
+    /// * `Ok(Err(String))` - The error reported by the game
+    /// * `Err` - The connection failed
+    pub fn execute(&mut self, code: &str) -> io::Result<Result<String, String>> {
+        let mut output = String::new();
+        let result = self.execute_with(code, |part| output.push_str(part))?;
+        Ok(result.map(|last| {
+            output.push_str(&last);
+            output
+        }))
+    }
+
+    /// Execute Lua code, passing each streamed part to `on_partial` as it arrives
+    ///
+    /// # Returns
+    /// * `Ok(Ok(String))` - The data in the final frame
+    /// * `Ok(Err(String))` - The error reported by the game
+    /// * `Err` - The connection failed
+    pub fn execute_with(&mut self, code: &str, mut on_partial: impl FnMut(&str)) -> io::Result<Result<String, String>> {
+        let id = self.send(code)?;
+        loop {
+            let Some(response) = self.read_response()? else {
+                return Err(io::Error::new(io::ErrorKind::ConnectionAborted, "Server closed the connection"));
+            };
+            // Errors for unparseable frames carry id 0 and belong to no request
+            if response.id() != id {
+                continue;
+            }
+            match response {
+                Response::Partial { data, .. } => on_partial(&data),
+                Response::Ok { data, .. } => return Ok(Ok(data)),
+                Response::Error { message, .. } => return Ok(Err(message)),
+            }
+        }
+    }
+
+    /// Execute Lua code through the JSON-RPC endpoint and wait for its structured result
+    ///
+    /// # Returns
+    /// * `Ok(Ok(Value))` - The value returned by the Lua chunk, as JSON
+    /// * `Ok(Err(RpcError))` - The error reported by the game (Lua errors carry a traceback in `data`)
+    /// * `Err` - The connection failed
+    pub fn execute_json(&mut self, code: &str) -> io::Result<Result<Value, RpcError>> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = RpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "execute".to_string(),
+            params: Some(serde_json::json!({ "code": code })),
+            id: Some(Value::from(id)),
+        };
+        write_frame(&mut self.writer, &request)?;
+
+        loop {
+            let Some(frame) = read_frame::<_, Value>(&mut self.reader)? else {
+                return Err(io::Error::new(io::ErrorKind::ConnectionAborted, "Server closed the connection"));
+            };
+            if frame.get("id") != Some(&Value::from(id)) {
+                continue;
+            }
+            let response: RpcResponse = serde_json::from_value(frame).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            return Ok(match (response.result, response.error) {
+                (_, Some(error)) => Err(error),
+                (result, None) => Ok(result.unwrap_or(Value::Null)),
+            });
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::io::{self, BufRead, Read, Write};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Largest frame either side will accept, in bytes
+pub const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// Results longer than this are streamed as several frames
+pub const CHUNK_LEN: usize = 16 * 1024;
+
+/// Lua code to execute, sent by the client
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Request {
+    /// Chosen by the client and echoed in every response to this request
+    pub id: u64,
+    pub code: String,
+}
+
+/// A response frame, sent by the server
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Response {
+    /// Part of the output; more frames follow for this request
+    Partial { id: u64, data: String },
+    /// Final frame, the request succeeded
+    Ok { id: u64, data: String },
+    /// Final frame, the request failed
+    Error { id: u64, message: String },
+}
+
+impl Response {
+    /// ID of the request this frame answers (0 if the request couldn't be parsed)
+    pub fn id(&self) -> u64 {
+        match self {
+            Response::Partial { id, .. } | Response::Ok { id, .. } | Response::Error { id, .. } => *id,
+        }
+    }
+
+    /// Whether this is the last frame for its request
+    pub fn is_final(&self) -> bool {
+        !matches!(self, Response::Partial { .. })
+    }
+}
+
+/// Write one message as a single line of JSON
+pub fn write_frame<W: Write, T: Serialize>(writer: &mut W, message: &T) -> io::Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    writer.write_all(&line)?;
+    writer.flush()
+}
+
+/// Read one message
+///
+/// # Returns
+/// * `Ok(Some(T))` - The next message
+/// * `Ok(None)` - The peer closed the connection
+/// * `Err` with kind `InvalidData` - The frame was too long or not a valid message; the
+///   stream is still positioned at the start of the next frame
+/// * `Err` - Any other I/O error
+pub fn read_frame<R: BufRead, T: DeserializeOwned>(reader: &mut R) -> io::Result<Option<T>> {
+    let mut line = Vec::new();
+    let read = reader.by_ref().take(MAX_FRAME_LEN as u64 + 1).read_until(b'\n', &mut line)?;
+    if read == 0 {
+        return Ok(None);
+    }
+
+    if line.last() != Some(&b'\n') {
+        if line.len() > MAX_FRAME_LEN {
+            // Skip the rest of the oversized frame so the next read starts cleanly
+            let mut discard = Vec::new();
+            reader.read_until(b'\n', &mut discard)?;
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Frame exceeds {} bytes", MAX_FRAME_LEN)));
+        }
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed mid-frame"));
+    }
+
+    serde_json::from_slice(&line)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid frame: {}", e)))
+}
+
+/// Split a result into frames of at most `CHUNK_LEN` bytes, never splitting a character
+pub fn split_chunks(data: &str) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = data;
+    while rest.len() > CHUNK_LEN {
+        let mut end = CHUNK_LEN;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks.push(rest);
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip_multiline_code() {
+        let request = Request {
+            id: 7,
+            code: "for i = 1, 3 do\n  print(i)\nend".to_string(),
+        };
+        let mut buffer = Vec::new();
+        write_frame(&mut buffer, &request).unwrap();
+        write_frame(&mut buffer, &Response::Ok { id: 7, data: "done".to_string() }).unwrap();
+
+        // Embedded newlines are escaped, so each message is exactly one line
+        assert_eq!(buffer.iter().filter(|b| **b == b'\n').count(), 2);
+
+        let mut reader = Cursor::new(buffer);
+        assert_eq!(read_frame::<_, Request>(&mut reader).unwrap(), Some(request));
+        assert_eq!(read_frame::<_, Response>(&mut reader).unwrap(), Some(Response::Ok { id: 7, data: "done".to_string() }));
+        assert_eq!(read_frame::<_, Response>(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid_frame_keeps_stream_in_sync() {
+        let mut reader = Cursor::new(b"not json\n{\"id\":2,\"code\":\"x\"}\n".to_vec());
+        let err = read_frame::<_, Request>(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(read_frame::<_, Request>(&mut reader).unwrap().unwrap().id, 2);
+    }
+
+    #[test]
+    fn test_response_wire_format() {
+        let json = serde_json::to_string(&Response::Error { id: 3, message: "boom".to_string() }).unwrap();
+        assert_eq!(json, r#"{"status":"error","id":3,"message":"boom"}"#);
+    }
+
+    #[test]
+    fn test_split_chunks_respects_char_boundaries() {
+        let data = "é".repeat(CHUNK_LEN);
+        let chunks = split_chunks(&data);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= CHUNK_LEN));
+        assert_eq!(chunks.concat(), data);
+
+        assert_eq!(split_chunks(""), vec![""]);
+    }
+}
@@ -0,0 +1,177 @@
+//! JSON-RPC 2.0 endpoint, served on the same socket as the raw console protocol
+//!
+//! A frame with a `"jsonrpc"` member is treated as a JSON-RPC request. The only method
+//! is `execute`, taking the Lua code as `{"code": "..."}` or `["..."]` and returning the
+//! result of the chunk as structured JSON:
+//!
+//! ```text
+//! > {"jsonrpc":"2.0","id":1,"method":"execute","params":{"code":"return {1, 2, x = true}"}}
+//! < {"jsonrpc":"2.0","id":1,"result":{"1":1,"2":2,"x":true}}
+//! ```
+//!
+//! Lua errors are reported with code [`EXECUTION_ERROR`] and `{"traceback": ...}` as data.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// Server-defined error: the Lua code raised an error
+pub const EXECUTION_ERROR: i64 = -32000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RpcRequest {
+    pub jsonrpc: String,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+    /// Absent for notifications, which get no response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RpcResponse {
+    pub jsonrpc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+    pub id: Value,
+}
+
+impl RpcResponse {
+    pub fn result(id: Value, result: Value) -> Self {
+        RpcResponse {
+            jsonrpc: "2.0".to_string(),
+            result: Some(result),
+            error: None,
+            id,
+        }
+    }
+
+    pub fn error(id: Value, error: RpcError) -> Self {
+        RpcResponse {
+            jsonrpc: "2.0".to_string(),
+            result: None,
+            error: Some(error),
+            id,
+        }
+    }
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+/// A Lua error, as reported to structured clients
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecuteError {
+    pub message: String,
+    pub traceback: Option<String>,
+}
+
+impl From<ExecuteError> for RpcError {
+    fn from(err: ExecuteError) -> Self {
+        RpcError {
+            code: EXECUTION_ERROR,
+            message: err.message,
+            data: err.traceback.map(|traceback| serde_json::json!({ "traceback": traceback })),
+        }
+    }
+}
+
+/// Validate a JSON-RPC request and extract the Lua code to execute
+///
+/// # Returns
+/// * `Ok((id, code))` - `id` is `None` for notifications
+/// * `Err(RpcResponse)` - The error response to send back
+pub fn parse_execute_request(frame: Value) -> Result<(Option<Value>, String), RpcResponse> {
+    // The id is echoed in errors whenever it can be recovered
+    let id = frame.get("id").cloned();
+    let request: RpcRequest = serde_json::from_value(frame)
+        .map_err(|e| RpcResponse::error(id.clone().unwrap_or(Value::Null), RpcError::new(INVALID_REQUEST, format!("Invalid request: {}", e))))?;
+    let error = |code, message: String| RpcResponse::error(request.id.clone().unwrap_or(Value::Null), RpcError::new(code, message));
+
+    if request.jsonrpc != "2.0" {
+        return Err(error(INVALID_REQUEST, format!("Unsupported jsonrpc version '{}'", request.jsonrpc)));
+    }
+    if request.method != "execute" {
+        return Err(error(METHOD_NOT_FOUND, format!("Method '{}' not found", request.method)));
+    }
+
+    let code = match &request.params {
+        Some(Value::Object(params)) => params.get("code").and_then(Value::as_str),
+        Some(Value::Array(params)) if params.len() == 1 => params[0].as_str(),
+        _ => None,
+    };
+    match code {
+        Some(code) => Ok((request.id.clone(), code.to_string())),
+        None => Err(error(INVALID_PARAMS, "execute expects params {\"code\": string} or [string]".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_execute_request() {
+        let (id, code) = parse_execute_request(json!({"jsonrpc": "2.0", "id": "a", "method": "execute", "params": {"code": "return 1"}})).unwrap();
+        assert_eq!(id, Some(json!("a")));
+        assert_eq!(code, "return 1");
+
+        let (id, code) = parse_execute_request(json!({"jsonrpc": "2.0", "method": "execute", "params": ["help()"]})).unwrap();
+        assert_eq!(id, None);
+        assert_eq!(code, "help()");
+    }
+
+    #[test]
+    fn test_parse_execute_request_errors() {
+        let err = parse_execute_request(json!({"jsonrpc": "2.0", "id": 4, "method": "eval", "params": ["x"]})).unwrap_err();
+        assert_eq!(err.id, json!(4));
+        assert_eq!(err.error.unwrap().code, METHOD_NOT_FOUND);
+
+        let err = parse_execute_request(json!({"jsonrpc": "2.0", "id": 5, "method": "execute", "params": {"lua": "x"}})).unwrap_err();
+        assert_eq!(err.error.unwrap().code, INVALID_PARAMS);
+
+        let err = parse_execute_request(json!({"jsonrpc": "1.0", "id": 6, "method": "execute"})).unwrap_err();
+        assert_eq!(err.error.unwrap().code, INVALID_REQUEST);
+
+        let err = parse_execute_request(json!({"jsonrpc": "2.0", "id": 7})).unwrap_err();
+        assert_eq!(err.id, json!(7));
+        assert_eq!(err.error.unwrap().code, INVALID_REQUEST);
+    }
+
+    #[test]
+    fn test_execute_error_wire_format() {
+        let error: RpcError = ExecuteError {
+            message: "boom".to_string(),
+            traceback: Some("stack traceback:\n\t[C]: in ?".to_string()),
+        }
+        .into();
+        let response = RpcResponse::error(json!(1), error);
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({"jsonrpc": "2.0", "error": {"code": -32000, "message": "boom", "data": {"traceback": "stack traceback:\n\t[C]: in ?"}}, "id": 1})
+        );
+    }
+}
@@ -0,0 +1,31 @@
+//! Wire protocol for the OpenZT Lua console
+//!
+//! Messages are newline-delimited JSON. A client sends [`Request`]s tagged with an ID of
+//! its choosing; the server answers each one with zero or more [`Response::Partial`]
+//! frames followed by exactly one final [`Response::Ok`] or [`Response::Error`] frame
+//! carrying the same ID. Requests from one client are executed in the order they were
+//! sent; requests from different clients are interleaved fairly.
+//!
+//! The same socket also accepts JSON-RPC 2.0 requests, which get structured JSON
+//! results instead of text (see [`jsonrpc`]).
+//!
+//! ```text
+//! > {"id":1,"code":"help()"}
+//! < {"status":"partial","id":1,"data":"..."}
+//! < {"status":"ok","id":1,"data":"..."}
+//! ```
+
+pub mod client;
+pub mod frame;
+pub mod jsonrpc;
+pub mod server;
+
+pub use client::ConsoleClient;
+pub use frame::{Request, Response};
+pub use jsonrpc::ExecuteError;
+pub use server::{ConsoleServer, PendingRequest, RequestId};
+
+/// Address the game listens on
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:8080";
+pub const DEFAULT_HOST: &str = "127.0.0.1";
+pub const DEFAULT_PORT: u16 = 8080;
@@ -0,0 +1,473 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    io::{self, BufReader},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::Duration,
+};
+
+use serde_json::Value;
+use tracing::error;
+
+use crate::{
+    frame::{read_frame, split_chunks, write_frame, Request, Response},
+    jsonrpc::{parse_execute_request, ExecuteError, RpcError, RpcResponse, PARSE_ERROR},
+};
+
+pub type ClientId = u64;
+
+/// Accepts console clients and queues their requests for an executor
+///
+/// The executor pulls requests with [`ConsoleServer::next_request`] (or
+/// [`ConsoleServer::wait_request`]) on whichever thread runs the Lua code, and answers
+/// them through the returned [`PendingRequest`]. Each client has its own FIFO queue and
+/// clients are served round-robin, so one busy client can't starve the others.
+#[derive(Clone, Default)]
+pub struct ConsoleServer {
+    shared: Arc<Shared>,
+}
+
+#[derive(Default)]
+struct Shared {
+    queues: Mutex<Queues>,
+    available: Condvar,
+}
+
+#[derive(Default)]
+struct Queues {
+    next_client: ClientId,
+    /// Round-robin order of connected clients
+    order: VecDeque<ClientId>,
+    pending: HashMap<ClientId, VecDeque<PendingRequest>>,
+}
+
+impl ConsoleServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bind to `address` and accept clients until the listener fails
+    ///
+    /// Blocks the calling thread; each client is handled on its own thread.
+    pub fn listen(&self, address: impl ToSocketAddrs) -> io::Result<()> {
+        let listener = TcpListener::bind(address)?;
+        self.serve(listener);
+        Ok(())
+    }
+
+    /// Accept clients from an already bound listener
+    pub fn serve(&self, listener: TcpListener) {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let server = self.clone();
+                    thread::spawn(move || {
+                        if let Err(err) = server.handle_client(stream) {
+                            error!("Console client error: {}", err);
+                        }
+                    });
+                }
+                Err(err) => error!("Error accepting console connection: {}", err),
+            }
+        }
+    }
+
+    /// Take the next request without blocking
+    pub fn next_request(&self) -> Option<PendingRequest> {
+        self.shared.queues.lock().unwrap().pop_next()
+    }
+
+    /// Take the next request, waiting up to `timeout` for one to arrive
+    pub fn wait_request(&self, timeout: Duration) -> Option<PendingRequest> {
+        let queues = self.shared.queues.lock().unwrap();
+        let (mut queues, _) = self
+            .shared
+            .available
+            .wait_timeout_while(queues, timeout, |queues| !queues.has_pending())
+            .unwrap();
+        queues.pop_next()
+    }
+
+    /// Number of requests waiting to be executed, across all clients
+    pub fn pending_count(&self) -> usize {
+        self.shared.queues.lock().unwrap().pending.values().map(VecDeque::len).sum()
+    }
+
+    fn handle_client(&self, stream: TcpStream) -> io::Result<()> {
+        let writer = Arc::new(Mutex::new(stream.try_clone()?));
+        let client = self.shared.queues.lock().unwrap().connect();
+        let mut reader = BufReader::new(stream);
+
+        // Once a client speaks JSON-RPC, unparseable frames get JSON-RPC errors too
+        let mut rpc_client = false;
+
+        let result = loop {
+            let frame = match read_frame::<_, Value>(&mut reader) {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break Ok(()),
+                Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                    let sent = if rpc_client {
+                        write_frame(&mut *writer.lock().unwrap(), &RpcResponse::error(Value::Null, RpcError::new(PARSE_ERROR, err.to_string())))
+                    } else {
+                        write_frame(&mut *writer.lock().unwrap(), &Response::Error { id: 0, message: err.to_string() })
+                    };
+                    if let Err(err) = sent {
+                        break Err(err);
+                    }
+                    continue;
+                }
+                Err(err) => break Err(err),
+            };
+
+            let parsed = if frame.get("jsonrpc").is_some() {
+                rpc_client = true;
+                parse_execute_request(frame).map(|(id, code)| (RequestId::Rpc(id), code)).map_err(|response| serde_json::to_value(response).unwrap_or_default())
+            } else {
+                serde_json::from_value::<Request>(frame)
+                    .map(|request| (RequestId::Raw(request.id), request.code))
+                    .map_err(|e| serde_json::to_value(Response::Error { id: 0, message: format!("Invalid frame: {}", e) }).unwrap_or_default())
+            };
+
+            match parsed {
+                Ok((id, code)) => {
+                    let pending = PendingRequest {
+                        client,
+                        id,
+                        code,
+                        writer: writer.clone(),
+                    };
+                    self.shared.queues.lock().unwrap().push(pending);
+                    self.shared.available.notify_one();
+                }
+                Err(response) => {
+                    if let Err(err) = write_frame(&mut *writer.lock().unwrap(), &response) {
+                        break Err(err);
+                    }
+                }
+            }
+        };
+
+        // Requests from a disconnected client are dropped unexecuted
+        self.shared.queues.lock().unwrap().disconnect(client);
+        result
+    }
+}
+
+impl Queues {
+    fn connect(&mut self) -> ClientId {
+        self.next_client += 1;
+        let client = self.next_client;
+        self.order.push_back(client);
+        self.pending.insert(client, VecDeque::new());
+        client
+    }
+
+    fn disconnect(&mut self, client: ClientId) {
+        self.order.retain(|id| *id != client);
+        self.pending.remove(&client);
+    }
+
+    fn push(&mut self, request: PendingRequest) {
+        if let Some(queue) = self.pending.get_mut(&request.client) {
+            queue.push_back(request);
+        }
+    }
+
+    fn has_pending(&self) -> bool {
+        self.pending.values().any(|queue| !queue.is_empty())
+    }
+
+    fn pop_next(&mut self) -> Option<PendingRequest> {
+        for _ in 0..self.order.len() {
+            let client = self.order.pop_front()?;
+            self.order.push_back(client);
+            if let Some(request) = self.pending.get_mut(&client).and_then(VecDeque::pop_front) {
+                return Some(request);
+            }
+        }
+        None
+    }
+}
+
+/// How a request was sent, and so how it must be answered
+#[derive(Debug, Clone, PartialEq)]
+pub enum RequestId {
+    /// Raw console protocol request
+    Raw(u64),
+    /// JSON-RPC request, `None` for notifications
+    Rpc(Option<Value>),
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RequestId::Raw(id) => write!(f, "{}", id),
+            RequestId::Rpc(Some(id)) => write!(f, "rpc {}", id),
+            RequestId::Rpc(None) => write!(f, "rpc notification"),
+        }
+    }
+}
+
+/// A request taken from a client's queue, answered through its methods
+///
+/// Raw requests should be answered with [`PendingRequest::finish`] and JSON-RPC requests
+/// with [`PendingRequest::finish_structured`] (see [`PendingRequest::is_structured`]);
+/// either method works for both, converting the result as needed. Dropping a request
+/// without finishing it leaves the client waiting.
+pub struct PendingRequest {
+    client: ClientId,
+    id: RequestId,
+    code: String,
+    writer: Arc<Mutex<TcpStream>>,
+}
+
+impl PendingRequest {
+    pub fn id(&self) -> &RequestId {
+        &self.id
+    }
+
+    pub fn client(&self) -> ClientId {
+        self.client
+    }
+
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// Whether the client expects a structured (JSON) result
+    pub fn is_structured(&self) -> bool {
+        matches!(self.id, RequestId::Rpc(_))
+    }
+
+    /// Send the final result, streaming it in several frames if it is long
+    pub fn finish(self, result: Result<String, String>) -> io::Result<()> {
+        let RequestId::Raw(id) = self.id else {
+            return self.finish_structured(result.map(Value::String).map_err(|message| ExecuteError { message, traceback: None }));
+        };
+        let mut writer = self.writer.lock().unwrap();
+        match result {
+            Ok(data) => {
+                let chunks = split_chunks(&data);
+                let (last, parts) = chunks.split_last().expect("split_chunks returns at least one chunk");
+                for part in parts {
+                    write_frame(&mut *writer, &Response::Partial { id, data: part.to_string() })?;
+                }
+                write_frame(&mut *writer, &Response::Ok { id, data: last.to_string() })
+            }
+            Err(message) => write_frame(&mut *writer, &Response::Error { id, message }),
+        }
+    }
+
+    /// Send a structured result
+    ///
+    /// Raw clients receive the value as pretty-printed JSON text, and errors with their
+    /// traceback appended.
+    pub fn finish_structured(self, result: Result<Value, ExecuteError>) -> io::Result<()> {
+        let RequestId::Rpc(id) = &self.id else {
+            let result = match result {
+                Ok(value) => Ok(serde_json::to_string_pretty(&value).unwrap_or_default()),
+                Err(err) => Err(match err.traceback {
+                    Some(traceback) => format!("{}\n{}", err.message, traceback),
+                    None => err.message,
+                }),
+            };
+            return self.finish(result);
+        };
+        // Notifications get no response
+        let Some(id) = id.clone() else {
+            return Ok(());
+        };
+        let response = match result {
+            Ok(value) => RpcResponse::result(id, value),
+            Err(err) => RpcResponse::error(id, err.into()),
+        };
+        write_frame(&mut *self.writer.lock().unwrap(), &response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, net::SocketAddr, thread::JoinHandle, time::Instant};
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{client::ConsoleClient, frame::CHUNK_LEN};
+
+    /// Start a server on an ephemeral port with a stub executor thread
+    ///
+    /// The stub echoes the code back, fails on "error", and returns a result longer than one
+    /// frame for "big". Structured requests get the
+    /// code back as `{"echo": code}`.
+    fn start_stub_server() -> (SocketAddr, ConsoleServer, JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = ConsoleServer::new();
+
+        let accept = server.clone();
+        thread::spawn(move || accept.serve(listener));
+
+        let executor = server.clone();
+        let handle = thread::spawn(move || loop {
+            let Some(request) = executor.wait_request(Duration::from_secs(5)) else {
+                return;
+            };
+            if request.is_structured() {
+                let result = match request.code() {
+                    "error" => Err(ExecuteError {
+                        message: "boom".to_string(),
+                        traceback: Some("stack traceback:\n\t[C]: in ?".to_string()),
+                    }),
+                    code => Ok(json!({ "echo": code })),
+                };
+                request.finish_structured(result).unwrap();
+                continue;
+            }
+            let result = match request.code() {
+                "error" => Err("Lua error: boom".to_string()),
+                "big" => Ok("x".repeat(CHUNK_LEN * 2 + 10)),
+                code => Ok(format!("echo: {}", code)),
+            };
+            request.finish(result).unwrap();
+        });
+
+        (address, server, handle)
+    }
+
+    #[test]
+    fn test_result_and_error_status() {
+        let (address, _server, _executor) = start_stub_server();
+        let mut client = ConsoleClient::connect(address).unwrap();
+
+        assert_eq!(client.execute("return 1").unwrap(), Ok("echo: return 1".to_string()));
+        assert_eq!(client.execute("error").unwrap(), Err("Lua error: boom".to_string()));
+    }
+
+    #[test]
+    fn test_long_script_and_streamed_results() {
+        let (address, _server, _executor) = start_stub_server();
+        let mut client = ConsoleClient::connect(address).unwrap();
+
+        // Longer than the old 1 KB read buffer, with newlines
+        let script = "x = 1\n".repeat(1000);
+        assert_eq!(client.execute(&script).unwrap(), Ok(format!("echo: {}", script)));
+
+        let mut parts = 0;
+        let result = client.execute_with("big", |_| parts += 1).unwrap();
+        assert_eq!(parts, 2);
+        assert_eq!(result.unwrap().len(), 10);
+
+        assert_eq!(client.execute("big").unwrap().unwrap().len(), CHUNK_LEN * 2 + 10);
+    }
+
+    #[test]
+    fn test_pipelined_requests_are_fifo() {
+        let (address, _server, _executor) = start_stub_server();
+        let mut client = ConsoleClient::connect(address).unwrap();
+
+        let ids: Vec<u64> = (0..5).map(|i| client.send(&format!("cmd {}", i)).unwrap()).collect();
+        for (i, id) in ids.into_iter().enumerate() {
+            let response = client.read_response().unwrap().unwrap();
+            assert_eq!(response, Response::Ok { id, data: format!("echo: cmd {}", i) });
+        }
+    }
+
+    #[test]
+    fn test_concurrent_clients_get_their_own_results() {
+        let (address, _server, _executor) = start_stub_server();
+
+        let clients: Vec<_> = (0..4)
+            .map(|c| {
+                thread::spawn(move || {
+                    let mut client = ConsoleClient::connect(address).unwrap();
+                    for i in 0..25 {
+                        let code = format!("client {} request {}", c, i);
+                        assert_eq!(client.execute(&code).unwrap(), Ok(format!("echo: {}", code)));
+                    }
+                })
+            })
+            .collect();
+
+        for client in clients {
+            client.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_invalid_frame_gets_error_response() {
+        let (address, _server, _executor) = start_stub_server();
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(b"print('not framed')\n").unwrap();
+
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let response: Response = read_frame(&mut reader).unwrap().unwrap();
+        assert!(matches!(response, Response::Error { id: 0, .. }));
+
+        // The connection is still usable afterwards
+        write_frame(&mut stream, &Request { id: 9, code: "ok".to_string() }).unwrap();
+        let response: Response = read_frame(&mut reader).unwrap().unwrap();
+        assert_eq!(response, Response::Ok { id: 9, data: "echo: ok".to_string() });
+    }
+
+    #[test]
+    fn test_clients_are_served_round_robin() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = ConsoleServer::new();
+        let accept = server.clone();
+        thread::spawn(move || accept.serve(listener));
+
+        // No executor yet, so both clients' requests queue up
+        let mut first = ConsoleClient::connect(address).unwrap();
+        let mut second = ConsoleClient::connect(address).unwrap();
+        for i in 0..3 {
+            first.send(&format!("a{}", i)).unwrap();
+        }
+        second.send("b0").unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while server.pending_count() < 4 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        let order: Vec<String> = (0..4).map(|_| server.next_request().unwrap().code().to_string()).collect();
+        assert_eq!(&order[2..], ["a1", "a2"]);
+        assert!(order[..2].contains(&"a0".to_string()) && order[..2].contains(&"b0".to_string()));
+        assert!(server.next_request().is_none());
+    }
+
+    #[test]
+    fn test_json_rpc_alongside_raw_requests() {
+        let (address, _server, _executor) = start_stub_server();
+        let mut client = ConsoleClient::connect(address).unwrap();
+
+        assert_eq!(client.execute_json("return 1").unwrap(), Ok(json!({ "echo": "return 1" })));
+        assert_eq!(client.execute("return 1").unwrap(), Ok("echo: return 1".to_string()));
+
+        let error = client.execute_json("error").unwrap().unwrap_err();
+        assert_eq!(error.code, crate::jsonrpc::EXECUTION_ERROR);
+        assert_eq!(error.message, "boom");
+        assert_eq!(error.data, Some(json!({ "traceback": "stack traceback:\n\t[C]: in ?" })));
+    }
+
+    #[test]
+    fn test_json_rpc_errors_and_notifications() {
+        let (address, _server, _executor) = start_stub_server();
+        let mut stream = TcpStream::connect(address).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        // A notification is executed but gets no response, so the next frame answers id 2
+        write_frame(&mut stream, &json!({"jsonrpc": "2.0", "method": "execute", "params": ["x"]})).unwrap();
+        write_frame(&mut stream, &json!({"jsonrpc": "2.0", "id": 2, "method": "nope"})).unwrap();
+        let response: RpcResponse = read_frame(&mut reader).unwrap().unwrap();
+        assert_eq!(response.id, json!(2));
+        assert_eq!(response.error.unwrap().code, crate::jsonrpc::METHOD_NOT_FOUND);
+
+        stream.write_all(b"{broken\n").unwrap();
+        let response: RpcResponse = read_frame(&mut reader).unwrap().unwrap();
+        assert_eq!(response.id, Value::Null);
+        assert_eq!(response.error.unwrap().code, PARSE_ERROR);
+    }
+}
@@ -0,0 +1,136 @@
+//! Detects whether Lua input is an incomplete chunk, so the REPL can keep reading lines
+
+/// Whether `code` is an unfinished Lua chunk
+///
+/// Counts block keywords (`function`, `if`, `do`, `repeat` against `end`/`until`) and
+/// brackets, skipping strings and comments. Unclosed long strings and comments are
+/// incomplete. Anything else, including syntax errors, counts as complete so the game
+/// can report the error.
+pub fn is_incomplete(code: &str) -> bool {
+    let bytes = code.as_bytes();
+    let mut depth: i32 = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                i += 2;
+                if let Some((level, start)) = long_bracket(bytes, i) {
+                    match long_bracket_end(bytes, start, level) {
+                        Some(end) => i = end,
+                        None => return true,
+                    }
+                } else {
+                    while i < bytes.len() && bytes[i] != b'\n' {
+                        i += 1;
+                    }
+                }
+            }
+            quote @ (b'"' | b'\'') => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    match bytes[i] {
+                        b'\\' if i + 1 == bytes.len() => return true,
+                        b'\\' => i += 2,
+                        // An unescaped newline ends the string with a syntax error
+                        b'\n' => break,
+                        _ => i += 1,
+                    }
+                }
+                i += 1;
+            }
+            b'[' => match long_bracket(bytes, i) {
+                Some((level, start)) => match long_bracket_end(bytes, start, level) {
+                    Some(end) => i = end,
+                    None => return true,
+                },
+                None => {
+                    depth += 1;
+                    i += 1;
+                }
+            },
+            b'(' | b'{' => {
+                depth += 1;
+                i += 1;
+            }
+            b')' | b'}' | b']' => {
+                depth -= 1;
+                i += 1;
+            }
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                let start = i;
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                match &code[start..i] {
+                    "function" | "if" | "do" | "repeat" => depth += 1,
+                    "end" | "until" => depth -= 1,
+                    _ => {}
+                }
+            }
+            c if c.is_ascii_digit() => {
+                // Skip numbers like 0x1e or 1e5 so their letters aren't read as words
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'.') {
+                    i += 1;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+
+    depth > 0
+}
+
+/// If a long bracket (`[[` or `[==[`) opens at `i`, return its level and the index after it
+fn long_bracket(bytes: &[u8], i: usize) -> Option<(usize, usize)> {
+    if bytes.get(i) != Some(&b'[') {
+        return None;
+    }
+    let mut j = i + 1;
+    while bytes.get(j) == Some(&b'=') {
+        j += 1;
+    }
+    (bytes.get(j) == Some(&b'[')).then_some((j - i - 1, j + 1))
+}
+
+/// Index just after the long bracket of `level` that closes one opened before `start`
+fn long_bracket_end(bytes: &[u8], start: usize, level: usize) -> Option<usize> {
+    let close = [b"]".as_slice(), &b"=".repeat(level), b"]"].concat();
+    bytes[start..].windows(close.len()).position(|window| window == close.as_slice()).map(|pos| start + pos + close.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete_chunks() {
+        assert!(!is_incomplete("help()"));
+        assert!(!is_incomplete("for i = 1, 3 do print(i) end"));
+        assert!(!is_incomplete("if x then y() elseif z then w() else v() end"));
+        assert!(!is_incomplete("repeat x = x + 1 until x > 3"));
+        assert!(!is_incomplete("local t = { a = { 1, 2 }, [\"k\"] = 3 }"));
+        assert!(!is_incomplete("print('end') -- function"));
+        assert!(!is_incomplete("s = [[\nfunction\n]]"));
+        assert!(!is_incomplete("x = 0x1end"));
+    }
+
+    #[test]
+    fn test_incomplete_chunks() {
+        assert!(is_incomplete("function f()"));
+        assert!(is_incomplete("for i = 1, 3 do\n  print(i)"));
+        assert!(is_incomplete("while true do\n  if x then break end"));
+        assert!(is_incomplete("repeat"));
+        assert!(is_incomplete("t = {"));
+        assert!(is_incomplete("print("));
+        assert!(is_incomplete("s = [==[ unfinished ]]"));
+        assert!(is_incomplete("--[[ comment"));
+        assert!(is_incomplete("s = 'line one\\"));
+    }
+
+    #[test]
+    fn test_syntax_errors_are_complete() {
+        assert!(!is_incomplete("end"));
+        assert!(!is_incomplete("x = 'unterminated\ny = 2"));
+    }
+}
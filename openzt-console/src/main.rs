@@ -1,89 +1,187 @@
-use std::io::{self, Read, Write};
-use std::net::TcpStream;
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use openzt_console_protocol::{jsonrpc::RpcError, ConsoleClient, DEFAULT_HOST, DEFAULT_PORT};
+
+mod lua_chunk;
+mod repl;
+
+const USAGE: &str = "\
+Usage: openzt-console [OPTIONS]
+
+Options:
+  --host <HOST>        Host the game is running on (default: 127.0.0.1)
+  --port <PORT>        Console port (default: 8080)
+  --json               Print results as JSON using the JSON-RPC endpoint
+  --oneshot <COMMAND>  Run a single Lua command and exit
+  --file <PATH>        Run a Lua script file and exit
+  --help               Show this message
+
+Without --oneshot or --file an interactive console is opened.";
+
+/// Command line options
+#[derive(Debug, PartialEq)]
+struct Options {
+    host: String,
+    port: u16,
+    json: bool,
+    oneshot: Option<String>,
+    file: Option<PathBuf>,
+    help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            host: DEFAULT_HOST.to_string(),
+            port: DEFAULT_PORT,
+            json: false,
+            oneshot: None,
+            file: None,
+            help: false,
+        }
+    }
+}
+
+impl Options {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or_else(|| format!("{} requires a value", name));
+            match arg.as_str() {
+                "--host" => options.host = value("--host")?,
+                "--port" => {
+                    let port = value("--port")?;
+                    options.port = port.parse().map_err(|_| format!("Invalid port '{}'", port))?;
+                }
+                "--json" => options.json = true,
+                "--oneshot" => options.oneshot = Some(value("--oneshot")?),
+                "--file" => options.file = Some(PathBuf::from(value("--file")?)),
+                "--help" | "-h" => options.help = true,
+                other => return Err(format!("Unknown argument '{}'", other)),
+            }
+        }
+
+        if options.oneshot.is_some() && options.file.is_some() {
+            return Err("--oneshot and --file can't be used together".to_string());
+        }
+        Ok(options)
+    }
+
+    fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
 
 fn main() -> io::Result<()> {
-    let args: Vec<String> = std::env::args().collect();
-    let server_address = "127.0.0.1:8080";
-
-    // Check for --oneshot flag
-    if let Some(pos) = args.iter().position(|arg| arg == "--oneshot") {
-        if pos + 1 >= args.len() {
-            eprintln!("Error: --oneshot requires a command argument");
-            eprintln!("Usage: openzt-console --oneshot <command>");
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            eprintln!("{}", USAGE);
             std::process::exit(1);
         }
+    };
 
-        let command = &args[pos + 1];
-        return run_oneshot(server_address, command);
+    if options.help {
+        println!("{}", USAGE);
+        return Ok(());
     }
 
-    // Interactive mode (existing behavior)
-    run_interactive(server_address)
+    let server_address = options.address();
+
+    if let Some(command) = &options.oneshot {
+        return run_oneshot(&server_address, command, options.json);
+    }
+
+    if let Some(path) = &options.file {
+        let script = std::fs::read_to_string(path).map_err(|e| io::Error::new(e.kind(), format!("Failed to read {}: {}", path.display(), e)))?;
+        return run_oneshot(&server_address, &script, options.json);
+    }
+
+    repl::run(&server_address, options.json)
 }
 
-fn run_oneshot(server_address: &str, command: &str) -> io::Result<()> {
-    let mut stream = TcpStream::connect(server_address)?;
+/// Execute through the JSON-RPC endpoint, returning pretty-printed JSON or the formatted error
+fn execute_json(client: &mut ConsoleClient, command: &str) -> io::Result<Result<String, String>> {
+    Ok(match client.execute_json(command)? {
+        Ok(value) => Ok(serde_json::to_string_pretty(&value).unwrap_or_default()),
+        Err(error) => Err(format_rpc_error(&error)),
+    })
+}
 
-    // Send command
-    stream.write_all(command.as_bytes())?;
+fn format_rpc_error(error: &RpcError) -> String {
+    let mut message = serde_json::json!({ "code": error.code, "message": error.message });
+    if let Some(data) = &error.data {
+        message["data"] = data.clone();
+    }
+    serde_json::to_string_pretty(&message).unwrap_or_default()
+}
 
-    // Read response
-    let mut buffer = [0; 100024];
-    let size = stream.read(&mut buffer)?;
+/// Execute a command, printing raw output as it streams in
+fn execute(client: &mut ConsoleClient, command: &str, json: bool) -> io::Result<Result<String, String>> {
+    if json {
+        return execute_json(client, command);
+    }
+    client.execute_with(command, |part| {
+        print!("{}", part);
+        let _ = io::stdout().flush();
+    })
+}
 
-    if size > 0 {
-        let response = String::from_utf8_lossy(&buffer[0..size]);
-        print!("{}", response);
+/// Print a result, ending with exactly one newline
+fn print_result(result: &str) {
+    if result.ends_with('\n') {
+        print!("{}", result);
+    } else {
+        println!("{}", result);
+    }
+}
+
+fn run_oneshot(server_address: &str, command: &str, json: bool) -> io::Result<()> {
+    let mut client = ConsoleClient::connect(server_address)?;
+
+    match execute(&mut client, command, json)? {
+        Ok(response) => print_result(&response),
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
     }
 
     Ok(())
 }
 
-fn run_interactive(server_address: &str) -> io::Result<()> {
-    let mut stream = TcpStream::connect(server_address)?;
-    println!("Connected to server at {}", server_address);
-
-    loop {
-        let mut input = String::new();
-        print!("Enter a command: ") ;
-        io::stdout().flush()?;
-        io::stdin().read_line(&mut input)?;
-
-        let trimmed = input.trim();
-        if trimmed.to_lowercase() == "quit" {
-            break;
-        } else if trimmed.is_empty() {
-            continue;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        match stream.write(trimmed.as_bytes()) {
-            Ok(_) => {
-                // Message sent successfully
-            }
-            Err(err) => {
-                eprintln!("Error sending data to server: {}", err);
-                break;
-            }
-        }
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
 
-        let mut buffer = [0; 100024];
-        match stream.read(&mut buffer) {
-            Ok(size) => {
-                if size == 0 {
-                    // Connection closed by server
-                    break;
-                }
+    #[test]
+    fn test_parse_options() {
+        assert_eq!(parse(&[]).unwrap(), Options::default());
+        assert_eq!(parse(&[]).unwrap().address(), "127.0.0.1:8080");
 
-                // Print server response
-                let response = String::from_utf8_lossy(&buffer[0..size]);
-                println!("Server response: {}", response);
-            }
-            Err(err) => {
-                eprintln!("Error reading data from server: {}", err);
-                break;
-            }
-        }
+        let options = parse(&["--host", "192.168.0.2", "--port", "9000", "--json", "--file", "setup.lua"]).unwrap();
+        assert_eq!(options.address(), "192.168.0.2:9000");
+        assert!(options.json);
+        assert_eq!(options.file, Some(PathBuf::from("setup.lua")));
+
+        assert_eq!(parse(&["--oneshot", "help()"]).unwrap().oneshot.as_deref(), Some("help()"));
     }
 
-    Ok(())
+    #[test]
+    fn test_parse_options_errors() {
+        assert_eq!(parse(&["--port", "http"]).unwrap_err(), "Invalid port 'http'");
+        assert_eq!(parse(&["--oneshot"]).unwrap_err(), "--oneshot requires a value");
+        assert_eq!(parse(&["--verbose"]).unwrap_err(), "Unknown argument '--verbose'");
+        assert!(parse(&["--oneshot", "x", "--file", "y.lua"]).is_err());
+    }
 }
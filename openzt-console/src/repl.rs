@@ -0,0 +1,166 @@
+use std::{io, path::PathBuf, thread, time::Duration};
+
+use openzt_console_protocol::ConsoleClient;
+use rustyline::{
+    completion::{Completer, Pair},
+    error::ReadlineError,
+    history::DefaultHistory,
+    validate::{ValidationContext, ValidationResult, Validator},
+    Config,
+    Context,
+    Editor,
+    Helper,
+    Highlighter,
+    Hinter,
+};
+
+use crate::{execute, lua_chunk, print_result};
+
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
+const LUA_KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in", "local", "nil", "not", "or", "repeat", "return",
+    "then", "true", "until", "while",
+];
+
+/// A Lua function registered in the game, as returned by `list_lua_functions()`
+struct LuaFunction {
+    name: String,
+    signature: String,
+}
+
+/// Completion and multi-line input for the editor
+#[derive(Helper, Hinter, Highlighter, Default)]
+struct ReplHelper {
+    functions: Vec<LuaFunction>,
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let start = line[..pos].rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.')).map(|i| i + 1).unwrap_or(0);
+        let prefix = &line[start..pos];
+        if prefix.is_empty() {
+            return Ok((pos, Vec::new()));
+        }
+
+        let functions = self.functions.iter().filter(|f| f.name.starts_with(prefix)).map(|f| Pair {
+            display: f.signature.clone(),
+            replacement: f.name.clone(),
+        });
+        let keywords = LUA_KEYWORDS.iter().filter(|k| k.starts_with(prefix)).map(|k| Pair {
+            display: k.to_string(),
+            replacement: k.to_string(),
+        });
+        Ok((start, functions.chain(keywords).collect()))
+    }
+}
+
+impl Validator for ReplHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if lua_chunk::is_incomplete(ctx.input()) {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+/// Run the interactive console until the user quits
+///
+/// If the game closes, waits for it to come back and reconnects.
+pub fn run(server_address: &str, json: bool) -> io::Result<()> {
+    let config = Config::builder().max_history_size(1000).map_err(io::Error::other)?.history_ignore_dups(true).map_err(io::Error::other)?.build();
+    let mut editor: Editor<ReplHelper, DefaultHistory> = Editor::with_config(config).map_err(io::Error::other)?;
+    editor.set_helper(Some(ReplHelper::default()));
+
+    let history = history_path();
+    // A missing history file is normal on first run
+    let _ = editor.load_history(&history);
+
+    let mut client = connect(server_address, &mut editor);
+    println!("Type help() for a list of functions, quit to exit. Tab completes function names.");
+
+    loop {
+        match editor.readline("lua> ") {
+            Ok(input) => {
+                let code = input.trim();
+                if code.is_empty() {
+                    continue;
+                }
+                let _ = editor.add_history_entry(code);
+                if code == "quit" || code == "exit" {
+                    break;
+                }
+
+                match execute(&mut client, code, json) {
+                    Ok(Ok(response)) => print_result(&response),
+                    Ok(Err(message)) => eprintln!("{}", message),
+                    Err(err) => {
+                        eprintln!("Connection lost: {}", err);
+                        client = connect(server_address, &mut editor);
+                        eprintln!("The last command may not have run");
+                    }
+                }
+            }
+            // Ctrl-C clears the current input
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                eprintln!("Error reading input: {}", err);
+                break;
+            }
+        }
+    }
+
+    editor.save_history(&history).map_err(io::Error::other)
+}
+
+/// Connect to the game, waiting for it to start if needed, and load completions
+fn connect(server_address: &str, editor: &mut Editor<ReplHelper, DefaultHistory>) -> ConsoleClient {
+    let mut waiting = false;
+    let mut client = loop {
+        match ConsoleClient::connect(server_address) {
+            Ok(client) => break client,
+            Err(err) => {
+                if !waiting {
+                    eprintln!("Waiting for game at {} ({})... press Ctrl-C to give up", server_address, err);
+                    waiting = true;
+                }
+                thread::sleep(RECONNECT_INTERVAL);
+            }
+        }
+    };
+    println!("Connected to server at {}", server_address);
+
+    if let Some(helper) = editor.helper_mut() {
+        helper.functions = fetch_functions(&mut client);
+    }
+    client
+}
+
+/// Ask the game for its registered Lua functions, empty if it can't tell us
+fn fetch_functions(client: &mut ConsoleClient) -> Vec<LuaFunction> {
+    let Ok(Ok(serde_json::Value::Array(functions))) = client.execute_json("return list_lua_functions()") else {
+        return Vec::new();
+    };
+    functions
+        .iter()
+        .filter_map(|function| {
+            Some(LuaFunction {
+                name: function.get("name")?.as_str()?.to_string(),
+                signature: function.get("signature")?.as_str()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// History is kept in the user's home directory, shared across sessions
+fn history_path() -> PathBuf {
+    std::env::var_os("USERPROFILE")
+        .or_else(|| std::env::var_os("HOME"))
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(".openzt_console_history")
+}
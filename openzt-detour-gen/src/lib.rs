@@ -0,0 +1,291 @@
+//! Generates `openzt-detour/src/gen.rs` from the symbol database in `openzt-detour/symbols.toml`
+//!
+//! Each function in the database becomes a `FunctionDef` constant, grouped into one module per class:
+//!
+//! ```toml
+//! [[function]]
+//! class = "BFTile"
+//! name = "GET_LOCAL_ELEVATION"
+//! address = 0x0040f24d
+//! convention = "thiscall"
+//! params = ["u32", "u32"]
+//! returns = "i32"
+//! signature = "int __thiscall OOAnalyzer::BFTile::getLocalElevation(BFTile *this,BFPos *param_1)"
+//! ```
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    path::Path,
+};
+
+use serde::Deserialize;
+
+pub const CALLING_CONVENTIONS: &[&str] = &["thiscall", "fastcall", "stdcall", "cdecl"];
+
+const PRIMITIVE_TYPES: &[&str] = &["bool", "u8", "i8", "u16", "i16", "u32", "i32", "u64", "i64", "f32", "f64"];
+
+const STANDALONE_MODULE: &str = "standalone";
+
+/// A function in the symbol database
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Function {
+    /// Ghidra class, functions without one go in the `standalone` module
+    pub class: Option<String>,
+    /// Name of the generated constant
+    pub name: String,
+    pub address: u32,
+    pub convention: String,
+    #[serde(default)]
+    pub params: Vec<String>,
+    pub returns: Option<String>,
+    /// Original Ghidra signature, emitted as a doc comment
+    pub signature: Option<String>,
+}
+
+impl Function {
+    /// The `FunctionDef` type parameter, e.g. `unsafe extern "thiscall" fn(u32) -> u32`
+    pub fn function_type(&self) -> String {
+        let returns = self.returns.as_ref().map(|returns| format!(" -> {}", returns)).unwrap_or_default();
+        format!("unsafe extern \"{}\" fn({}){}", self.convention, self.params.join(", "), returns)
+    }
+
+    fn module(&self) -> String {
+        self.class.as_deref().map(module_name).unwrap_or_else(|| STANDALONE_MODULE.to_string())
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Symbols {
+    #[serde(default, rename = "function")]
+    pub functions: Vec<Function>,
+}
+
+/// Module name for a class, e.g. `ZTUI::general` -> `ztui_general`, `std::basic_string<>` -> `std_basic_string`
+pub fn module_name(class: &str) -> String {
+    let mut name = String::new();
+    for c in class.chars() {
+        if c.is_ascii_alphanumeric() {
+            name.push(c.to_ascii_lowercase());
+        } else if !name.is_empty() && !name.ends_with('_') {
+            name.push('_');
+        }
+    }
+    name.trim_end_matches('_').to_string()
+}
+
+fn is_constant_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase())
+        && name.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn is_valid_type(ty: &str) -> bool {
+    let pointee = ty.strip_prefix("*mut ").or_else(|| ty.strip_prefix("*const "));
+    PRIMITIVE_TYPES.contains(&pointee.unwrap_or(ty))
+}
+
+impl Symbols {
+    pub fn parse(source: &str) -> Result<Symbols, String> {
+        toml::from_str(source).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Symbols, String> {
+        let source = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Symbols::parse(&source).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+    }
+
+    /// Checks names, conventions and types, and that no address, constant or module is defined twice
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        let mut addresses: HashMap<u32, &Function> = HashMap::new();
+        let mut names: HashMap<(String, &str), &Function> = HashMap::new();
+        let mut classes: HashMap<String, &str> = HashMap::new();
+
+        for function in &self.functions {
+            let location = format!("{} ({:#010x})", function.name, function.address);
+            if !is_constant_name(&function.name) {
+                errors.push(format!("{}: name must be SCREAMING_SNAKE_CASE", location));
+            }
+            if !CALLING_CONVENTIONS.contains(&function.convention.as_str()) {
+                errors.push(format!("{}: unknown calling convention '{}'", location, function.convention));
+            }
+            for ty in function.params.iter().chain(&function.returns) {
+                if !is_valid_type(ty) {
+                    errors.push(format!("{}: unsupported type '{}'", location, ty));
+                }
+            }
+            if let Some(class) = &function.class {
+                let module = module_name(class);
+                if module.is_empty() || module.starts_with(|c: char| c.is_ascii_digit()) || module == STANDALONE_MODULE {
+                    errors.push(format!("{}: class '{}' does not make a valid module name", location, class));
+                } else if let Some(other) = classes.insert(module.clone(), class).filter(|other| other != class) {
+                    errors.push(format!("{}: classes '{}' and '{}' both map to module '{}'", location, other, class, module));
+                }
+            }
+            if let Some(other) = addresses.insert(function.address, function) {
+                errors.push(format!("{}: address is also used by {}", location, other.name));
+            }
+            if names.insert((function.module(), &function.name), function).is_some() {
+                errors.push(format!("{}: {}::{} is defined twice", location, function.module(), function.name));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Renders gen.rs, classes sorted by name with standalone functions last, functions sorted by address
+    pub fn render(&self) -> String {
+        let mut classes: BTreeMap<&str, Vec<&Function>> = BTreeMap::new();
+        let mut standalone = Vec::new();
+        for function in &self.functions {
+            match &function.class {
+                Some(class) => classes.entry(class).or_default().push(function),
+                None => standalone.push(function),
+            }
+        }
+
+        let mut out = String::new();
+        out.push_str("// Auto-generated Rust function definitions for Zoo Tycoon\n");
+        out.push_str("// Generated by openzt-detour-gen from symbols.toml, edit that file and run `cargo run -p openzt-detour-gen`\n\n");
+        out.push_str("#![allow(clippy::type_complexity)]\n\n");
+        out.push_str("use std::marker::PhantomData;\n\n");
+        out.push_str("use crate::FunctionDef;\n");
+
+        let modules = classes
+            .into_iter()
+            .map(|(class, functions)| (format!("{} class functions", class), module_name(class), functions))
+            .chain((!standalone.is_empty()).then(|| ("Standalone functions".to_string(), STANDALONE_MODULE.to_string(), standalone)));
+        for (comment, module, mut functions) in modules {
+            functions.sort_by_key(|function| function.address);
+            let _ = write!(out, "\n// {}\npub mod {} {{\n    use super::*;\n\n", comment, module);
+            for function in functions {
+                if let Some(signature) = &function.signature {
+                    let _ = writeln!(out, "    /// `{}`", signature);
+                }
+                let _ = writeln!(
+                    out,
+                    "    pub const {}: FunctionDef<{}> = FunctionDef{{address: {:#010x}, function_type: PhantomData}};",
+                    function.name,
+                    function.function_type(),
+                    function.address
+                );
+            }
+            out.push_str("}\n");
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYMBOLS: &str = r#"
+[[function]]
+name = "NULLSUB_0"
+address = 0x0040100b
+convention = "thiscall"
+params = ["u32"]
+
+[[function]]
+class = "ZTUI::general"
+name = "GET_SELECTED_ENTITY"
+address = 0x00410f84
+convention = "stdcall"
+returns = "u32"
+
+[[function]]
+class = "BFTile"
+name = "GET_LOCAL_ELEVATION"
+address = 0x0040f24d
+convention = "thiscall"
+params = ["u32", "*mut u32"]
+returns = "i32"
+signature = "int __thiscall BFTile::getLocalElevation(BFTile *this,BFPos *param_1)"
+"#;
+
+    #[test]
+    fn test_module_name() {
+        assert_eq!(module_name("BFAIMgr"), "bfaimgr");
+        assert_eq!(module_name("ZTUI::general"), "ztui_general");
+        assert_eq!(module_name("std::__vector_deleter<>"), "std_vector_deleter");
+        assert_eq!(module_name("AI_cls_0x404fd6"), "ai_cls_0x404fd6");
+    }
+
+    #[test]
+    fn test_render() {
+        let symbols = Symbols::parse(SYMBOLS).unwrap();
+        assert_eq!(symbols.validate(), Ok(()));
+        let rendered = symbols.render();
+        let expected = r#"
+// BFTile class functions
+pub mod bftile {
+    use super::*;
+
+    /// `int __thiscall BFTile::getLocalElevation(BFTile *this,BFPos *param_1)`
+    pub const GET_LOCAL_ELEVATION: FunctionDef<unsafe extern "thiscall" fn(u32, *mut u32) -> i32> = FunctionDef{address: 0x0040f24d, function_type: PhantomData};
+}
+
+// ZTUI::general class functions
+pub mod ztui_general {
+    use super::*;
+
+    pub const GET_SELECTED_ENTITY: FunctionDef<unsafe extern "stdcall" fn() -> u32> = FunctionDef{address: 0x00410f84, function_type: PhantomData};
+}
+
+// Standalone functions
+pub mod standalone {
+    use super::*;
+
+    pub const NULLSUB_0: FunctionDef<unsafe extern "thiscall" fn(u32)> = FunctionDef{address: 0x0040100b, function_type: PhantomData};
+}
+"#;
+        assert!(rendered.ends_with(expected), "{}", rendered);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut symbols = Symbols::parse(SYMBOLS).unwrap();
+        let mut duplicate = symbols.functions[2].clone();
+        duplicate.convention = "pascal".to_string();
+        duplicate.params.push("String".to_string());
+        symbols.functions.push(duplicate);
+        let mut renamed = symbols.functions[0].clone();
+        renamed.name = "nullsub".to_string();
+        renamed.address = 0x0040100c;
+        symbols.functions.push(renamed);
+
+        let errors = symbols.validate().unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                "GET_LOCAL_ELEVATION (0x0040f24d): unknown calling convention 'pascal'",
+                "GET_LOCAL_ELEVATION (0x0040f24d): unsupported type 'String'",
+                "GET_LOCAL_ELEVATION (0x0040f24d): address is also used by GET_LOCAL_ELEVATION",
+                "GET_LOCAL_ELEVATION (0x0040f24d): bftile::GET_LOCAL_ELEVATION is defined twice",
+                "nullsub (0x0040100c): name must be SCREAMING_SNAKE_CASE",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_fields() {
+        let error = Symbols::parse("[[function]]\nname = \"A\"\naddress = 1\nconvention = \"cdecl\"\nreturn = \"u32\"\n").unwrap_err();
+        assert!(error.contains("unknown field `return`"), "{}", error);
+    }
+
+    /// gen.rs must be regenerated whenever symbols.toml changes
+    #[test]
+    fn test_gen_rs_is_up_to_date() {
+        let detour_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../openzt-detour");
+        let symbols = Symbols::load(&detour_dir.join("symbols.toml")).unwrap();
+        assert_eq!(symbols.validate(), Ok(()));
+        let generated = std::fs::read_to_string(detour_dir.join("src/gen.rs")).unwrap();
+        assert!(symbols.render() == generated, "openzt-detour/src/gen.rs is out of date, run `cargo run -p openzt-detour-gen`");
+    }
+}
@@ -0,0 +1,93 @@
+use std::path::{Path, PathBuf};
+
+use openzt_detour_gen::Symbols;
+
+const USAGE: &str = "\
+Usage: openzt-detour-gen [OPTIONS]
+
+Options:
+  --symbols <PATH>  Symbol database (default: openzt-detour/symbols.toml)
+  --output <PATH>   Generated file (default: openzt-detour/src/gen.rs)
+  --check           Fail if the output is not up to date instead of writing it
+  --help            Show this message";
+
+/// Command line options
+#[derive(Debug, PartialEq)]
+struct Options {
+    symbols: PathBuf,
+    output: PathBuf,
+    check: bool,
+    help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        let detour_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../openzt-detour");
+        Options {
+            symbols: detour_dir.join("symbols.toml"),
+            output: detour_dir.join("src/gen.rs"),
+            check: false,
+            help: false,
+        }
+    }
+}
+
+impl Options {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or_else(|| format!("{} requires a value", name));
+            match arg.as_str() {
+                "--symbols" => options.symbols = PathBuf::from(value("--symbols")?),
+                "--output" => options.output = PathBuf::from(value("--output")?),
+                "--check" => options.check = true,
+                "--help" | "-h" => options.help = true,
+                other => return Err(format!("Unknown argument '{}'", other)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let symbols = Symbols::load(&options.symbols)?;
+    if let Err(errors) = symbols.validate() {
+        return Err(format!("{} has {} error(s):\n  {}", options.symbols.display(), errors.len(), errors.join("\n  ")));
+    }
+    let generated = symbols.render();
+
+    if options.check {
+        let current = std::fs::read_to_string(&options.output).unwrap_or_default();
+        if current != generated {
+            return Err(format!("{} is out of date, run `cargo run -p openzt-detour-gen`", options.output.display()));
+        }
+        println!("{} is up to date ({} functions)", options.output.display(), symbols.functions.len());
+    } else {
+        std::fs::write(&options.output, generated).map_err(|e| format!("Failed to write {}: {}", options.output.display(), e))?;
+        println!("Wrote {} functions to {}", symbols.functions.len(), options.output.display());
+    }
+    Ok(())
+}
+
+fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    if options.help {
+        println!("{}", USAGE);
+        return;
+    }
+
+    if let Err(message) = run(&options) {
+        eprintln!("Error: {}", message);
+        std::process::exit(1);
+    }
+}
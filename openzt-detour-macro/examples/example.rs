@@ -21,13 +21,41 @@ mod detour_mod {
             unsafe { LOAD_LANG_DLLS.detour(detour_target).unwrap() }
         });
     
-    unsafe extern "thiscall" fn detour_target(_: u32) -> u32 {
-        info!("Detour success");
-        1
+    // The body runs in catch_unwind, a panic is reported and the original function is called instead
+    // (or the process aborts if the detour is marked #[no_fallback])
+    unsafe extern "thiscall" fn detour_target(__detour_arg0: u32) -> u32 {
+        let __detour_result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| -> u32 {
+            let _ = __detour_arg0;
+            {
+                info!("Detour success");
+                1
+            }
+        }));
+        match __detour_result {
+            Ok(result) => result,
+            Err(payload) => {
+                ::openzt_detour::crash::detour_panicked(
+                    module_path!(),
+                    stringify!(detour_target),
+                    stringify!(LOAD_LANG_DLLS),
+                    true,
+                    payload,
+                );
+                LOAD_LANG_DLLS_DETOUR.call(__detour_arg0)
+            }
+        }
     }
     
-    pub fn init() {
-        LOAD_LANG_DLLS_DETOUR.enable().unwrap();
+    pub unsafe fn init_detours() -> ::std::result::Result<(), ::openzt_detour::registry::DetourError> {
+        // Fails if another module already detours LOAD_LANG_DLLS
+        ::openzt_detour::registry::register(
+            module_path!(),
+            stringify!(detour_target),
+            stringify!(LOAD_LANG_DLLS),
+            LOAD_LANG_DLLS.address,
+            || &*LOAD_LANG_DLLS_DETOUR as &'static dyn ::openzt_detour::registry::Toggle,
+        )?;
+        Ok(())
     }
 }
 
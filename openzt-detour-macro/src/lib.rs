@@ -1,17 +1,25 @@
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, parse_quote, ItemMod, FnArg, ReturnType, Ident, Stmt};
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, parse_quote,
+    visit_mut::{self, VisitMut},
+    Expr, FnArg, Ident, ItemFn, ItemMod, ReturnType, Stmt,
+};
 
 struct DetourInfo {
     detour_name: Ident,
     function_name: Ident,
     function_signature: syn::Signature,
+    bootstrap: bool,
 }
 
 #[proc_macro_attribute]
 pub fn detour_mod(_attr: TokenStream, input: TokenStream) -> TokenStream {
-    let mut module = parse_macro_input!(input as ItemMod);
-    
+    let module = parse_macro_input!(input as ItemMod);
+    TokenStream::from(expand_detour_mod(module))
+}
+
+fn expand_detour_mod(mut module: ItemMod) -> proc_macro2::TokenStream {
     let mut detour_infos = Vec::new();
     
     if let Some((_, items)) = &mut module.content {
@@ -29,11 +37,30 @@ pub fn detour_mod(_attr: TokenStream, input: TokenStream) -> TokenStream {
                         panic!("detour attribute must be in the form #[detour(DETOUR_NAME)]");
                     };
                     
+                    let fallback = match func.attrs.iter().position(|attr| attr.path().is_ident("no_fallback")) {
+                        Some(index) => {
+                            func.attrs.remove(index);
+                            false
+                        }
+                        None => true,
+                    };
+
+                    let bootstrap = match func.attrs.iter().position(|attr| attr.path().is_ident("bootstrap")) {
+                        Some(index) => {
+                            func.attrs.remove(index);
+                            true
+                        }
+                        None => false,
+                    };
+
                     detour_infos.push(DetourInfo {
                         detour_name: detour_name.clone(),
                         function_name: func.sig.ident.clone(),
                         function_signature: func.sig.clone(),
+                        bootstrap,
                     });
+
+                    catch_panics(func, &detour_name, fallback);
                 }
             }
         }
@@ -59,20 +86,30 @@ pub fn detour_mod(_attr: TokenStream, input: TokenStream) -> TokenStream {
         }
         
         if !detour_infos.is_empty() {
-            let enables: Vec<Stmt> = detour_infos.iter().map(|info| {
+            // Each detour is registered before it's created, so a hook on an address another module
+            // already detours fails with a conflict instead of replacing it
+            let registrations: Vec<Stmt> = detour_infos.iter().map(|info| {
                 let detour_name = &info.detour_name;
                 let detour_static_name = Ident::new(
                     &format!("{}_DETOUR", detour_name), 
                     detour_name.span()
                 );
+                let function_name = &info.function_name;
+                let register = if info.bootstrap { format_ident!("register_bootstrap") } else { format_ident!("register") };
                 parse_quote! {
-                    #detour_static_name.enable()?;
+                    ::openzt_detour::registry::#register(
+                        module_path!(),
+                        stringify!(#function_name),
+                        stringify!(#detour_name),
+                        #detour_name.address,
+                        || &*#detour_static_name as &'static dyn ::openzt_detour::registry::Toggle,
+                    )?;
                 }
             }).collect();
             
             let init_fn: syn::Item = parse_quote! {
-                pub unsafe fn init_detours() -> ::retour::Result<()> {
-                    #(#enables)*
+                pub unsafe fn init_detours() -> ::std::result::Result<(), ::openzt_detour::registry::DetourError> {
+                    #(#registrations)*
                     Ok(())
                 }
             };
@@ -81,7 +118,97 @@ pub fn detour_mod(_attr: TokenStream, input: TokenStream) -> TokenStream {
         }
     }
     
-    TokenStream::from(quote! { #module })
+    quote! { #module }
+}
+
+/// Records the result of each call to the original function in `__detour_original`
+struct TrackOriginalCalls<'a> {
+    detour_static_name: &'a Ident,
+    found: bool,
+}
+
+impl VisitMut for TrackOriginalCalls<'_> {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        visit_mut::visit_expr_mut(self, expr);
+        if let Expr::MethodCall(call) = expr {
+            let calls_original = call.method == "call" && matches!(&*call.receiver, Expr::Path(path) if path.path.is_ident(self.detour_static_name));
+            if calls_original {
+                self.found = true;
+                *expr = parse_quote! {{
+                    let __detour_original_result = #call;
+                    __detour_original.set(::std::option::Option::Some(__detour_original_result));
+                    __detour_original_result
+                }};
+            }
+        }
+    }
+}
+
+/// Wraps the body of a detour in `catch_unwind`, unwinding into the game is undefined behaviour
+///
+/// A panic is reported through `openzt_detour::crash`, then the original function is called with the
+/// same arguments, or the process aborts if the detour is marked `#[no_fallback]`. If the body had
+/// already called the original, its result is returned instead so the game function doesn't run twice.
+/// Arguments are renamed so they can be passed on even when the detour ignores them, and the original
+/// patterns are bound again inside the closure.
+fn catch_panics(func: &mut ItemFn, detour_name: &Ident, fallback: bool) {
+    let detour_static_name = Ident::new(&format!("{}_DETOUR", detour_name), detour_name.span());
+    let function_name = &func.sig.ident;
+
+    let mut bindings: Vec<Stmt> = Vec::new();
+    let mut arguments = Vec::new();
+    for (index, arg) in func.sig.inputs.iter_mut().enumerate() {
+        if let FnArg::Typed(pat_type) = arg {
+            let argument = format_ident!("__detour_arg{}", index);
+            let pat = std::mem::replace(&mut *pat_type.pat, parse_quote!(#argument));
+            bindings.push(parse_quote! { let #pat = #argument; });
+            arguments.push(argument);
+        }
+    }
+
+    let return_type = match &func.sig.output {
+        ReturnType::Default => quote! { () },
+        ReturnType::Type(_, ty) => quote! { #ty },
+    };
+    let mut tracker = TrackOriginalCalls { detour_static_name: &detour_static_name, found: false };
+    if fallback {
+        tracker.visit_block_mut(&mut func.block);
+    }
+    let (track, recover) = match (fallback, tracker.found) {
+        (true, true) => (
+            quote! { let __detour_original = ::std::cell::Cell::<::std::option::Option<#return_type>>::new(::std::option::Option::None); },
+            quote! {
+                match __detour_original.take() {
+                    ::std::option::Option::Some(result) => result,
+                    ::std::option::Option::None => #detour_static_name.call(#(#arguments),*),
+                }
+            },
+        ),
+        (true, false) => (quote! {}, quote! { #detour_static_name.call(#(#arguments),*) }),
+        (false, _) => (quote! {}, quote! { ::std::process::abort() }),
+    };
+    let body = &func.block;
+
+    func.block = parse_quote! {{
+        #track
+        let __detour_result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| -> #return_type {
+            #(#bindings)*
+            #body
+        }));
+        match __detour_result {
+            Ok(result) => result,
+            Err(payload) => {
+                ::openzt_detour::crash::detour_panicked(
+                    module_path!(),
+                    stringify!(#function_name),
+                    stringify!(#detour_name),
+                    #fallback,
+                    payload,
+                );
+                #recover
+            }
+        }
+    }};
 }
 
 fn build_function_type(sig: &syn::Signature) -> proc_macro2::TokenStream {
@@ -112,3 +239,170 @@ fn build_function_type(sig: &syn::Signature) -> proc_macro2::TokenStream {
 pub fn detour(_attr: TokenStream, input: TokenStream) -> TokenStream {
     input
 }
+
+/// Marks a detour whose original function must not be called after the detour panics, e.g. because
+/// the detour replaces it entirely or has already changed state the original depends on. A panic in
+/// such a detour aborts the process after writing the crash report.
+#[proc_macro_attribute]
+pub fn no_fallback(_attr: TokenStream, input: TokenStream) -> TokenStream {
+    input
+}
+
+/// Marks the detour that detects the game build, it's installed from `DllMain` before detection so
+/// the registry's build check is skipped for it. It must check the build itself before doing
+/// anything else.
+#[proc_macro_attribute]
+pub fn bootstrap(_attr: TokenStream, input: TokenStream) -> TokenStream {
+    input
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Token streams print with different spacing depending on how they were built, so compare without it
+    fn normalize(tokens: proc_macro2::TokenStream) -> String {
+        tokens.to_string().split_whitespace().collect()
+    }
+
+    fn expand(input: proc_macro2::TokenStream) -> String {
+        normalize(expand_detour_mod(syn::parse2(input).unwrap()))
+    }
+
+    #[test]
+    fn test_body_is_wrapped_with_fallback() {
+        let expanded = expand(quote! {
+            mod hooks {
+                #[detour(LOAD_LANG_DLLS)]
+                unsafe extern "thiscall" fn load_lang_dlls(_: u32, mut count: u32) -> u32 {
+                    count += 1;
+                    count
+                }
+            }
+        });
+
+        let wrapped = normalize(quote! {
+            unsafe extern "thiscall" fn load_lang_dlls(__detour_arg0: u32, __detour_arg1: u32) -> u32 {
+                let __detour_result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| -> u32 {
+                    let _ = __detour_arg0;
+                    let mut count = __detour_arg1;
+                    {
+                        count += 1;
+                        count
+                    }
+                }));
+                match __detour_result {
+                    Ok(result) => result,
+                    Err(payload) => {
+                        ::openzt_detour::crash::detour_panicked(
+                            module_path!(),
+                            stringify!(load_lang_dlls),
+                            stringify!(LOAD_LANG_DLLS),
+                            true,
+                            payload,
+                        );
+                        LOAD_LANG_DLLS_DETOUR.call(__detour_arg0, __detour_arg1)
+                    }
+                }
+            }
+        });
+        assert!(expanded.contains(&wrapped), "{}", expanded);
+
+        // The detour type comes from the declared argument types, not the renamed patterns
+        let detour_type = normalize(quote! { GenericDetour<unsafe extern "thiscall" fn(u32, u32) -> u32 });
+        assert!(expanded.contains(&detour_type), "{}", expanded);
+        assert!(expanded.contains("pubunsafefninit_detours"), "{}", expanded);
+    }
+
+    #[test]
+    fn test_fallback_after_original_returns_its_result() {
+        let expanded = expand(quote! {
+            mod hooks {
+                #[detour(ADD_TO_MAP)]
+                unsafe extern "thiscall" fn add_to_map(this: u32) -> u32 {
+                    let result = unsafe { ADD_TO_MAP_DETOUR.call(this) };
+                    publish(this);
+                    result
+                }
+            }
+        });
+
+        let tracked_call = normalize(quote! {
+            let result = unsafe {{
+                let __detour_original_result = ADD_TO_MAP_DETOUR.call(this);
+                __detour_original.set(::std::option::Option::Some(__detour_original_result));
+                __detour_original_result
+            }};
+        });
+        assert!(expanded.contains(&tracked_call), "{}", expanded);
+        let recovery = normalize(quote! {
+            match __detour_original.take() {
+                ::std::option::Option::Some(result) => result,
+                ::std::option::Option::None => ADD_TO_MAP_DETOUR.call(__detour_arg0),
+            }
+        });
+        assert!(expanded.contains(&recovery), "{}", expanded);
+    }
+
+    #[test]
+    fn test_no_fallback_aborts() {
+        let expanded = expand(quote! {
+            mod hooks {
+                #[detour(UPDATE_SIM)]
+                #[no_fallback]
+                unsafe extern "thiscall" fn update_sim(this: u32) {
+                    tick(this);
+                }
+            }
+        });
+
+        assert!(!expanded.contains("no_fallback"), "{}", expanded);
+        assert!(!expanded.contains("UPDATE_SIM_DETOUR.call"), "{}", expanded);
+        let recovery = normalize(quote! {
+            ::openzt_detour::crash::detour_panicked(
+                module_path!(),
+                stringify!(update_sim),
+                stringify!(UPDATE_SIM),
+                false,
+                payload,
+            );
+            ::std::process::abort()
+        });
+        assert!(expanded.contains(&recovery), "{}", expanded);
+        assert!(expanded.contains(&normalize(quote! { let this = __detour_arg0; })), "{}", expanded);
+    }
+
+    #[test]
+    fn test_bootstrap_skips_the_build_check() {
+        let expanded = expand(quote! {
+            mod hooks {
+                #[detour(LOAD_LANG_DLLS)]
+                #[bootstrap]
+                unsafe extern "thiscall" fn load_lang_dlls(this: u32) -> u32 {
+                    LOAD_LANG_DLLS_DETOUR.call(this)
+                }
+
+                #[detour(UPDATE_SIM)]
+                unsafe extern "thiscall" fn update_sim(this: u32) {
+                    tick(this);
+                }
+            }
+        });
+
+        assert!(!expanded.contains("#[bootstrap]"), "{}", expanded);
+        assert!(expanded.contains("::openzt_detour::registry::register_bootstrap(module_path!(),stringify!(load_lang_dlls)"), "{}", expanded);
+        assert!(expanded.contains("::openzt_detour::registry::register(module_path!(),stringify!(update_sim)"), "{}", expanded);
+    }
+
+    #[test]
+    fn test_other_items_are_untouched() {
+        let expanded = expand(quote! {
+            mod hooks {
+                fn helper(value: u32) -> u32 {
+                    value
+                }
+            }
+        });
+        assert_eq!(expanded, normalize(quote! { mod hooks { fn helper(value: u32) -> u32 { value } } }));
+    }
+}
@@ -0,0 +1,461 @@
+//! Several handlers on one game function
+//!
+//! Only one detour can hook a game function (see [`crate::registry`]), so features that want to
+//! intercept the same function share a [`HookChain`]. Declare one per function with
+//! [`hook_chain!`](crate::hook_chain), which installs the single detour, and add handlers to it:
+//!
+//! ```ignore
+//! openzt_detour::hook_chain! {
+//!     /// ZTApp::updateSim, runs once per game tick
+//!     pub static UPDATE_SIM = gen::ztapp::UPDATE_SIM: unsafe extern "thiscall" fn(this: u32, delta: i32);
+//! }
+//!
+//! unsafe { UPDATE_SIM.install() }?;
+//! UPDATE_SIM.post("scheduler", 0, |_, _| scheduler::tick());
+//! ```
+//!
+//! Each call runs the pre handlers, then the original function, then the post handlers, all in
+//! priority order (higher first, ties in the order they were added, as with event subscribers).
+//! A pre handler can change the arguments the later handlers and the original function see, or
+//! return [`Flow::Return`] to skip the remaining pre handlers and the original function. Post
+//! handlers always run, and can change the result. A handler that panics is reported like a
+//! panicking detour and the game falls back to the original function, see
+//! [`ChainedDetour::dispatch_catching`].
+
+use std::{
+    cell::Cell,
+    fmt,
+    marker::PhantomData,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::{
+    crash,
+    registry::{self, DetourError, Toggle},
+};
+
+/// What a pre handler wants to happen next
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Flow<R> {
+    /// Run the next handler, or the original function
+    Continue,
+    /// Skip the original function and return this, post handlers still run
+    Return(R),
+}
+
+/// Identifies a handler so it can be removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HandlerId(u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Pre,
+    Post,
+}
+
+/// A handler in a chain, for listing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandlerInfo {
+    pub id: HandlerId,
+    pub stage: Stage,
+    /// Feature or mod that added the handler
+    pub owner: &'static str,
+    pub priority: i32,
+}
+
+type PreFn<A, R> = dyn Fn(&mut A) -> Flow<R> + Send + Sync;
+type PostFn<A, R> = dyn Fn(&A, &mut R) + Send + Sync;
+type Handlers<F> = Mutex<Vec<Arc<Handler<F>>>>;
+
+struct Handler<F: ?Sized> {
+    id: HandlerId,
+    owner: &'static str,
+    priority: i32,
+    function: Box<F>,
+}
+
+/// Inserts after every handler with the same or a higher priority
+fn insert_ordered<F: ?Sized>(handlers: &mut Vec<Arc<Handler<F>>>, handler: Handler<F>) {
+    let index = handlers.partition_point(|other| other.priority >= handler.priority);
+    handlers.insert(index, Arc::new(handler));
+}
+
+/// Ordered pre and post handlers for a function taking the argument tuple `A` and returning `R`
+pub struct HookChain<A, R> {
+    pre: Handlers<PreFn<A, R>>,
+    post: Handlers<PostFn<A, R>>,
+    next_id: AtomicU64,
+}
+
+impl<A, R> Default for HookChain<A, R> {
+    fn default() -> Self {
+        HookChain::new()
+    }
+}
+
+impl<A, R> HookChain<A, R> {
+    pub const fn new() -> Self {
+        HookChain {
+            pre: Mutex::new(Vec::new()),
+            post: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    fn next_id(&self) -> HandlerId {
+        HandlerId(self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Adds a handler that runs before the original function
+    pub fn pre(&self, owner: &'static str, priority: i32, function: impl Fn(&mut A) -> Flow<R> + Send + Sync + 'static) -> HandlerId {
+        let id = self.next_id();
+        insert_ordered(&mut self.pre.lock().unwrap(), Handler { id, owner, priority, function: Box::new(function) });
+        id
+    }
+
+    /// Adds a handler that runs after the original function, or after a pre handler returned early
+    pub fn post(&self, owner: &'static str, priority: i32, function: impl Fn(&A, &mut R) + Send + Sync + 'static) -> HandlerId {
+        let id = self.next_id();
+        insert_ordered(&mut self.post.lock().unwrap(), Handler { id, owner, priority, function: Box::new(function) });
+        id
+    }
+
+    /// Removes a handler, returning whether it was in the chain
+    pub fn remove(&self, id: HandlerId) -> bool {
+        let mut pre = self.pre.lock().unwrap();
+        let mut post = self.post.lock().unwrap();
+        let before = pre.len() + post.len();
+        pre.retain(|handler| handler.id != id);
+        post.retain(|handler| handler.id != id);
+        pre.len() + post.len() != before
+    }
+
+    /// The handlers in the order they run
+    pub fn handlers(&self) -> Vec<HandlerInfo> {
+        let pre = self.pre.lock().unwrap();
+        let post = self.post.lock().unwrap();
+        let info = |stage, id, owner, priority| HandlerInfo { id, stage, owner, priority };
+        pre.iter()
+            .map(|handler| info(Stage::Pre, handler.id, handler.owner, handler.priority))
+            .chain(post.iter().map(|handler| info(Stage::Post, handler.id, handler.owner, handler.priority)))
+            .collect()
+    }
+
+    /// Calls `original` with `args`, running the handlers around it
+    ///
+    /// The handlers are copied out before any of them run, so a handler can add or remove handlers
+    /// and the original function can call back into the chain.
+    pub fn dispatch(&self, mut args: A, original: impl FnOnce(A) -> R) -> R
+    where
+        A: Clone,
+    {
+        let pre = self.pre.lock().unwrap().clone();
+        let post = self.post.lock().unwrap().clone();
+
+        let early_return = pre.iter().find_map(|handler| match (handler.function)(&mut args) {
+            Flow::Continue => None,
+            Flow::Return(result) => Some(result),
+        });
+        let mut result = match early_return {
+            Some(result) => result,
+            None => original(args.clone()),
+        };
+        for handler in &post {
+            (handler.function)(&args, &mut result);
+        }
+        result
+    }
+}
+
+/// A [`HookChain`] on a game function, along with the detour that feeds it
+///
+/// Created by [`hook_chain!`](crate::hook_chain), derefs to the chain.
+pub struct ChainedDetour<A, R> {
+    chain: HookChain<A, R>,
+    module: &'static str,
+    name: &'static str,
+    target: &'static str,
+    address: u32,
+    detour: fn() -> &'static dyn Toggle,
+    _function: PhantomData<fn(A) -> R>,
+}
+
+impl<A, R> ChainedDetour<A, R> {
+    #[doc(hidden)]
+    pub const fn new(module: &'static str, name: &'static str, target: &'static str, address: u32, detour: fn() -> &'static dyn Toggle) -> Self {
+        ChainedDetour {
+            chain: HookChain::new(),
+            module,
+            name,
+            target,
+            address,
+            detour,
+            _function: PhantomData,
+        }
+    }
+
+    pub fn chain(&self) -> &HookChain<A, R> {
+        &self.chain
+    }
+
+    pub fn address(&self) -> u32 {
+        self.address
+    }
+
+    /// Registers and enables the detour, installing it again is a no-op
+    ///
+    /// Handlers can be added before or after, but only run once the chain is installed.
+    ///
+    /// # Safety
+    ///
+    /// The chain's signature must match the game function at its address
+    pub unsafe fn install(&self) -> Result<(), DetourError> {
+        unsafe { registry::register(self.module, self.name, self.target, self.address, self.detour) }
+    }
+
+    /// [`dispatch`](HookChain::dispatch) for the detour's hook, which must not unwind into the game
+    ///
+    /// A panicking handler is reported through [`crash::detour_panicked`], then the game continues with
+    /// the original function, or with its result if it had already run.
+    pub fn dispatch_catching(&self, args: A, original: impl Fn(A) -> R) -> R
+    where
+        A: Clone,
+        R: Clone,
+    {
+        let original_result = Cell::new(None);
+        let fallback_args = args.clone();
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            self.chain.dispatch(args, |args| {
+                let result = original(args);
+                original_result.set(Some(result.clone()));
+                result
+            })
+        }));
+        match result {
+            Ok(result) => result,
+            Err(payload) => {
+                crash::detour_panicked(self.module, self.name, self.target, true, payload);
+                original_result.take().unwrap_or_else(|| original(fallback_args))
+            }
+        }
+    }
+}
+
+impl<A, R> std::ops::Deref for ChainedDetour<A, R> {
+    type Target = HookChain<A, R>;
+
+    fn deref(&self) -> &HookChain<A, R> {
+        &self.chain
+    }
+}
+
+impl<A, R> fmt::Debug for ChainedDetour<A, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChainedDetour")
+            .field("target", &self.target)
+            .field("address", &format_args!("{:#010x}", self.address))
+            .field("handlers", &self.chain.handlers())
+            .finish()
+    }
+}
+
+/// Declares a [`ChainedDetour`] on a game function
+///
+/// ```ignore
+/// openzt_detour::hook_chain! {
+///     /// ZTMapView::placeEntityOnMap
+///     pub static PLACE_ENTITY_ON_MAP = gen::ztmapview::PLACE_ENTITY_ON_MAP_1:
+///         unsafe extern "thiscall" fn(this: u32, entity: u32, pos: f32, rotation: i32) -> u32;
+/// }
+/// ```
+///
+/// The chain's argument type is the tuple of the parameters, `(u32, u32, f32, i32)` here, and
+/// its result is the return type, or `()`.
+#[macro_export]
+macro_rules! hook_chain {
+    (@ret) => { () };
+    (@ret $ret:ty) => { $ret };
+
+    (
+        $(#[$meta:meta])*
+        $vis:vis static $name:ident = $target:path: unsafe extern $abi:literal fn($($arg:ident: $ty:ty),* $(,)?) $(-> $ret:ty)?;
+    ) => {
+        $(#[$meta])*
+        $vis static $name: $crate::chain::ChainedDetour<($($ty,)*), $crate::hook_chain!(@ret $($ret)?)> = {
+            // The game's calling conventions only exist on x86, elsewhere handlers can be added and
+            // dispatched but there is nothing to hook
+            #[cfg(target_arch = "x86")]
+            static DETOUR: ::std::sync::LazyLock<::retour::GenericDetour<unsafe extern $abi fn($($ty),*) $(-> $ret)?>> =
+                ::std::sync::LazyLock::new(|| unsafe { $target.detour(hook).unwrap() });
+
+            #[cfg(target_arch = "x86")]
+            unsafe extern $abi fn hook($($arg: $ty),*) $(-> $ret)? {
+                $name.dispatch_catching(($($arg,)*), |($($arg,)*)| DETOUR.call($($arg),*))
+            }
+
+            #[cfg(target_arch = "x86")]
+            let detour: fn() -> &'static dyn $crate::registry::Toggle = || &*DETOUR;
+            #[cfg(not(target_arch = "x86"))]
+            let detour: fn() -> &'static dyn $crate::registry::Toggle = || unreachable!("game functions can only be hooked on x86");
+
+            $crate::chain::ChainedDetour::new(module_path!(), stringify!($name), stringify!($target), $target.address, detour)
+        };
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::FunctionDef;
+
+    static CALLS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    // Plain function pointers standing in for the game function and for feature handlers
+
+    fn original((a, b): (u32, u32)) -> u32 {
+        CALLS.lock().unwrap().push(format!("original({}, {})", a, b));
+        a + b
+    }
+
+    fn double_first(args: &mut (u32, u32)) -> Flow<u32> {
+        CALLS.lock().unwrap().push("double_first".to_string());
+        args.0 *= 2;
+        Flow::Continue
+    }
+
+    fn block_zero(args: &mut (u32, u32)) -> Flow<u32> {
+        CALLS.lock().unwrap().push("block_zero".to_string());
+        if args.1 == 0 {
+            Flow::Return(0)
+        } else {
+            Flow::Continue
+        }
+    }
+
+    fn add_hundred(_: &(u32, u32), result: &mut u32) {
+        CALLS.lock().unwrap().push("add_hundred".to_string());
+        *result += 100;
+    }
+
+    fn log_args(args: &(u32, u32), result: &mut u32) {
+        CALLS.lock().unwrap().push(format!("log_args({}, {}) -> {}", args.0, args.1, result));
+    }
+
+    /// Runs the chain, returning the result and the calls made
+    fn dispatch(chain: &HookChain<(u32, u32), u32>, args: (u32, u32)) -> (u32, Vec<String>) {
+        let _guard = TEST_LOCK.lock().unwrap();
+        CALLS.lock().unwrap().clear();
+        let result = chain.dispatch(args, original);
+        (result, std::mem::take(&mut *CALLS.lock().unwrap()))
+    }
+
+    static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn test_empty_chain_calls_original() {
+        let chain = HookChain::new();
+        assert_eq!(dispatch(&chain, (1, 2)), (3, vec!["original(1, 2)".to_string()]));
+    }
+
+    #[test]
+    fn test_order_and_argument_changes() {
+        let chain = HookChain::new();
+        chain.post("log", -10, log_args);
+        chain.post("bonus", 0, add_hundred);
+        chain.pre("block", -5, block_zero);
+        chain.pre("double", 5, double_first);
+
+        let (result, calls) = dispatch(&chain, (2, 3));
+        assert_eq!(result, 107);
+        assert_eq!(calls, vec!["double_first", "block_zero", "original(4, 3)", "add_hundred", "log_args(4, 3) -> 107"]);
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        let chain = HookChain::new();
+        chain.pre("block", 1, block_zero);
+        chain.pre("double", 0, double_first);
+        chain.post("log", 0, log_args);
+
+        let (result, calls) = dispatch(&chain, (2, 0));
+        assert_eq!(result, 0);
+        assert_eq!(calls, vec!["block_zero", "log_args(2, 0) -> 0"]);
+    }
+
+    #[test]
+    fn test_equal_priorities_keep_insertion_order() {
+        let chain = HookChain::new();
+        chain.post("bonus", 0, add_hundred);
+        chain.post("log", 0, log_args);
+        let first = chain.pre("double", 0, double_first);
+        chain.pre("double_again", 0, double_first);
+
+        let owners: Vec<_> = chain.handlers().iter().map(|handler| (handler.stage, handler.owner)).collect();
+        assert_eq!(owners, vec![(Stage::Pre, "double"), (Stage::Pre, "double_again"), (Stage::Post, "bonus"), (Stage::Post, "log")]);
+        assert_eq!(dispatch(&chain, (1, 1)).0, 105);
+
+        assert!(chain.remove(first));
+        assert!(!chain.remove(first));
+        assert_eq!(dispatch(&chain, (1, 1)).0, 103);
+    }
+
+    #[test]
+    fn test_handlers_can_modify_chain_while_dispatching() {
+        static CHAIN: HookChain<(u32, u32), u32> = HookChain::new();
+        CHAIN.pre("adds_handler", 0, |_| {
+            CHAIN.post("added", 0, add_hundred);
+            Flow::Continue
+        });
+        // Handlers added during a call run from the next call on
+        assert_eq!(dispatch(&CHAIN, (1, 1)).0, 2);
+        assert_eq!(dispatch(&CHAIN, (1, 1)).0, 102);
+    }
+
+    const ADD: FunctionDef<unsafe extern "C" fn(u32, u32) -> u32> = FunctionDef { address: 0x0040_1000, function_type: PhantomData };
+
+    crate::hook_chain! {
+        /// Never installed, checks the macro's expansion
+        static ADD_CHAIN = ADD: unsafe extern "C" fn(a: u32, b: u32) -> u32;
+    }
+
+    #[test]
+    fn test_panicking_handler_falls_back() {
+        crate::hook_chain! {
+            /// Never installed
+            static PANIC_CHAIN = ADD: unsafe extern "C" fn(a: u32, b: u32) -> u32;
+        }
+        let panics = Arc::new(Mutex::new((true, false)));
+        let (pre, post) = (panics.clone(), panics.clone());
+        PANIC_CHAIN.pre("doubles_then_panics", 0, move |args| {
+            args.0 *= 2;
+            assert!(!pre.lock().unwrap().0, "pre handler panicked");
+            Flow::Continue
+        });
+        PANIC_CHAIN.post("panics", 0, move |_, _| assert!(!post.lock().unwrap().1, "post handler panicked"));
+
+        // The original is called with the game's arguments, not the ones the handler changed
+        let _guard = TEST_LOCK.lock().unwrap();
+        CALLS.lock().unwrap().clear();
+        assert_eq!(PANIC_CHAIN.dispatch_catching((1, 2), original), 3);
+        assert_eq!(std::mem::take(&mut *CALLS.lock().unwrap()), vec!["original(1, 2)"]);
+
+        // After the original ran its result is returned, without calling it again
+        *panics.lock().unwrap() = (false, true);
+        assert_eq!(PANIC_CHAIN.dispatch_catching((1, 2), original), 4);
+        assert_eq!(std::mem::take(&mut *CALLS.lock().unwrap()), vec!["original(2, 2)"]);
+    }
+
+    #[test]
+    fn test_hook_chain_macro() {
+        assert_eq!(ADD_CHAIN.address(), 0x0040_1000);
+        ADD_CHAIN.pre("double", 0, double_first);
+        assert_eq!(dispatch(&ADD_CHAIN, (1, 2)).0, 4);
+        assert!(format!("{:?}", ADD_CHAIN).contains("target: \"ADD\""));
+    }
+}
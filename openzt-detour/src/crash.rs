@@ -0,0 +1,197 @@
+//! Panic handling for detours
+//!
+//! Unwinding out of a detour into the game is undefined behaviour, so `#[detour_mod]` wraps every
+//! detour body in `catch_unwind`. When a body panics the wrapper calls [`detour_panicked`], which
+//! logs the panic and writes a crash report, and then falls back to the original game function, or
+//! to its result if the detour had already called it (or aborts for `#[no_fallback]` detours).
+
+use std::{
+    any::Any,
+    backtrace::Backtrace,
+    cell::RefCell,
+    fmt::Write as _,
+    path::PathBuf,
+    sync::{Mutex, Once},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tracing::error;
+
+/// What the panic hook saw, the backtrace is gone by the time `catch_unwind` returns
+struct PanicDetails {
+    location: Option<String>,
+    backtrace: Backtrace,
+}
+
+thread_local! {
+    static LAST_PANIC: RefCell<Option<PanicDetails>> = const { RefCell::new(None) };
+}
+
+static PANIC_HOOK: Once = Once::new();
+
+static REPORT_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// A panic caught in a detour
+#[derive(Debug)]
+pub struct DetourPanic {
+    /// Module of the `#[detour_mod]` the hook is in
+    pub module: &'static str,
+    /// Name of the hook function
+    pub hook: &'static str,
+    /// Name of the `FunctionDef` the hook detours
+    pub target: &'static str,
+    /// Whether the game continues with the original function, or its result if the hook already called it
+    pub fallback: bool,
+    pub message: String,
+    pub location: Option<String>,
+    pub backtrace: String,
+}
+
+impl DetourPanic {
+    /// Contents of the crash report file
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        let _ = writeln!(report, "OpenZT detour panic");
+        let _ = writeln!(report, "Detour: {}::{} ({})", self.module, self.hook, self.target);
+        let _ = writeln!(report, "Message: {}", self.message);
+        let _ = writeln!(report, "Location: {}", self.location.as_deref().unwrap_or("unknown"));
+        let _ = writeln!(
+            report,
+            "Recovery: {}",
+            if self.fallback { "fell back to the original function" } else { "aborted, detour has no fallback" }
+        );
+        let _ = writeln!(report, "\nBacktrace:\n{}", self.backtrace);
+        report
+    }
+}
+
+/// Installs a panic hook that keeps the backtrace of the last panic on each thread, then runs the
+/// previously installed hook
+///
+/// Called when the first detour is registered, calling it again does nothing.
+pub fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let details = PanicDetails {
+                location: info.location().map(ToString::to_string),
+                backtrace: Backtrace::force_capture(),
+            };
+            LAST_PANIC.with(|last| *last.borrow_mut() = Some(details));
+            previous(info);
+        }));
+    });
+}
+
+/// Sets the directory crash reports are written to, defaults to the directory of the executable
+pub fn set_report_dir(dir: impl Into<PathBuf>) {
+    *REPORT_DIR.lock().unwrap() = Some(dir.into());
+}
+
+fn report_dir() -> Option<PathBuf> {
+    REPORT_DIR
+        .lock()
+        .unwrap()
+        .clone()
+        .or_else(|| std::env::current_exe().ok().and_then(|exe| exe.parent().map(PathBuf::from)))
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".to_string())
+}
+
+/// Builds a [`DetourPanic`] from a panic payload and the backtrace recorded by the panic hook
+pub fn caught(
+    module: &'static str,
+    hook: &'static str,
+    target: &'static str,
+    fallback: bool,
+    payload: &(dyn Any + Send),
+) -> DetourPanic {
+    let details = LAST_PANIC.with(|last| last.borrow_mut().take());
+    DetourPanic {
+        module,
+        hook,
+        target,
+        fallback,
+        message: panic_message(payload),
+        location: details.as_ref().and_then(|details| details.location.clone()),
+        backtrace: details.map(|details| details.backtrace.to_string()).unwrap_or_else(|| "unavailable".to_string()),
+    }
+}
+
+/// Writes the report to `openzt-crash-<unix time>.log` in the report directory, returning its path
+pub fn write_report(panic: &DetourPanic) -> std::io::Result<PathBuf> {
+    let dir = report_dir().ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no crash report directory"))?;
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_millis()).unwrap_or_default();
+    let path = dir.join(format!("openzt-crash-{}.log", timestamp));
+    std::fs::write(&path, panic.report())?;
+    Ok(path)
+}
+
+/// Called by the `#[detour_mod]` wrapper when a detour body panics
+pub fn detour_panicked(module: &'static str, hook: &'static str, target: &'static str, fallback: bool, payload: Box<dyn Any + Send>) {
+    let panic = caught(module, hook, target, fallback, &*payload);
+    error!(
+        "Detour {}::{} ({}) panicked at {}: {}\n{}",
+        panic.module,
+        panic.hook,
+        panic.target,
+        panic.location.as_deref().unwrap_or("unknown location"),
+        panic.message,
+        panic.backtrace
+    );
+    match write_report(&panic) {
+        Ok(path) => error!("Crash report written to {}", path.display()),
+        Err(e) => error!("Failed to write crash report: {}", e),
+    }
+    if fallback {
+        error!("Falling back to the original {}", panic.target);
+    } else {
+        error!("{} has no fallback, aborting", panic.hook);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_caught_panic() {
+        install_panic_hook();
+        let payload = std::panic::catch_unwind(|| panic!("bad entity {}", 7)).unwrap_err();
+        let panic = caught("openztlib::roofs::hooks", "place_entity", "PLACE_ENTITY_ON_MAP_1", true, &*payload);
+        assert_eq!(panic.message, "bad entity 7");
+        assert!(panic.location.as_deref().is_some_and(|location| location.contains("crash.rs")), "{:?}", panic.location);
+        assert_ne!(panic.backtrace, "unavailable");
+
+        let report = panic.report();
+        assert!(report.contains("Detour: openztlib::roofs::hooks::place_entity (PLACE_ENTITY_ON_MAP_1)"), "{}", report);
+        assert!(report.contains("Recovery: fell back to the original function"), "{}", report);
+
+        // The backtrace is only used once
+        let payload = std::panic::catch_unwind(|| std::panic::resume_unwind(Box::new(3))).unwrap_err();
+        let panic = caught("m", "h", "T", false, &*payload);
+        assert_eq!(panic.message, "Box<dyn Any>");
+        assert_eq!(panic.backtrace, "unavailable");
+    }
+
+    #[test]
+    fn test_write_report() {
+        let dir = std::env::temp_dir().join(format!("openzt-crash-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        set_report_dir(&dir);
+        let payload: Box<dyn Any + Send> = Box::new("oops");
+        let panic = caught("m", "hook", "TARGET", false, &*payload);
+        let path = write_report(&panic).unwrap();
+        assert!(path.starts_with(&dir));
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("Message: oops"), "{}", contents);
+        assert!(contents.contains("aborted, detour has no fallback"), "{}", contents);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
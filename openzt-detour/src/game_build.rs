@@ -0,0 +1,321 @@
+//! Detection of the `zoo.exe` build OpenZT is loaded into
+//!
+//! Every address OpenZT uses (the `FunctionDef`s in [`crate::gen`] and the globals in the main crate)
+//! belongs to one specific build of the game. On any other build writing to them corrupts memory, so
+//! the host executable is identified by its PE header and a CRC32 of the file, and
+//! [`crate::registry::register`] refuses to install detours unless the build is in [`KNOWN_BUILDS`].
+//!
+//! Globals that move between builds are declared with [`game_address!`](crate::game_address), which
+//! keeps one address per build and resolves it against the detected build:
+//!
+//! ```ignore
+//! const GLOBAL_BFAPP: GameAddress = game_address!(CompleteCollection: 0x00638148);
+//!
+//! let bfapp = GLOBAL_BFAPP.get();
+//! ```
+
+use std::{fmt, path::Path, sync::OnceLock};
+
+/// A game build OpenZT has addresses for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuildId {
+    /// Zoo Tycoon: Complete Collection, the build `symbols.toml` was taken from
+    CompleteCollection,
+}
+
+/// Identifies one `zoo.exe` file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint {
+    /// `TimeDateStamp` from the COFF header
+    pub timestamp: u32,
+    /// `SizeOfImage` from the optional header
+    pub size_of_image: u32,
+    /// CRC32 of the whole file
+    pub crc32: u32,
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "timestamp {:#010x}, size of image {:#x}, crc32 {:#010x}", self.timestamp, self.size_of_image, self.crc32)
+    }
+}
+
+impl Fingerprint {
+    pub fn of(exe: &[u8]) -> Result<Fingerprint, BuildError> {
+        let header = PeHeader::parse(exe)?;
+        Ok(Fingerprint {
+            timestamp: header.timestamp,
+            size_of_image: header.size_of_image,
+            crc32: crc32fast::hash(exe),
+        })
+    }
+}
+
+/// A known game build
+#[derive(Debug)]
+pub struct GameBuild {
+    pub id: BuildId,
+    pub name: &'static str,
+    /// Every release of the executable that has this build's addresses, e.g. the same build
+    /// repackaged with a different installer
+    pub fingerprints: &'static [Fingerprint],
+}
+
+/// Builds OpenZT supports, each needs its addresses in `symbols.toml` and every [`game_address!`](crate::game_address)
+///
+/// Add a fingerprint here only after checking the build's addresses against a disassembly, the
+/// message for an unknown build prints the fingerprint of the executable it was loaded into.
+pub static KNOWN_BUILDS: &[GameBuild] = &[GameBuild {
+    id: BuildId::CompleteCollection,
+    name: "Zoo Tycoon: Complete Collection",
+    fingerprints: &[],
+}];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    /// The file is not a valid 32-bit PE executable
+    InvalidPe(&'static str),
+    /// A valid executable that is not in [`KNOWN_BUILDS`]
+    Unknown(Fingerprint),
+    Io(String),
+    /// [`detect`] has not run yet
+    NotDetected,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::InvalidPe(reason) => write!(f, "The game executable is not a valid PE file: {}", reason),
+            BuildError::Unknown(fingerprint) => write!(
+                f,
+                "Unsupported Zoo Tycoon build ({}). OpenZT only supports {}, its addresses would corrupt this build's memory",
+                fingerprint,
+                KNOWN_BUILDS.iter().map(|build| build.name).collect::<Vec<_>>().join(", ")
+            ),
+            BuildError::Io(error) => write!(f, "Failed to read the game executable: {}", error),
+            BuildError::NotDetected => write!(f, "The game build has not been detected"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// The parts of a PE header used to identify a build
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeHeader {
+    pub machine: u16,
+    pub timestamp: u32,
+    pub image_base: u32,
+    pub size_of_image: u32,
+    pub checksum: u32,
+}
+
+const IMAGE_FILE_MACHINE_I386: u16 = 0x14c;
+const PE32_MAGIC: u16 = 0x10b;
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+impl PeHeader {
+    /// Parses the DOS, COFF and optional headers at the start of `bytes`, only 32-bit x86 images are accepted
+    pub fn parse(bytes: &[u8]) -> Result<PeHeader, BuildError> {
+        let truncated = BuildError::InvalidPe("truncated header");
+        if bytes.get(0..2) != Some(b"MZ") {
+            return Err(BuildError::InvalidPe("missing MZ signature"));
+        }
+        let pe_offset = read_u32(bytes, 0x3c).ok_or(truncated.clone())? as usize;
+        if bytes.get(pe_offset..pe_offset + 4) != Some(b"PE\0\0") {
+            return Err(BuildError::InvalidPe("missing PE signature"));
+        }
+        let coff = pe_offset + 4;
+        let machine = read_u16(bytes, coff).ok_or(truncated.clone())?;
+        if machine != IMAGE_FILE_MACHINE_I386 {
+            return Err(BuildError::InvalidPe("not a 32-bit x86 image"));
+        }
+        let timestamp = read_u32(bytes, coff + 4).ok_or(truncated.clone())?;
+        let optional = coff + 20;
+        if read_u16(bytes, optional).ok_or(truncated.clone())? != PE32_MAGIC {
+            return Err(BuildError::InvalidPe("not a PE32 optional header"));
+        }
+        Ok(PeHeader {
+            machine,
+            timestamp,
+            image_base: read_u32(bytes, optional + 28).ok_or(truncated.clone())?,
+            size_of_image: read_u32(bytes, optional + 56).ok_or(truncated.clone())?,
+            checksum: read_u32(bytes, optional + 64).ok_or(truncated)?,
+        })
+    }
+}
+
+/// Finds the known build with `fingerprint`
+pub fn identify(fingerprint: &Fingerprint) -> Result<&'static GameBuild, BuildError> {
+    identify_in(KNOWN_BUILDS, fingerprint)
+}
+
+fn identify_in(builds: &'static [GameBuild], fingerprint: &Fingerprint) -> Result<&'static GameBuild, BuildError> {
+    builds
+        .iter()
+        .find(|build| build.fingerprints.contains(fingerprint))
+        .ok_or(BuildError::Unknown(*fingerprint))
+}
+
+static DETECTED: OnceLock<Result<&'static GameBuild, BuildError>> = OnceLock::new();
+
+/// Identifies the executable at `path` and records the result for [`current`]
+///
+/// Only the first call has any effect. With `allow_unknown` an unknown build is treated as the first
+/// known build, for players who have checked their executable matches it.
+pub fn detect(path: &Path, allow_unknown: bool) -> Result<&'static GameBuild, BuildError> {
+    DETECTED
+        .get_or_init(|| {
+            let exe = std::fs::read(path).map_err(|e| BuildError::Io(format!("{}: {}", path.display(), e)))?;
+            match identify(&Fingerprint::of(&exe)?) {
+                Err(BuildError::Unknown(_)) if allow_unknown => Ok(&KNOWN_BUILDS[0]),
+                result => result,
+            }
+        })
+        .clone()
+}
+
+/// Records `build` as the detected build without reading an executable, e.g. in tests
+///
+/// Returns false if a build was already detected.
+pub fn set_detected(build: &'static GameBuild) -> bool {
+    DETECTED.set(Ok(build)).is_ok()
+}
+
+/// The build [`detect`] found
+pub fn current() -> Result<&'static GameBuild, BuildError> {
+    DETECTED.get().cloned().unwrap_or(Err(BuildError::NotDetected))
+}
+
+/// An address that differs between game builds, see [`game_address!`](crate::game_address)
+#[derive(Debug, Clone, Copy)]
+pub struct GameAddress {
+    addresses: &'static [(BuildId, u32)],
+}
+
+impl GameAddress {
+    pub const fn new(addresses: &'static [(BuildId, u32)]) -> GameAddress {
+        GameAddress { addresses }
+    }
+
+    /// The address in `build`, if it has one
+    pub fn for_build(&self, build: BuildId) -> Option<u32> {
+        self.addresses.iter().find(|(id, _)| *id == build).map(|(_, address)| *address)
+    }
+
+    /// The address in the detected build
+    ///
+    /// # Panics
+    ///
+    /// If no supported build was detected, or the build lacks this address. Nothing is hooked on an
+    /// unsupported build, so code reading game addresses can't run there.
+    pub fn get(&self) -> u32 {
+        let build = current().unwrap_or_else(|e| panic!("Game address used without a supported build: {}", e));
+        self.for_build(build.id)
+            .unwrap_or_else(|| panic!("{} has no address for {:?}", build.name, self.addresses))
+    }
+}
+
+/// Declares a [`GameAddress`] with one address per [`BuildId`]
+///
+/// ```ignore
+/// const EXPANSION_CURRENT: GameAddress = game_address!(CompleteCollection: 0x00638d4c);
+/// ```
+#[macro_export]
+macro_rules! game_address {
+    ($($build:ident: $address:expr),+ $(,)?) => {
+        $crate::game_build::GameAddress::new(&[$(($crate::game_build::BuildId::$build, $address)),+])
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Smallest header `PeHeader::parse` accepts: DOS header, PE signature, COFF header and the
+    /// start of a PE32 optional header
+    fn sample_exe(machine: u16, timestamp: u32, size_of_image: u32) -> Vec<u8> {
+        let mut exe = vec![0u8; 0x200];
+        exe[0..2].copy_from_slice(b"MZ");
+        exe[0x3c..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+        exe[0x80..0x84].copy_from_slice(b"PE\0\0");
+        exe[0x84..0x86].copy_from_slice(&machine.to_le_bytes());
+        exe[0x88..0x8c].copy_from_slice(&timestamp.to_le_bytes());
+        exe[0x98..0x9a].copy_from_slice(&PE32_MAGIC.to_le_bytes());
+        exe[0xb4..0xb8].copy_from_slice(&0x00400000u32.to_le_bytes());
+        exe[0xd0..0xd4].copy_from_slice(&size_of_image.to_le_bytes());
+        exe[0xd8..0xdc].copy_from_slice(&0x12345u32.to_le_bytes());
+        exe
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let header = PeHeader::parse(&sample_exe(IMAGE_FILE_MACHINE_I386, 0x3c8a1f2b, 0x2a6000)).unwrap();
+        assert_eq!(
+            header,
+            PeHeader {
+                machine: IMAGE_FILE_MACHINE_I386,
+                timestamp: 0x3c8a1f2b,
+                image_base: 0x00400000,
+                size_of_image: 0x2a6000,
+                checksum: 0x12345,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_headers() {
+        let exe = sample_exe(IMAGE_FILE_MACHINE_I386, 1, 2);
+        assert_eq!(PeHeader::parse(b"ZM"), Err(BuildError::InvalidPe("missing MZ signature")));
+        assert_eq!(PeHeader::parse(&exe[..0x3e]), Err(BuildError::InvalidPe("truncated header")));
+        assert_eq!(PeHeader::parse(&exe[..0xc0]), Err(BuildError::InvalidPe("truncated header")));
+
+        let mut bad_signature = exe.clone();
+        bad_signature[0x82] = b'X';
+        assert_eq!(PeHeader::parse(&bad_signature), Err(BuildError::InvalidPe("missing PE signature")));
+
+        let amd64 = sample_exe(0x8664, 1, 2);
+        assert_eq!(PeHeader::parse(&amd64), Err(BuildError::InvalidPe("not a 32-bit x86 image")));
+
+        let mut pe32_plus = exe;
+        pe32_plus[0x98..0x9a].copy_from_slice(&0x20bu16.to_le_bytes());
+        assert_eq!(PeHeader::parse(&pe32_plus), Err(BuildError::InvalidPe("not a PE32 optional header")));
+    }
+
+    #[test]
+    fn test_identify() {
+        let exe = sample_exe(IMAGE_FILE_MACHINE_I386, 0x3c8a1f2b, 0x2a6000);
+        let fingerprint = Fingerprint::of(&exe).unwrap();
+        assert_eq!(fingerprint.crc32, crc32fast::hash(&exe));
+
+        let error = identify(&fingerprint).unwrap_err();
+        assert_eq!(error, BuildError::Unknown(fingerprint));
+        assert!(error.to_string().starts_with("Unsupported Zoo Tycoon build (timestamp 0x3c8a1f2b, size of image 0x2a6000, crc32 "));
+
+        static BUILDS: &[GameBuild] = &[GameBuild {
+            id: BuildId::CompleteCollection,
+            name: "Test build",
+            fingerprints: &[Fingerprint { timestamp: 1, size_of_image: 2, crc32: 3 }],
+        }];
+        let known = Fingerprint { timestamp: 1, size_of_image: 2, crc32: 3 };
+        assert_eq!(identify_in(BUILDS, &known).unwrap().name, "Test build");
+        assert_eq!(identify_in(BUILDS, &fingerprint).unwrap_err(), BuildError::Unknown(fingerprint));
+    }
+
+    #[test]
+    fn test_game_address() {
+        const ADDRESS: GameAddress = game_address!(CompleteCollection: 0x00638148);
+        assert_eq!(ADDRESS.for_build(BuildId::CompleteCollection), Some(0x00638148));
+
+        set_detected(&KNOWN_BUILDS[0]);
+        assert_eq!(current().unwrap().id, BuildId::CompleteCollection);
+        assert_eq!(ADDRESS.get(), 0x00638148);
+    }
+}
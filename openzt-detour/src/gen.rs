@@ -1,5 +1,5 @@
 // Auto-generated Rust function definitions for Zoo Tycoon
-// Generated from Ghidra analysis
+// Generated by openzt-detour-gen from symbols.toml, edit that file and run `cargo run -p openzt-detour-gen`
 
 #![allow(clippy::type_complexity)]
 
@@ -183,6 +183,7 @@ pub mod bfentity {
 
     pub const INIT_AMBIENT_ANIMS: FunctionDef<unsafe extern "thiscall" fn(u32)> = FunctionDef{address: 0x00401115, function_type: PhantomData};
     pub const GET_TILE: FunctionDef<unsafe extern "thiscall" fn(u32) -> i32> = FunctionDef{address: 0x0040f8ac, function_type: PhantomData};
+    /// `int * __thiscall OOAnalyzer::BFEntity::getFootprint(BFEntity *this,undefined4 *param_1)`
     pub const GET_FOOTPRINT: FunctionDef<unsafe extern "thiscall" fn(u32, u32, bool) -> u32> = FunctionDef{address: 0x0040f916, function_type: PhantomData};
     pub const IS_WALKABLE: FunctionDef<unsafe extern "thiscall" fn(u32)> = FunctionDef{address: 0x0040fbbd, function_type: PhantomData};
     pub const DIR_TO_SET: FunctionDef<unsafe extern "thiscall" fn(u32, i32, u32, i32, u32) -> u32> = FunctionDef{address: 0x0040ff43, function_type: PhantomData};
@@ -202,6 +203,7 @@ pub mod bfentity {
     pub const SET_VISIBLE: FunctionDef<unsafe extern "thiscall" fn(u32, u8)> = FunctionDef{address: 0x0041e0f0, function_type: PhantomData};
     pub const IS_REMOVED_UNDO: FunctionDef<unsafe extern "thiscall" fn(u32)> = FunctionDef{address: 0x0041e1b5, function_type: PhantomData};
     pub const CREATE_NAME: FunctionDef<unsafe extern "thiscall" fn(u32)> = FunctionDef{address: 0x0041e84f, function_type: PhantomData};
+    /// `u32 __thiscall OOAnalyzer::BFEntity::getBlockingRect(BFEntity *this,u32 param_1)`
     pub const GET_BLOCKING_RECT: FunctionDef<unsafe extern "thiscall" fn(u32, u32) -> u32> = FunctionDef{address: 0x0042721a, function_type: PhantomData};
     pub const GET_PLACEMENT_FOOTPRINT: FunctionDef<unsafe extern "thiscall" fn(u32)> = FunctionDef{address: 0x004272d4, function_type: PhantomData};
     pub const CLEAR_BS: FunctionDef<unsafe extern "thiscall" fn(u32) -> u32> = FunctionDef{address: 0x004274de, function_type: PhantomData};
@@ -230,11 +232,13 @@ pub mod bfentity {
     pub const DRAW_UNDERWATER_SECTION: FunctionDef<unsafe extern "thiscall" fn(u32, u32, u32, u32, i32, u32)> = FunctionDef{address: 0x00496b99, function_type: PhantomData};
     pub const GET_HEIGHT: FunctionDef<unsafe extern "thiscall" fn(u32)> = FunctionDef{address: 0x00498d30, function_type: PhantomData};
     pub const SET_BSNEW: FunctionDef<unsafe extern "thiscall" fn(u32, u32, bool, u32) -> u32> = FunctionDef{address: 0x004a7e94, function_type: PhantomData};
+    /// `bool __thiscall OOAnalyzer::BFEntity::isOnTile(BFEntity *this,BFTile *param_1)`
     pub const IS_ON_TILE: FunctionDef<unsafe extern "thiscall" fn(u32, u32) -> bool> = FunctionDef{address: 0x004e16f1, function_type: PhantomData};
     pub const DRAW_SELECTION_GRAPHIC: FunctionDef<unsafe extern "thiscall" fn(u32, i32, i32, u32)> = FunctionDef{address: 0x004ed85d, function_type: PhantomData};
     pub const SET_SELECTED: FunctionDef<unsafe extern "thiscall" fn(u32)> = FunctionDef{address: 0x004ee29a, function_type: PhantomData};
     pub const SEND_EVENT_0: FunctionDef<unsafe extern "thiscall" fn(u32)> = FunctionDef{address: 0x004f2bd5, function_type: PhantomData};
     pub const ADD_TO_MAP: FunctionDef<unsafe extern "thiscall" fn(u32)> = FunctionDef{address: 0x004f421f, function_type: PhantomData};
+    /// `u32 __thiscall OOAnalyzer::BFEntity::getBlockingRect(BFEntity *this,u32 param_1)`
     pub const GET_BLOCKING_RECT_VIRT_ZTPATH: FunctionDef<unsafe extern "thiscall" fn(u32, u32) -> u32> = FunctionDef{address: 0x004fbbee, function_type: PhantomData};
     pub const DESTROY_SELECTION_GRAPHICS: FunctionDef<unsafe extern "stdcall" fn()> = FunctionDef{address: 0x005028c5, function_type: PhantomData};
     pub const SNAP_TO_GRID: FunctionDef<unsafe extern "thiscall" fn(u32)> = FunctionDef{address: 0x00505763, function_type: PhantomData};
@@ -707,6 +711,7 @@ pub mod bftext {
 pub mod bftile {
     use super::*;
 
+    /// `int __thiscall OOAnalyzer::BFTile::getLocalElevation(BFTile *this,BFPos *param_1)`
     pub const GET_LOCAL_ELEVATION: FunctionDef<unsafe extern "thiscall" fn(u32, u32) -> i32> = FunctionDef{address: 0x0040f24d, function_type: PhantomData};
     pub const GET_CORNER_ELEVATION: FunctionDef<unsafe extern "thiscall" fn(u32, i32) -> i32> = FunctionDef{address: 0x0040f4f9, function_type: PhantomData};
     pub const IS_IN_ZOO: FunctionDef<unsafe extern "thiscall" fn(u32, i8) -> u32> = FunctionDef{address: 0x0040fb8d, function_type: PhantomData};
@@ -748,6 +753,7 @@ pub mod bfuimgr {
     pub const DESELECT_ELEMENT: FunctionDef<unsafe extern "stdcall" fn(i32)> = FunctionDef{address: 0x00443dd0, function_type: PhantomData};
     pub const ENABLE_ELEMENT: FunctionDef<unsafe extern "stdcall" fn(i32)> = FunctionDef{address: 0x00443e3e, function_type: PhantomData};
     pub const TRANSLATE_KEY: FunctionDef<unsafe extern "thiscall" fn(u32, u32) -> u32> = FunctionDef{address: 0x0046bbd3, function_type: PhantomData};
+    /// `void __thiscall BFUIMgr::displayMessage(void *this,uint param_1,int param_2,BFTile *param_3,BFEntity *param_4,bool param_5, bool param_6)`
     pub const DISPLAY_MESSAGE_0: FunctionDef<unsafe extern "thiscall" fn(u32, u32, i32, u32, u32, bool, bool)> = FunctionDef{address: 0x0049ccc3, function_type: PhantomData};
     pub const DISPLAY_MESSAGE_1: FunctionDef<unsafe extern "thiscall" fn(u32, u32, i32, u32, u32, bool, bool)> = FunctionDef{address: 0x0049cec0, function_type: PhantomData};
     pub const SHOW_LAST_DIALOG: FunctionDef<unsafe extern "thiscall" fn(u32)> = FunctionDef{address: 0x004a09cc, function_type: PhantomData};
@@ -2034,6 +2040,7 @@ pub mod zthabitat {
     pub const SET_DIRTY_CHARACTERISTICS: FunctionDef<unsafe extern "thiscall" fn(u32)> = FunctionDef{address: 0x0040f5a9, function_type: PhantomData};
     pub const IS_SHOW_TANK: FunctionDef<unsafe extern "thiscall" fn(u32) -> u32> = FunctionDef{address: 0x0040fba2, function_type: PhantomData};
     pub const GET_SHOW_INFO_ID: FunctionDef<unsafe extern "thiscall" fn(u32) -> u32> = FunctionDef{address: 0x0040fbc7, function_type: PhantomData};
+    /// `BFTile * __thiscall OOAnalyzer::ZTHabitat::getGateTileIn(ZTHabitat *this)`
     pub const GET_GATE_TILE_IN: FunctionDef<unsafe extern "thiscall" fn(u32) -> u32> = FunctionDef{address: 0x00410349, function_type: PhantomData};
     pub const GET_ALL_ANIMALS: FunctionDef<unsafe extern "thiscall" fn(u32, i8) -> u32> = FunctionDef{address: 0x00410def, function_type: PhantomData};
     pub const GET_GATE_TILE_OUT: FunctionDef<unsafe extern "thiscall" fn(u32) -> i32> = FunctionDef{address: 0x00411285, function_type: PhantomData};
@@ -3668,4 +3675,4 @@ pub mod standalone {
     pub const IMM_NOTIFY_IME: FunctionDef<unsafe extern "stdcall" fn(u32, i32, i32, i32) -> bool> = FunctionDef{address: 0x0062c7d3, function_type: PhantomData};
     pub const IMM_GET_OPEN_STATUS: FunctionDef<unsafe extern "stdcall" fn(u32) -> bool> = FunctionDef{address: 0x0062c7d9, function_type: PhantomData};
     pub const IMM_GET_CONTEXT: FunctionDef<unsafe extern "stdcall" fn(u32) -> u32> = FunctionDef{address: 0x0062c7df, function_type: PhantomData};
-}
\ No newline at end of file
+}
@@ -2,7 +2,13 @@ use std::marker::PhantomData;
 
 use retour::GenericDetour;
 
+pub mod chain;
+pub mod crash;
+pub mod game_build;
+// Only called on x86, where the game's calling conventions exist
+#[cfg_attr(not(target_arch = "x86"), allow(unsupported_fn_ptr_calling_conventions))]
 pub mod gen;
+pub mod registry;
 
 pub struct FunctionDef<T> {
     pub address: u32,
@@ -21,7 +27,21 @@ impl<T> FunctionDef<T> where T: retour::Function {
     /// # Safety
     /// 
     /// This function will cause issues if the address is not correct
+    #[cfg(target_arch = "x86")]
     pub unsafe fn original(&self) -> T {
         unsafe { ::retour::Function::from_ptr(self.address as *const ()) }
     }
 }
+
+// retour only implements `retour::Function` for the game's calling conventions on x86, so elsewhere
+// `original` isn't bound on it and code calling game functions still compiles
+#[cfg(not(target_arch = "x86"))]
+impl<T: Copy> FunctionDef<T> {
+    /// # Safety
+    /// 
+    /// This function will cause issues if the address is not correct
+    pub unsafe fn original(&self) -> T {
+        assert_eq!(std::mem::size_of::<T>(), std::mem::size_of::<usize>(), "FunctionDef must be a function pointer");
+        unsafe { std::mem::transmute_copy(&(self.address as usize)) }
+    }
+}
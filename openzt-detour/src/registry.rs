@@ -0,0 +1,304 @@
+//! Registry of every detour installed by a `#[detour_mod]` module
+//!
+//! The `init_detours()` function generated by `#[detour_mod]` registers each of its detours here
+//! before enabling it. Only one detour can hook a game function, so registering a second hook on an
+//! address that is already taken fails with [`DetourError::Conflict`] naming both hooks instead of
+//! silently replacing or stacking on the first. Registered detours can be listed and switched on
+//! and off at runtime for debugging.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{LazyLock, Mutex},
+};
+
+use retour::GenericDetour;
+
+use crate::game_build::{self, BuildError};
+
+/// A detour that can be switched on and off
+pub trait Toggle: Sync {
+    /// # Safety
+    ///
+    /// The detour's target must be a valid function with the detour's signature
+    unsafe fn enable(&self) -> retour::Result<()>;
+
+    /// # Safety
+    ///
+    /// The detour's target must be a valid function with the detour's signature
+    unsafe fn disable(&self) -> retour::Result<()>;
+
+    fn is_enabled(&self) -> bool;
+}
+
+impl<T: retour::Function> Toggle for GenericDetour<T> {
+    unsafe fn enable(&self) -> retour::Result<()> {
+        unsafe { GenericDetour::enable(self) }
+    }
+
+    unsafe fn disable(&self) -> retour::Result<()> {
+        unsafe { GenericDetour::disable(self) }
+    }
+
+    fn is_enabled(&self) -> bool {
+        GenericDetour::is_enabled(self)
+    }
+}
+
+/// A registered detour
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetourInfo {
+    /// Module of the `#[detour_mod]` the hook is in
+    pub module: &'static str,
+    /// Name of the hook function
+    pub hook: &'static str,
+    /// Name of the `FunctionDef` the hook detours
+    pub target: &'static str,
+    pub address: u32,
+    pub enabled: bool,
+}
+
+impl DetourInfo {
+    /// Full path of the hook function, e.g. `openztlib::roofs::hooks_place_entity::place_entity_on_map`
+    pub fn path(&self) -> String {
+        format!("{}::{}", self.module, self.hook)
+    }
+}
+
+impl fmt::Display for DetourInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:#010x} {} -> {} [{}]",
+            self.address,
+            self.target,
+            self.path(),
+            if self.enabled { "enabled" } else { "disabled" }
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum DetourError {
+    /// Another hook already detours the address
+    Conflict { address: u32, target: &'static str, existing: String, new: String },
+    /// No registered detour matches the query
+    NotFound(String),
+    /// More than one registered detour matches the query
+    Ambiguous(String, Vec<String>),
+    /// The game build is unknown, so its addresses can't be trusted
+    UnsupportedBuild(BuildError),
+    Retour(retour::Error),
+}
+
+impl fmt::Display for DetourError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DetourError::Conflict { address, target, existing, new } => write!(
+                f,
+                "{} ({:#010x}) is already detoured by {}, can't also detour it with {}",
+                target, address, existing, new
+            ),
+            DetourError::NotFound(query) => write!(f, "No detour matches '{}'", query),
+            DetourError::Ambiguous(query, matches) => write!(f, "'{}' matches more than one detour: {}", query, matches.join(", ")),
+            DetourError::UnsupportedBuild(error) => write!(f, "{}", error),
+            DetourError::Retour(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for DetourError {}
+
+impl From<retour::Error> for DetourError {
+    fn from(error: retour::Error) -> Self {
+        DetourError::Retour(error)
+    }
+}
+
+struct Entry {
+    module: &'static str,
+    hook: &'static str,
+    target: &'static str,
+    detour: &'static dyn Toggle,
+}
+
+impl Entry {
+    fn info(&self, address: u32) -> DetourInfo {
+        DetourInfo {
+            module: self.module,
+            hook: self.hook,
+            target: self.target,
+            address,
+            enabled: self.detour.is_enabled(),
+        }
+    }
+}
+
+static DETOURS: LazyLock<Mutex<BTreeMap<u32, Entry>>> = LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+/// Registers and enables a detour, `detour` creates it once the address is known to be free
+///
+/// Registering the same hook again only enables it again. Fails on a game build that is not in
+/// [`game_build::KNOWN_BUILDS`].
+///
+/// # Safety
+///
+/// `address` must be a valid function with the detour's signature
+pub unsafe fn register(
+    module: &'static str,
+    hook: &'static str,
+    target: &'static str,
+    address: u32,
+    detour: impl FnOnce() -> &'static dyn Toggle,
+) -> Result<(), DetourError> {
+    game_build::current().map_err(DetourError::UnsupportedBuild)?;
+    unsafe { register_bootstrap(module, hook, target, address, detour) }
+}
+
+/// Registers and enables a detour without checking the game build, for the one hook installed from
+/// `DllMain` that runs [`game_build::detect`] once the loader lock is released
+///
+/// # Safety
+///
+/// `address` must be a valid function with the detour's signature
+pub unsafe fn register_bootstrap(
+    module: &'static str,
+    hook: &'static str,
+    target: &'static str,
+    address: u32,
+    detour: impl FnOnce() -> &'static dyn Toggle,
+) -> Result<(), DetourError> {
+    crate::crash::install_panic_hook();
+    let mut detours = DETOURS.lock().unwrap();
+    if let Some(entry) = detours.get(&address).filter(|entry| entry.module != module || entry.hook != hook) {
+        return Err(DetourError::Conflict {
+            address,
+            target,
+            existing: entry.info(address).path(),
+            new: format!("{}::{}", module, hook),
+        });
+    }
+    let entry = detours.entry(address).or_insert_with(|| Entry { module, hook, target, detour: detour() });
+    unsafe { entry.detour.enable()? };
+    Ok(())
+}
+
+/// All registered detours, by address
+pub fn detours() -> Vec<DetourInfo> {
+    DETOURS.lock().unwrap().iter().map(|(address, entry)| entry.info(*address)).collect()
+}
+
+/// Finds a detour by address (`0x...`), target name or hook path, a suffix of the path such as
+/// `roofs::hooks_place_entity::place_entity_on_map` is enough
+pub fn find(query: &str) -> Result<DetourInfo, DetourError> {
+    let address = query.strip_prefix("0x").and_then(|hex| u32::from_str_radix(hex, 16).ok());
+    let matches: Vec<DetourInfo> = detours()
+        .into_iter()
+        .filter(|info| Some(info.address) == address || info.target == query || info.path() == query || info.path().ends_with(&format!("::{}", query)))
+        .collect();
+    match matches.as_slice() {
+        [] => Err(DetourError::NotFound(query.to_string())),
+        [info] => Ok(info.clone()),
+        _ => Err(DetourError::Ambiguous(query.to_string(), matches.iter().map(DetourInfo::path).collect())),
+    }
+}
+
+/// Enables or disables a registered detour, returning it with its new state
+///
+/// # Safety
+///
+/// Disabling a detour restores the original function, so the game must not depend on the hook
+/// having run, e.g. to keep state it set up in sync
+pub unsafe fn set_enabled(address: u32, enabled: bool) -> Result<DetourInfo, DetourError> {
+    let detours = DETOURS.lock().unwrap();
+    let entry = detours.get(&address).ok_or_else(|| DetourError::NotFound(format!("{:#010x}", address)))?;
+    unsafe {
+        if enabled {
+            entry.detour.enable()?;
+        } else {
+            entry.detour.disable()?;
+        }
+    }
+    Ok(entry.info(address))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+
+    struct FakeDetour(AtomicBool);
+
+    impl FakeDetour {
+        fn leak() -> &'static FakeDetour {
+            Box::leak(Box::new(FakeDetour(AtomicBool::new(false))))
+        }
+    }
+
+    impl Toggle for FakeDetour {
+        unsafe fn enable(&self) -> retour::Result<()> {
+            self.0.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+
+        unsafe fn disable(&self) -> retour::Result<()> {
+            self.0.store(false, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn is_enabled(&self) -> bool {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    // Tests share the registry, so each one uses its own addresses
+
+    fn detect_supported_build() {
+        game_build::set_detected(&game_build::KNOWN_BUILDS[0]);
+    }
+
+    #[test]
+    fn test_register() {
+        detect_supported_build();
+        unsafe { register("openztlib::roofs::hooks", "place_entity", "PLACE_ENTITY_ON_MAP_1", 0x1000, || FakeDetour::leak()) }.unwrap();
+        let info = find("0x1000").unwrap();
+        assert_eq!(info.path(), "openztlib::roofs::hooks::place_entity");
+        assert_eq!(info.target, "PLACE_ENTITY_ON_MAP_1");
+        assert!(info.enabled);
+        assert!(detours().contains(&info));
+
+        // Registering the same hook again is fine
+        unsafe { register("openztlib::roofs::hooks", "place_entity", "PLACE_ENTITY_ON_MAP_1", 0x1000, || unreachable!()) }.unwrap();
+    }
+
+    #[test]
+    fn test_conflict() {
+        detect_supported_build();
+        unsafe { register("openztlib::a::hooks", "update", "UPDATE_SIM", 0x2000, || FakeDetour::leak()) }.unwrap();
+        let error = unsafe { register("openztlib::b::hooks", "update", "UPDATE_SIM", 0x2000, || unreachable!()) }.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "UPDATE_SIM (0x00002000) is already detoured by openztlib::a::hooks::update, can't also detour it with openztlib::b::hooks::update"
+        );
+        assert_eq!(find("UPDATE_SIM").unwrap().module, "openztlib::a::hooks");
+    }
+
+    #[test]
+    fn test_find_and_toggle() {
+        detect_supported_build();
+        unsafe { register("openztlib::c::hooks", "load", "LOAD", 0x3000, || FakeDetour::leak()) }.unwrap();
+        unsafe { register("openztlib::d::hooks", "load", "LOAD_2", 0x3004, || FakeDetour::leak()) }.unwrap();
+
+        assert_eq!(find("c::hooks::load").unwrap().address, 0x3000);
+        assert_eq!(find("LOAD_2").unwrap().address, 0x3004);
+        assert!(matches!(find("hooks::load"), Err(DetourError::Ambiguous(_, matches)) if matches.len() == 2));
+        assert!(matches!(find("0x3008"), Err(DetourError::NotFound(_))));
+
+        let info = unsafe { set_enabled(0x3000, false) }.unwrap();
+        assert!(!info.enabled);
+        assert!(!find("0x3000").unwrap().enabled);
+        assert!(unsafe { set_enabled(0x3000, true) }.unwrap().enabled);
+        assert!(matches!(unsafe { set_enabled(0x3008, true) }, Err(DetourError::NotFound(_))));
+    }
+}
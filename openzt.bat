@@ -387,6 +387,8 @@ echo   openzt.bat docs                      Generate and open docs
 echo   openzt.bat console                   Open interactive Lua console
 echo   openzt.bat console --oneshot "help()"          Run single Lua command and exit
 echo   openzt.bat console --oneshot "add_cash(10000)" Add cash via oneshot command
+echo   openzt.bat console --json --oneshot "get_date()" Print the result as JSON
+echo   openzt.bat console --file setup.lua      Run a Lua script file and exit
 echo   openzt.bat run --wait                Build debug, launch game, wait for exit
 echo   openzt.bat run --release --wait      Build release, launch game, wait for exit
 echo.
@@ -3,7 +3,9 @@ use std::{fmt, ops::Deref};
 
 use field_accessor_as_string::FieldAccessorAsString;
 use field_accessor_as_string_trait::FieldAccessorAsStringTrait;
+use game_struct_layout::game_struct;
 use getset::{Getters, Setters};
+use mlua::{IntoLua, MetaMethod, UserData, UserDataMethods, UserDataRef};
 use num_enum::FromPrimitive;
 use tracing::info;
 
@@ -11,6 +13,7 @@ use crate::{
     command_console::CommandError,
     expansions::is_member,
     lua_fn,
+    scripting::{userdata, LuaType},
     util::{get_from_memory, get_string_from_memory, map_from_memory, Checkable},
     ztui::get_selected_entity_type_address,
     ztworldmgr,
@@ -43,30 +46,32 @@ pub trait EntityType: FieldAccessorAsStringTrait {
     }
 }
 
+#[game_struct]
+#[size(0x100)]
 #[derive(Debug, Getters, Setters, FieldAccessorAsString)]
 #[repr(C)]
 pub struct BFEntityType {
     vtable: u32,                      // 0x000
-    pad1: [u8; 0x034],                // ----------------------- padding: 52 bytes
-    pub ncolors: u32,                 // 0x038
-    pad2: [u8; 0x050 - 0x03C],        // ----------------------- padding: 20 bytes
-    pub icon_zoom: bool,              // 0x050
-    pad3: [u8; 0x054 - 0x051],        // ----------------------- padding: 3 bytes
-    pub expansion_id: bool,           // 0x054
+    #[offset(0x038)]
+    pub ncolors: u32,
+    #[offset(0x050)]
+    pub icon_zoom: bool,
+    #[offset(0x054)]
+    pub expansion_id: bool,
     pub movable: bool,                // 0x055
     pub walkable: bool,               // 0x056
     pub walkable_by_tall: bool,       // 0x057
-    pad4: [u8; 0x059 - 0x058],        // ----------------------- padding: 1 byte
-    pub rubbleable: bool,             // 0x059
-    pad5: [u8; 0x05B - 0x05A],        // ----------------------- padding: 1 byte
-    pub use_numbers_in_name: bool,    // 0x05B
+    #[offset(0x059)]
+    pub rubbleable: bool,
+    #[offset(0x05B)]
+    pub use_numbers_in_name: bool,
     pub uses_real_shadows: bool,      // 0x05C
     pub has_shadow_images: bool,      // 0x05D
     pub force_shadow_black: bool,     // 0x05E
-    pad6: [u8; 0x060 - 0x05F],        // ----------------------- padding: 1 byte
-    pub draws_late: bool,             // 0x060
-    pad7: [u8; 0x064 - 0x061],        // ----------------------- padding: 3 bytes
-    pub height: u32,                  // 0x064
+    #[offset(0x060)]
+    pub draws_late: bool,
+    #[offset(0x064)]
+    pub height: u32,
     pub depth: u32,                   // 0x068
     pub has_underwater_section: bool, // 0x06C
     pub is_transient: bool,           // 0x06D
@@ -75,20 +80,19 @@ pub struct BFEntityType {
     pub hit_threshold: u32,           // 0x070
     pub avoid_edges: bool,            // 0x074
     // TODO: Add to display impl and test these bits, if they work replace usage of ZTEntityType with BFEntityType
-    pad8: [u8; 0x080 - 0x075],        // ----------------------- padding: 11 bytes
-    pub bf_config_file_ptr: u32,        // 0x080
-    pad9: [u8; 0x098 - 0x084],        // ----------------------- padding: 20 bytes
-    pub zt_type: ZTBoundedString,     // 0x098
+    #[offset(0x080)]
+    pub bf_config_file_ptr: u32,
+    #[offset(0x098)]
+    pub zt_type: ZTBoundedString,
     pub zt_sub_type: ZTBoundedString, // 0x0A4
-    pad10: [u8; 0x0B4 - 0x0A8],       // ----------------------- padding: 4 bytes
-    pub footprintx: i32,              // 0x0B4
+    #[offset(0x0B4)]
+    pub footprintx: i32,
     pub footprinty: i32,              // 0x0B8
     pub footprintz: i32,              // 0x0BC
     pub placement_footprintx: i32,    // 0x0C0
     pub placement_footprinty: i32,    // 0x0C4
     pub placement_footprintz: i32,    // 0x0C8
     pub available_at_startup: bool,   // 0x0CC
-    pad11: [u8; 0x100 - 0x0CD],       // ----------------------- padding: 35 bytes
 }
 
 impl BFEntityType {
@@ -180,6 +184,8 @@ impl EntityType for BFEntityType {
 // Should be able to use the Type init functions to get very accurate sizes and to confirm what strings correspond to what memory locations
 // Can also figure out some of the gaps using *Type::loadCharacteristics()
 // Does BF/ZTUnitType also load purchase cost, name id etc?
+#[game_struct]
+#[size(0x168)]
 #[derive(Debug, Getters, Setters, FieldAccessorAsString)]
 #[repr(C)]
 pub struct ZTSceneryType {
@@ -193,14 +199,14 @@ pub struct ZTSceneryType {
     pub era: u32,                    // 0x114
     pub max_food_units: u32,         // 0x118
     pub stink: bool,                 // 0x11C
-    pad3: [u8; 0x120 - 0x11D],       // ----------------------- padding: 3 bytes
-    pub esthetic_weight: u32,        // 0x120
-    pad4: [u8; 0x128 - 0x124],       // ----------------------- padding: 4 bytes
-    pub selectable: bool,            // 0x128
+    #[offset(0x120)]
+    pub esthetic_weight: u32,
+    #[offset(0x128)]
+    pub selectable: bool,
     pub deletable: bool,             // 0x129
     pub foliage: bool,               // 0x12A
-    pad6: [u8; 0x12D - 0x12B],       // ----------------------- padding: 2 bytes
-    pub auto_rotate: bool,           // 0x12D
+    #[offset(0x12D)]
+    pub auto_rotate: bool,
     pub land: bool,                  // 0x12E
     pub swims: bool,                 // 0x12F
     pub underwater: bool,            // 0x130
@@ -215,7 +221,6 @@ pub struct ZTSceneryType {
     pub uses_tree_rubble: bool,      // 0x139
     pub forces_scenery_rubble: bool, // 0x13A
     pub blocks_los: bool,            // 0x13B
-    pad7: [u8; 0x168 - 0x13C],       // ----------------------- padding: 51 bytes
 }
 
 impl ZTSceneryType {
@@ -290,13 +295,14 @@ impl Checkable for ZTSceneryType {
 }
 
 // ------------ ZTBuildingType, Implementation, and Related Functions ------------ //
+#[game_struct]
 #[derive(Debug, Getters, Setters, FieldAccessorAsString)]
 #[repr(C)]
 struct ZTBuildingType {
     #[deref_field]
     pub ztscenerytype: ZTSceneryType, // bytes: 0x168 - 0x000 = 0x16C = 364 bytes
-    pad0: [u8; 0x16C - 0x168],                      // -------------------------- padding: 4 bytes
-    pub i_capacity: i32,                            // 0x16C
+    #[offset(0x16C)]
+    pub i_capacity: i32,
     pub toy_satisfaction: i32,                      // 0x170
     pub time_inside: i32,                           // 0x174
     pub default_cost: f32,                          // 0x178
@@ -305,8 +311,8 @@ struct ZTBuildingType {
     pub high_cost: f32,                             // 0x184
     pub price_factor: f32,                          // 0x188
     pub upkeep: f32,                                // 0x18C
-    pad1: [u8; 0x194 - 0x190],                      // -------------------------- padding: 4 bytes
-    pub hide_user: bool,                            // 0x194
+    #[offset(0x194)]
+    pub hide_user: bool,
     pub set_letter_facing: bool,                    // 0x195
     pub draw_user: bool,                            // 0x196
     pub hide_cost_change: bool,                     // 0x197
@@ -316,16 +322,16 @@ struct ZTBuildingType {
     pub user_tracker: bool,                         // 0x19B
     pub idler: bool,                                // 0x19C
     pub exhibit_viewer: bool,                       // 0x19D
-    pad2: [u8; 0x1A0 - 0x19E],                      // -------------------------- padding: 2 bytes
-    pub alternate_panel_title: u32,                 // 0x1A0
+    #[offset(0x1A0)]
+    pub alternate_panel_title: u32,
     pub direct_entrance: bool,                      // 0x1A4
     pub hide_building: bool,                        // 0x1A5
     pub user_stays_outside: bool,                   // 0x1A6
     pub user_teleports_inside: bool,                // 0x1A7
     pub user_uses_exit: bool,                       // 0x1A8
     pub user_uses_entrance_as_emergency_exit: bool, // 0x1A9
-    pad3: [u8; 0x1B8 - 0x1AA],                      // -------------------------- padding: 9 bytes
-    pub adult_change: i32,                          // 0x1B8
+    #[offset(0x1B8)]
+    pub adult_change: i32,
     pub child_change: i32,                          // 0x1BC
     pub hunger_change: i32,                         // 0x1C0
     pub thirst_change: i32,                         // 0x1C4
@@ -399,6 +405,7 @@ impl Deref for ZTBuildingType {
 
 // ------------ ZTFenceType, Implementation, and Related Functions ------------ //
 
+#[game_struct]
 #[derive(Debug, Getters, Setters, FieldAccessorAsString)]
 #[repr(C)]
 pub struct ZTFenceType {
@@ -412,8 +419,8 @@ pub struct ZTFenceType {
     pub open_sound_atten: i32,  // 0x17C
     // break_sound: String, // 0x184
     // open_sound: String, // 0x188
-    pad2: [u8; 0x194 - 0x180], // ----------------------- padding: 20 bytes
-    pub see_through: bool,     // 0x194
+    #[offset(0x194)]
+    pub see_through: bool,
     pub is_jumpable: bool,     // 0x195
     pub is_climbable: bool,    // 0x196
     pub indestructible: bool,  // 0x197
@@ -572,6 +579,7 @@ impl Deref for ZTFoodType {
 
 // ------------ ZTTankeFilterType, Implementation, and Related Functions ------------ //
 
+#[game_struct]
 #[derive(Debug, Getters, Setters, FieldAccessorAsString)]
 #[repr(C)]
 pub struct ZTTankFilterType {
@@ -586,8 +594,8 @@ pub struct ZTTankFilterType {
     pub filter_decayed_clean_amount: i32, // 0x180
     // healthy_sound: String, // 0x184
     // decayed_sound: String, // 0x190
-    pad1: [u8; 0x19C - 0x184], // ----------------------- padding: 24 bytes
-    pub healthy_atten: i32,    // 0x19C
+    #[offset(0x19C)]
+    pub healthy_atten: i32,
     pub decayed_atten: i32,    // 0x1A0
 }
 
@@ -687,6 +695,7 @@ impl Deref for ZTPathType {
 
 // ------------ ZTRubbleType, Implementation, and Related Functions ------------ //
 
+#[game_struct]
 #[derive(Debug, Getters, Setters, FieldAccessorAsString)]
 #[repr(C)]
 pub struct ZTRubbleType {
@@ -694,9 +703,8 @@ pub struct ZTRubbleType {
     ztscenerytype: ZTSceneryType,
     // bytes: 0x168 - 0x000 = 0x168 = 360 bytes
     // explosion_sound: String, // 0x168
-    pad0: [u8; 0x16C - 0x168],
-    // ----------------------- padding: 4 bytes
-    pub explosion_sound_atten: i32, // 0x16C
+    #[offset(0x16C)]
+    pub explosion_sound_atten: i32,
 }
 
 impl ZTRubbleType {
@@ -738,6 +746,7 @@ impl Deref for ZTRubbleType {
 
 // ------------ BFUnitType, Implementation, and Related Functions ------------ //
 
+#[game_struct]
 #[derive(Debug, Getters, Setters, FieldAccessorAsString)]
 #[repr(C)]
 pub struct BFUnitType {
@@ -749,8 +758,8 @@ pub struct BFUnitType {
     pub slow_anim_speed: u16,   // 0x10C
     pub medium_anim_speed: u16, // 0x10E
     pub fast_anim_speed: u16,   // 0x110
-    pad0: [u8; 0x114 - 0x112],  // ----------------------- padding: 2 bytes
-    pub min_height: u32,        // 0x114 <--- unsure if accurate
+    #[offset(0x114)]
+    pub min_height: u32,        // <--- unsure if accurate
     pub max_height: u32,        // 0x118 <--- unsure if accurate
 }
 
@@ -792,28 +801,29 @@ impl Deref for BFUnitType {
 
 // ------------ ZTUnitType, Implementation, and Related Functions ------------ //
 
+#[game_struct]
 #[derive(Debug, Getters, Setters, FieldAccessorAsString)]
 #[repr(C)]
 pub struct ZTUnitType {
     #[deref_field]
     pub bfunit_type: BFUnitType, // bytes: 0x11C - 0x100 = 0x1C = 28 bytes
-    pad0: [u8; 0x12C - 0x11C],        // ----------------------- padding: 16 bytes
-    pub purchase_cost: f32,           // 0x12C
+    #[offset(0x12C)]
+    pub purchase_cost: f32,
     pub name_id: i32,                 // 0x130
     pub help_id: i32,                 // 0x134
-    pad1: [u8; 0x150 - 0x138],        // ----------------------- padding: 24 bytes
-    pub map_footprint: i32,           // 0x150
+    #[offset(0x150)]
+    pub map_footprint: i32,
     pub slow_anim_speed_water: u16,   // 0x154
     pub medium_anim_speed_water: u16, // 0x156
     pub fast_anim_speed_water: u16,   // 0x158
-    pad2: [u8; 0x17C - 0x15C],        // ----------------------- padding: 32 bytes
     // pub list_image_name: String,    // 0x168 TODO: fix offset for string getters in unittype
-    pub swims: bool,               // 0x17C
+    #[offset(0x17C)]
+    pub swims: bool,
     pub surface: bool,             // 0x17D
     pub underwater: bool,          // 0x17E
     pub only_underwater: bool,     // 0x17F
-    pad3: [u8; 0x180 - 0x17F],     // ----------------------- padding: 1 byte
-    pub skip_trick_happiness: u32, // 0x180 TODO: potentially not accurate
+    #[offset(0x180)]
+    pub skip_trick_happiness: u32, // TODO: potentially not accurate
     pub skip_trick_chance: i32,    // 0x184
 }
 
@@ -859,13 +869,14 @@ impl EntityType for ZTUnitType {
 
 // ------------ ZTGuestType, Implementation, and Related Functions ------------ //
 
+#[game_struct]
 #[derive(Debug, Getters, Setters, FieldAccessorAsString)]
 #[repr(C)]
 pub struct ZTGuestType {
     #[deref_field]
     pub ztunit_type: ZTUnitType, // bytes: 0x188 - 0x100 = 0x88 = 136 bytes
-    pad00: [u8; 0x1B4 - 0x188],                    // ----------------------- padding: 44 bytes
-    pub hunger_check: i32,                         // 0x1B4
+    #[offset(0x1B4)]
+    pub hunger_check: i32,
     pub thirsty_check: i32,                        // 0x1B8
     pub bathroom_check: i32,                       // 0x1BC
     pub leave_zoo_check: i32,                      // 0x1C0
@@ -877,12 +888,12 @@ pub struct ZTGuestType {
     pub viewing_area_check: i32,                   // 0x1D8
     pub environment_effect_check: i32,             // 0x1DC
     pub saw_animal_reset: i32,                     // 0x1E0
-    pad01: [u8; 0x1E8 - 0x1E4],                    // ----------------------- padding: 4 bytes
-    pub initial_happiness: i32,                    // 0x1E8
-    pad02: [u8; 0x200 - 0x1EC],                    // ----------------------- padding: 20 bytes
-    pub max_energy: i32,                           // 0x200
-    pad03: [u8; 0x210 - 0x204],                    // ----------------------- padding: 12 bytes
-    pub energy_increment: i32,                     // 0x210
+    #[offset(0x1E8)]
+    pub initial_happiness: i32,
+    #[offset(0x200)]
+    pub max_energy: i32,
+    #[offset(0x210)]
+    pub energy_increment: i32,
     pub energy_threshold: i32,                     // 0x214
     pub angry_energy_change: i32,                  // 0x218
     pub hunger_increment: i32,                     // 0x21C
@@ -1021,25 +1032,26 @@ impl Deref for ZTGuestType {
 
 // ------------ ZTAnimalType, Implementation, and Related Functions ------------ //
 
+#[game_struct]
 #[derive(Debug, Getters, Setters, FieldAccessorAsString)]
 #[repr(C)]
 pub struct ZTAnimalType {
     #[deref_field]
     pub ztunit_type: ZTUnitType, // bytes: 0x188 - 0x100 = 0x88 = 136 bytes
-    pad00: [u8; 0x1D8 - 0x188],         // ----------------------- padding: 72 bytes
-    pub box_footprint_x: i32,           // 0x1D8
+    #[offset(0x1D8)]
+    pub box_footprint_x: i32,
     pub box_footprint_y: i32,           // 0x1DC
     pub box_footprint_z: i32,           // 0x1E0
     pub family: i32,                    // 0x1E4
     pub genus: i32,                     // 0x1E8
-    pad01: [u8; 0x1F0 - 0x1EC],         // ----------------------- padding: 4 bytes
-    pub habitat: i32,                   // 0x1F0
+    #[offset(0x1F0)]
+    pub habitat: i32,
     pub location: i32,                  // 0x1F4
     pub era: i32,                       // 0x1F8
     pub breath_threshold: i32,          // 0x1FC
     pub breath_increment: i32,          // 0x200
-    pad02: [u8; 0x20C - 0x204],         // ----------------------- padding: 8 bytes
-    pub hunger_threshold: i32,          // 0x20C
+    #[offset(0x20C)]
+    pub hunger_threshold: i32,
     pub hungry_health_change: i32,      // 0x210
     pub hunger_increment: i32,          // 0x214
     pub food_unit_value: i32,           // 0x218
@@ -1047,14 +1059,14 @@ pub struct ZTAnimalType {
     pub needed_food: i32,               // 0x220
     pub no_food_change: i32,            // 0x224
     pub initial_happiness: i32,         // 0x228
-    pad04: [u8; 0x234 - 0x22C],         // ----------------------- padding: 12 bytes
-    pub max_hits: i32,                  // 0x234
-    pad004: [u8; 0x23C - 0x238],        // ----------------------- padding: 4 bytes
-    pub pct_hits: i32,                  // 0x23C
-    pad05: [u8; 0x248 - 0x240],         // ----------------------- padding: 8 bytes
-    pub max_energy: i32,                // 0x248
-    pad07: [u8; 0x250 - 0x24C],         // ----------------------- padding: 4 bytes
-    pub max_dirty: i32,                 // 0x250
+    #[offset(0x234)]
+    pub max_hits: i32,
+    #[offset(0x23C)]
+    pub pct_hits: i32,
+    #[offset(0x248)]
+    pub max_energy: i32,
+    #[offset(0x250)]
+    pub max_dirty: i32,
     pub min_dirty: i32,                 // 0x254
     pub sick_change: i32,               // 0x258
     pub other_animal_sick_change: i32,  // 0x25C
@@ -1069,19 +1081,19 @@ pub struct ZTAnimalType {
     pub mating_type: i32,               // 0x280
     pub offspring: i32,                 // 0x284
     pub keeper_frequency: i32,          // 0x288
-    pad08: [u8; 0x290 - 0x28C],         // ----------------------- padding: 4 bytes
-    pub not_enough_keepers_change: i32, // 0x290
+    #[offset(0x290)]
+    pub not_enough_keepers_change: i32,
     pub social: i32,                    // 0x294
     pub habitat_size: i32,              // 0x298
     pub number_animals_min: i32,        // 0x29C
     pub number_animals_max: i32,        // 0x2A0
-    pad09: [u8; 0x2AC - 0x2A4],         // ----------------------- padding: 8 bytes
-    pub number_min_change: i32,         // 0x2AC
+    #[offset(0x2AC)]
+    pub number_min_change: i32,
     pub number_max_change: i32,         // 0x2B0
-    pad10: [u8; 0x2BC - 0x2B4],         // ----------------------- padding: 8 bytes
-    pub habitat_preference: i32,        // 0x2BC
-    pad11: [u8; 0x31C - 0x2C0],         // ----------------------- padding: 92 bytes
-    pub baby_born_change: i32,          // 0x31C
+    #[offset(0x2BC)]
+    pub habitat_preference: i32,
+    #[offset(0x31C)]
+    pub baby_born_change: i32,
     // pad12: [u8; 0x320 - 0x320],         // ----------------------- padding: 4 bytes
     pub energy_increment: i32,          // 0x320
     pub energy_threshold: i32,          // 0x324
@@ -1089,8 +1101,8 @@ pub struct ZTAnimalType {
     pub dirty_threshold: i32,           // 0x32C
     // pad13: [u8; 0x330 - 0x330],         // ----------------------- padding: 4 bytes
     pub sick_time: i32,                 // 0x330
-    pad14: [u8; 0x344 - 0x334],         // ----------------------- padding: 16 bytes
-    pub baby_to_adult: i32,             // 0x344
+    #[offset(0x344)]
+    pub baby_to_adult: i32,
     // pad15: [u8; 0x348 - 0x348],         // ----------------------- padding: 4 bytes
     pub other_food: i32,                // 0x348
     pub tree_pref: i32,                 // 0x34C
@@ -1102,8 +1114,8 @@ pub struct ZTAnimalType {
     pub depth_change: i32,              // 0x364
     pub salinity_change: i32,           // 0x368
     pub salinity_health_change: i32,    // 0x36C
-    pad16: [u8; 0x378 - 0x370],         // ----------------------- padding: 8 bytes
-    pub happy_reproduce_threshold: i32, // 0x378
+    #[offset(0x378)]
+    pub happy_reproduce_threshold: i32,
     // pad17: [u8; 0x37C - 0x37C],         // ----------------------- padding: 4 bytes
     pub building_use_chance: i32,       // 0x37C
     pub no_mate_change: i32,            // 0x380
@@ -1121,8 +1133,8 @@ pub struct ZTAnimalType {
     pub climbs_cliffs: i32,             // 0x3B0
     pub bash_strength: i32,             // 0x3B4
     pub attractiveness: i32,            // 0x3B8
-    pad18: [u8; 0x3C8 - 0x3BC],         // ----------------------- padding: 8 bytes
-    pub keeper_food_type: i32,          // 0x3C8
+    #[offset(0x3C8)]
+    pub keeper_food_type: i32,
     pub is_climber: bool,               // 0x3CC
     pub is_jumper: bool,                // 0x3CD
     pub small_zoodoo: bool,             // 0x3CE
@@ -1252,19 +1264,20 @@ impl Deref for ZTAnimalType {
 
 // ------------ ZTStaffType, Implementation, and Related Functions ------------ //
 
+#[game_struct]
 #[derive(Debug, Getters, Setters, FieldAccessorAsString)]
 #[repr(C)]
 pub struct ZTStaffType {
     #[deref_field]
     pub ztunit_type: ZTUnitType, // bytes: 0x188 - 0x100 = 0x88 = 136 bytes
-    pad01: [u8; 0x1B4 - 0x188], // ----------------------- padding: 44 bytes
-    pub work_check: i32,        // 0x1B4
+    #[offset(0x1B4)]
+    pub work_check: i32,
     pub chase_check: i32,       // 0x1B8
     // pad02: [u8; 0x1BC - 0x1BC], // ----------------------- padding: 4 bytes
     pub monthly_cost: f32,      // 0x1BC
     // pub training_icon_name: string ptr, // 0x1D8 TODO: implement string ptr as function getter
-    pad03: [u8; 0x1E8 - 0x1C0], // ----------------------- padding: 24 bytes
-    pub duties_text_id: i32,    // 0x1E8
+    #[offset(0x1E8)]
+    pub duties_text_id: i32,
     pub weapon_range: i32,      // 0x1EC
 }
 
@@ -1303,13 +1316,14 @@ impl Deref for ZTStaffType {
 
 // ------------ ZTMaintType, Implementation, and Related Functions ------------ //
 
+#[game_struct]
 #[derive(Debug, Getters, Setters, FieldAccessorAsString)]
 #[repr(C)]
 pub struct ZTMaintType {
     #[deref_field]
     pub ztstaff_type: ZTStaffType, // bytes: 0x1F0 - 0x1B4 = 0x3C = 60 bytes
-    pad01: [u8; 0x1F4 - 0x1F0],           // ----------------------- padding: 4 bytes
-    pub clean_trash_radius: i32,          // 0x1F4
+    #[offset(0x1F4)]
+    pub clean_trash_radius: i32,
     pub fix_fence_modifier: i32,          // 0x1F8
     pub clear_invalid_list_interval: i32, // 0x1FC
 }
@@ -1351,15 +1365,15 @@ impl Deref for ZTMaintType {
 // TODO: DRT staff are not selectable in-game, so this struct needs a bit more testing to ensure it works as expected.
 // For now, assumptions are that the offets are correct and the struct is implemented correctly.
 
+#[game_struct]
 #[derive(Debug, Getters, Setters, FieldAccessorAsString)]
 #[repr(C)]
 pub struct ZTHelicopterType {
     #[deref_field]
     pub ztstaff_type: ZTStaffType, // bytes: 0x1F0 - 0x1B4 = 0x3C = 60 bytes
-    pad01: [u8; 0x1F4 - 0x1F0], // ----------------------- padding: 4 bytes
     // pub loop_sound_name: i32, // 0x1F4 TODO: implement string ptr as function getter
-    pad02: [u8; 0x1F8 - 0x1F4], // ----------------------- padding: 4 bytes
-    pub loop_sound_atten: i32,  // 0x1F8
+    #[offset(0x1F8)]
+    pub loop_sound_atten: i32,
 }
 
 impl EntityType for ZTHelicopterType {
@@ -1390,13 +1404,14 @@ impl Deref for ZTHelicopterType {
 
 // ------------ ZTGuideType, Implementation, and Related Functions ------------ //
 
+#[game_struct]
 #[derive(Debug, Getters, Setters, FieldAccessorAsString)]
 #[repr(C)]
 pub struct ZTGuideType {
     #[deref_field]
     pub ztstaff_type: ZTStaffType, // bytes: 0x1F0 - 0x1B4 = 0x3C = 60 bytes
-    pad01: [u8; 0x1F4 - 0x1F0], // ----------------------- padding: 4 bytes
-    pub inform_guest_time: i32, // 0x1F4
+    #[offset(0x1F4)]
+    pub inform_guest_time: i32,
     pub tour_guide_bonus: i32,  // 0x1F8
     pub crowd_check: i32,       // 0x1FC
     pub crowd_radius: i32,      // 0x200
@@ -1441,13 +1456,14 @@ impl Deref for ZTGuideType {
 
 // ------------ ZTKeeperType, Implementation, and Related Functions ------------ //
 
+#[game_struct]
 #[derive(Debug, Getters, Setters, FieldAccessorAsString)]
 #[repr(C)]
 pub struct ZTKeeperType {
     #[deref_field]
     pub ztstaff_type: ZTStaffType, // bytes: 0x1F0 - 0x1B4 = 0x3C = 60 bytes
-    pad01: [u8; 0x1F4 - 0x1F0], // ----------------------- padding: 4 bytes
-    pub food_units_second: i32, // 0x1F4
+    #[offset(0x1F4)]
+    pub food_units_second: i32,
     pub clean_time: i32,        // 0x1F8
     pub heal_units_second: i32, // 0x1FC
     pub food_per_tile: i32,     // 0x200
@@ -1706,6 +1722,66 @@ fn print_info_image_name(entity_type: &BFEntityType, config: &mut String) {
 // }
     
 
+/// An entity type, exposed to Lua
+///
+/// Fields are those of the type's class (e.g. `ZTAnimalType` for animals) plus `type_name`,
+/// `codename` and `class`. As with `sel_type`, every field can be set. Accessing a type that is no
+/// longer loaded raises a Lua error.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BFEntityTypeRef(u32);
+
+impl BFEntityTypeRef {
+    pub fn new(entity_type_ptr: u32) -> Self {
+        BFEntityTypeRef(entity_type_ptr)
+    }
+
+    /// Checks the type is still one of the world's entity types
+    fn check_loaded(&self) -> mlua::Result<()> {
+        if !ztworldmgr::read_zt_world_mgr_from_global().contains_entity_type(self.0) {
+            return Err(userdata::stale_handle("BFEntityType", self.0));
+        }
+        Ok(())
+    }
+
+    fn entity_type(&self) -> mlua::Result<&'static mut dyn EntityType> {
+        self.check_loaded()?;
+        map_bfentitytype(self.0).map_err(mlua::Error::runtime)
+    }
+}
+
+impl LuaType for BFEntityTypeRef {
+    fn lua_type() -> String {
+        "BFEntityType".to_string()
+    }
+}
+
+impl UserData for BFEntityTypeRef {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("is_member", |_, this, member: String| {
+            this.check_loaded()?;
+            Ok(read_zt_entity_type_from_memory(this.0).is_member(member))
+        });
+        methods.add_meta_method(MetaMethod::Index, |lua, this, name: String| {
+            this.check_loaded()?;
+            let bf_entity_type = map_from_memory::<BFEntityType>(this.0);
+            match name.as_str() {
+                "type_name" => bf_entity_type.get_type_name().into_lua(lua),
+                "codename" => bf_entity_type.get_codename().into_lua(lua),
+                "class" => format!("{:?}", ZTEntityTypeClass::from(bf_entity_type.vtable)).into_lua(lua),
+                _ => userdata::get_field(lua, "BFEntityType", this.entity_type()?, &name),
+            }
+        });
+        methods.add_meta_method(MetaMethod::NewIndex, |_, this, (name, value): (String, mlua::Value)| {
+            userdata::set_field("BFEntityType", this.entity_type()?, None, &name, value)
+        });
+        methods.add_meta_method(MetaMethod::ToString, |_, this, ()| {
+            this.check_loaded()?;
+            Ok(read_zt_entity_type_from_memory(this.0).to_string())
+        });
+        methods.add_meta_method(MetaMethod::Eq, |_, this, other: UserDataRef<BFEntityTypeRef>| Ok(*this == *other));
+    }
+}
+
 // This returns a dynamic trait object, which lets us call the methods of the entity type without knowing the exact type
 fn get_bfentitytype(address: u32) -> Result<Box<dyn EntityType>, String> {
     // create a copied instance of the entity type
@@ -1770,7 +1846,7 @@ fn get_bfentitytype(address: u32) -> Result<Box<dyn EntityType>, String> {
     // initializes the custom command
     pub fn init() {
         // sel_type([key], [value]) - optional arguments
-        lua_fn!("sel_type", "Gets selected entity type config, with optional key/value to set", "sel_type([key], [value]) or sel_type(\"-v\")", |args: mlua::Variadic<String>| {
+        lua_fn!("sel_type", "Gets selected entity type config, with optional key/value to set", "sel_type([key], [value]) or sel_type(\"-v\")", ModifyWorld, |args: mlua::Variadic<String>| {
             let args_vec: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
             match command_sel_type(args_vec) {
                 Ok(result) => Ok((Some(result), None::<String>)),
@@ -1779,7 +1855,7 @@ fn get_bfentitytype(address: u32) -> Result<Box<dyn EntityType>, String> {
         });
 
         // make_sel(id) - single u32 arg
-        lua_fn!("make_sel", "Makes entity type selectable", "make_sel(id)", |id: u32| {
+        lua_fn!("make_sel", "Makes entity type selectable", "make_sel(id)", ModifyWorld, |id: u32| {
             let id_str = id.to_string();
             match command_make_sel(vec![&id_str]) {
                 Ok(result) => Ok((Some(result), None::<String>)),
@@ -1,6 +1,7 @@
 use std::{collections::HashMap, sync::Mutex};
 
 use std::sync::LazyLock;
+#[cfg(target_os = "windows")]
 use openzt_detour_macro::detour_mod;
 use tracing::info;
 
@@ -57,6 +58,7 @@ pub fn read_bf_registry() {
     }
 }
 
+#[cfg(target_os = "windows")]
 #[detour_mod]
 mod zoo_bf_registry {
     use crate::{
@@ -68,12 +70,15 @@ mod zoo_bf_registry {
     use openzt_detour::gen::bfregistry::PTR_GET;
     use openzt_detour::gen::bfmgr::REGISTERIT as BFMGR_REGISTERIT;
 
+    // The game's registry is never filled, so there is nothing to fall back to
     #[detour(PTR_GET)]
+    #[no_fallback]
     unsafe extern "thiscall" fn prt_get(_this_prt: u32, class_name: u32, _param_2: bool) -> u32 {
         get_from_registry(get_string_from_memory(get_from_memory::<u32>(class_name))).unwrap()
     }
 
     #[detour(BFMGR_REGISTERIT)]
+    #[no_fallback]
     unsafe extern "cdecl" fn add_to_bfregistry(param_1: u32, param_2: u32) -> u32 {
         let param_1_string = get_string_from_memory(get_from_memory::<u32>(param_1));
         add_to_registry(&param_1_string, param_2);
@@ -81,6 +86,7 @@ mod zoo_bf_registry {
     }
 
     #[detour(UIELEMENT_REGISTERIT)]
+    #[no_fallback]
     unsafe extern "cdecl" fn add_to_bfregistry_ui(param_1: u32, param_2: u32) -> u32 {
         let param_1_string = get_string_from_memory(get_from_memory::<u32>(param_1));
         add_to_registry(&param_1_string, param_2);
@@ -90,12 +96,13 @@ mod zoo_bf_registry {
 
 #[deprecated(since = "0.1.0", note = "no longer needed")]
 pub fn init() {
+    #[cfg(target_os = "windows")]
     if let Err(e) = unsafe { zoo_bf_registry::init_detours() } {
         info!("Error initialising bf_registry detours: {}", e);
     };
 
     // list_bf_registry() - no args
-    lua_fn!("list_bf_registry", "Lists BF registry entries (deprecated)", "list_bf_registry()", || {
+    lua_fn!("list_bf_registry", "Lists BF registry entries (deprecated)", "list_bf_registry()", DebugMemory, || {
         match command_list_registry(vec![]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
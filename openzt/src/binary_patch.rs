@@ -0,0 +1,444 @@
+//! Reversible patches to the game's code
+//!
+//! A [`PatchSet`] is a group of [`BinaryPatch`]es that fix one thing, e.g. a crash, and are applied
+//! or restored together. Before writing, every patch's target bytes are checked against the bytes it
+//! expects to replace, so a patch never lands in code that isn't what it was written for. If a write
+//! fails part way the patches already written are restored.
+//!
+//! Registered patch sets can be listed and toggled from the console with `list_patches()` and
+//! `set_patch_enabled(name, enabled)`.
+
+use std::{
+    fmt,
+    sync::{LazyLock, Mutex},
+};
+
+use tracing::{error, info};
+
+use crate::{game_memory, lua_fn};
+
+/// Bytes to write at an address, and the bytes expected there before
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryPatch {
+    pub address: u32,
+    /// The game's own bytes, `None` where they haven't been recorded yet, in which case whatever is
+    /// there when the patch is applied is kept for restoring
+    pub original: Option<Vec<u8>>,
+    pub replacement: Vec<u8>,
+    pub description: &'static str,
+}
+
+impl BinaryPatch {
+    pub fn new(address: u32, original: &[u8], replacement: &[u8], description: &'static str) -> BinaryPatch {
+        assert_eq!(original.len(), replacement.len(), "{} at {:#x} changes the size of the code", description, address);
+        BinaryPatch {
+            address,
+            original: Some(original.to_vec()),
+            replacement: replacement.to_vec(),
+            description,
+        }
+    }
+
+    /// A patch whose original bytes aren't known, it can be applied and restored but not verified
+    pub fn unverified(address: u32, replacement: &[u8], description: &'static str) -> BinaryPatch {
+        BinaryPatch {
+            address,
+            original: None,
+            replacement: replacement.to_vec(),
+            description,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    /// The target doesn't hold the bytes the patch expects
+    Mismatch { address: u32, description: &'static str, expected: Vec<u8>, found: Vec<u8> },
+    Write { address: u32, description: &'static str, error: String },
+    NotFound(String),
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(" ")
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::Mismatch { address, description, expected, found } => write!(
+                f,
+                "{} at {:#010x}: expected [{}] but found [{}]",
+                description,
+                address,
+                hex(expected),
+                hex(found)
+            ),
+            PatchError::Write { address, description, error } => write!(f, "{} at {:#010x}: {}", description, address, error),
+            PatchError::NotFound(name) => write!(f, "No patch named '{}'", name),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchStatus {
+    Applied,
+    Disabled,
+    /// Applying or restoring failed, the game's code is as it was before the attempt, so a set that
+    /// failed to restore is still in memory
+    Failed(String),
+}
+
+impl fmt::Display for PatchStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchStatus::Applied => write!(f, "applied"),
+            PatchStatus::Disabled => write!(f, "disabled"),
+            PatchStatus::Failed(error) => write!(f, "failed: {}", error),
+        }
+    }
+}
+
+/// Patches that are applied and restored together
+#[derive(Debug, Clone)]
+pub struct PatchSet {
+    /// Key in the `[bugfixes]` section of openzt.toml
+    pub name: &'static str,
+    pub description: &'static str,
+    pub patches: Vec<BinaryPatch>,
+    /// Bytes each patch replaced, recorded when applied
+    restore: Vec<Vec<u8>>,
+    /// Whether the replacements are in memory, which a failed restore leaves true
+    applied: bool,
+    pub status: PatchStatus,
+}
+
+impl PatchSet {
+    pub fn new(name: &'static str, description: &'static str, patches: Vec<BinaryPatch>) -> PatchSet {
+        PatchSet {
+            name,
+            description,
+            patches,
+            restore: Vec::new(),
+            applied: false,
+            status: PatchStatus::Disabled,
+        }
+    }
+
+    /// Verifies every patch's target, then writes all of them
+    ///
+    /// Targets that already hold the replacement are left alone, so applying twice is fine.
+    pub fn apply(&mut self) -> Result<(), PatchError> {
+        if self.applied {
+            self.status = PatchStatus::Applied;
+            return Ok(());
+        }
+        let result = self.try_apply();
+        self.applied = result.is_ok();
+        self.status = match &result {
+            Ok(()) => PatchStatus::Applied,
+            Err(e) => PatchStatus::Failed(e.to_string()),
+        };
+        result
+    }
+
+    fn try_apply(&mut self) -> Result<(), PatchError> {
+        let mut restore = Vec::new();
+        for patch in &self.patches {
+            let found = game_memory::read_bytes(patch.address, patch.replacement.len());
+            match &patch.original {
+                _ if found == patch.replacement => restore.push(patch.original.clone().unwrap_or(found)),
+                Some(original) if found != *original => {
+                    return Err(PatchError::Mismatch {
+                        address: patch.address,
+                        description: patch.description,
+                        expected: original.clone(),
+                        found,
+                    })
+                }
+                _ => restore.push(found),
+            }
+        }
+
+        let replacements: Vec<_> = self.patches.iter().map(|patch| patch.replacement.clone()).collect();
+        write_all(&self.patches, &replacements, &restore)?;
+        self.restore = restore;
+        Ok(())
+    }
+
+    /// Writes back the bytes each patch replaced, after checking the patches are still in place
+    pub fn restore(&mut self) -> Result<(), PatchError> {
+        if !self.applied {
+            return Ok(());
+        }
+        for patch in &self.patches {
+            let found = game_memory::read_bytes(patch.address, patch.replacement.len());
+            if found != patch.replacement {
+                let error = PatchError::Mismatch {
+                    address: patch.address,
+                    description: patch.description,
+                    expected: patch.replacement.clone(),
+                    found,
+                };
+                self.status = PatchStatus::Failed(error.to_string());
+                return Err(error);
+            }
+        }
+
+        let replacements: Vec<_> = self.patches.iter().map(|patch| patch.replacement.clone()).collect();
+        match write_all(&self.patches, &self.restore, &replacements) {
+            Ok(()) => {
+                self.applied = false;
+                self.status = PatchStatus::Disabled;
+                Ok(())
+            }
+            Err(e) => {
+                self.status = PatchStatus::Failed(e.to_string());
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Writes `bytes` over each patch's target, putting back `previous` for the ones already written if
+/// any write fails
+fn write_all(patches: &[BinaryPatch], bytes: &[Vec<u8>], previous: &[Vec<u8>]) -> Result<(), PatchError> {
+    for (index, patch) in patches.iter().enumerate() {
+        if let Err(e) = game_memory::write_protected_bytes(patch.address, &bytes[index]) {
+            for (written, previous) in patches[..index].iter().zip(previous) {
+                if let Err(e) = game_memory::write_protected_bytes(written.address, previous) {
+                    error!("Failed to roll back {} at {:#010x}: {}", written.description, written.address, e);
+                }
+            }
+            return Err(PatchError::Write {
+                address: patch.address,
+                description: patch.description,
+                error: e.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+static PATCH_SETS: LazyLock<Mutex<Vec<PatchSet>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// Registers a patch set, applying it if `enabled`
+pub fn register(mut patch_set: PatchSet, enabled: bool) {
+    if enabled {
+        match patch_set.apply() {
+            Ok(()) => info!("Applied {}", patch_set.name),
+            Err(e) => error!("Failed to apply {}: {}", patch_set.name, e),
+        }
+    }
+    let mut patch_sets = PATCH_SETS.lock().unwrap();
+    patch_sets.retain(|existing| existing.name != patch_set.name);
+    patch_sets.push(patch_set);
+}
+
+/// Applies or restores a registered patch set, returning its new status
+pub fn set_enabled(name: &str, enabled: bool) -> Result<PatchStatus, PatchError> {
+    let mut patch_sets = PATCH_SETS.lock().unwrap();
+    let patch_set = patch_sets
+        .iter_mut()
+        .find(|patch_set| patch_set.name == name)
+        .ok_or_else(|| PatchError::NotFound(name.to_string()))?;
+    if enabled {
+        patch_set.apply()?;
+    } else {
+        patch_set.restore()?;
+    }
+    Ok(patch_set.status.clone())
+}
+
+fn list_patches() -> String {
+    let patch_sets = PATCH_SETS.lock().unwrap();
+    if patch_sets.is_empty() {
+        return "No patches registered".to_string();
+    }
+    patch_sets
+        .iter()
+        .map(|patch_set| {
+            let unverified = patch_set.patches.iter().filter(|patch| patch.original.is_none()).count();
+            let mut line = format!("{} [{}] - {}", patch_set.name, patch_set.status, patch_set.description);
+            if unverified > 0 {
+                line.push_str(&format!(" ({} of {} patches unverified)", unverified, patch_set.patches.len()));
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn init() {
+    // list_patches() - lists patches to the game's code
+    lua_fn!(
+        "list_patches",
+        "Lists the patches OpenZT makes to the game's code and whether each is applied",
+        "list_patches()",
+        DebugMemory,
+        || { Ok(list_patches()) }
+    );
+
+    // set_patch_enabled(name, enabled) - applies or restores a patch
+    lua_fn!(
+        "set_patch_enabled",
+        "Applies or restores the game's original code for a patch, by the name list_patches() shows",
+        "set_patch_enabled(name, enabled)",
+        DebugMemory,
+        |name: String, enabled: bool| {
+            match set_enabled(&name, enabled) {
+                Ok(status) => Ok((Some(format!("{} [{}]", name, status)), None::<String>)),
+                Err(e) => Ok((None::<String>, Some(e.to_string()))),
+            }
+        }
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::game_memory::{with_memory, FakeMemory, GameMemory};
+
+    fn memory_with(bytes: &[u8]) -> (Arc<FakeMemory>, u32) {
+        let memory = Arc::new(FakeMemory::new());
+        let address = memory.alloc(bytes.len());
+        memory.write_bytes(address, bytes);
+        (memory, address)
+    }
+
+    #[test]
+    fn test_apply_and_restore() {
+        let (memory, code) = memory_with(&[0x0f, 0xbe, 0x04, 0x90, 0x90]);
+        let mut patch_set = PatchSet::new(
+            "test_fix",
+            "Test",
+            vec![
+                BinaryPatch::new(code + 1, &[0xbe], &[0xb6], "movsx -> movzx"),
+                BinaryPatch::unverified(code + 3, &[0x85, 0xc0], "test eax, eax"),
+            ],
+        );
+
+        with_memory(memory.clone(), || {
+            patch_set.apply().unwrap();
+            assert_eq!(patch_set.status, PatchStatus::Applied);
+            assert_eq!(game_memory::read_bytes(code, 5), vec![0x0f, 0xb6, 0x04, 0x85, 0xc0]);
+
+            patch_set.restore().unwrap();
+            assert_eq!(patch_set.status, PatchStatus::Disabled);
+            assert_eq!(game_memory::read_bytes(code, 5), vec![0x0f, 0xbe, 0x04, 0x90, 0x90]);
+        });
+    }
+
+    #[test]
+    fn test_mismatch_writes_nothing() {
+        let (memory, code) = memory_with(&[0x0f, 0xbe, 0x74, 0x05]);
+        let mut patch_set = PatchSet::new(
+            "test_mismatch",
+            "Test",
+            vec![
+                BinaryPatch::new(code + 1, &[0xbe], &[0xb6], "movsx -> movzx"),
+                BinaryPatch::new(code + 2, &[0x75], &[0xeb], "jnz -> jmp"),
+            ],
+        );
+
+        with_memory(memory, || {
+            let error = patch_set.apply().unwrap_err();
+            assert_eq!(
+                error,
+                PatchError::Mismatch { address: code + 2, description: "jnz -> jmp", expected: vec![0x75], found: vec![0x74] }
+            );
+            assert_eq!(patch_set.status, PatchStatus::Failed(error.to_string()));
+            // The first patch matched but isn't written on its own
+            assert_eq!(game_memory::read_bytes(code, 4), vec![0x0f, 0xbe, 0x74, 0x05]);
+        });
+    }
+
+    #[test]
+    fn test_already_applied_and_changed_since() {
+        let (memory, code) = memory_with(&[0xb6]);
+        let mut patch_set = PatchSet::new("test_reapply", "Test", vec![BinaryPatch::new(code, &[0xbe], &[0xb6], "movsx -> movzx")]);
+
+        with_memory(memory.clone(), || {
+            // The replacement is already there, e.g. from an earlier load
+            patch_set.apply().unwrap();
+            patch_set.restore().unwrap();
+            assert_eq!(game_memory::read_bytes(code, 1), vec![0xbe]);
+
+            // Something else overwrote the patch, restoring would clobber it
+            patch_set.apply().unwrap();
+            memory.write(code, 0xccu8);
+            assert!(matches!(patch_set.restore(), Err(PatchError::Mismatch { .. })));
+            assert_eq!(game_memory::read_bytes(code, 1), vec![0xcc]);
+        });
+    }
+
+    /// Fails writes to one address once `fail_at` is set, like a page the game protected again
+    struct FailingWrites {
+        memory: FakeMemory,
+        fail_at: Mutex<Option<u32>>,
+    }
+
+    impl GameMemory for FailingWrites {
+        fn host_ptr(&self, address: u32, len: usize) -> *mut u8 {
+            self.memory.host_ptr(address, len)
+        }
+
+        fn write_protected_bytes(&self, address: u32, bytes: &[u8]) -> anyhow::Result<()> {
+            if *self.fail_at.lock().unwrap() == Some(address) {
+                anyhow::bail!("access denied");
+            }
+            self.memory.write_bytes(address, bytes);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_failed_restore_stays_applied() {
+        let memory = FakeMemory::new();
+        let code = memory.alloc(2);
+        memory.write_bytes(code, &[0xbe, 0x75]);
+        let memory = Arc::new(FailingWrites { memory, fail_at: Mutex::new(None) });
+        let mut patch_set = PatchSet::new(
+            "test_failed_restore",
+            "Test",
+            vec![
+                BinaryPatch::new(code, &[0xbe], &[0xb6], "movsx -> movzx"),
+                BinaryPatch::new(code + 1, &[0x75], &[0xeb], "jnz -> jmp"),
+            ],
+        );
+
+        with_memory(memory.clone(), || {
+            patch_set.apply().unwrap();
+            *memory.fail_at.lock().unwrap() = Some(code + 1);
+            assert!(matches!(patch_set.restore(), Err(PatchError::Write { .. })));
+            assert!(matches!(patch_set.status, PatchStatus::Failed(_)));
+            // Rolled back to the replacements, so restoring again has to try again
+            assert_eq!(game_memory::read_bytes(code, 2), vec![0xb6, 0xeb]);
+            assert!(matches!(patch_set.restore(), Err(PatchError::Write { .. })));
+
+            *memory.fail_at.lock().unwrap() = None;
+            patch_set.restore().unwrap();
+            assert_eq!(patch_set.status, PatchStatus::Disabled);
+            assert_eq!(game_memory::read_bytes(code, 2), vec![0xbe, 0x75]);
+        });
+    }
+
+    #[test]
+    fn test_registry() {
+        let (memory, code) = memory_with(&[0xbe]);
+        with_memory(memory, || {
+            register(PatchSet::new("test_registry", "Registry test", vec![BinaryPatch::new(code, &[0xbe], &[0xb6], "movsx -> movzx")]), false);
+            assert!(list_patches().contains("test_registry [disabled] - Registry test"));
+
+            assert_eq!(set_enabled("test_registry", true), Ok(PatchStatus::Applied));
+            assert!(list_patches().contains("test_registry [applied] - Registry test"));
+            assert_eq!(game_memory::read_bytes(code, 1), vec![0xb6]);
+
+            assert_eq!(set_enabled("test_registry", false), Ok(PatchStatus::Disabled));
+            assert_eq!(set_enabled("missing", true), Err(PatchError::NotFound("missing".to_string())));
+        });
+    }
+}
@@ -1,72 +1,78 @@
-use tracing::error;
+use openzt_detour::{game_address, game_build::GameAddress};
+use tracing::info;
 
-use crate::util::{patch_nop, save_to_protected_memory};
+use crate::{
+    binary_patch::{self, BinaryPatch, PatchSet},
+    resource_manager::mod_config::get_openzt_config,
+};
 
-const ZOOWALL_MAP_EDGE_CRASH_ADDRESS: u32 = 0x050b260;
+const ZOOWALL_MAP_EDGE_CRASH_ADDRESS: GameAddress = game_address!(CompleteCollection: 0x0050b260);
 
-const ZOOFENCE_ONE_TILE_FROM_MAP_EDGE_CRASH_ADDRESS_1: u32 = 0x4a1fc0;
-const ZOOFENCE_ONE_TILE_FROM_MAP_EDGE_CRASH_ADDRESS_2: u32 = 0x4a1fe7;
+const ZOOFENCE_ONE_TILE_FROM_MAP_EDGE_CRASH_ADDRESS_1: GameAddress = game_address!(CompleteCollection: 0x004a1fc0);
+const ZOOFENCE_ONE_TILE_FROM_MAP_EDGE_CRASH_ADDRESS_2: GameAddress = game_address!(CompleteCollection: 0x004a1fe7);
 // const ZOOFENCE_ONE_TILE_FROM_MAP_EDGE_CRASH_ADDRESS_1: u32 = ;
 
-const LOWERCASE_LOOKUP_TABLE_SIGNED_CHAR_BUG: [u32; 6] = [
-    0x004036b9, // FUN_004036ae (may be unused)
-    0x004039e9, // makeName
-    0x00404240, // BFResourceZip::load
-    0x0040697f, // BFResourceZip::prepare
-    0x00528d0f, // BFResourceZip::BFResourceZip
-    0x00529cc7, // dirsearch
+const LOWERCASE_LOOKUP_TABLE_SIGNED_CHAR_BUG: [GameAddress; 6] = [
+    game_address!(CompleteCollection: 0x004036b9), // FUN_004036ae (may be unused)
+    game_address!(CompleteCollection: 0x004039e9), // makeName
+    game_address!(CompleteCollection: 0x00404240), // BFResourceZip::load
+    game_address!(CompleteCollection: 0x0040697f), // BFResourceZip::prepare
+    game_address!(CompleteCollection: 0x00528d0f), // BFResourceZip::BFResourceZip
+    game_address!(CompleteCollection: 0x00529cc7), // dirsearch
     ];
 
 // Addresses where the vanilla game references its 128-entry lowercase table
 // These need to be patched to point to our 256-entry table instead
-const LOWERCASE_TABLE_REFERENCE_ADDRESSES: [u32; 6] = [
-    0x004036be, // FUN_004036ae (may be unused)
-    0x004039ee, // makeName
-    0x00404245, // BFResourceZip::load
-    0x00406984, // BFResourceZip::prepare
-    0x00528d14, // BFResourceZip::BFResourceZip
-    0x00529ccc, // dirsearch
+const LOWERCASE_TABLE_REFERENCE_ADDRESSES: [GameAddress; 6] = [
+    game_address!(CompleteCollection: 0x004036be), // FUN_004036ae (may be unused)
+    game_address!(CompleteCollection: 0x004039ee), // makeName
+    game_address!(CompleteCollection: 0x00404245), // BFResourceZip::load
+    game_address!(CompleteCollection: 0x00406984), // BFResourceZip::prepare
+    game_address!(CompleteCollection: 0x00528d14), // BFResourceZip::BFResourceZip
+    game_address!(CompleteCollection: 0x00529ccc), // dirsearch
 ];
 
 pub fn init() {
-    if let Err(e) = fix_zoowall_map_edge_crash() {
-        error!("Failed to fix ZooWall map edge crash: {}", e);
+    let config = get_openzt_config().bugfixes;
+    for patch_set in bugfixes() {
+        let enabled = config.is_enabled(patch_set.name);
+        if !enabled {
+            info!("Bugfix {} disabled in openzt.toml", patch_set.name);
+        }
+        binary_patch::register(patch_set, enabled);
     }
-    if let Err(e) = fix_fence_one_tile_from_map_edge_crash() {
-        error!("Failed to fix ZooFence one tile from map edge crash: {}", e);
-    }
-
-    if let Err(e) = fix_lowercase_lookup_table_signed_char_bug() {
-        error!("Failed to fix lowercase lookup table signed char bug: {}", e);
-    }
-
-    if let Err(e) = patch_lowercase_table_references() {
-        error!("Failed to patch lowercase table references: {}", e);
-    }
-}
-
-fn fix_zoowall_map_edge_crash() -> anyhow::Result<()> {
-    // We change a jump address to fix a bug trying access a null pointer
-    save_to_protected_memory(ZOOWALL_MAP_EDGE_CRASH_ADDRESS, 0xfffffcfeu32 as i32)?;
-    Ok(())
-}
-
-fn fix_fence_one_tile_from_map_edge_crash() -> anyhow::Result<()> {
-    // This changes an if statement to cover the entire inner loop
-    save_to_protected_memory::<u8>(ZOOFENCE_ONE_TILE_FROM_MAP_EDGE_CRASH_ADDRESS_1, 0x45)?;
-    // The above change makes the second if statement redundant so we can add in a check for the null pointer
-    save_to_protected_memory::<u8>(ZOOFENCE_ONE_TILE_FROM_MAP_EDGE_CRASH_ADDRESS_2, 0x85)?;
-    save_to_protected_memory::<u8>(ZOOFENCE_ONE_TILE_FROM_MAP_EDGE_CRASH_ADDRESS_2 + 1, 0xC0)?;
-    patch_nop(ZOOFENCE_ONE_TILE_FROM_MAP_EDGE_CRASH_ADDRESS_2 + 2)?;
-    Ok(())
 }
 
-fn fix_lowercase_lookup_table_signed_char_bug() -> anyhow::Result<()> {
-    for address in LOWERCASE_LOOKUP_TABLE_SIGNED_CHAR_BUG {
-        // This changes a movsx to a movzx to fix signed char bug in lowercase lookup table
-        save_to_protected_memory::<u8>(address, 0xB6)?;
-    }
-    Ok(())
+/// Every bugfix, named by its key in the `[bugfixes]` section of openzt.toml
+fn bugfixes() -> Vec<PatchSet> {
+    vec![
+        PatchSet::new(
+            "zoowall_map_edge_crash",
+            "Fixes a crash placing a ZooWall on the map edge",
+            // We change a jump address to fix a bug trying access a null pointer
+            vec![BinaryPatch::unverified(ZOOWALL_MAP_EDGE_CRASH_ADDRESS.get(), &0xfffffcfeu32.to_le_bytes(), "Jump past null tile")],
+        ),
+        PatchSet::new(
+            "zoofence_map_edge_crash",
+            "Fixes a crash placing a ZooFence one tile from the map edge",
+            vec![
+                // This changes an if statement to cover the entire inner loop
+                BinaryPatch::unverified(ZOOFENCE_ONE_TILE_FROM_MAP_EDGE_CRASH_ADDRESS_1.get(), &[0x45], "Extend if over inner loop"),
+                // The above change makes the second if statement redundant so we can add in a check for the null pointer
+                BinaryPatch::unverified(ZOOFENCE_ONE_TILE_FROM_MAP_EDGE_CRASH_ADDRESS_2.get(), &[0x85, 0xc0, 0x90], "TEST EAX, EAX; NOP"),
+            ],
+        ),
+        PatchSet::new(
+            "lowercase_signed_char",
+            "Fixes the lowercase lookup table being indexed with signed chars",
+            // This changes a movsx to a movzx to fix signed char bug in lowercase lookup table
+            LOWERCASE_LOOKUP_TABLE_SIGNED_CHAR_BUG
+                .iter()
+                .map(|address| BinaryPatch::new(address.get(), &[0xbe], &[0xb6], "MOVSX -> MOVZX"))
+                .collect(),
+        ),
+        lowercase_table_references(),
+    ]
 }
 
 /// Patch the vanilla game to use our 256-entry locale-aware lowercase table
@@ -76,14 +82,17 @@ fn fix_lowercase_lookup_table_signed_char_bug() -> anyhow::Result<()> {
 ///
 /// This function patches all references to the vanilla table to point to our
 /// extended 256-entry table that properly handles the system's ANSI code page.
-fn patch_lowercase_table_references() -> anyhow::Result<()> {
+fn lowercase_table_references() -> PatchSet {
     let table_ptr = crate::encoding_utils::get_lowercase_table_ptr();
 
-    for &address in &LOWERCASE_TABLE_REFERENCE_ADDRESSES {
-        save_to_protected_memory::<u32>(address, table_ptr)?;
-    }
-
-    Ok(())
+    PatchSet::new(
+        "lowercase_table_references",
+        "Uses a 256-entry lowercase table so filenames with non-ASCII characters load",
+        LOWERCASE_TABLE_REFERENCE_ADDRESSES
+            .iter()
+            .map(|address| BinaryPatch::unverified(address.get(), &table_ptr.to_le_bytes(), "Lowercase table pointer"))
+            .collect(),
+    )
 }
 
 // Leaving this in incase future bugfixes require inline assembly
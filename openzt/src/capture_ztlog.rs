@@ -1,3 +1,4 @@
+#[cfg(target_os = "windows")]
 use openzt_detour_macro::detour_mod;
 use tracing::info;
 
@@ -39,6 +40,7 @@ pub fn log_from_zt(source_file: &String, line_number: i32, level: i32, message:
     info!("{}({}) : {} : {}", source_file, line_number, level.as_str(), message);
 }
 
+#[cfg(target_os = "windows")]
 #[detour_mod]
 mod zoo_logging {
     use crate::{capture_ztlog::log_from_zt, util::get_string_from_memory};
@@ -53,6 +55,7 @@ mod zoo_logging {
 }
 
 pub fn init() {
+    #[cfg(target_os = "windows")]
     if let Err(e) = unsafe { zoo_logging::init_detours() } {
         info!("Error initialising zt logging detours: {}", e);
     };
@@ -1,16 +1,21 @@
 use std::{
     error::Error,
     fmt,
-    io::{Read, Write},
-    net::{TcpListener, TcpStream},
     sync::Mutex,
-    thread,
 };
 
 use std::sync::LazyLock;
-use openzt_detour_macro::detour_mod;
+use openzt_console_protocol::{ConsoleServer, ExecuteError, DEFAULT_ADDRESS};
+use openzt_detour::chain::Flow;
 use tracing::{error, info};
 
+use crate::{
+    hook_chains::UPDATE_SIM,
+    resource_manager::openzt_mods::scripts::{dispatch, LifecycleHook},
+    scripting::scheduler,
+    ztgamemgr::publish_date_changes,
+};
+
 /// Error type for command execution (kept for backward compatibility with existing command implementations)
 #[derive(Debug)]
 pub struct CommandError {
@@ -67,32 +72,25 @@ impl From<&str> for CommandError {
     }
 }
 
-#[detour_mod]
-pub mod zoo_console {
-    use tracing::error;
-    use openzt_detour::gen::ztapp::UPDATE_SIM;
-
-    use super::call_next_command;
-
-    #[detour(UPDATE_SIM)]
-    unsafe extern "thiscall" fn zoo_zt_app_update_game(_this_ptr: u32, param_2: i32) {
-        call_next_command();
-        unsafe { UPDATE_SIM_DETOUR.call(_this_ptr, param_2) }
-    }
-
-    pub fn init() {
-        unsafe {
-            if init_detours().is_err() {
-                error!("Failed to initialize console detours");
-            }
-        };
+/// Adds the sim update handlers, which also drive mod script `on_tick` callbacks
+///
+/// Console requests are only served with the `command-console` feature.
+pub fn init() {
+    if cfg!(feature = "command-console") {
+        info!("Initializing Lua console on {}", DEFAULT_ADDRESS);
+        UPDATE_SIM.pre("command_console", 0, |_| {
+            call_next_command();
+            Flow::Continue
+        });
     }
+    UPDATE_SIM.post("tick", 0, |&(_, delta), _| {
+        dispatch(LifecycleHook::Tick, delta);
+        publish_date_changes();
+        scheduler::tick();
+    });
 }
 
-pub fn init() {
-    info!("Initializing Lua console on 127.0.0.1:8080");
-    zoo_console::init();
-}
+static CONSOLE_SERVER: LazyLock<ConsoleServer> = LazyLock::new(ConsoleServer::new);
 
 static COMMAND_THREAD: LazyLock<Mutex<std::thread::JoinHandle<()>>> = LazyLock::new(|| Mutex::new(std::thread::spawn(|| {
             start_server();
@@ -100,96 +98,36 @@ static COMMAND_THREAD: LazyLock<Mutex<std::thread::JoinHandle<()>>> = LazyLock::
     ))
 );
 
-static COMMAND_RESULTS: LazyLock<Mutex<Vec<String>>> = LazyLock::new(|| Mutex::new(Vec::<String>::new()));
-
-static COMMAND_QUEUE: LazyLock<Mutex<Vec<String>>> = LazyLock::new(|| Mutex::new(Vec::<String>::new()));
-
-/// Executes the next Lua code from the command queue on the game thread
+/// Executes the next queued Lua request on the game thread and sends its result to the client
 pub fn call_next_command() {
     let _unused = COMMAND_THREAD.lock().unwrap();
-    let Some(lua_code) = get_from_command_queue() else {
+    let Some(request) = CONSOLE_SERVER.next_request() else {
         return;
     };
 
-    info!("Executing Lua: {}", lua_code.clone());
-
-    let result = match crate::scripting::execute_lua(&lua_code) {
-        Ok(result) => result,
-        Err(err) => err,
+    info!("Executing Lua (client {}, request {}): {}", request.client(), request.id(), request.code());
+
+    // JSON-RPC clients get the result as JSON, raw clients as text
+    let sent = if request.is_structured() {
+        let result = crate::scripting::execute_lua_json(request.code()).map_err(|err| {
+            let (message, traceback) = crate::scripting::split_lua_error(&err);
+            ExecuteError { message, traceback }
+        });
+        request.finish_structured(result)
+    } else {
+        let result = crate::scripting::execute_lua(request.code());
+        request.finish(result)
     };
 
-    let mut result_mutex = COMMAND_RESULTS.lock().unwrap();
-    result_mutex.push(result);
-}
-
-pub fn get_next_result() -> Option<String> {
-    let mut data_mutex = COMMAND_RESULTS.lock().unwrap();
-    data_mutex.pop()
-}
-
-fn add_to_command_queue(command: String) {
-    info!("Adding Lua code to queue: {}", command);
-    let mut data_mutex = COMMAND_QUEUE.lock().unwrap();
-    data_mutex.push(command);
-}
-
-pub fn get_from_command_queue() -> Option<String> {
-    let mut data_mutex = COMMAND_QUEUE.lock().unwrap();
-    data_mutex.pop()
-}
-
-fn handle_client(mut stream: TcpStream) {
-    let mut buffer = [0; 1024];
-
-    loop {
-        match stream.read(&mut buffer) {
-            Ok(size) => {
-                if size == 0 {
-                    // Connection closed by client
-                    break;
-                }
-
-                // Received Lua code to execute
-                let received_string = String::from_utf8_lossy(&buffer[0..size]);
-                add_to_command_queue(received_string.to_string());
-                info!("Received Lua code: {}", received_string);
-
-                loop {
-                    if let Some(result) = get_next_result() {
-                        if let Err(err) = stream.write_all(result.as_bytes()) {
-                            info!("Error sending data: {}", err);
-                        }
-                        break;
-                    }
-                }
-            }
-            Err(err) => {
-                info!("Error reading data: {}", err);
-                break;
-            }
-        }
+    if let Err(err) = sent {
+        info!("Error sending console result: {}", err);
     }
 }
 
 pub fn start_server() {
-    let Ok(listener) = TcpListener::bind("127.0.0.1:8080") else {
-        error!("Failed to bind socket 127.0.0.1:8080, console will not work");
-        return;
-    };
+    info!("Starting console server on {}...", DEFAULT_ADDRESS);
 
-    info!("Listening on 127.0.0.1:8080...");
-
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
-                // Spawn a new thread for each incoming connection
-                thread::spawn(move || {
-                    handle_client(stream);
-                });
-            }
-            Err(err) => {
-                info!("Error accepting connection: {}", err);
-            }
-        }
+    if let Err(err) = CONSOLE_SERVER.listen(DEFAULT_ADDRESS) {
+        error!("Failed to bind socket {}, console will not work: {}", DEFAULT_ADDRESS, err);
     }
 }
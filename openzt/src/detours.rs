@@ -0,0 +1,92 @@
+//! Console functions for the detour registry in [`openzt_detour::registry`]
+
+use openzt_detour::registry::{self, DetourInfo};
+
+use crate::lua_fn;
+
+/// Lists registered detours, those whose target or hook path contains `filter` if given
+fn list_detours(filter: Option<&str>) -> String {
+    let detours: Vec<DetourInfo> = registry::detours()
+        .into_iter()
+        .filter(|info| filter.is_none_or(|filter| info.target.contains(filter) || info.path().contains(filter)))
+        .collect();
+    if detours.is_empty() {
+        return "No detours registered".to_string();
+    }
+    detours.iter().map(DetourInfo::to_string).collect::<Vec<_>>().join("\n")
+}
+
+fn set_detour_enabled(query: &str, enabled: bool) -> Result<String, String> {
+    let info = registry::find(query).map_err(|e| e.to_string())?;
+    // Toggling is for debugging; the hook's own module is responsible for coping with being skipped
+    let info = unsafe { registry::set_enabled(info.address, enabled) }.map_err(|e| e.to_string())?;
+    Ok(info.to_string())
+}
+
+pub fn init() {
+    // list_detours([filter]) - lists hooked game functions
+    lua_fn!(
+        "list_detours",
+        "Lists the game functions OpenZT detours, optionally only those whose name or hook contains filter",
+        "list_detours([filter])",
+        DebugMemory,
+        |filter: Option<String>| { Ok(list_detours(filter.as_deref())) }
+    );
+
+    // set_detour_enabled(detour, enabled) - switches a detour on or off
+    lua_fn!(
+        "set_detour_enabled",
+        "Enables or disables a detour, found by address (0x...), function name or hook path",
+        "set_detour_enabled(detour, enabled)",
+        DebugMemory,
+        |detour: String, enabled: bool| {
+            match set_detour_enabled(&detour, enabled) {
+                Ok(result) => Ok((Some(result), None::<String>)),
+                Err(e) => Ok((None::<String>, Some(e))),
+            }
+        }
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use openzt_detour::registry::Toggle;
+
+    use super::*;
+
+    struct FakeDetour(AtomicBool);
+
+    impl Toggle for FakeDetour {
+        unsafe fn enable(&self) -> retour::Result<()> {
+            self.0.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+
+        unsafe fn disable(&self) -> retour::Result<()> {
+            self.0.store(false, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn is_enabled(&self) -> bool {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    #[test]
+    fn test_list_and_toggle() {
+        static DETOUR: FakeDetour = FakeDetour(AtomicBool::new(false));
+        openzt_detour::game_build::set_detected(&openzt_detour::game_build::KNOWN_BUILDS[0]);
+        unsafe { registry::register("openztlib::detours::tests", "fake_hook", "FAKE_FUNCTION", 0x0000_1234, || &DETOUR) }.unwrap();
+
+        assert_eq!(list_detours(Some("FAKE_FUNCTION")), "0x00001234 FAKE_FUNCTION -> openztlib::detours::tests::fake_hook [enabled]");
+        assert_eq!(
+            set_detour_enabled("tests::fake_hook", false),
+            Ok("0x00001234 FAKE_FUNCTION -> openztlib::detours::tests::fake_hook [disabled]".to_string())
+        );
+        assert!(!DETOUR.is_enabled());
+        assert_eq!(set_detour_enabled("NOT_HOOKED", true), Err("No detour matches 'NOT_HOOKED'".to_string()));
+        assert_eq!(list_detours(Some("NOT_HOOKED")), "No detours registered");
+    }
+}
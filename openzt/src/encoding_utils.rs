@@ -1,5 +1,6 @@
 use encoding_rs::Encoding;
 use std::sync::LazyLock;
+#[cfg(windows)]
 use tracing::{info, warn};
 
 /// The system's ANSI code page encoding, detected at runtime
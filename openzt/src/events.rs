@@ -0,0 +1,554 @@
+//! Event bus for game events
+//!
+//! Detours publish [`Event`]s to the bus, and Rust modules and Lua scripts subscribe to the
+//! kinds they care about, so a feature that reacts to the game doesn't need a detour of its own
+//! (only one detour can be installed per function). Subscribers run highest priority first, and
+//! in subscription order within a priority. A subscriber can cancel a cancellable event, which
+//! skips the remaining subscribers and the game's own handling of it.
+//!
+//! Lua code subscribes with `events.subscribe(name, handler, [priority])` and cancels by
+//! returning `false`, which requires the `modify_world` capability. Lua handlers run with the
+//! sandbox limits of the code that subscribed them, and a handler that errors is unsubscribed.
+
+use std::sync::{Arc, LazyLock, Mutex};
+
+use mlua::{Function, IntoLuaMulti, Lua, MultiValue};
+#[cfg(target_os = "windows")]
+use openzt_detour_macro::detour_mod;
+use tracing::{error, info, warn};
+
+use crate::{
+    hook_chains::PLACE_ENTITY_ON_MAP,
+    scripting::{
+        add_lua_function,
+        lua_handle,
+        sandbox::{current_limits, require_capability, with_limits, ExecutionLimits},
+        Capability,
+        LuaTypes,
+    },
+    ztworldmgr::ZTEntityRef,
+};
+
+/// The kinds of [`Event`] that can be subscribed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    EntityPlaced,
+    EntityDeleted,
+    AnimalBorn,
+    AnimalDied,
+    GuestEntered,
+    GuestLeft,
+    MonthChanged,
+    YearChanged,
+    KeyPressed,
+    ButtonClicked,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 10] = [
+        EventKind::EntityPlaced,
+        EventKind::EntityDeleted,
+        EventKind::AnimalBorn,
+        EventKind::AnimalDied,
+        EventKind::GuestEntered,
+        EventKind::GuestLeft,
+        EventKind::MonthChanged,
+        EventKind::YearChanged,
+        EventKind::KeyPressed,
+        EventKind::ButtonClicked,
+    ];
+
+    /// The name Lua code subscribes with
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::EntityPlaced => "entity_placed",
+            EventKind::EntityDeleted => "entity_deleted",
+            EventKind::AnimalBorn => "animal_born",
+            EventKind::AnimalDied => "animal_died",
+            EventKind::GuestEntered => "guest_entered",
+            EventKind::GuestLeft => "guest_left",
+            EventKind::MonthChanged => "month_changed",
+            EventKind::YearChanged => "year_changed",
+            EventKind::KeyPressed => "key_pressed",
+            EventKind::ButtonClicked => "button_clicked",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<EventKind> {
+        EventKind::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    /// Whether subscribers can stop the game from handling the event
+    pub fn cancellable(&self) -> bool {
+        matches!(self, EventKind::EntityDeleted | EventKind::KeyPressed | EventKind::ButtonClicked)
+    }
+}
+
+/// Something that happened in the game, published by the detour that observed it
+///
+/// Entities are passed by address; Lua handlers receive them as `ZTEntity` userdata.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// An entity was placed on the map
+    EntityPlaced { entity: u32 },
+    /// An entity is about to be removed from the map, cancelling keeps it
+    EntityDeleted { entity: u32 },
+    /// An animal was born, or an egg was laid
+    AnimalBorn { animal: u32 },
+    /// An animal started dying
+    AnimalDied { animal: u32 },
+    /// A guest entered the zoo
+    GuestEntered { guest: u32 },
+    /// A guest left the zoo
+    GuestLeft { guest: u32 },
+    /// The in-game month advanced, `month` is 1-12
+    MonthChanged { year: u16, month: u16 },
+    /// The in-game year advanced
+    YearChanged { year: u16 },
+    /// A key was pressed that no shortcut handled, cancelling hides it from the game
+    KeyPressed { key: i32 },
+    /// A UI control was clicked, cancelling ignores the click
+    ButtonClicked { name: String },
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::EntityPlaced { .. } => EventKind::EntityPlaced,
+            Event::EntityDeleted { .. } => EventKind::EntityDeleted,
+            Event::AnimalBorn { .. } => EventKind::AnimalBorn,
+            Event::AnimalDied { .. } => EventKind::AnimalDied,
+            Event::GuestEntered { .. } => EventKind::GuestEntered,
+            Event::GuestLeft { .. } => EventKind::GuestLeft,
+            Event::MonthChanged { .. } => EventKind::MonthChanged,
+            Event::YearChanged { .. } => EventKind::YearChanged,
+            Event::KeyPressed { .. } => EventKind::KeyPressed,
+            Event::ButtonClicked { .. } => EventKind::ButtonClicked,
+        }
+    }
+
+    /// The arguments Lua handlers are called with
+    fn lua_args(&self, lua: &Lua) -> mlua::Result<MultiValue> {
+        match self {
+            Event::EntityPlaced { entity }
+            | Event::EntityDeleted { entity }
+            | Event::AnimalBorn { animal: entity }
+            | Event::AnimalDied { animal: entity }
+            | Event::GuestEntered { guest: entity }
+            | Event::GuestLeft { guest: entity } => ZTEntityRef::new(*entity).into_lua_multi(lua),
+            Event::MonthChanged { year, month } => (*year, *month).into_lua_multi(lua),
+            Event::YearChanged { year } => year.into_lua_multi(lua),
+            Event::KeyPressed { key } => key.into_lua_multi(lua),
+            Event::ButtonClicked { name } => name.as_str().into_lua_multi(lua),
+        }
+    }
+}
+
+/// What a subscriber wants to happen after it handled an event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Propagation {
+    Continue,
+    /// Skip the remaining subscribers and the game's handling, ignored for events that aren't cancellable
+    Cancel,
+}
+
+pub type SubscriptionId = u64;
+
+#[derive(Clone)]
+enum Handler {
+    Rust(Arc<dyn Fn(&Event) -> Propagation + Send + Sync>),
+    Lua { function: Function, limits: ExecutionLimits },
+}
+
+#[derive(Clone)]
+struct Subscriber {
+    id: SubscriptionId,
+    kind: EventKind,
+    priority: i32,
+    handler: Handler,
+}
+
+#[derive(Default)]
+struct Subscribers {
+    next_id: SubscriptionId,
+    /// Sorted by descending priority, then by subscription order
+    entries: Vec<Subscriber>,
+}
+
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Mutex<Subscribers>,
+}
+
+impl EventBus {
+    fn add(&self, kind: EventKind, priority: i32, handler: Handler) -> SubscriptionId {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.next_id += 1;
+        let id = subscribers.next_id;
+        let index = subscribers.entries.partition_point(|subscriber| subscriber.priority >= priority);
+        subscribers.entries.insert(index, Subscriber { id, kind, priority, handler });
+        id
+    }
+
+    /// Call `handler` for every published event of `kind`
+    ///
+    /// # Arguments
+    /// * `priority` - Higher priorities run first, 0 is the default
+    pub fn subscribe(&self, kind: EventKind, priority: i32, handler: impl Fn(&Event) -> Propagation + Send + Sync + 'static) -> SubscriptionId {
+        self.add(kind, priority, Handler::Rust(Arc::new(handler)))
+    }
+
+    /// Call the Lua `function` for every published event of `kind`, running it under `limits`
+    pub fn subscribe_lua(&self, kind: EventKind, priority: i32, function: Function, limits: ExecutionLimits) -> SubscriptionId {
+        self.add(kind, priority, Handler::Lua { function, limits })
+    }
+
+    /// Remove a subscription, returns false if there was none with `id`
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let before = subscribers.entries.len();
+        subscribers.entries.retain(|subscriber| subscriber.id != id);
+        subscribers.entries.len() != before
+    }
+
+    /// Run the subscribers of `event` in priority order
+    ///
+    /// # Returns
+    /// * `false` if a subscriber cancelled the event
+    pub fn publish(&self, lua: &Lua, event: &Event) -> bool {
+        let kind = event.kind();
+        // Handlers may subscribe or publish themselves, so they run from a snapshot rather than under the lock
+        let subscribers: Vec<Subscriber> = self.subscribers.lock().unwrap().entries.iter().filter(|subscriber| subscriber.kind == kind).cloned().collect();
+
+        for subscriber in subscribers {
+            let propagation = match &subscriber.handler {
+                Handler::Rust(handler) => handler(event),
+                Handler::Lua { function, limits } => call_lua(lua, function, limits, event).unwrap_or_else(|err| {
+                    let info = function.info();
+                    error!(
+                        "Lua handler for '{}' ({}:{}) failed and was unsubscribed: {}",
+                        kind.name(),
+                        info.short_src.unwrap_or_default(),
+                        info.line_defined.unwrap_or_default(),
+                        err
+                    );
+                    self.unsubscribe(subscriber.id);
+                    Propagation::Continue
+                }),
+            };
+            if propagation == Propagation::Cancel {
+                if kind.cancellable() {
+                    return false;
+                }
+                warn!("Ignoring cancellation of '{}', which is not cancellable", kind.name());
+            }
+        }
+        true
+    }
+}
+
+/// Call a Lua handler, which cancels the event by returning `false`
+fn call_lua(lua: &Lua, function: &Function, limits: &ExecutionLimits, event: &Event) -> mlua::Result<Propagation> {
+    with_limits(lua, limits, || {
+        let result: mlua::Value = function.call(event.lua_args(lua)?)?;
+        if result == mlua::Value::Boolean(false) && event.kind().cancellable() {
+            require_capability(Capability::ModifyWorld, &format!("cancelling {}", event.kind().name()))?;
+            return Ok(Propagation::Cancel);
+        }
+        Ok(Propagation::Continue)
+    })
+}
+
+static EVENT_BUS: LazyLock<EventBus> = LazyLock::new(EventBus::default);
+
+/// Subscribe a Rust handler to the global event bus, see [`EventBus::subscribe`]
+pub fn subscribe(kind: EventKind, priority: i32, handler: impl Fn(&Event) -> Propagation + Send + Sync + 'static) -> SubscriptionId {
+    EVENT_BUS.subscribe(kind, priority, handler)
+}
+
+pub fn unsubscribe(id: SubscriptionId) -> bool {
+    EVENT_BUS.unsubscribe(id)
+}
+
+/// Publish `event` on the global event bus
+///
+/// # Returns
+/// * `false` if a subscriber cancelled the event, in which case the game should not handle it
+pub fn publish(event: Event) -> bool {
+    EVENT_BUS.publish(&lua_handle(), &event)
+}
+
+#[cfg(target_os = "windows")]
+#[detour_mod]
+pub mod event_hooks {
+    use openzt_detour::gen::{
+        uicontrol::HANDLE_LEFT_BUTTON_UP,
+        ztanimal::{DO_REPRODUCE_CHECK, SET_DYING},
+        ztmapview::REMOVE_ENTITY,
+        ztunit::{ADD_TO_MAP, REMOVE_FROM_MAP},
+    };
+
+    use super::{publish, Event};
+    use crate::{
+        ztui::element_name,
+        ztworldmgr::{read_zt_world_mgr_from_global, ZTEntityClass, ZTEntityRef},
+    };
+
+    #[detour(REMOVE_ENTITY)]
+    unsafe extern "thiscall" fn zt_map_view_remove_entity(this_ptr: u32, entity_ptr: u32, param_3: i8) {
+        if entity_ptr == 0 || publish(Event::EntityDeleted { entity: entity_ptr }) {
+            unsafe { REMOVE_ENTITY_DETOUR.call(this_ptr, entity_ptr, param_3) };
+        }
+    }
+
+    #[detour(SET_DYING)]
+    unsafe extern "thiscall" fn zt_animal_set_dying(this_ptr: u32, dying: bool) {
+        unsafe { SET_DYING_DETOUR.call(this_ptr, dying) };
+        if dying {
+            publish(Event::AnimalDied { animal: this_ptr });
+        }
+    }
+
+    /// Offspring are appended to the entity array, so anything past the old end was born in the check
+    ///
+    /// The array may be reallocated when offspring are added, so it's compared by entity count, not address.
+    #[detour(DO_REPRODUCE_CHECK)]
+    unsafe extern "thiscall" fn zt_animal_do_reproduce_check(this_ptr: u32) {
+        let before = read_zt_world_mgr_from_global().entity_count();
+        unsafe { DO_REPRODUCE_CHECK_DETOUR.call(this_ptr) };
+        let entities = read_zt_world_mgr_from_global().entity_ptrs();
+        if entities.len() <= before {
+            return;
+        }
+        for animal in entities.into_iter().skip(before) {
+            if ZTEntityRef::new(animal).class() == ZTEntityClass::Animal {
+                publish(Event::AnimalBorn { animal });
+            }
+        }
+    }
+
+    #[detour(ADD_TO_MAP)]
+    unsafe extern "thiscall" fn zt_unit_add_to_map(this_ptr: u32) -> u32 {
+        let result = unsafe { ADD_TO_MAP_DETOUR.call(this_ptr) };
+        if ZTEntityRef::new(this_ptr).class() == ZTEntityClass::Guest {
+            publish(Event::GuestEntered { guest: this_ptr });
+        }
+        result
+    }
+
+    #[detour(REMOVE_FROM_MAP)]
+    unsafe extern "thiscall" fn zt_unit_remove_from_map(this_ptr: u32, param_2: u32) {
+        if ZTEntityRef::new(this_ptr).class() == ZTEntityClass::Guest {
+            publish(Event::GuestLeft { guest: this_ptr });
+        }
+        unsafe { REMOVE_FROM_MAP_DETOUR.call(this_ptr, param_2) };
+    }
+
+    #[detour(HANDLE_LEFT_BUTTON_UP)]
+    unsafe extern "thiscall" fn ui_control_handle_left_button_up(this_ptr: u32, param_2: u32, param_3: u32) -> u32 {
+        if !publish(Event::ButtonClicked { name: element_name(this_ptr) }) {
+            return 0;
+        }
+        unsafe { HANDLE_LEFT_BUTTON_UP_DETOUR.call(this_ptr, param_2, param_3) }
+    }
+}
+
+pub fn init() {
+    info!("Initializing event bus");
+    #[cfg(target_os = "windows")]
+    if let Err(e) = unsafe { event_hooks::init_detours() } {
+        error!("Error initializing event detours: {}", e);
+    }
+    // After other features' handlers, so subscribers see e.g. roofs already hidden
+    PLACE_ENTITY_ON_MAP.post("events", -100, |&(_, entity, _, _), result| {
+        if *result != 0 && entity != 0 {
+            publish(Event::EntityPlaced { entity });
+        }
+    });
+    register_lua_functions();
+}
+
+pub(crate) fn register_lua_functions() {
+    // events.subscribe(name, handler, [priority]) - handlers run with the caller's sandbox limits
+    add_lua_function(
+        "events.subscribe",
+        "Calls handler for every event of a kind, such as \"entity_placed\"; higher priorities run first and returning false cancels",
+        "events.subscribe(name, handler, [priority])",
+        Capability::ReadWorld,
+        LuaTypes::new()
+            .param::<String>("name")
+            .param::<Function>("handler")
+            .param::<Option<i32>>("priority")
+            .returns::<SubscriptionId>(),
+        |lua| {
+            lua.create_function(|_, (name, handler, priority): (String, Function, Option<i32>)| {
+                let kind = EventKind::from_name(&name).ok_or_else(|| {
+                    let names: Vec<&str> = EventKind::ALL.iter().map(EventKind::name).collect();
+                    mlua::Error::runtime(format!("Unknown event '{}', expected one of: {}", name, names.join(", ")))
+                })?;
+                Ok(EVENT_BUS.subscribe_lua(kind, priority.unwrap_or(0), handler, current_limits()))
+            })
+            .unwrap()
+        },
+    )
+    .unwrap();
+
+    // events.unsubscribe(id)
+    add_lua_function(
+        "events.unsubscribe",
+        "Removes a handler added by events.subscribe",
+        "events.unsubscribe(id)",
+        Capability::ReadWorld,
+        LuaTypes::new().param::<SubscriptionId>("id").returns::<bool>(),
+        |lua| lua.create_function(|_, id: SubscriptionId| Ok(EVENT_BUS.unsubscribe(id))).unwrap(),
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A handler that records `label` and then returns `propagation`
+    fn recorder(log: &Arc<Mutex<Vec<String>>>, label: &str, propagation: Propagation) -> impl Fn(&Event) -> Propagation + Send + Sync + 'static {
+        let log = Arc::clone(log);
+        let label = label.to_string();
+        move |_| {
+            log.lock().unwrap().push(label.clone());
+            propagation
+        }
+    }
+
+    fn lua_with_log(log: &Arc<Mutex<Vec<String>>>) -> Lua {
+        let lua = Lua::new();
+        let sink = Arc::clone(log);
+        let record = lua
+            .create_function(move |_, value: String| {
+                sink.lock().unwrap().push(value);
+                Ok(())
+            })
+            .unwrap();
+        lua.globals().set("record", record).unwrap();
+        lua
+    }
+
+    fn lua_function(lua: &Lua, code: &str) -> Function {
+        lua.load(code).eval().unwrap()
+    }
+
+    #[test]
+    fn test_priority_order() {
+        let bus = EventBus::default();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        bus.subscribe(EventKind::KeyPressed, 0, recorder(&log, "default", Propagation::Continue));
+        bus.subscribe(EventKind::KeyPressed, 10, recorder(&log, "high", Propagation::Continue));
+        bus.subscribe(EventKind::KeyPressed, -5, recorder(&log, "low", Propagation::Continue));
+        bus.subscribe(EventKind::KeyPressed, 0, recorder(&log, "default later", Propagation::Continue));
+        bus.subscribe(EventKind::YearChanged, 100, recorder(&log, "other kind", Propagation::Continue));
+
+        assert!(bus.publish(&Lua::new(), &Event::KeyPressed { key: 65 }));
+        assert_eq!(*log.lock().unwrap(), vec!["high", "default", "default later", "low"]);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let bus = EventBus::default();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        bus.subscribe(EventKind::KeyPressed, 1, recorder(&log, "first", Propagation::Cancel));
+        bus.subscribe(EventKind::KeyPressed, 0, recorder(&log, "second", Propagation::Continue));
+        bus.subscribe(EventKind::YearChanged, 1, recorder(&log, "year first", Propagation::Cancel));
+        bus.subscribe(EventKind::YearChanged, 0, recorder(&log, "year second", Propagation::Continue));
+
+        assert!(!bus.publish(&Lua::new(), &Event::KeyPressed { key: 65 }));
+        // Year changes can't be cancelled, so every subscriber still runs
+        assert!(bus.publish(&Lua::new(), &Event::YearChanged { year: 2002 }));
+        assert_eq!(*log.lock().unwrap(), vec!["first", "year first", "year second"]);
+    }
+
+    #[test]
+    fn test_unsubscribe() {
+        let bus = EventBus::default();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let id = bus.subscribe(EventKind::KeyPressed, 0, recorder(&log, "removed", Propagation::Cancel));
+        bus.subscribe(EventKind::KeyPressed, 0, recorder(&log, "kept", Propagation::Continue));
+
+        assert!(bus.unsubscribe(id));
+        assert!(!bus.unsubscribe(id));
+        assert!(bus.publish(&Lua::new(), &Event::KeyPressed { key: 65 }));
+        assert_eq!(*log.lock().unwrap(), vec!["kept"]);
+    }
+
+    #[test]
+    fn test_lua_subscribers() {
+        let bus = EventBus::default();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let lua = lua_with_log(&log);
+        bus.subscribe(EventKind::MonthChanged, 5, recorder(&log, "rust", Propagation::Continue));
+        let handler = lua_function(&lua, "function(year, month) record('lua ' .. year .. '-' .. month) end");
+        bus.subscribe_lua(EventKind::MonthChanged, 10, handler, ExecutionLimits::default());
+        let handler = lua_function(&lua, "function(key) record('key ' .. key) return false end");
+        bus.subscribe_lua(EventKind::KeyPressed, 0, handler, ExecutionLimits::default());
+        bus.subscribe(EventKind::KeyPressed, -1, recorder(&log, "after cancel", Propagation::Continue));
+
+        assert!(bus.publish(&lua, &Event::MonthChanged { year: 2001, month: 7 }));
+        assert!(!bus.publish(&lua, &Event::KeyPressed { key: 65 }));
+        assert_eq!(*log.lock().unwrap(), vec!["lua 2001-7", "rust", "key 65"]);
+    }
+
+    #[test]
+    fn test_lua_cancel_requires_modify_world() {
+        let bus = EventBus::default();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let lua = lua_with_log(&log);
+        let read_only = ExecutionLimits {
+            capabilities: Some(vec![Capability::ReadWorld]),
+            ..Default::default()
+        };
+        let handler = lua_function(&lua, "function() record('handled') return false end");
+        bus.subscribe_lua(EventKind::KeyPressed, 0, handler, read_only);
+
+        assert!(bus.publish(&lua, &Event::KeyPressed { key: 65 }));
+        // The failed cancellation unsubscribed the handler
+        assert!(bus.publish(&lua, &Event::KeyPressed { key: 65 }));
+        assert_eq!(*log.lock().unwrap(), vec!["handled"]);
+    }
+
+    #[test]
+    fn test_failing_lua_handler_is_unsubscribed() {
+        let bus = EventBus::default();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let lua = lua_with_log(&log);
+        let handler = lua_function(&lua, "function() record('failing') error('boom') end");
+        bus.subscribe_lua(EventKind::YearChanged, 1, handler, ExecutionLimits::default());
+        bus.subscribe(EventKind::YearChanged, 0, recorder(&log, "rust", Propagation::Continue));
+        let limited = ExecutionLimits {
+            instructions: Some(10_000),
+            ..Default::default()
+        };
+        let handler = lua_function(&lua, "function() while true do end end");
+        bus.subscribe_lua(EventKind::YearChanged, -1, handler, limited);
+
+        bus.publish(&lua, &Event::YearChanged { year: 2002 });
+        bus.publish(&lua, &Event::YearChanged { year: 2003 });
+        assert_eq!(*log.lock().unwrap(), vec!["failing", "rust", "rust"]);
+        assert_eq!(bus.subscribers.lock().unwrap().entries.len(), 1);
+    }
+
+    #[test]
+    fn test_subscribe_from_handler() {
+        let bus = Arc::new(EventBus::default());
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let inner_bus = Arc::clone(&bus);
+        let inner_log = Arc::clone(&log);
+        bus.subscribe(EventKind::KeyPressed, 0, move |_| {
+            inner_bus.subscribe(EventKind::KeyPressed, 0, recorder(&inner_log, "added", Propagation::Continue));
+            Propagation::Continue
+        });
+
+        // Subscribers added while publishing only see later events
+        bus.publish(&Lua::new(), &Event::KeyPressed { key: 65 });
+        assert!(log.lock().unwrap().is_empty());
+        bus.publish(&Lua::new(), &Event::KeyPressed { key: 65 });
+        assert_eq!(*log.lock().unwrap(), vec!["added"]);
+    }
+}
@@ -11,6 +11,8 @@ use anyhow::{anyhow, Context};
 use openzt_configparser::ini::Ini;
 use maplit::hashset;
 use std::sync::LazyLock;
+use openzt_detour::{game_address, game_build::GameAddress};
+#[cfg(target_os = "windows")]
 use openzt_detour_macro::detour_mod;
 use tracing::{debug, error, info};
 
@@ -133,9 +135,9 @@ const CUSTOM_CONTENT_EXPANSION_STRING_PREFIX: &str = "openzt_";
 const CUSTOM_CONTENT_EXPANSION_STRING_ALL: &str = "all";
 const CUSTOM_CONTENT_EXPANSION_STRING_SUBDIR: &str = "subdir_";
 
-const EXPANSION_LIST_START: u32 = 0x00639030;
+const EXPANSION_LIST_START: GameAddress = game_address!(CompleteCollection: 0x00639030);
 const EXPANSION_SIZE: u32 = 0x14;
-const EXPANSION_CURRENT: u32 = 0x00638d4c;
+const EXPANSION_CURRENT: GameAddress = game_address!(CompleteCollection: 0x00638d4c);
 
 const EXPANSION_ZT_RESOURCE_PREFIX: &str = "ui/sharedui/listbk/";
 const EXPANSION_OPENZT_RESOURCE_PREFIX: &str = "openzt.patches.expansion";
@@ -287,7 +289,7 @@ impl Expansion {
 }
 
 fn read_expansion_list_from_memory() -> ExpansionList {
-    get_from_memory(EXPANSION_LIST_START)
+    get_from_memory(EXPANSION_LIST_START.get())
 }
 
 fn read_expansion_from_memory(address: u32) -> Expansion {
@@ -312,7 +314,7 @@ fn read_expansions_from_memory() -> Vec<Expansion> {
 }
 
 fn read_current_expansion() -> Option<Expansion> {
-    let current_expansion_id = get_from_memory(EXPANSION_CURRENT);
+    let current_expansion_id = get_from_memory(EXPANSION_CURRENT.get());
     match get_expansion(current_expansion_id) {
         Some(expansion) => Some(expansion),
         None => {
@@ -323,11 +325,11 @@ fn read_current_expansion() -> Option<Expansion> {
 }
 
 fn save_current_expansion(expansion_id: u32) {
-    save_to_memory(EXPANSION_CURRENT, expansion_id);
+    save_to_memory(EXPANSION_CURRENT.get(), expansion_id);
 }
 
 fn save_expansion_list_to_memory(expansion_list: ExpansionList) {
-    save_to_memory(EXPANSION_LIST_START, expansion_list);
+    save_to_memory(EXPANSION_LIST_START.get(), expansion_list);
 }
 
 impl Display for Expansion {
@@ -352,6 +354,7 @@ impl Display for ExpansionList {
     }
 }
 
+#[cfg(target_os = "windows")]
 #[detour_mod]
 pub mod custom_expansion {
     use tracing::debug;
@@ -388,7 +391,9 @@ pub mod custom_expansion {
         reimplemented_result
     }
 
+    // Running the original setup twice would add the vanilla expansions again
     #[detour(SETUP)]
+    #[no_fallback]
     pub unsafe extern "stdcall" fn ztui_expansionselect_setup() {
         unsafe { SETUP_DETOUR.call() }; //TODO: Remove this call once all functionality has been replicated, need to figure out why removing is causes crashes currently
 
@@ -871,29 +876,7 @@ fn command_get_current_expansion(_args: Vec<&str>) -> Result<String, CommandErro
 }
 
 pub fn init() {
-    // list_expansion() - no args
-    lua_fn!("list_expansion", "Lists all loaded expansions", "list_expansion()", || {
-        match command_get_expansions(vec![]) {
-            Ok(result) => Ok((Some(result), None::<String>)),
-            Err(e) => Ok((None::<String>, Some(e.to_string())))
-        }
-    });
-
-    // get_current_expansion() - no args
-    lua_fn!("get_current_expansion", "Returns current active expansion", "get_current_expansion()", || {
-        match command_get_current_expansion(vec![]) {
-            Ok(result) => Ok((Some(result), None::<String>)),
-            Err(e) => Ok((None::<String>, Some(e.to_string())))
-        }
-    });
-
-    // get_members() - no args
-    lua_fn!("get_members", "Lists expansion member sets", "get_members()", || {
-        match command_get_members(vec![]) {
-            Ok(result) => Ok((Some(result), None::<String>)),
-            Err(e) => Ok((None::<String>, Some(e.to_string())))
-        }
-    });
+    register_lua_functions();
     add_handler(
         Handler::builder()
             .prefix("xpac")
@@ -939,7 +922,72 @@ pub fn init() {
             .animation_handler(handle_expansion_dropdown_animation)
             .build(),
     );
+    #[cfg(target_os = "windows")]
     if unsafe { custom_expansion::init_detours() }.is_err() {
         error!("Error initialising custom expansion detours");
     };
 }
+
+pub(crate) fn register_lua_functions() {
+    // list_expansion() - no args
+    lua_fn!("list_expansion", "Lists all loaded expansions", "list_expansion()", ReadWorld, || {
+        match command_get_expansions(vec![]) {
+            Ok(result) => Ok((Some(result), None::<String>)),
+            Err(e) => Ok((None::<String>, Some(e.to_string())))
+        }
+    });
+
+    // get_current_expansion() - no args
+    lua_fn!("get_current_expansion", "Returns current active expansion", "get_current_expansion()", ReadWorld, || {
+        match command_get_current_expansion(vec![]) {
+            Ok(result) => Ok((Some(result), None::<String>)),
+            Err(e) => Ok((None::<String>, Some(e.to_string())))
+        }
+    });
+
+    // get_members() - no args
+    lua_fn!("get_members", "Lists expansion member sets", "get_members()", ReadWorld, || {
+        match command_get_members(vec![]) {
+            Ok(result) => Ok((Some(result), None::<String>)),
+            Err(e) => Ok((None::<String>, Some(e.to_string())))
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::game_memory::{bytes_of, with_memory, FakeMemory};
+
+    fn fake_expansion(memory: &FakeMemory, expansion_id: u32, name: &str) -> Expansion {
+        let name_ptr = memory.alloc_c_string(name);
+        Expansion {
+            expansion_id,
+            name_id: 0,
+            name_string_start_ptr: name_ptr,
+            name_string_end_ptr: name_ptr + name.len() as u32,
+            name_string_buffer_end_ptr: name_ptr + name.len() as u32 + 1,
+        }
+    }
+
+    #[test]
+    fn test_read_expansions_from_memory() {
+        openzt_detour::game_build::set_detected(&openzt_detour::game_build::KNOWN_BUILDS[0]);
+        let memory = Arc::new(FakeMemory::new());
+        let array = memory.alloc_value([fake_expansion(&memory, 0x4000, "All"), fake_expansion(&memory, 0x4001, "Dinosaur Digs")]);
+        memory.map(EXPANSION_LIST_START.get(), bytes_of(&[array, array + 2 * EXPANSION_SIZE, array + 2 * EXPANSION_SIZE]));
+
+        with_memory(memory.clone(), || {
+            let expansions = read_expansions_from_memory();
+            let names: Vec<(u32, String)> = expansions.iter().map(|expansion| (expansion.expansion_id, expansion.name_string())).collect();
+            assert_eq!(names, vec![(0x4000, "All".to_string()), (0x4001, "Dinosaur Digs".to_string())]);
+
+            // Writing the list back goes to the same global
+            save_expansion_list_to_memory(ExpansionList { array_start: array, array_end: array + EXPANSION_SIZE, buffer_end: array + 2 * EXPANSION_SIZE });
+            assert_eq!(read_expansions_from_memory().len(), 1);
+        });
+        assert_eq!(memory.read::<u32>(EXPANSION_LIST_START.get() + 4), array + EXPANSION_SIZE);
+    }
+}
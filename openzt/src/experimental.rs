@@ -1,4 +1,6 @@
+#[cfg(target_os = "windows")]
 use tracing::info;
+#[cfg(target_os = "windows")]
 use openzt_detour_macro::detour_mod;
 
 // use crate::{
@@ -10,6 +12,7 @@ use openzt_detour_macro::detour_mod;
 // void __thiscall BFUIMgr::displayMessage(void *this,uint param_1,int param_2,BFTile *param_3,BFEntity *param_4,bool param_5, bool param_6)
 
 
+#[cfg(target_os = "windows")]
 #[detour_mod]
 pub mod zoo_experimental {
     use tracing::info;
@@ -52,6 +55,7 @@ pub mod zoo_experimental {
 }
 
 pub fn init() {
+    #[cfg(target_os = "windows")]
     if let Err(e) = unsafe { zoo_experimental::init_detours() } {
         info!("Error initialising experimental detours: {}", e);
     };
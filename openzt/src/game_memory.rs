@@ -0,0 +1,405 @@
+//! Access to the game's memory behind the [`GameMemory`] trait
+//!
+//! The readers and writers in [`crate::util`] go through the current thread's backend, which is
+//! the game's own address space ([`LiveMemory`]) unless [`with_memory`] installed another. A
+//! [`FakeMemory`] is an in-process address space made of regions, built by hand or loaded from a
+//! [`Snapshot`] captured in a running game with `save_memory_snapshot`, so code mirroring the
+//! game's structs can be covered by plain unit tests.
+
+use std::{
+    cell::{RefCell, UnsafeCell},
+    collections::BTreeMap,
+    fmt::Write,
+    fs,
+    mem::size_of,
+    path::Path,
+    ptr,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::lua_fn;
+
+/// An address space holding the game's memory
+pub trait GameMemory: Send + Sync {
+    /// A pointer in this process to `len` bytes of game memory starting at `address`
+    ///
+    /// # Panics
+    /// If the range isn't mapped, like the game would crash reading it
+    fn host_ptr(&self, address: u32, len: usize) -> *mut u8;
+
+    /// Copies `len` bytes starting at `address`
+    fn read_bytes(&self, address: u32, len: usize) -> Vec<u8> {
+        let mut bytes = vec![0; len];
+        unsafe { ptr::copy_nonoverlapping(self.host_ptr(address, len), bytes.as_mut_ptr(), len) };
+        bytes
+    }
+
+    /// Overwrites the bytes starting at `address`
+    fn write_bytes(&self, address: u32, bytes: &[u8]) {
+        unsafe { ptr::copy_nonoverlapping(bytes.as_ptr(), self.host_ptr(address, bytes.len()), bytes.len()) };
+    }
+
+    /// Overwrites bytes in pages that may be read-only, such as the game's code
+    fn write_protected_bytes(&self, address: u32, bytes: &[u8]) -> anyhow::Result<()> {
+        self.write_bytes(address, bytes);
+        Ok(())
+    }
+}
+
+/// The game's memory, when running inside its process
+pub struct LiveMemory;
+
+impl GameMemory for LiveMemory {
+    fn host_ptr(&self, address: u32, _len: usize) -> *mut u8 {
+        address as usize as *mut u8
+    }
+
+    #[cfg(target_os = "windows")]
+    fn write_protected_bytes(&self, address: u32, bytes: &[u8]) -> anyhow::Result<()> {
+        use windows::Win32::System::Memory::{VirtualProtect, PAGE_EXECUTE_READWRITE, PAGE_PROTECTION_FLAGS};
+
+        unsafe {
+            let mut old_protect: PAGE_PROTECTION_FLAGS = PAGE_PROTECTION_FLAGS(0);
+            VirtualProtect(address as *mut _, bytes.len(), PAGE_EXECUTE_READWRITE, &mut old_protect)?;
+            self.write_bytes(address, bytes);
+            VirtualProtect(address as *mut _, bytes.len(), old_protect, &mut old_protect)?;
+        }
+        Ok(())
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<dyn GameMemory>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with `memory` as the current thread's backend, instead of the game's memory
+///
+/// References from [`crate::util::map_from_memory`] into `memory` must not outlive it.
+pub fn with_memory<R>(memory: Arc<dyn GameMemory>, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<Arc<dyn GameMemory>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CURRENT.set(self.0.take());
+        }
+    }
+
+    let _restore = Restore(CURRENT.replace(Some(memory)));
+    f()
+}
+
+/// [`GameMemory::host_ptr`] of the current thread's backend
+pub fn host_ptr(address: u32, len: usize) -> *mut u8 {
+    CURRENT.with_borrow(|current| match current {
+        Some(memory) => memory.host_ptr(address, len),
+        None => LiveMemory.host_ptr(address, len),
+    })
+}
+
+/// [`GameMemory::read_bytes`] of the current thread's backend
+pub fn read_bytes(address: u32, len: usize) -> Vec<u8> {
+    CURRENT.with_borrow(|current| match current {
+        Some(memory) => memory.read_bytes(address, len),
+        None => LiveMemory.read_bytes(address, len),
+    })
+}
+
+/// [`GameMemory::write_protected_bytes`] of the current thread's backend
+pub fn write_protected_bytes(address: u32, bytes: &[u8]) -> anyhow::Result<()> {
+    CURRENT.with_borrow(|current| match current {
+        Some(memory) => memory.write_protected_bytes(address, bytes),
+        None => LiveMemory.write_protected_bytes(address, bytes),
+    })
+}
+
+/// The bytes of a value, for writing it to memory
+pub fn bytes_of<T>(value: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+}
+
+/// Where [`FakeMemory::alloc`] places new regions, above anything the game maps
+const ALLOC_BASE: u32 = 0x4000_0000;
+
+/// A mapped range of a [`FakeMemory`]
+///
+/// The storage starts at the 8 byte aligned address at or below `start`, so values in it are
+/// as aligned in this process as they are in the game.
+struct Region {
+    start: u32,
+    len: usize,
+    words: Box<[UnsafeCell<u64>]>,
+}
+
+impl Region {
+    fn new(start: u32, bytes: &[u8]) -> Self {
+        let offset = (start % 8) as usize;
+        let words = (0..(offset + bytes.len()).div_ceil(8)).map(|_| UnsafeCell::new(0)).collect();
+        let region = Region { start, len: bytes.len(), words };
+        unsafe { ptr::copy_nonoverlapping(bytes.as_ptr(), region.host_ptr(start), bytes.len()) };
+        region
+    }
+
+    fn end(&self) -> u64 {
+        self.start as u64 + self.len as u64
+    }
+
+    fn host_ptr(&self, address: u32) -> *mut u8 {
+        let offset = (address - (self.start & !7)) as usize;
+        unsafe { UnsafeCell::raw_get(self.words.as_ptr()).cast::<u8>().add(offset) }
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0; self.len];
+        unsafe { ptr::copy_nonoverlapping(self.host_ptr(self.start), bytes.as_mut_ptr(), self.len) };
+        bytes
+    }
+}
+
+/// An in-process address space for tests, made of the regions mapped into it
+///
+/// Reading or writing outside the regions panics. Regions never move once mapped, so pointers
+/// into them stay valid for the lifetime of the `FakeMemory`.
+#[derive(Default)]
+pub struct FakeMemory {
+    regions: Mutex<BTreeMap<u32, Region>>,
+}
+
+// Safety: like the game's memory, the regions are shared without synchronisation; the map of
+// regions itself is behind a mutex.
+unsafe impl Send for FakeMemory {}
+unsafe impl Sync for FakeMemory {}
+
+impl FakeMemory {
+    pub fn new() -> Self {
+        FakeMemory::default()
+    }
+
+    /// An address space with the regions of `snapshot`
+    pub fn from_snapshot(snapshot: &Snapshot) -> anyhow::Result<Self> {
+        let memory = FakeMemory::new();
+        for region in &snapshot.regions {
+            memory.map(region.address, &decode_hex(&region.bytes).with_context(|| format!("Invalid bytes at {:#x}", region.address))?);
+        }
+        Ok(memory)
+    }
+
+    /// Loads a snapshot file written by [`Snapshot::save`]
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        FakeMemory::from_snapshot(&Snapshot::load(path)?)
+    }
+
+    /// Maps `bytes` at `address`
+    ///
+    /// # Panics
+    /// If the range overlaps a region that is already mapped
+    pub fn map(&self, address: u32, bytes: &[u8]) {
+        let mut regions = self.regions.lock().unwrap();
+        let end = address as u64 + bytes.len() as u64;
+        if let Some(region) = regions.values().find(|region| (region.start as u64) < end && region.end() > address as u64) {
+            panic!("Mapping {:#x}..{:#x} overlaps {:#x}..{:#x}", address, end, region.start, region.end());
+        }
+        regions.insert(address, Region::new(address, bytes));
+    }
+
+    /// Maps `len` zeroed bytes at a free address and returns it
+    pub fn alloc(&self, len: usize) -> u32 {
+        let address = {
+            let regions = self.regions.lock().unwrap();
+            let top = regions.values().map(Region::end).filter(|end| *end > ALLOC_BASE as u64).max().unwrap_or(ALLOC_BASE as u64);
+            // 16 byte aligned, with a gap so overruns don't land in the next allocation
+            ((top + 16 + 15) & !15) as u32
+        };
+        self.map(address, &vec![0; len]);
+        address
+    }
+
+    /// Maps `value` at a free address and returns it
+    pub fn alloc_value<T>(&self, value: T) -> u32 {
+        let address = self.alloc(size_of::<T>());
+        self.write(address, value);
+        address
+    }
+
+    /// Maps a NUL terminated copy of `string` at a free address and returns it
+    pub fn alloc_c_string(&self, string: &str) -> u32 {
+        let address = self.alloc(string.len() + 1);
+        self.write_bytes(address, string.as_bytes());
+        address
+    }
+
+    pub fn read<T>(&self, address: u32) -> T {
+        unsafe { ptr::read_unaligned(self.host_ptr(address, size_of::<T>()) as *const T) }
+    }
+
+    pub fn write<T>(&self, address: u32, value: T) {
+        unsafe { ptr::write_unaligned(self.host_ptr(address, size_of::<T>()) as *mut T, value) };
+    }
+
+    /// All mapped regions, to save as a fixture
+    pub fn snapshot(&self) -> Snapshot {
+        let regions = self.regions.lock().unwrap();
+        Snapshot {
+            regions: regions
+                .values()
+                .map(|region| SnapshotRegion { address: region.start, bytes: encode_hex(&region.bytes()) })
+                .collect(),
+        }
+    }
+}
+
+impl GameMemory for FakeMemory {
+    fn host_ptr(&self, address: u32, len: usize) -> *mut u8 {
+        let regions = self.regions.lock().unwrap();
+        match regions.range(..=address).next_back() {
+            Some((_, region)) if address as u64 + len as u64 <= region.end() => region.host_ptr(address),
+            _ => panic!("Access to unmapped fake memory at {:#x} (len {:#x})", address, len),
+        }
+    }
+}
+
+/// Ranges of game memory saved to a file, loaded back as a [`FakeMemory`]
+///
+/// The file is JSON with each region's bytes hex encoded:
+/// ```json
+/// { "regions": [{ "address": 6520896, "bytes": "00a0c1..." }] }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub regions: Vec<SnapshotRegion>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotRegion {
+    pub address: u32,
+    pub bytes: String,
+}
+
+impl Snapshot {
+    /// Copies the ranges `(address, len)` out of `memory`
+    pub fn capture(memory: &dyn GameMemory, ranges: &[(u32, usize)]) -> Self {
+        Snapshot {
+            regions: ranges
+                .iter()
+                .map(|(address, len)| SnapshotRegion { address: *address, bytes: encode_hex(&memory.read_bytes(*address, *len)) })
+                .collect(),
+        }
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path).with_context(|| format!("Failed to read snapshot {}", path.display()))?;
+        serde_json::from_str(&contents).with_context(|| format!("Failed to parse snapshot {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?).with_context(|| format!("Failed to write snapshot {}", path.display()))
+    }
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+fn decode_hex(hex: &str) -> anyhow::Result<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return Err(anyhow!("Odd number of hex digits"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()).ok_or_else(|| anyhow!("Invalid hex digits at {}", i)))
+        .collect()
+}
+
+pub fn init() {
+    // save_memory_snapshot(path, ranges) - captures game memory for FakeMemory fixtures
+    lua_fn!(
+        "save_memory_snapshot",
+        "Saves ranges of game memory, given as {address, length} pairs, to a snapshot file for tests",
+        "save_memory_snapshot(path, {{address, length}, ...})",
+        DebugMemory,
+        |path: String, ranges: Vec<Vec<u32>>| {
+            let ranges: Vec<(u32, usize)> = ranges.iter().filter_map(|range| Some((*range.first()?, *range.get(1)? as usize))).collect();
+            match Snapshot::capture(&LiveMemory, &ranges).save(Path::new(&path)) {
+                Ok(()) => Ok((Some(format!("Saved {} regions to {}", ranges.len(), path)), None::<String>)),
+                Err(e) => Ok((None::<String>, Some(e.to_string()))),
+            }
+        }
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_write() {
+        let memory = FakeMemory::new();
+        memory.map(0x0063_8041, &[1, 2, 3, 4, 5]);
+        assert_eq!(memory.read::<u32>(0x0063_8041), 0x0403_0201);
+        memory.write::<u16>(0x0063_8044, 0xbeef);
+        assert_eq!(memory.read_bytes(0x0063_8041, 5), vec![1, 2, 3, 0xef, 0xbe]);
+        // Storage keeps the game's alignment
+        assert_eq!(memory.host_ptr(0x0063_8044, 1) as usize % 4, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "unmapped fake memory at 0x638045")]
+    fn test_unmapped_read_panics() {
+        let memory = FakeMemory::new();
+        memory.map(0x0063_8040, &[0; 4]);
+        memory.read::<u16>(0x0063_8042);
+        memory.read::<u8>(0x0063_8045);
+    }
+
+    #[test]
+    #[should_panic(expected = "overlaps")]
+    fn test_overlapping_map_panics() {
+        let memory = FakeMemory::new();
+        memory.map(0x1000, &[0; 16]);
+        memory.map(0x100c, &[0; 4]);
+    }
+
+    #[test]
+    fn test_alloc() {
+        let memory = FakeMemory::new();
+        let a = memory.alloc_value(7u32);
+        let b = memory.alloc_c_string("Custom Content");
+        assert!(a >= ALLOC_BASE && b > a);
+        assert_eq!((a % 16, b % 16), (0, 0));
+        assert_eq!(memory.read::<u32>(a), 7);
+        assert_eq!(memory.read_bytes(b, 15), b"Custom Content\0");
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let memory = FakeMemory::new();
+        memory.map(0x0063_8040, &[0xde, 0xad, 0xbe, 0xef]);
+        let snapshot = Snapshot::capture(&memory, &[(0x0063_8041, 2)]);
+        assert_eq!(snapshot.regions, vec![SnapshotRegion { address: 0x0063_8041, bytes: "adbe".to_string() }]);
+
+        let path = std::env::temp_dir().join(format!("openzt_snapshot_{}.json", std::process::id()));
+        memory.snapshot().save(&path).unwrap();
+        let loaded = FakeMemory::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.read::<u32>(0x0063_8040), 0xefbe_adde);
+
+        assert!(FakeMemory::from_snapshot(&Snapshot { regions: vec![SnapshotRegion { address: 0, bytes: "abc".to_string() }] }).is_err());
+    }
+
+    #[test]
+    fn test_with_memory_is_per_thread_and_restored() {
+        let memory = Arc::new(FakeMemory::new());
+        let address = memory.alloc_value(42u32);
+        let read = || unsafe { *(host_ptr(address, 4) as *const u32) };
+        assert_eq!(with_memory(memory.clone(), read), 42);
+        let inner = Arc::new(FakeMemory::new());
+        inner.map(address, &[1, 0, 0, 0]);
+        assert_eq!(with_memory(memory.clone(), || (with_memory(inner, read), read())), (1, 42));
+        assert!(CURRENT.with_borrow(Option::is_none));
+    }
+}
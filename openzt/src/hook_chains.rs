@@ -0,0 +1,30 @@
+//! Game functions more than one feature intercepts
+//!
+//! Only one detour can hook a game function, so instead of a `#[detour_mod]` each feature adds
+//! pre or post handlers to the function's [`HookChain`](openzt_detour::chain::HookChain) here.
+
+use openzt_detour::{gen, hook_chain};
+use tracing::error;
+
+hook_chain! {
+    /// ZTApp::updateSim, runs once per game tick with the elapsed time
+    pub static UPDATE_SIM = gen::ztapp::UPDATE_SIM: unsafe extern "thiscall" fn(this: u32, delta: i32);
+}
+
+hook_chain! {
+    /// ZTMapView::placeEntityOnMap, returns non-zero if the entity was placed
+    pub static PLACE_ENTITY_ON_MAP = gen::ztmapview::PLACE_ENTITY_ON_MAP_1:
+        unsafe extern "thiscall" fn(this: u32, entity: u32, pos: f32, rotation: i32) -> u32;
+}
+
+/// Installs the chains' detours, handlers can be added before or after
+pub fn init() {
+    unsafe {
+        if let Err(e) = UPDATE_SIM.install() {
+            error!("Error installing the UPDATE_SIM hook chain: {}", e);
+        }
+        if let Err(e) = PLACE_ENTITY_ON_MAP.install() {
+            error!("Error installing the PLACE_ENTITY_ON_MAP hook chain: {}", e);
+        }
+    }
+}
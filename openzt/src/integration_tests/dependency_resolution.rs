@@ -9,7 +9,7 @@ use std::collections::HashMap;
 // Embedded Test Resources for Dependency Tests
 // ============================================================================
 
-#[cfg(feature = "integration-tests")]
+#[cfg(any(test, feature = "integration-tests"))]
 mod embedded_resources {
     // Test Mod A - No dependencies (base mod)
     pub const META_MOD_A: &str = r#"
@@ -287,7 +287,7 @@ dependencies = [
 }
 
 /// Create test mod metadata map for dependency tests
-#[cfg(feature = "integration-tests")]
+#[cfg(any(test, feature = "integration-tests"))]
 fn create_test_mods() -> HashMap<String, &'static str> {
     use embedded_resources::*;
     let mut mods = HashMap::new();
@@ -317,7 +317,7 @@ fn create_test_mods() -> HashMap<String, &'static str> {
 }
 
 /// Parse test mods into Meta objects
-#[cfg(feature = "integration-tests")]
+#[cfg(any(test, feature = "integration-tests"))]
 fn parse_test_mods() -> HashMap<String, crate::mods::Meta> {
     let test_mods = create_test_mods();
     let mut parsed = HashMap::new();
@@ -12,7 +12,7 @@ use super::TestResult;
 // Embedded Test Resources
 // ============================================================================
 
-#[cfg(feature = "integration-tests")]
+#[cfg(any(test, feature = "integration-tests"))]
 mod embedded_resources {
     // Test ZTD content files
     pub const ANIMALS_CFG: &str = include_str!("../../resources/test/disabled-ztd-test/animals.cfg");
@@ -211,7 +211,7 @@ fn test_unsupported_file_type_no_resource() -> TestResult {
     }
 
     // Add a regular .bmp resource (not empty, just for testing)
-    if let Err(_) = add_test_binary_resource(test_path, b"BM", ZTFileType::Bmp) {
+    if add_test_binary_resource(test_path, b"BM", ZTFileType::Bmp).is_err() {
         // This is expected - we can't create empty resources for unsupported types
         return TestResult::pass(test_name);
     }
@@ -52,7 +52,7 @@ fn test_registry_tag_validation() -> TestResult {
         HashMap::new(),
     );
     match add_extension("test_mod".to_string(), "animals.elephant".to_string(), ext2) {
-        Ok(_) => return TestResult::fail(test_name, "Animal with roof tag should fail".to_string()),
+        Ok(_) => TestResult::fail(test_name, "Animal with roof tag should fail".to_string()),
         Err(_) => TestResult::pass(test_name),
     }
 }
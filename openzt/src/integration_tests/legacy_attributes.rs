@@ -10,7 +10,7 @@ use std::path::Path;
 use super::TestResult;
 
 // Embedded Test Resources
-#[cfg(feature = "integration-tests")]
+#[cfg(any(test, feature = "integration-tests"))]
 mod embedded_resources {
     // .cfg files
     pub const ANIMAL_CFG: &str = include_str!("../../resources/test/legacy-attributes-test/animal.cfg");
@@ -33,7 +33,7 @@ mod embedded_resources {
 }
 
 /// Load test legacy .cfg and .ai files into the resource system
-#[cfg(feature = "integration-tests")]
+#[cfg(any(test, feature = "integration-tests"))]
 pub fn load_test_legacy_files() -> anyhow::Result<()> {
     use embedded_resources::*;
 
@@ -10,7 +10,7 @@ use super::TestResult;
 // Embedded Test Resources
 // ============================================================================
 
-#[cfg(feature = "integration-tests")]
+#[cfg(any(test, feature = "integration-tests"))]
 mod embedded_resources {
     // Meta file
     pub const META_TOML: &str = include_str!("../../resources/test/loading-order-test/meta.toml");
@@ -31,7 +31,7 @@ mod embedded_resources {
 }
 
 /// Create an in-memory file map for the test mod (mimics ZIP structure)
-#[cfg(feature = "integration-tests")]
+#[cfg(any(test, feature = "integration-tests"))]
 pub fn create_test_mod_file_map() -> std::collections::HashMap<String, Box<[u8]>> {
     use embedded_resources::*;
 
@@ -195,7 +195,7 @@ fn test_alphabetical_within_nopatch() -> TestResult {
 
     // Expected order (case-insensitive alphabetical):
     // 00-habitat-only.toml, 01-location-only.toml, 02-another-habitat.toml, Capitals-Test.toml
-    let expected = vec![
+    let expected = [
         "defs/00-habitat-only.toml",
         "defs/01-location-only.toml",
         "defs/02-another-habitat.toml",
@@ -238,7 +238,7 @@ fn test_alphabetical_within_mixed() -> TestResult {
     }
 
     // Expected order: 50-mixed-content.toml, 51-second-mixed.toml
-    let expected = vec!["defs/50-mixed-content.toml", "defs/51-second-mixed.toml"];
+    let expected = ["defs/50-mixed-content.toml", "defs/51-second-mixed.toml"];
 
     if filenames.len() != expected.len() {
         return TestResult::fail(
@@ -276,7 +276,7 @@ fn test_alphabetical_within_patchonly() -> TestResult {
     }
 
     // Expected order: 98-early-patch.toml, 99-patches-only.toml
-    let expected = vec!["defs/98-early-patch.toml", "defs/99-patches-only.toml"];
+    let expected = ["defs/98-early-patch.toml", "defs/99-patches-only.toml"];
 
     if filenames.len() != expected.len() {
         return TestResult::fail(
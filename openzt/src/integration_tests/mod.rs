@@ -1,9 +1,8 @@
 #![allow(dead_code)]
 
-use std::fs::OpenOptions;
-use std::io::Write as IoWrite;
-
-use tracing::{error, info};
+#[cfg(target_os = "windows")]
+use tracing::error;
+use tracing::info;
 #[cfg(target_os = "windows")]
 use windows::Win32::System::Console::{AllocConsole, FreeConsole};
 
@@ -137,7 +136,7 @@ fn setup_test_files() -> anyhow::Result<()> {
 }
 
 /// Load the embedded test mod into the game
-#[cfg(feature = "integration-tests")]
+#[cfg(any(test, feature = "integration-tests"))]
 fn load_test_mod() -> anyhow::Result<()> {
     use crate::resource_manager::openzt_mods::load_open_zt_mod_from_memory;
     use std::path::Path;
@@ -151,6 +150,63 @@ fn load_test_mod() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Loads the test files, the test mod and the legacy test entities every suite expects
+fn setup() -> anyhow::Result<()> {
+    use anyhow::Context;
+
+    // Clear load order tracker
+    crate::resource_manager::openzt_mods::clear_load_tracker();
+
+    // Setup test target files for loading order tests
+    setup_test_files().context("Failed to setup test files")?;
+
+    // Load embedded test mod
+    load_test_mod().context("Failed to load test mod")?;
+
+    // Load legacy test files and trigger legacy loading
+    info!("Loading test legacy .cfg and .ai files...");
+    legacy_attributes::load_test_legacy_files().context("Failed to load test legacy files")?;
+    crate::resource_manager::load_legacy_entities_from_test_files().context("Failed to load legacy entities from test files")?;
+
+    Ok(())
+}
+
+/// A group of tests, run in the order of [`SUITES`] as later suites see the resources earlier ones leave
+struct Suite {
+    name: &'static str,
+    run: fn() -> Vec<TestResult>,
+    /// Only run inside the game, the other suites also run under `cargo test`
+    needs_game: bool,
+}
+
+const SUITES: &[Suite] = &[
+    Suite { name: "dependency resolution", run: dependency_resolution::run_all_tests, needs_game: false },
+    Suite { name: "patch rollback", run: patch_rollback::run_all_tests, needs_game: false },
+    Suite { name: "loading order", run: loading_order::run_all_tests, needs_game: false },
+    Suite { name: "legacy attributes", run: legacy_attributes::run_all_tests, needs_game: false },
+    Suite { name: "disabled ZTD", run: disabled_ztd::run_all_tests, needs_game: false },
+    Suite { name: "shortcut", run: shortcuts::run_all_tests, needs_game: false },
+    Suite { name: "extension", run: extensions::run_all_tests, needs_game: false },
+];
+
+/// Runs the suites, logging each result, and returns the results of every suite
+fn run_suites<'a>(suites: impl IntoIterator<Item = &'a Suite>, mut write_log: impl FnMut(&str)) -> Vec<TestResult> {
+    let mut results = Vec::new();
+    for suite in suites {
+        write_log(&format!("Running {} tests...", suite.name));
+        for result in (suite.run)() {
+            if result.passed {
+                write_log(&format!("  ✓ {}", result.name));
+            } else {
+                write_log(&format!("  ✗ {} - {}", result.name, result.error.as_deref().unwrap_or("Unknown error")));
+            }
+            results.push(result);
+        }
+        write_log("");
+    }
+    results
+}
+
 #[cfg(target_os = "windows")]
 #[detour_mod]
 mod detour_zoo_main {
@@ -162,39 +218,18 @@ mod detour_zoo_main {
     use std::io::Write as IoWrite;
 
     #[detour(LOAD_LANG_DLLS)]
+    #[bootstrap]
     unsafe extern "thiscall" fn detour_target(_this: u32) -> u32 {
-        info!("Integration tests starting...");
-
-        // Clear load order tracker
-        #[cfg(feature = "integration-tests")]
-        crate::resource_manager::openzt_mods::loading::clear_load_tracker();
-
-        // Setup test target files for loading order tests
-        if let Err(e) = super::setup_test_files() {
-            error!("Failed to setup test files: {}", e);
+        if let Err(e) = crate::detect_game_build() {
+            error!("Integration tests can't run: {}", e);
             std::process::exit(1);
         }
 
-        // Load embedded test mod
-        if let Err(e) = super::load_test_mod() {
-            error!("Failed to load test mod: {}", e);
-            std::process::exit(1);
-        }
+        info!("Integration tests starting...");
 
-        // Load legacy test files and trigger legacy loading
-        #[cfg(feature = "integration-tests")]
-        {
-            info!("Loading test legacy .cfg and .ai files...");
-            // Load test files into resource system
-            if let Err(e) = crate::integration_tests::legacy_attributes::load_test_legacy_files() {
-                error!("Failed to load test legacy files: {}", e);
-                std::process::exit(1);
-            }
-            // Trigger legacy loading from test files
-            if let Err(e) = crate::resource_manager::load_legacy_entities_from_test_files() {
-                error!("Failed to load legacy entities from test files: {}", e);
-                std::process::exit(1);
-            }
+        if let Err(e) = super::setup() {
+            error!("{:#}", e);
+            std::process::exit(1);
         }
 
         // Read filepath from environment variable with default
@@ -220,120 +255,11 @@ mod detour_zoo_main {
         write_log("=== OpenZT Integration Tests ===");
         write_log("");
 
-        // Run dependency resolution tests
-        write_log("Running dependency resolution tests...");
-        let dependency_results = super::dependency_resolution::run_all_tests();
-
-        let mut total_passed = 0;
-        let mut total_failed = 0;
-
-        for result in &dependency_results {
-            if result.passed {
-                write_log(&format!("  ✓ {}", result.name));
-                total_passed += 1;
-            } else {
-                write_log(&format!("  ✗ {} - {}", result.name, result.error.as_ref().unwrap_or(&"Unknown error".to_string())));
-                total_failed += 1;
-            }
-        }
-
-        write_log("");
-
-        // Run patch rollback tests
-        write_log("Running patch rollback tests...");
-        let patch_results = super::patch_rollback::run_all_tests();
-
-        for result in &patch_results {
-            if result.passed {
-                write_log(&format!("  ✓ {}", result.name));
-                total_passed += 1;
-            } else {
-                write_log(&format!("  ✗ {} - {}", result.name, result.error.as_ref().unwrap_or(&"Unknown error".to_string())));
-                total_failed += 1;
-            }
-        }
-
-        write_log("");
+        // Inside the game every suite runs, including the ones cargo test also covers
+        let results = super::run_suites(super::SUITES, &mut write_log);
+        let total_passed = results.iter().filter(|result| result.passed).count();
+        let total_failed = results.len() - total_passed;
 
-        // Run loading order tests
-        write_log("Running loading order tests...");
-        let loading_results = super::loading_order::run_all_tests();
-
-        for result in &loading_results {
-            if result.passed {
-                write_log(&format!("  ✓ {}", result.name));
-                total_passed += 1;
-            } else {
-                write_log(&format!("  ✗ {} - {}", result.name, result.error.as_ref().unwrap_or(&"Unknown error".to_string())));
-                total_failed += 1;
-            }
-        }
-
-        write_log("");
-
-        // Run legacy attributes tests
-        write_log("Running legacy attributes tests...");
-        let legacy_attributes_results = super::legacy_attributes::run_all_tests();
-
-        for result in &legacy_attributes_results {
-            if result.passed {
-                write_log(&format!("  ✓ {}", result.name));
-                total_passed += 1;
-            } else {
-                write_log(&format!("  ✗ {} - {}", result.name, result.error.as_ref().unwrap_or(&"Unknown error".to_string())));
-                total_failed += 1;
-            }
-        }
-
-        write_log("");
-
-        // Run disabled ZTD tests
-        write_log("Running disabled ZTD tests...");
-        let disabled_ztd_results = super::disabled_ztd::run_all_tests();
-
-        for result in &disabled_ztd_results {
-            if result.passed {
-                write_log(&format!("  ✓ {}", result.name));
-                total_passed += 1;
-            } else {
-                write_log(&format!("  ✗ {} - {}", result.name, result.error.as_ref().unwrap_or(&"Unknown error".to_string())));
-                total_failed += 1;
-            }
-        }
-
-        write_log("");
-
-        // Run shortcut tests
-        write_log("Running shortcut tests...");
-        let shortcuts_results = super::shortcuts::run_all_tests();
-
-        for result in &shortcuts_results {
-            if result.passed {
-                write_log(&format!("  ✓ {}", result.name));
-                total_passed += 1;
-            } else {
-                write_log(&format!("  ✗ {} - {}", result.name, result.error.as_ref().unwrap_or(&"Unknown error".to_string())));
-                total_failed += 1;
-            }
-        }
-
-        write_log("");
-
-        // Run extension tests
-        write_log("Running extension tests...");
-        let extensions_results = super::extensions::run_all_tests();
-
-        for result in &extensions_results {
-            if result.passed {
-                write_log(&format!("  ✓ {}", result.name));
-                total_passed += 1;
-            } else {
-                write_log(&format!("  ✗ {} - {}", result.name, result.error.as_ref().unwrap_or(&"Unknown error".to_string())));
-                total_failed += 1;
-            }
-        }
-
-        write_log("");
         write_log(&format!("Results: {} passed, {} failed", total_passed, total_failed));
 
         if total_failed > 0 {
@@ -347,3 +273,24 @@ mod detour_zoo_main {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The suites that don't need the game, against the in-memory resource store
+    ///
+    /// One test as the suites share the global resource map and depend on running in order
+    #[test]
+    fn test_suites_without_game() {
+        setup().unwrap();
+
+        let results = run_suites(SUITES.iter().filter(|suite| !suite.needs_game), |msg| println!("{}", msg));
+        let failed: Vec<String> = results
+            .iter()
+            .filter(|result| !result.passed)
+            .map(|result| format!("{}: {}", result.name, result.error.as_deref().unwrap_or("Unknown error")))
+            .collect();
+        assert!(failed.is_empty(), "{} of {} integration tests failed:\n{}", failed.len(), results.len(), failed.join("\n"));
+    }
+}
@@ -1,6 +1,8 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::path::Path;
 
+use indexmap::IndexMap;
+
 use crate::mods::{
     AddSectionPatch, DeletePatch, ErrorHandling, OnExists, Patch, PatchMeta, SetKeyPatch,
 };
@@ -328,7 +330,7 @@ fn test_shadow_multiple_patches_same_file() -> TestResult {
         Patch::AddSection(AddSectionPatch {
             target: test_file.to_string(),
             section: "Section2".to_string(),
-            keys: HashMap::new(),
+            keys: IndexMap::new(),
             on_exists: OnExists::Skip,
             condition: None,
         }),
@@ -1,4 +1,6 @@
 #![allow(dead_code)]
+// Game function pointers use the game's x86 calling conventions, off x86 they're never called
+#![cfg_attr(not(target_arch = "x86"), allow(unsupported_fn_ptr_calling_conventions))]
 /// Reimplementation of the BFRegistry, a vanilla system used to store pointers to the ZT*Mgr classes. In theory this
 /// allowed customization via zoo.ini, but in practice it appears unused.
 mod bfregistry;
@@ -68,8 +70,11 @@ mod experimental;
 /// Roof tag extension for scenery entities
 mod roofs;
 
-/// Global runtime state store for cross-module state sharing
-mod runtime_state;
+/// Event bus that detours publish game events to, with Rust and Lua subscribers
+mod events;
+
+/// Typed per-zoo state, namespaced by mod and saved alongside the zoo
+mod zoo_state;
 
 /// Keyboard shortcut registration system for game thread callbacks
 mod shortcuts;
@@ -81,6 +86,18 @@ mod version;
 /// OpenZT mod structs
 mod mods;
 
+/// Console functions to list and toggle the detours registered by `#[detour_mod]` modules
+mod detours;
+
+/// Reversible patches to the game's code, verified against the bytes they replace
+mod binary_patch;
+
+/// Hook chains for game functions that more than one feature intercepts
+mod hook_chains;
+
+/// The [`game_memory::GameMemory`] backend that memory reads and writes go through, and a fake address space for tests
+mod game_memory;
+
 /// Utility functions for working with the game's memory, including reading and writing memory, and patching the game's assembly.
 /// Common structs like ZTString are also defined here
 mod util;
@@ -99,8 +116,8 @@ pub mod reimplementation_tests;
 #[cfg(feature = "patch-integration-tests")]
 pub mod patch_integration_tests;
 
-/// Integration tests that run via detours in live game (for CI)
-#[cfg(feature = "integration-tests")]
+/// Integration tests that run via detours in live game (for CI), and under `cargo test` where they don't need it
+#[cfg(any(test, feature = "integration-tests"))]
 pub mod integration_tests;
 
 #[cfg(target_os = "windows")]
@@ -121,7 +138,18 @@ mod zoo_init {
     // Note(finn): We hook the LoadRes function to perform some later initialization steps. Starting
     //  the console starts a new thead which is not recommended in the DllMain function.
     #[detour(LOAD_LANG_DLLS)]
+    #[bootstrap]
     unsafe extern "thiscall" fn load_res_dlls(this: u32) -> u32 {
+        // Every address OpenZT uses belongs to one build of the game, on any other build nothing else is hooked
+        if let Err(e) = detect_game_build() {
+            show_startup_error(&format!(
+                "{}\n\nOpenZT is disabled and the game will run without it. If this executable is known to match a supported \
+                 build, set allow_unknown_build = true in the [game] section of openzt.toml.",
+                e
+            ));
+            return unsafe { LOAD_LANG_DLLS_DETOUR.call(this) };
+        }
+
         match init_console() {
             Ok(_) => {
                 let _enable_ansi = enable_ansi_support::enable_ansi_support().is_ok();
@@ -142,10 +170,10 @@ mod zoo_init {
             }
         }
 
-        // Command console is broken on latest stable Rust so we disable it by default.
-        if cfg!(feature = "command-console") {
-            command_console::init();
-        }
+        // Command console is broken on latest stable Rust so we only serve it with the feature enabled,
+        // the update hook it installs is always needed for mod script on_tick callbacks.
+        hook_chains::init();
+        command_console::init();
         resource_manager::init();
         expansions::init();
         string_registry::init();
@@ -155,7 +183,12 @@ mod zoo_init {
         ztworldmgr::init();
         bfentitytype::init();
         settings::init();
+        game_memory::init();
+        detours::init();
+        binary_patch::init();
         scripting::init();
+        events::init();
+        zoo_state::init();
         roofs::init();
 
         if cfg!(feature = "capture_ztlog") {
@@ -177,6 +210,8 @@ mod zoo_init {
     }
 }
 
+/// Called from `DllMain`, which runs under the loader lock, so this only installs the hook that
+/// detects the game build and does the rest of the initialization
 #[cfg(target_os = "windows")]
 pub fn init() {
     // If integration tests are enabled, run those instead of the main game
@@ -192,6 +227,27 @@ pub fn init() {
     }
 }
 
+#[cfg(target_os = "windows")]
+fn detect_game_build() -> Result<&'static openzt_detour::game_build::GameBuild, openzt_detour::game_build::BuildError> {
+    use openzt_detour::game_build::{self, BuildError};
+
+    let allow_unknown = resource_manager::mod_config::get_openzt_config().game.allow_unknown_build;
+    let exe = std::env::current_exe().map_err(|e| BuildError::Io(e.to_string()))?;
+    game_build::detect(&exe, allow_unknown)
+}
+
+/// Shows an error before the console and logging are set up
+#[cfg(target_os = "windows")]
+fn show_startup_error(message: &str) {
+    use windows::{
+        core::HSTRING,
+        Win32::UI::WindowsAndMessaging::{MessageBoxW, MB_ICONERROR, MB_OK},
+    };
+
+    eprintln!("OpenZT: {}", message);
+    unsafe { MessageBoxW(None, &HSTRING::from(message), &HSTRING::from("OpenZT"), MB_OK | MB_ICONERROR) };
+}
+
 #[cfg(target_os = "windows")]
 fn init_console() -> windows::core::Result<()> {
         // Free the current console
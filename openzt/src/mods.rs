@@ -10,6 +10,8 @@ use indexmap::IndexMap;
 use serde::{
     de::{self, Deserializer, Visitor},
     Deserialize,
+    Serialize,
+    Serializer,
 };
 use toml::Value;
 
@@ -54,6 +56,64 @@ pub struct Meta {
     link: Option<String>,
     #[serde(default = "default_empty_dependencies", deserialize_with = "deserialize_dependencies")]
     dependencies: Vec<Dependencies>,
+    /// Player-configurable options, declared as `[options.<name>]` tables
+    #[serde(default)]
+    options: IndexMap<String, ModOption>,
+    /// Lua scripts under `scripts/`, run in this order after the mod's defs are applied
+    #[serde(default)]
+    scripts: Vec<String>,
+}
+
+/// An option a mod exposes to the player
+///
+/// The player's choice is stored in openzt.toml under `[mod_options.<mod_id>]`,
+/// falling back to `default` when unset.
+///
+/// # Example TOML
+/// ```toml
+/// [options.difficulty]
+/// type = "int"
+/// default = 3
+/// min = 1
+/// max = 5
+/// description = "How hard guests are to please"
+/// ```
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ModOption {
+    #[serde(default)]
+    pub description: String,
+    #[serde(flatten)]
+    pub kind: ModOptionKind,
+}
+
+/// Type, default and bounds of a mod option
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ModOptionKind {
+    Bool {
+        default: bool,
+    },
+    Int {
+        default: i64,
+        #[serde(default)]
+        min: Option<i64>,
+        #[serde(default)]
+        max: Option<i64>,
+    },
+    Float {
+        default: f64,
+        #[serde(default)]
+        min: Option<f64>,
+        #[serde(default)]
+        max: Option<f64>,
+    },
+    Enum {
+        default: String,
+        values: Vec<String>,
+    },
+    String {
+        default: String,
+    },
 }
 
 fn default_empty_dependencies() -> Vec<Dependencies> {
@@ -168,6 +228,13 @@ where
     deserializer.deserialize_str(VersionVisitor)
 }
 
+fn serialize_version<S>(version: &Version, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_str(version)
+}
+
 fn deserialize_version_option<'de, D>(deserializer: D) -> Result<Option<Version>, D::Error>
 where
     D: Deserializer<'de>,
@@ -252,6 +319,8 @@ pub struct ModDefinition {
     // Patch system - split into metadata and patches
     patch_meta: Option<PatchMeta>,
     patches: Option<IndexMap<String, Patch>>,  // MUST use IndexMap for order preservation
+    #[serde(default)]
+    patch_groups: Option<IndexMap<String, PatchGroup>>,
 }
 
 impl ModDefinition {
@@ -378,7 +447,7 @@ pub struct EntityExtension {
 }
 
 impl EntityExtension {
-    #[cfg(feature = "integration-tests")]
+    #[cfg(any(test, feature = "integration-tests"))]
     pub fn new_test(base: String, tags: Vec<String>, attributes: HashMap<String, String>) -> Self {
         EntityExtension {
             base,
@@ -420,6 +489,34 @@ fn default_on_error() -> ErrorHandling {
     ErrorHandling::Continue
 }
 
+/// Named group of patches that commit or roll back together
+///
+/// Groups are declared alongside `[patches]` in a defs file:
+///
+/// ```toml
+/// [patch_groups.hd_compat]
+/// patches = ["hd_palette", "hd_resolution"]
+/// on_error = "continue"
+/// fallback_group = "sd_compat"
+/// ```
+///
+/// A group is applied at the position of its first patch in `[patches]`. If any patch in
+/// the group fails, every change made by the group is rolled back and `fallback_group`
+/// (if set) is applied instead. Groups only referenced as a fallback are never applied on their own.
+#[derive(Deserialize, Debug, Clone)]
+pub struct PatchGroup {
+    /// Names of patches from `[patches]` in this group, applied in this order
+    pub patches: Vec<String>,
+
+    /// What to do with the rest of the patch file if the group (and its fallback) fails
+    #[serde(default = "default_on_error")]
+    pub on_error: ErrorHandling,
+
+    /// Group to apply if this group is rolled back
+    #[serde(default)]
+    pub fallback_group: Option<String>,
+}
+
 #[derive(Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum ErrorHandling {
@@ -428,7 +525,7 @@ pub enum ErrorHandling {
     AbortMod,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(tag = "operation", rename_all = "snake_case")]
 pub enum Patch {
     Replace(ReplacePatch),
@@ -446,7 +543,7 @@ pub enum Patch {
     RemoveSection(RemoveSectionPatch),
 }
 
-#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum MergeMode {
     PatchPriority,
@@ -457,7 +554,7 @@ fn default_merge_mode() -> MergeMode {
     MergeMode::PatchPriority
 }
 
-#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum OnExists {
     Error,
@@ -470,56 +567,182 @@ fn default_on_exists() -> OnExists {
     OnExists::Error
 }
 
-#[derive(Deserialize, Debug, Clone)]
+/// Condition deciding whether a patch (or patch file) is applied
+///
+/// Every check that is set must pass. `all`, `any` and `not` nest further conditions,
+/// which inherit `target` unless they set their own.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
 pub struct PatchCondition {
-    /// Target file for key_exists/value_equals conditions
-    /// Required at top-level if using key_exists or value_equals
+    /// Target file for key_exists/value_* conditions
+    /// Required at top-level if using key_exists or value_* conditions
     /// Optional at patch-level (defaults to the patch's own target)
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub target: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub mod_loaded: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub key_exists: Option<KeyCheck>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub value_equals: Option<ValueCheck>,
+
+    /// Passes if every nested condition passes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub all: Option<Vec<PatchCondition>>,
+    /// Passes if at least one nested condition passes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub any: Option<Vec<PatchCondition>>,
+    /// Passes if the nested condition fails
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not: Option<Box<PatchCondition>>,
+
+    /// Passes if a mod is loaded and its version compares as requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mod_version: Option<ModVersionCheck>,
+    /// Passes if a file exists in the resource system
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_exists: Option<String>,
+    /// Passes if a legacy entity (e.g. animals/elephant) has been loaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity_exists: Option<EntityCheck>,
+    /// Passes if a setting in openzt.toml has the given value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub setting_equals: Option<SettingCheck>,
+    /// Passes if a mod option (see `[options]` in meta.toml) has the given value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub option_equals: Option<OptionCheck>,
+
+    /// Passes if a key's value matches a regex (unanchored, use ^...$ for a full match)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_matches: Option<ValueCheck>,
+    /// Passes if a key's value is a number greater than `value`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_gt: Option<NumericCheck>,
+    /// Passes if a key's value is a number less than `value`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_lt: Option<NumericCheck>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct KeyCheck {
     pub section: String,
     pub key: String,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ValueCheck {
     pub section: String,
     pub key: String,
     pub value: String,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct NumericCheck {
+    pub section: String,
+    pub key: String,
+    pub value: f64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ModVersionCheck {
+    pub mod_id: String,
+    #[serde(default)]
+    pub op: VersionOp,
+    #[serde(deserialize_with = "deserialize_version", serialize_with = "serialize_version")]
+    pub version: Version,
+}
+
+/// Comparison used by `mod_version`, as `loaded_version <op> version`
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum VersionOp {
+    #[serde(rename = "==")]
+    Eq,
+    #[serde(rename = "!=")]
+    Ne,
+    #[serde(rename = ">")]
+    Gt,
+    #[default]
+    #[serde(rename = ">=")]
+    Ge,
+    #[serde(rename = "<")]
+    Lt,
+    #[serde(rename = "<=")]
+    Le,
+}
+
+impl VersionOp {
+    pub fn compare(&self, loaded: &Version, expected: &Version) -> bool {
+        match self {
+            VersionOp::Eq => loaded == expected,
+            VersionOp::Ne => loaded != expected,
+            VersionOp::Gt => loaded > expected,
+            VersionOp::Ge => loaded >= expected,
+            VersionOp::Lt => loaded < expected,
+            VersionOp::Le => loaded <= expected,
+        }
+    }
+}
+
+impl fmt::Display for VersionOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self {
+            VersionOp::Eq => "==",
+            VersionOp::Ne => "!=",
+            VersionOp::Gt => ">",
+            VersionOp::Ge => ">=",
+            VersionOp::Lt => "<",
+            VersionOp::Le => "<=",
+        };
+        f.write_str(op)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct EntityCheck {
+    /// Legacy entity type, as used in `{legacy.<type>.<name>}` (e.g. "animals")
+    #[serde(rename = "type")]
+    pub entity_type: String,
+    pub name: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct OptionCheck {
+    /// Option name, as declared in the mod's meta.toml
+    pub option: String,
+    pub value: String,
+    /// Mod declaring the option (defaults to the mod being patched)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mod_id: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SettingCheck {
+    /// Dotted path to the setting in openzt.toml (e.g. "resource_cache.enabled")
+    pub setting: String,
+    pub value: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ReplacePatch {
     pub target: String,
     pub source: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub condition: Option<PatchCondition>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct MergePatch {
     pub target: String,
     pub source: String,
     #[serde(default = "default_merge_mode")]
     pub merge_mode: MergeMode,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub condition: Option<PatchCondition>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct DeletePatch {
     pub target: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub condition: Option<PatchCondition>,
 }
 
@@ -553,99 +776,99 @@ pub struct DeletePatch {
 /// - Target has extension (not an animation file)
 /// - Palette path empty or has wrong extension
 /// - Animation file parsing/writing fails
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct SetPalettePatch {
     /// Target animation file path (must have no extension)
     pub target: String,
     /// Path to the palette file (.pal) to reference
     pub palette: String,
     /// Optional conditions for applying this patch
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub condition: Option<PatchCondition>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct SetKeyPatch {
     pub target: String,
     pub section: String,
     pub key: String,
     pub value: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub condition: Option<PatchCondition>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct SetKeysPatch {
     pub target: String,
     pub section: String,
-    pub keys: HashMap<String, String>,
-    #[serde(default)]
+    pub keys: IndexMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub condition: Option<PatchCondition>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct AppendValuePatch {
     pub target: String,
     pub section: String,
     pub key: String,
     pub value: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub condition: Option<PatchCondition>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct AppendValuesPatch {
     pub target: String,
     pub section: String,
     pub key: String,
     pub values: Vec<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub condition: Option<PatchCondition>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct RemoveKeyPatch {
     pub target: String,
     pub section: String,
     pub key: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub condition: Option<PatchCondition>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct RemoveKeysPatch {
     pub target: String,
     pub section: String,
     pub keys: Vec<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub condition: Option<PatchCondition>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct AddSectionPatch {
     pub target: String,
     pub section: String,
     #[serde(default)]
-    pub keys: HashMap<String, String>,
+    pub keys: IndexMap<String, String>,
     #[serde(default = "default_on_exists")]
     pub on_exists: OnExists,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub condition: Option<PatchCondition>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ClearSectionPatch {
     pub target: String,
     pub section: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub condition: Option<PatchCondition>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct RemoveSectionPatch {
     pub target: String,
     pub section: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub condition: Option<PatchCondition>,
 }
 
@@ -686,6 +909,7 @@ impl ModDefinition {
             items: None,
             patch_meta,
             patches,
+            patch_groups: None,
         }
     }
 }
@@ -756,6 +980,65 @@ dependencies = [
         assert_eq!(meta.ztd_type, super::ZtdType::Legacy);
     }
 
+    #[test]
+    fn test_parse_meta_options() {
+        let toml_str = r#"
+name = "test mod"
+description = "test"
+authors = ["test"]
+mod_id = "test.mod"
+version = "1.0.0"
+
+[options.hardcore]
+type = "bool"
+default = false
+
+[options.difficulty]
+type = "int"
+default = 3
+min = 1
+max = 5
+description = "How hard guests are to please"
+
+[options.price_scale]
+type = "float"
+default = 1
+
+[options.theme]
+type = "enum"
+default = "jungle"
+values = ["jungle", "desert"]
+"#;
+        let meta: super::Meta = toml::from_str(toml_str).unwrap();
+        assert_eq!(meta.options.len(), 4);
+        assert_eq!(meta.options["hardcore"].kind, super::ModOptionKind::Bool { default: false });
+        assert_eq!(meta.options["difficulty"].kind, super::ModOptionKind::Int { default: 3, min: Some(1), max: Some(5) });
+        assert_eq!(meta.options["difficulty"].description, "How hard guests are to please");
+        assert_eq!(meta.options["price_scale"].kind, super::ModOptionKind::Float { default: 1.0, min: None, max: None });
+        assert_eq!(
+            meta.options["theme"].kind,
+            super::ModOptionKind::Enum { default: "jungle".to_string(), values: vec!["jungle".to_string(), "desert".to_string()] }
+        );
+
+        let meta: super::Meta = toml::from_str(include_str!("../resources/test/meta.toml")).unwrap();
+        assert!(meta.options.is_empty());
+        assert!(meta.scripts.is_empty());
+    }
+
+    #[test]
+    fn test_parse_meta_scripts() {
+        let toml_str = r#"
+name = "test mod"
+description = "test"
+authors = ["test"]
+mod_id = "test.mod"
+version = "1.0.0"
+scripts = ["scripts/main.lua", "scripts/events.lua"]
+"#;
+        let meta: super::Meta = toml::from_str(toml_str).unwrap();
+        assert_eq!(meta.scripts, vec!["scripts/main.lua".to_string(), "scripts/events.lua".to_string()]);
+    }
+
     fn check_moon_location(location: &super::IconDefinition) {
         assert_eq!(location.name, "Moon");
         assert_eq!(location.icon_path, "resources/moon/N");
@@ -955,6 +1238,71 @@ dependencies = [
         assert_eq!(patch_names[9], "conditional_palette_swap");
     }
 
+    #[test]
+    fn test_parse_patch_groups() {
+        let mod_def: super::ModDefinition = toml::from_str(include_str!("../resources/test/patch_groups.toml")).unwrap();
+        let patches = mod_def.patches.expect("patches should be present");
+        let groups = mod_def.patch_groups.expect("patch_groups should be present");
+
+        assert_eq!(patches.len(), 3);
+        assert_eq!(groups.len(), 2);
+
+        let hd = groups.get("hd_compat").expect("hd_compat group not found");
+        assert_eq!(hd.patches, vec!["hd_palette".to_string(), "hd_resolution".to_string()]);
+        assert_eq!(hd.on_error, super::ErrorHandling::Abort);
+        assert_eq!(hd.fallback_group, Some("sd_compat".to_string()));
+
+        // on_error defaults to continue
+        let sd = groups.get("sd_compat").expect("sd_compat group not found");
+        assert_eq!(sd.patches, vec!["sd_resolution".to_string()]);
+        assert_eq!(sd.on_error, super::ErrorHandling::Continue);
+        assert!(sd.fallback_group.is_none());
+    }
+
+    #[test]
+    fn test_parse_nested_conditions() {
+        let condition: super::PatchCondition = toml::from_str(
+            r#"
+            target = "animals/elephant.ai"
+            file_exists = "animals/elephant.ai"
+
+            [[any]]
+            mod_loaded = "finn.hd_textures"
+
+            [[any]]
+            mod_version = { mod_id = "finn.other", op = "<", version = "2.0.0" }
+
+            [not]
+            entity_exists = { type = "animals", name = "elephant" }
+
+            [not.value_gt]
+            section = "Characteristics/Integers"
+            key = "cSpeed"
+            value = 4.5
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(condition.file_exists, Some("animals/elephant.ai".to_string()));
+
+        let any = condition.any.as_ref().expect("any should be present");
+        assert_eq!(any.len(), 2);
+        assert_eq!(any[0].mod_loaded, Some("finn.hd_textures".to_string()));
+        let version_check = any[1].mod_version.as_ref().expect("mod_version should be present");
+        assert_eq!(version_check.op, super::VersionOp::Lt);
+        assert_eq!(version_check.version, "2.0.0".parse::<super::Version>().unwrap());
+
+        let not = condition.not.as_ref().expect("not should be present");
+        assert_eq!(not.entity_exists.as_ref().unwrap().entity_type, "animals");
+        assert_eq!(not.value_gt.as_ref().unwrap().value, 4.5);
+
+        // mod_version defaults to >=
+        let check: super::ModVersionCheck = toml::from_str("mod_id = \"a\"\nversion = \"1.0.0\"").unwrap();
+        assert_eq!(check.op, super::VersionOp::Ge);
+        assert!(check.op.compare(&"1.2.0".parse::<super::Version>().unwrap(), &check.version));
+        assert!(!check.op.compare(&"0.9.9".parse::<super::Version>().unwrap(), &check.version));
+    }
+
     #[test]
     fn test_parse_extensions_nested_tables() {
         let mod_def: super::ModDefinition = toml::from_str(include_str!("../resources/test/extensions.toml")).unwrap();
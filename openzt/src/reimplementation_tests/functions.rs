@@ -0,0 +1,33 @@
+//! Every differential test, add an entry here when a game function is reimplemented
+
+use openzt_detour::gen::bftile::GET_LOCAL_ELEVATION;
+
+use super::harness::DifferentialTest;
+use crate::{ztmapview::BFTile, ztworldmgr::IVec3};
+
+/// `BFTile::unknown_byte_2` values, the tile shapes `getLocalElevation` handles
+const TILE_SHAPES: [u8; 18] = [0x1, 0x4, 0x5, 0x10, 0x11, 0x14, 0x15, 0x19, 0x40, 0x41, 0x44, 0x45, 0x46, 0x50, 0x51, 0x54, 0x64, 0x91];
+
+pub fn tests() -> Vec<DifferentialTest> {
+    let mut tests = Vec::new();
+
+    // One test per tile shape, so each shape gets its own cases
+    for shape in TILE_SHAPES {
+        tests.push(DifferentialTest::new(
+            format!("BFTILE_GET_LOCAL_ELEVATION[{:#x}]", shape),
+            GET_LOCAL_ELEVATION,
+            (0..1000i32, 0..1000i32),
+            move |original, &(x, y)| {
+                let pos = IVec3::new(x, y, 0);
+                let tile = BFTile::new(pos, shape);
+                unsafe { original(&raw const tile as u32, &raw const pos as u32) }
+            },
+            move |&(x, y)| {
+                let pos = IVec3::new(x, y, 0);
+                BFTile::new(pos, shape).get_local_elevation(pos)
+            },
+        ));
+    }
+
+    tests
+}
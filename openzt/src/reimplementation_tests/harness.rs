@@ -0,0 +1,180 @@
+//! Differential tests comparing game functions with their Rust reimplementations
+//!
+//! A [`DifferentialTest`] generates inputs with a proptest strategy, passes each to the original
+//! game function and to the reimplementation, and fails with the shrunk input if the results differ.
+
+use std::{fmt::Debug, time::Instant};
+
+use openzt_detour::FunctionDef;
+use proptest::{
+    prelude::ProptestConfig,
+    prop_assert_eq,
+    strategy::Strategy,
+    test_runner::{TestError, TestRunner},
+};
+use serde::Serialize;
+
+use super::NoopFailurePersistence;
+
+/// The bound [`FunctionDef::original`] has, `retour::Function` on x86 and any `Copy` type elsewhere
+#[cfg(target_arch = "x86")]
+pub trait GameFunction: retour::Function {}
+#[cfg(target_arch = "x86")]
+impl<T: retour::Function> GameFunction for T {}
+
+#[cfg(not(target_arch = "x86"))]
+pub trait GameFunction: Copy {}
+#[cfg(not(target_arch = "x86"))]
+impl<T: Copy> GameFunction for T {}
+
+/// Result of one differential test
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Outcome {
+    pub name: String,
+    /// Address of the original function
+    pub address: u32,
+    pub seconds: f64,
+    /// `None` if every case matched
+    pub failure: Option<String>,
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+type Run = Box<dyn Fn(&mut TestRunner) -> Result<(), String>>;
+
+/// A game function, its reimplementation and the inputs to compare them on
+pub struct DifferentialTest {
+    pub name: String,
+    pub address: u32,
+    run: Run,
+}
+
+impl DifferentialTest {
+    /// `call_original` calls the game function with an input, `reimplementation` computes the same
+    /// result in Rust
+    ///
+    /// ```ignore
+    /// DifferentialTest::new(
+    ///     "BFTILE_GET_LOCAL_ELEVATION",
+    ///     GET_LOCAL_ELEVATION,
+    ///     (0..1000i32, 0..1000i32),
+    ///     |original, &(x, y)| unsafe { original(tile_ptr, pos_ptr(x, y)) },
+    ///     |&(x, y)| tile.get_local_elevation(IVec3::new(x, y, 0)),
+    /// )
+    /// ```
+    pub fn new<F, S, O>(
+        name: impl Into<String>,
+        function: FunctionDef<F>,
+        strategy: S,
+        call_original: impl Fn(F, &S::Value) -> O + 'static,
+        reimplementation: impl Fn(&S::Value) -> O + 'static,
+    ) -> DifferentialTest
+    where
+        F: GameFunction + 'static,
+        S: Strategy + 'static,
+        S::Value: Clone,
+        O: PartialEq + Debug,
+    {
+        let address = function.address;
+        // The address comes from a FunctionDef, which is only declared for functions with this signature
+        let original = unsafe { function.original() };
+        DifferentialTest::with_original(name, address, original, strategy, call_original, reimplementation)
+    }
+
+    fn with_original<F, S, O>(
+        name: impl Into<String>,
+        address: u32,
+        original: F,
+        strategy: S,
+        call_original: impl Fn(F, &S::Value) -> O + 'static,
+        reimplementation: impl Fn(&S::Value) -> O + 'static,
+    ) -> DifferentialTest
+    where
+        F: Copy + 'static,
+        S: Strategy + 'static,
+        S::Value: Clone,
+        O: PartialEq + Debug,
+    {
+        let run = move |runner: &mut TestRunner| {
+            runner
+                .run(&strategy, |input| {
+                    let expected = call_original(original, &input);
+                    let actual = reimplementation(&input);
+                    prop_assert_eq!(expected, actual, "original vs reimplementation");
+                    Ok(())
+                })
+                .map_err(|error| match error {
+                    TestError::Fail(reason, input) => format!("{} for input {:?}", reason, input),
+                    TestError::Abort(reason) => format!("Aborted: {}", reason),
+                })
+        };
+        DifferentialTest {
+            name: name.into(),
+            address,
+            run: Box::new(run),
+        }
+    }
+
+    pub fn run(&self, config: &ProptestConfig) -> Outcome {
+        let mut config = config.clone();
+        config.failure_persistence = Some(Box::new(NoopFailurePersistence));
+        let mut runner = TestRunner::new(config);
+
+        let start = Instant::now();
+        let failure = (self.run)(&mut runner).err();
+        Outcome {
+            name: self.name.clone(),
+            address: self.address,
+            seconds: start.elapsed().as_secs_f64(),
+            failure,
+        }
+    }
+}
+
+/// Runs every test, a failing test doesn't stop the others
+pub fn run_all(tests: &[DifferentialTest], config: &ProptestConfig) -> Vec<Outcome> {
+    tests.iter().map(|test| test.run(config)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn double(value: i32) -> i32 {
+        value * 2
+    }
+
+    fn config() -> ProptestConfig {
+        ProptestConfig { cases: 64, ..ProptestConfig::default() }
+    }
+
+    #[test]
+    fn test_matching_reimplementation_passes() {
+        let test = DifferentialTest::with_original("DOUBLE", 0x1000, double as extern "C" fn(i32) -> i32, -1000..1000i32, |f, &x| f(x), |&x| x + x);
+        let outcome = test.run(&config());
+        assert_eq!(outcome.name, "DOUBLE");
+        assert_eq!(outcome.address, 0x1000);
+        assert!(outcome.passed(), "{:?}", outcome.failure);
+    }
+
+    #[test]
+    fn test_mismatch_reports_shrunk_input() {
+        let test = DifferentialTest::with_original(
+            "DOUBLE",
+            0x1000,
+            double as extern "C" fn(i32) -> i32,
+            0..1000i32,
+            |f, &x| f(x),
+            |&x| if x >= 10 { x * 3 } else { x * 2 },
+        );
+        let outcome = run_all(&[test], &config()).remove(0);
+        let failure = outcome.failure.unwrap();
+        // Shrinking finds the smallest input that differs
+        assert!(failure.ends_with("for input 10"), "{}", failure);
+        assert!(failure.contains("original vs reimplementation"), "{}", failure);
+    }
+}
@@ -2,27 +2,23 @@
 
 use std::{any::Any, fmt};
 
+mod functions;
+mod harness;
+mod report;
+
 use proptest::test_runner::{FailurePersistence, PersistedSeed};
-use tracing::{error, info};
+#[cfg(target_os = "windows")]
+use tracing::error;
 #[cfg(target_os = "windows")]
 use windows::Win32::System::Console::{AllocConsole, FreeConsole};
 
 #[cfg(target_os = "windows")]
 use crate::detour_mod;
 
+/// Called from `DllMain`, the console and build detection wait for the `LOAD_LANG_DLLS` hook
 pub fn init() {
     #[cfg(target_os = "windows")]
     {
-        match init_console() {
-            Ok(_) => {
-                let enable_ansi = enable_ansi_support::enable_ansi_support().is_ok();
-                tracing_subscriber::fmt().with_ansi(enable_ansi).init();
-            }
-            Err(e) => {
-                info!("Failed to initialize console: {}", e);
-            }
-        }
-
         unsafe { detour_zoo_main::init_detours() }.is_err().then(|| {
             error!("Error initialising zoo_main detours");
         });
@@ -66,96 +62,53 @@ impl FailurePersistence for NoopFailurePersistence {
 #[cfg(target_os = "windows")]
 #[detour_mod]
 mod detour_zoo_main {
-    use std::{backtrace::Backtrace, cell::Cell, fs::OpenOptions, io::Write};
-
-    thread_local! {
-        static BACKTRACE: Cell<Option<Backtrace>> = const { Cell::new(None) };
-    }
+    use std::path::PathBuf;
 
     #[cfg(target_os = "windows")]
     use openzt_detour::gen::bfapp::LOAD_LANG_DLLS;
-    use openzt_detour::gen::bftile::GET_LOCAL_ELEVATION;
     use proptest::prelude::ProptestConfig;
     use tracing::{error, info};
 
-    use crate::{ztmapview::BFTile, ztworldmgr::IVec3};
+    use super::{functions, harness, report::Report};
 
-    // TODO: Fix this so it works with a crate/mod prefix
     #[detour(LOAD_LANG_DLLS)]
+    #[no_fallback]
+    #[bootstrap]
     unsafe extern "thiscall" fn detour_target(_this: u32) -> u32 {
-        info!("Detour success");
-
-        // Read filepath from environment variable with default
-        let failure_log_path =
-            std::env::var("OPENZT_TEST_LOG").unwrap_or_else(|_| "C:\\Program Files (x86)\\Microsoft Games\\Zoo Tycoon\\openzt_test.log".to_string());
-
-        // Create or truncate the file
-        let mut failure_log = match OpenOptions::new().create(true).write(true).truncate(true).open(&failure_log_path) {
-            Ok(file) => Some(file),
-            Err(e) => {
-                error!("Failed to create failure log file '{}': {}", failure_log_path, e);
-                None
+        match super::init_console() {
+            Ok(_) => {
+                let enable_ansi = enable_ansi_support::enable_ansi_support().is_ok();
+                tracing_subscriber::fmt().with_ansi(enable_ansi).init();
             }
-        };
-
-        let runner_config = ProptestConfig {
-            failure_persistence: Some(Box::new(super::NoopFailurePersistence)),
-            ..ProptestConfig::default()
-        };
-        let mut runner = proptest::test_runner::TestRunner::new(runner_config);
-        let test_name = "BFTILE_GET_LOCAL_ELEVATION";
-        // Create a enum strategy so this is all tested in one test and test that each enum variant is tested
-        let unknown_byte_values = vec![
-            0x1, 0x4, 0x5, 0x10, 0x11, 0x14, 0x15, 0x19, 0x40, 0x41, 0x44, 0x45, 0x46, 0x50, 0x51, 0x54, 0x64, 0x91,
-        ];
-        let mut fail_flag = false;
-        for unknown_byte_2 in unknown_byte_values {
-            match runner.run(&(0..1000i32, 0..1000i32), |(x, y)| {
-                let pos = IVec3::new(x, y, 0);
-                let tile = BFTile::new(pos, unknown_byte_2);
-                let reimplemented_result = tile.get_local_elevation(pos);
-
-                let result = GET_LOCAL_ELEVATION.original()(&raw const tile as u32, &raw const pos as u32);
-                assert_eq!(
-                    result, reimplemented_result+1,
-                    "Failed for pos: {:?}, tile: {:?}, unknown_byte_2: {}",
-                    pos, tile, unknown_byte_2
-                );
-                Ok(())
-            }) {
-                Ok(_) => {
-                    info!("Proptest passed for unknown_byte_2: {}", unknown_byte_2);
-                }
-                Err(e) => {
-                    error!("Proptest failed: {:?}", e);
-                    if let proptest::test_runner::TestError::Fail(r, (x, y)) = e {
-                        let failure_line = format!("unknown_byte_2: {}, x: {}, y: {}\n", unknown_byte_2, x, y);
-
-                        if let Some(ref mut log_file) = failure_log {
-                            if let Err(write_err) = log_file.write_all(failure_line.as_bytes()) {
-                                error!("Failed to write to failure log: {}", write_err);
-                            }
-                        }
-
-                        info!("Failed case ({}): x: {}, y: {}", r, x, y);
-                        fail_flag = true;
-                    }
-                }
+            Err(e) => {
+                info!("Failed to initialize console: {}", e);
             }
         }
 
-        if fail_flag {
-            error!("Proptest failed for some cases, check the failure log at: {}", failure_log_path);
+        if let Err(e) = crate::detect_game_build() {
+            crate::show_startup_error(&format!("Reimplementation tests can't run: {}", e));
             std::process::exit(1);
-        } else {
-            let success_line = format!("Test Passed {}\n", test_name);
+        }
+
+        info!("Running reimplementation tests");
 
-            if let Some(ref mut log_file) = failure_log {
-                if let Err(write_err) = log_file.write_all(success_line.as_bytes()) {
-                    error!("Failed to write to failure log: {}", write_err);
-                }
+        // Reports go next to zoo.exe unless OPENZT_TEST_REPORT_DIR says otherwise
+        let report_dir = std::env::var("OPENZT_TEST_REPORT_DIR").map(PathBuf::from).unwrap_or_else(|_| crate::util::get_base_path());
+
+        let tests = functions::tests();
+        let report = Report::new(harness::run_all(&tests, &ProptestConfig::default()));
+        for outcome in &report.outcomes {
+            match &outcome.failure {
+                None => info!("PASS {} ({:.2}s)", outcome.name, outcome.seconds),
+                Some(failure) => error!("FAIL {}: {}", outcome.name, failure),
             }
         }
-        std::process::exit(0);
+        info!("{} passed, {} failed", report.passed, report.failed);
+
+        match report.write(&report_dir) {
+            Ok(()) => info!("Reports written to {}", report_dir.display()),
+            Err(e) => error!("Failed to write reports to {}: {}", report_dir.display(), e),
+        }
+        std::process::exit(report.exit_code());
     }
 }
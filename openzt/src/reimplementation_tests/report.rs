@@ -0,0 +1,125 @@
+//! JUnit and JSON reports of a differential test run, for CI
+
+use std::{fmt::Write as _, path::Path};
+
+use serde::Serialize;
+
+use super::harness::Outcome;
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub passed: usize,
+    pub failed: usize,
+    pub outcomes: Vec<Outcome>,
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+impl Report {
+    pub fn new(outcomes: Vec<Outcome>) -> Report {
+        let passed = outcomes.iter().filter(|outcome| outcome.passed()).count();
+        Report {
+            passed,
+            failed: outcomes.len() - passed,
+            outcomes,
+        }
+    }
+
+    /// 0 if every test passed, 1 otherwise
+    pub fn exit_code(&self) -> i32 {
+        if self.failed == 0 {
+            0
+        } else {
+            1
+        }
+    }
+
+    pub fn to_junit(&self) -> String {
+        let seconds: f64 = self.outcomes.iter().map(|outcome| outcome.seconds).sum();
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            xml,
+            "<testsuite name=\"reimplementation-tests\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">",
+            self.outcomes.len(),
+            self.failed,
+            seconds
+        );
+        for outcome in &self.outcomes {
+            let _ = write!(
+                xml,
+                "  <testcase name=\"{}\" classname=\"{:#010x}\" time=\"{:.3}\"",
+                escape_xml(&outcome.name),
+                outcome.address,
+                outcome.seconds
+            );
+            match &outcome.failure {
+                Some(failure) => {
+                    let _ = writeln!(xml, ">\n    <failure message=\"{}\"/>\n  </testcase>", escape_xml(failure));
+                }
+                None => xml.push_str("/>\n"),
+            }
+        }
+        xml.push_str("</testsuite>\n");
+        xml
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// Writes `reimplementation-tests.xml` and `reimplementation-tests.json` to `dir`
+    pub fn write(&self, dir: &Path) -> std::io::Result<()> {
+        std::fs::write(dir.join("reimplementation-tests.xml"), self.to_junit())?;
+        std::fs::write(dir.join("reimplementation-tests.json"), self.to_json())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> Report {
+        Report::new(vec![
+            Outcome { name: "PASSING".to_string(), address: 0x0040f24d, seconds: 0.5, failure: None },
+            Outcome {
+                name: "FAILING[0x1]".to_string(),
+                address: 0x0040f24d,
+                seconds: 0.25,
+                failure: Some("assertion failed: `left == right` for input (1, 2) & <3>".to_string()),
+            },
+        ])
+    }
+
+    #[test]
+    fn test_junit() {
+        let report = report();
+        assert_eq!(report.exit_code(), 1);
+        assert_eq!(
+            report.to_junit(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuite name="reimplementation-tests" tests="2" failures="1" time="0.750">
+  <testcase name="PASSING" classname="0x0040f24d" time="0.500"/>
+  <testcase name="FAILING[0x1]" classname="0x0040f24d" time="0.250">
+    <failure message="assertion failed: `left == right` for input (1, 2) &amp; &lt;3&gt;"/>
+  </testcase>
+</testsuite>
+"#
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let json: serde_json::Value = serde_json::from_str(&report().to_json()).unwrap();
+        assert_eq!(json["passed"], 1);
+        assert_eq!(json["failed"], 1);
+        assert_eq!(json["outcomes"][0]["name"], "PASSING");
+        assert_eq!(json["outcomes"][0]["failure"], serde_json::Value::Null);
+        assert_eq!(Report::new(Vec::new()).exit_code(), 0);
+    }
+}
@@ -1,23 +1,24 @@
 mod bfresourcemgr;
-mod commands;
+pub(crate) mod commands;
 mod handlers;
 mod hooks;
 pub(crate) mod lazyresourcemap;
 mod legacy_loading;
 pub(crate) mod openzt_mods;
+pub(crate) mod resource_store;
 mod ztd;
 pub(crate) mod ztfile;
 
 // Export for integration tests
-#[cfg(feature = "integration-tests")]
+#[cfg(any(test, feature = "integration-tests"))]
 pub mod dependency_resolver;
-#[cfg(feature = "integration-tests")]
+#[cfg(any(test, feature = "integration-tests"))]
 pub mod validation;
 
 // Private modules when not testing
-#[cfg(not(feature = "integration-tests"))]
+#[cfg(not(any(test, feature = "integration-tests")))]
 mod dependency_resolver;
-#[cfg(not(feature = "integration-tests"))]
+#[cfg(not(any(test, feature = "integration-tests")))]
 mod validation;
 
 // Always available internally for config loading
@@ -29,8 +30,8 @@ use hooks::init_hooks;
 pub use legacy_loading::OPENZT_DIR0;
 
 // Export for integration tests
-#[cfg(feature = "integration-tests")]
-pub use legacy_loading::{load_legacy_entities_for_tests, load_legacy_entities_from_test_files};
+#[cfg(any(test, feature = "integration-tests"))]
+pub use legacy_loading::load_legacy_entities_from_test_files;
 
 pub use ztfile::{modify_ztfile_as_animation, modify_ztfile_as_ini};
 
@@ -38,4 +39,5 @@ pub use ztfile::{modify_ztfile_as_animation, modify_ztfile_as_ini};
 pub fn init() {
     init_hooks();
     init_commands();
+    openzt_mods::scripts::init();
 }
@@ -1,11 +1,13 @@
 use std::fmt::{Display, Formatter, Result};
 
+use game_struct_layout::game_struct;
+use openzt_detour::{game_address, game_build::GameAddress};
 use public::public;
 use tracing::{error, info};
 
 use crate::util::{get_from_memory, ZTBoundedString, ZTString, ZTStringPtr};
 
-const GLOBAL_BFRESOURCEMGR_ADDRESS: u32 = 0x006380C0;
+const GLOBAL_BFRESOURCEMGR_ADDRESS: GameAddress = game_address!(CompleteCollection: 0x006380C0);
 
 #[public]
 #[derive(Debug)]
@@ -69,10 +71,11 @@ struct BFResourcePtr {
     content_size: u32,
 }
 
+#[game_struct]
 #[derive(Debug)]
 #[repr(C)]
 struct GXLLEAnim {
-    padding: [u8; 5],
+    #[offset(0x8)]
     bfresource_maybe: u32,
 }
 
@@ -91,7 +94,7 @@ impl Display for BFResourcePtr {
 }
 
 pub fn read_bf_resource_mgr_from_memory() -> BFResourceMgr {
-    get_from_memory::<BFResourceMgr>(GLOBAL_BFRESOURCEMGR_ADDRESS)
+    get_from_memory::<BFResourceMgr>(GLOBAL_BFRESOURCEMGR_ADDRESS.get())
 }
 
 pub fn read_bf_resource_dir_contents_from_memory() -> Vec<BFResourceDirContents> {
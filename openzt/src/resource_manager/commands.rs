@@ -4,7 +4,13 @@ use crate::{
     resource_manager::{
         bfresourcemgr::{read_bf_resource_dir_contents_from_memory, read_bf_resource_mgr_from_memory},
         lazyresourcemap::{decrement_ref, get_cache_stats, get_file_names, get_ref_count, increment_ref, unload_all_resources, UnloadResult},
-        openzt_mods::{get_location_habitat_ids, get_mod_ids},
+        openzt_mods::{
+            get_location_habitat_ids,
+            get_mod_ids,
+            mod_options::{describe_mod_options, format_option_value, get_mod_option, set_mod_option},
+            patch_generator::generate_patches_for_resource,
+            patch_report::get_patch_report,
+        },
     },
     string_registry::get_string_from_registry,
     util::ZTString,
@@ -12,7 +18,7 @@ use crate::{
 
 pub fn init_commands() {
     // list_resources() - no args
-    lua_fn!("list_resources", "Lists all BF resource directories and files", "list_resources()", || {
+    lua_fn!("list_resources", "Lists all BF resource directories and files", "list_resources()", ReadWorld, || {
         match command_list_resources(vec![]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
@@ -20,7 +26,7 @@ pub fn init_commands() {
     });
 
     // get_bfresourcemgr() - no args
-    lua_fn!("get_bfresourcemgr", "Returns BF resource manager details", "get_bfresourcemgr()", || {
+    lua_fn!("get_bfresourcemgr", "Returns BF resource manager details", "get_bfresourcemgr()", DebugMemory, || {
         match command_get_bf_resource_mgr(vec![]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
@@ -28,7 +34,7 @@ pub fn init_commands() {
     });
 
     // list_resource_strings([prefix]) - optional string arg
-    lua_fn!("list_resource_strings", "Lists resource strings, optionally filtered by prefix", "list_resource_strings([prefix])", |prefix: Option<String>| {
+    lua_fn!("list_resource_strings", "Lists resource strings, optionally filtered by prefix", "list_resource_strings([prefix])", ReadWorld, |prefix: Option<String>| {
         match prefix {
             Some(p) => {
                 match command_list_resource_strings(vec![&p]) {
@@ -46,7 +52,7 @@ pub fn init_commands() {
     });
 
     // list_openzt_resource_strings() - no args
-    lua_fn!("list_openzt_resource_strings", "Lists all OpenZT resource strings", "list_openzt_resource_strings()", || {
+    lua_fn!("list_openzt_resource_strings", "Lists all OpenZT resource strings", "list_openzt_resource_strings()", ReadWorld, || {
         match command_list_openzt_resource_strings(vec![]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
@@ -54,7 +60,7 @@ pub fn init_commands() {
     });
 
     // list_openzt_mods() - no args
-    lua_fn!("list_openzt_mods", "Lists all OpenZT mod IDs", "list_openzt_mods()", || {
+    lua_fn!("list_openzt_mods", "Lists all OpenZT mod IDs", "list_openzt_mods()", ReadWorld, || {
         match command_list_openzt_mod_ids(vec![]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
@@ -62,7 +68,7 @@ pub fn init_commands() {
     });
 
     // list_openzt_locations_habitats() - no args
-    lua_fn!("list_openzt_locations_habitats", "Lists all OpenZT location and habitat IDs", "list_openzt_locations_habitats()", || {
+    lua_fn!("list_openzt_locations_habitats", "Lists all OpenZT location and habitat IDs", "list_openzt_locations_habitats()", ReadWorld, || {
         match command_list_openzt_locations_habitats(vec![]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
@@ -70,7 +76,7 @@ pub fn init_commands() {
     });
 
     // unload_resources() - no args
-    lua_fn!("unload_resources", "Unload all loaded resources to free memory", "unload_resources()", || {
+    lua_fn!("unload_resources", "Unload all loaded resources to free memory", "unload_resources()", ModifyWorld, || {
         let UnloadResult { count, total_size } = unload_all_resources();
         Ok((Some(format!(
             "Unloaded {} resources (freed {} bytes, {} MB)",
@@ -81,7 +87,7 @@ pub fn init_commands() {
     });
 
     // cache_stats() - no args
-    lua_fn!("cache_stats", "Show resource cache statistics", "cache_stats()", || {
+    lua_fn!("cache_stats", "Show resource cache statistics", "cache_stats()", ReadWorld, || {
         let stats = get_cache_stats();
         Ok((Some(format!(
             "Loaded: {} resources\nMemory: {} MB ({} bytes)",
@@ -90,7 +96,7 @@ pub fn init_commands() {
     });
 
     // increment_ref(file_name) - string arg
-    lua_fn!("increment_ref", "Increment reference count for a resource", "increment_ref(file_name)", |file_name: String| {
+    lua_fn!("increment_ref", "Increment reference count for a resource", "increment_ref(file_name)", DebugMemory, |file_name: String| {
         if increment_ref(&file_name) {
             Ok((Some(format!("Incremented ref count for: {}", file_name)), None::<String>))
         } else {
@@ -99,7 +105,7 @@ pub fn init_commands() {
     });
 
     // decrement_ref(file_name) - string arg
-    lua_fn!("decrement_ref", "Decrement reference count for a resource", "decrement_ref(file_name)", |file_name: String| {
+    lua_fn!("decrement_ref", "Decrement reference count for a resource", "decrement_ref(file_name)", DebugMemory, |file_name: String| {
         match decrement_ref(&file_name) {
             Some(new_count) => Ok((Some(format!("Decremented ref count for: {} (new count: {})", file_name, new_count)), None::<String>)),
             None => Ok((None::<String>, Some(format!("Resource not found: {}", file_name)))),
@@ -107,12 +113,73 @@ pub fn init_commands() {
     });
 
     // get_ref_count(file_name) - string arg
-    lua_fn!("get_ref_count", "Get reference count for a resource", "get_ref_count(file_name)", |file_name: String| {
+    lua_fn!("get_ref_count", "Get reference count for a resource", "get_ref_count(file_name)", ReadWorld, |file_name: String| {
         match get_ref_count(&file_name) {
             Some(count) => Ok((Some(format!("Ref count for {}: {}", file_name, count)), None::<String>)),
             None => Ok((None::<String>, Some(format!("Resource not found: {}", file_name)))),
         }
     });
+
+    // patch_report(mod_id) - string arg
+    lua_fn!("patch_report", "Shows which patches a mod applied, skipped or failed", "patch_report(mod_id)", ReadWorld, |mod_id: String| {
+        match get_patch_report(&mod_id) {
+            Some(report) => Ok((Some(report.summary()), None::<String>)),
+            None => Ok((None::<String>, Some(format!("No patches recorded for mod: {}", mod_id)))),
+        }
+    });
+
+    // generate_patches(target, modified_path, [output_path]) - diff a resource against an edited copy
+    lua_fn!(
+        "generate_patches",
+        "Generates [patches] TOML that turns a loaded INI resource into an edited copy on disk",
+        "generate_patches(target, modified_path, [output_path])",
+        ModifyConfig,
+        |target: String, modified_path: String, output_path: Option<String>| {
+            let toml = match generate_patches_for_resource(&target, std::path::Path::new(&modified_path)) {
+                Ok(toml) => toml,
+                Err(e) => return Ok((None::<String>, Some(e.to_string()))),
+            };
+            match output_path {
+                Some(path) => match std::fs::write(&path, toml) {
+                    Ok(()) => Ok((Some(format!("Wrote patches for {} to {}", target, path)), None::<String>)),
+                    Err(e) => Ok((None::<String>, Some(format!("Failed to write {}: {}", path, e)))),
+                },
+                None => Ok((Some(toml), None::<String>)),
+            }
+        }
+    );
+
+    // get_mod_option(mod_id, [name]) - one option's value, or all options when name is omitted
+    lua_fn!(
+        "get_mod_option",
+        "Gets a mod option's current value, or lists all of a mod's options",
+        "get_mod_option(mod_id, [name])",
+        ReadWorld,
+        |mod_id: String, name: Option<String>| {
+            let result = match name {
+                Some(name) => get_mod_option(&mod_id, &name).map(|value| format_option_value(&value)),
+                None => describe_mod_options(&mod_id),
+            };
+            match result {
+                Ok(result) => Ok((Some(result), None::<String>)),
+                Err(e) => Ok((None::<String>, Some(e.to_string()))),
+            }
+        }
+    );
+
+    // set_mod_option(mod_id, name, value) - validated, then saved to openzt.toml
+    lua_fn!(
+        "set_mod_option",
+        "Sets a mod option and saves it to openzt.toml (takes effect on next load)",
+        "set_mod_option(mod_id, name, value)",
+        ModifyConfig,
+        |mod_id: String, name: String, value: String| {
+            match set_mod_option(&mod_id, &name, &value) {
+                Ok(value) => Ok((Some(format!("Set {}.{} = {}", mod_id, name, format_option_value(&value))), None::<String>)),
+                Err(e) => Ok((None::<String>, Some(e.to_string()))),
+            }
+        }
+    );
 }
 
 fn command_list_resource_strings(args: Vec<&str>) -> Result<String, CommandError> {
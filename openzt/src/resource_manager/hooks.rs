@@ -1,12 +1,16 @@
+#[cfg(target_os = "windows")]
 use openzt_detour_macro::detour_mod;
+#[cfg(target_os = "windows")]
 use tracing::error;
 
 pub fn init_hooks() {
+    #[cfg(target_os = "windows")]
     if unsafe { zoo_resource_mgr::init_detours() }.is_err() {
         error!("Error initialising custom expansion detours");
     };
 }
 
+#[cfg(target_os = "windows")]
 #[detour_mod]
 mod zoo_resource_mgr {
     use std::ffi::CString;
@@ -133,7 +137,9 @@ mod zoo_resource_mgr {
         (mod_ids, ztd_files)
     }
 
+    // The original constructor has already run by the time anything here can panic
     #[detour(CONSTRUCTOR)]
+    #[no_fallback]
     unsafe extern "thiscall" fn zoo_bf_resource_mgr_constructor(this_ptr: u32) -> u32 {
         info!("BFResourceMgr::constructor({:X})", this_ptr);
 
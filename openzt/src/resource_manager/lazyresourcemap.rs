@@ -1,8 +1,6 @@
 use std::{
     collections::{HashMap, HashSet},
-    ffi::CString,
     path::Path,
-    slice,
     sync::atomic::{AtomicU32, AtomicU64, Ordering},
     sync::{Arc, Mutex},
     time::Instant,
@@ -13,12 +11,9 @@ use std::sync::LazyLock;
 use tracing::{error, info, trace};
 
 use super::ztd::ZtdArchive;
-use crate::{
-    resource_manager::{
-        bfresourcemgr::BFResourcePtr,
-        ztfile::{ztfile_to_raw_resource, ZTFile, ZTFileType},
-    },
-    util::{get_from_memory, ZTString},
+use crate::resource_manager::{
+    resource_store::store,
+    ztfile::{ZTFile, ZTFileType},
 };
 
 static LAZY_RESOURCE_MAP: LazyLock<Mutex<HashMap<String, LazyResource>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
@@ -33,15 +28,15 @@ struct LazyResourceMap {}
 #[derive(Clone)]
 enum ResourceBacking {
     LazyZipFile { archive: Arc<Mutex<ZtdArchive>> },
-    LoadedZipFile { archive: Arc<Mutex<ZtdArchive>>, data: u32 },
-    Custom { data: u32 },
+    LoadedZipFile { archive: Arc<Mutex<ZtdArchive>>, handle: u32 },
+    Custom { handle: u32 },
 }
 
 struct ConcreteResource {
     archive_name: Option<String>,
     filename: String,
     type_: ZTFileType,
-    data: u32,
+    handle: u32,
 }
 
 struct LazyResource {
@@ -59,9 +54,7 @@ impl LazyResourceMap {
 
         // Subtract size if resource was loaded
         let size = match &value.backing {
-            ResourceBacking::LoadedZipFile { data, .. } | ResourceBacking::Custom { data } => {
-                unsafe { &*(*data as *const BFResourcePtr) }.content_size as u64
-            }
+            ResourceBacking::LoadedZipFile { handle, .. } | ResourceBacking::Custom { handle } => store().content_size(*handle) as u64,
             ResourceBacking::LazyZipFile { .. } => 0,
         };
         if size > 0 {
@@ -73,45 +66,22 @@ impl LazyResourceMap {
     }
 
     fn drop_inner(resource: LazyResource) {
-        let data = match resource.backing {
-            ResourceBacking::LoadedZipFile { data, archive: _ } => data,
-            ResourceBacking::Custom { data } => data,
+        let handle = match resource.backing {
+            ResourceBacking::LoadedZipFile { handle, archive: _ } => handle,
+            ResourceBacking::Custom { handle } => handle,
             ResourceBacking::LazyZipFile { archive: _ } => {
                 return;
             }
         };
-        let bf_resource_ptr = unsafe { Box::from_raw(data as *mut BFResourcePtr) };
-        let size = bf_resource_ptr.content_size;
 
         trace!(
             "Dropping resource: {} (type: {:?}, size: {} bytes)",
             resource.filename,
             resource.type_,
-            size
+            store().content_size(handle)
         );
 
-        match resource.type_ {
-            ZTFileType::Ini
-            | ZTFileType::Ai
-            | ZTFileType::Ani
-            | ZTFileType::Cfg
-            | ZTFileType::Lyt
-            | ZTFileType::Scn
-            | ZTFileType::Uca
-            | ZTFileType::Ucs
-            | ZTFileType::Ucb
-            | ZTFileType::Toml
-            | ZTFileType::Txt => {
-                let data_string = unsafe { CString::from_raw(data as *mut i8) };
-                drop(data_string);
-            }
-            ZTFileType::Animation | ZTFileType::Bmp | ZTFileType::Lle | ZTFileType::Tga | ZTFileType::Wav | ZTFileType::Palette | ZTFileType::Zoo => {
-                let data_vec: Box<[u8]> =
-                    unsafe { Box::from_raw(slice::from_raw_parts_mut(bf_resource_ptr.data_ptr as *mut _, bf_resource_ptr.content_size as usize)) };
-                drop(data_vec);
-            }
-        }
-        drop(bf_resource_ptr);
+        store().remove(handle);
     }
 
     fn insert_lazy(file_name: String, archive: Arc<Mutex<ZtdArchive>>) {
@@ -145,16 +115,15 @@ impl LazyResourceMap {
         }
     }
 
-    fn insert_custom(file_name: String, file_type: ZTFileType, data: u32) {
+    fn insert_custom(file_name: String, file_type: ZTFileType, handle: u32) {
         // Add to counter for custom resources
-        let bf_ptr = unsafe { &*(data as *const BFResourcePtr) };
-        TOTAL_LOADED_BYTES.fetch_add(bf_ptr.content_size as u64, Ordering::Relaxed);
+        TOTAL_LOADED_BYTES.fetch_add(store().content_size(handle) as u64, Ordering::Relaxed);
 
         let mut binding = LAZY_RESOURCE_MAP.lock().unwrap();
         if let Some(existing) = binding.insert(
             file_name.to_ascii_lowercase(),
             LazyResource {
-                backing: ResourceBacking::Custom { data },
+                backing: ResourceBacking::Custom { handle },
                 filename: file_name.clone(),
                 type_: file_type,
                 last_accessed: Instant::now(),
@@ -163,9 +132,7 @@ impl LazyResourceMap {
         ) {
             // Subtract size of replaced resource
             let old_size = match &existing.backing {
-                ResourceBacking::LoadedZipFile { data, .. } | ResourceBacking::Custom { data } => {
-                    unsafe { &*(*data as *const BFResourcePtr) }.content_size as u64
-                }
+                ResourceBacking::LoadedZipFile { handle, .. } | ResourceBacking::Custom { handle } => store().content_size(*handle) as u64,
                 ResourceBacking::LazyZipFile { .. } => 0,
             };
             if old_size > 0 {
@@ -197,7 +164,7 @@ impl LazyResourceMap {
         let type_ = resource.type_;
 
         // TODO: Use std::mem::take/replace to avoid cloning
-        let (archive_name, data) = match resource.backing.clone() {
+        let (archive_name, handle) = match resource.backing.clone() {
             ResourceBacking::LazyZipFile { archive } => {
                 let mut binding = archive.lock().unwrap();
                 let archive_name = binding.name().to_string();
@@ -209,28 +176,21 @@ impl LazyResourceMap {
                 file.read_exact(&mut file_buffer)
                     .with_context(|| format!("Error reading file: {}", filename))?;
 
-                let ztfile = ZTFile::builder()
-                    .file_name(filename.clone())
-                    .file_size(file_buffer.len() as u32)
-                    .type_(type_)
-                    .raw_data(file_buffer)
-                    .build();
-                let data = ztfile_to_raw_resource(&archive_name, filename.clone(), ztfile)?;
-                resource.backing = ResourceBacking::LoadedZipFile { archive: archive.clone(), data: data.2 };
-                (Some(archive_name.clone()), data.2)
+                let handle = store().insert(&zip_name(&archive_name), &filename.to_lowercase(), file_buffer)?;
+                resource.backing = ResourceBacking::LoadedZipFile { archive: archive.clone(), handle };
+                (Some(archive_name.clone()), handle)
             }
-            ResourceBacking::LoadedZipFile { archive, data } => {
+            ResourceBacking::LoadedZipFile { archive, handle } => {
                 let binding = archive.lock().unwrap();
-                (Some(binding.name().to_string()), data)
+                (Some(binding.name().to_string()), handle)
             }
-            ResourceBacking::Custom { data } => (None, data),
+            ResourceBacking::Custom { handle } => (None, handle),
         };
 
         // If this was a lazy load, add to counter and check if we need to unload
         if was_lazy {
             // Add to total size counter when resource is loaded
-            let bf_ptr = unsafe { &*(data as *const BFResourcePtr) };
-            TOTAL_LOADED_BYTES.fetch_add(bf_ptr.content_size as u64, Ordering::Relaxed);
+            TOTAL_LOADED_BYTES.fetch_add(store().content_size(handle) as u64, Ordering::Relaxed);
 
             // Trigger auto-unload check after loading new resource
             drop(binding);  // Release lock before calling unload
@@ -241,7 +201,7 @@ impl LazyResourceMap {
             archive_name: archive_name.clone(),
             filename,
             type_,
-            data,
+            handle,
         }))
     }
 
@@ -281,8 +241,8 @@ impl LazyResourceMap {
         let resource = binding.get_mut(key)?;
 
         // Only unload LoadedZipFile (NOT Custom or LazyZipFile)
-        let (data, archive) = match &resource.backing {
-            ResourceBacking::LoadedZipFile { data, archive } => (*data, archive.clone()),
+        let (handle, archive) = match &resource.backing {
+            ResourceBacking::LoadedZipFile { handle, archive } => (*handle, archive.clone()),
             _ => return None,
         };
 
@@ -298,7 +258,7 @@ impl LazyResourceMap {
         }
 
         // Get size before dropping
-        let size = unsafe { &*(data as *const BFResourcePtr) }.content_size as u64;
+        let size = store().content_size(handle) as u64;
 
         trace!(
             "Unloading resource: {} (size: {} bytes)",
@@ -312,7 +272,7 @@ impl LazyResourceMap {
 
         // Drop the loaded data (preserve ref_count)
         let temp_resource = LazyResource {
-            backing: ResourceBacking::LoadedZipFile { archive: archive.clone(), data },
+            backing: ResourceBacking::LoadedZipFile { archive: archive.clone(), handle },
             filename: resource.filename.clone(),
             type_: resource.type_,
             last_accessed: Instant::now(),
@@ -346,9 +306,7 @@ impl LazyResourceMap {
             if let Some(resource) = binding.remove(&key) {
                 // Get size before dropping
                 let size = match &resource.backing {
-                    ResourceBacking::LoadedZipFile { data, .. } => {
-                        unsafe { &*(*data as *const BFResourcePtr) }.content_size as u64
-                    }
+                    ResourceBacking::LoadedZipFile { handle, .. } => store().content_size(*handle) as u64,
                     _ => 0,
                 };
                 total_size += size;
@@ -397,14 +355,8 @@ impl LazyResourceMap {
                     return None;
                 }
                 match &r.backing {
-                    ResourceBacking::LoadedZipFile { data, .. } => {
-                        let ptr = unsafe { &*(*data as *const BFResourcePtr) };
-                        Some((k.clone(), r.last_accessed, ptr.content_size as u64, false))
-                    }
-                    ResourceBacking::Custom { data } => {
-                        let ptr = unsafe { &*(*data as *const BFResourcePtr) };
-                        Some((k.clone(), r.last_accessed, ptr.content_size as u64, true))
-                    }
+                    ResourceBacking::LoadedZipFile { handle, .. } => Some((k.clone(), r.last_accessed, store().content_size(*handle) as u64, false)),
+                    ResourceBacking::Custom { handle } => Some((k.clone(), r.last_accessed, store().content_size(*handle) as u64, true)),
                     ResourceBacking::LazyZipFile { .. } => None,
                 }
             })
@@ -454,13 +406,19 @@ impl LazyResourceMap {
     }
 }
 
+/// The archive name the game expects, e.g. `zip::./mods/example.ztd`
+fn zip_name(path: &str) -> String {
+    path.replace('\\', "/").replace("./", "zip::./")
+}
+
 pub fn check_file(file_name: &str) -> bool {
     LazyResourceMap::contains_key(&file_name.to_lowercase())
 }
 
+/// The resource's handle in the [`store`], inside the game the address of its `BFResourcePtr`
 pub fn get_file_ptr(file_name: &str) -> Option<u32> {
     if let Ok(Some(resource)) = LazyResourceMap::get(&file_name.to_lowercase()) {
-        Some(resource.data)
+        Some(resource.handle)
     } else {
         None
     }
@@ -468,13 +426,7 @@ pub fn get_file_ptr(file_name: &str) -> Option<u32> {
 
 pub fn get_file(file_name: &str) -> Option<(String, Box<[u8]>)> {
     match LazyResourceMap::get(file_name) {
-        Ok(Some(file)) => {
-            let resource_ptr = get_from_memory::<BFResourcePtr>(file.data);
-            let tmp_slice = unsafe { slice::from_raw_parts(resource_ptr.data_ptr as *const _, resource_ptr.content_size as usize) };
-            let mut new_slice = vec![0; resource_ptr.content_size as usize];
-            new_slice.copy_from_slice(tmp_slice);
-            Some((resource_ptr.bf_zip_name.copy_to_string(), new_slice.into_boxed_slice()))
-        }
+        Ok(Some(file)) => Some(store().read(file.handle)),
         Ok(None) => {
             info!("File not found: {}", file_name);
             None
@@ -497,8 +449,10 @@ pub fn get_num_resources() -> usize {
 pub fn add_ztfile(path: &Path, file_name: String, ztfile: ZTFile) -> anyhow::Result<()> {
     let ztd_path = path.to_str() 
         .with_context(|| format!("Failed to convert path to string: {}", path.display()))?;
-    let (file_name, type_, data) = ztfile_to_raw_resource(ztd_path, file_name, ztfile)?;
-    LazyResourceMap::insert_custom(file_name, type_, data);
+    let file_name = file_name.to_lowercase();
+    let type_ = ztfile.file_type();
+    let handle = store().insert(&zip_name(ztd_path), &file_name, ztfile.into_contents())?;
+    LazyResourceMap::insert_custom(file_name, type_, handle);
     Ok(())
 }
 
@@ -510,19 +464,6 @@ pub fn remove_resource(file_name: &str) -> bool {
     LazyResourceMap::remove(file_name.to_lowercase()).is_some()
 }
 
-/// Update or insert a resource in the resource map
-///
-/// This is more efficient than calling remove_resource() + add_ztfile() as it only
-/// acquires the lock once and atomically replaces the resource if it exists.
-///
-/// # Arguments
-/// * `file_name` - The resource file name (will be lowercased for case-insensitive lookup)
-/// * `file_type` - The type of the file
-/// * `data` - Pointer to the resource data
-pub fn update_resource(file_name: String, file_type: ZTFileType, data: u32) {
-    LazyResourceMap::insert_custom(file_name, file_type, data)
-}
-
 /// Cache statistics for resource management
 pub struct CacheStats {
     pub loaded_resources: usize,
@@ -655,24 +596,8 @@ pub fn deref_resource(file_name: &str) -> bool {
 /// * `file_type` - The type of the file
 pub fn create_empty_resource(filename: String, file_type: ZTFileType) -> anyhow::Result<()> {
     let lowercase_filename = filename.to_lowercase();
-
-    // Create empty CString for text files
-    let empty_cstring = CString::new("")?;
-    let data_ptr = empty_cstring.into_raw() as u32;
-
-    let bf_zip_name = CString::new("<disabled>".to_string())?;
-    let bf_resource_name = CString::new(lowercase_filename.clone())?;
-
-    let resource_ptr = Box::into_raw(Box::new(BFResourcePtr {
-        num_refs: 100,
-        bf_zip_name: bf_zip_name.into(),
-        bf_resource_name: bf_resource_name.into(),
-        data_ptr,
-        content_size: 0,
-    }));
-
-    // Use LazyResourceMap::insert_custom to add the empty resource
-    LazyResourceMap::insert_custom(lowercase_filename, file_type, resource_ptr as u32);
+    let handle = store().insert("<disabled>", &lowercase_filename, Box::new([]))?;
+    LazyResourceMap::insert_custom(lowercase_filename, file_type, handle);
     Ok(())
 }
 
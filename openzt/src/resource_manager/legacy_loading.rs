@@ -18,7 +18,13 @@ use crate::{
     resource_manager::{
         handlers::{get_handlers, RunStage},
         lazyresourcemap::{add_lazy, check_file_loaded, create_empty_resource, get_file, get_file_names, get_num_resources, mark_disabled_ztd_file},
-        openzt_mods::{get_num_mod_ids, legacy_attributes::{add_legacy_entity, LegacyEntityAttributes, LegacyEntityType, SubtypeAttributes}, load_open_zt_mod},
+        openzt_mods::{
+            get_num_mod_ids,
+            legacy_attributes::{add_legacy_entity, LegacyEntityAttributes, LegacyEntityType, SubtypeAttributes},
+            load_open_zt_mod,
+            patch_report::save_patch_report,
+            scripts::{dispatch, LifecycleHook},
+        },
         ztfile::ZTFileType,
     },
 };
@@ -155,6 +161,10 @@ pub fn load_resources(
         elapsed
     );
 
+    if let Err(e) = save_patch_report() {
+        error!("Failed to save patch report: {}", e);
+    }
+
     let now = Instant::now();
 
     info!("Running BeforeOpenZTMods handlers");
@@ -205,6 +215,9 @@ pub fn load_resources(
 
     let elapsed = now.elapsed();
     info!("Extra handling took an extra: {:.2?}", elapsed);
+
+    // Every mod and resource is in place, let mod scripts finish setting up
+    dispatch(LifecycleHook::Load, ());
 }
 
 fn handle_ztd(resource: &Path, disabled_ztds: &[String]) -> anyhow::Result<i32> {
@@ -647,7 +660,7 @@ fn get_legacy_cfg_type(file_name: &str) -> Option<LegacyCfg> {
 /// # Returns
 /// * `Ok(count)` if legacy loading succeeded, where count is the number of .cfg files processed
 /// * `Err(_)` if there was an error reading or parsing the .cfg files, or if no .cfg files were found
-#[cfg(feature = "integration-tests")]
+#[cfg(any(test, feature = "integration-tests"))]
 pub fn load_legacy_entities_for_tests() -> anyhow::Result<usize> {
     use std::fs;
 
@@ -719,7 +732,7 @@ pub fn load_legacy_entities_for_tests() -> anyhow::Result<usize> {
 ///
 /// This function is called after test .cfg and .ai files have been added to the resource
 /// system. It triggers the actual legacy loading code path to parse those files.
-#[cfg(feature = "integration-tests")]
+#[cfg(any(test, feature = "integration-tests"))]
 pub fn load_legacy_entities_from_test_files() -> anyhow::Result<()> {
     info!("Loading legacy entities from test .cfg files...");
 
@@ -738,7 +751,7 @@ pub fn load_legacy_entities_from_test_files() -> anyhow::Result<()> {
 
     for cfg_file in cfg_files {
         // Check if file exists in resource system
-        if let Some((filename, data)) = crate::resource_manager::lazyresourcemap::get_file(cfg_file) {
+        if let Some((_, data)) = crate::resource_manager::lazyresourcemap::get_file(cfg_file) {
             // Parse the .cfg file
             let input_string = crate::encoding_utils::decode_game_text(&data);
             let mut ini = Ini::new_cs();
@@ -6,6 +6,8 @@ use std::sync::Mutex;
 use tracing::info;
 use tracing_subscriber::filter::LevelFilter;
 
+use crate::scripting::sandbox::{Capability, StdModule};
+
 static LOGGING_INITIALIZED: LazyLock<Mutex<bool>> = LazyLock::new(|| Mutex::new(false));
 
 /// Initialize logging with settings from openzt.toml
@@ -90,6 +92,25 @@ pub struct OpenZTConfig {
 
     #[serde(default)]
     pub expansions: ExpansionConfig,
+
+    /// Player choices for options declared by mods, keyed by mod ID then option name
+    ///
+    /// Example:
+    /// ```toml
+    /// [mod_options."finn.my_fun_mod"]
+    /// difficulty = 4
+    /// ```
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub mod_options: IndexMap<String, IndexMap<String, toml::Value>>,
+
+    #[serde(default)]
+    pub scripting: ScriptingConfig,
+
+    #[serde(default)]
+    pub game: GameConfig,
+
+    #[serde(default)]
+    pub bugfixes: BugfixConfig,
 }
 
 /// Mod loading configuration section
@@ -195,6 +216,157 @@ pub struct ExpansionConfig {
     pub custom: IndexMap<String, Vec<String>>,
 }
 
+/// Game executable configuration section
+///
+/// Example:
+/// ```toml
+/// [game]
+/// allow_unknown_build = false
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct GameConfig {
+    /// Load OpenZT into a zoo.exe it doesn't recognise, using the Complete Collection addresses (default: false)
+    ///
+    /// Only for executables known to match the Complete Collection, e.g. one patched in a way that
+    /// doesn't move code. On any other build this corrupts the game's memory.
+    #[serde(default)]
+    pub allow_unknown_build: bool,
+}
+
+/// Bugfix configuration section
+///
+/// Each of OpenZT's fixes to the game's code can be turned off by name, all are on by default.
+/// `list_patches()` in the console shows the names.
+///
+/// Example:
+/// ```toml
+/// [bugfixes]
+/// zoowall_map_edge_crash = false
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct BugfixConfig {
+    #[serde(default, flatten)]
+    pub enabled: IndexMap<String, bool>,
+}
+
+impl BugfixConfig {
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.enabled.get(name).copied().unwrap_or(true)
+    }
+}
+
+/// Lua sandbox configuration section
+///
+/// The console and mod scripts each run in their own sandboxed environments, configured separately.
+///
+/// Example:
+/// ```toml
+/// [scripting]
+/// tick_budget_ms = 5
+///
+/// [scripting.mods]
+/// stdlib = ["base", "string", "table", "math"]
+/// capabilities = ["read_world", "modify_world"]
+/// instruction_limit = 10000000
+/// memory_limit_mb = 64
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ScriptingConfig {
+    /// Sandbox for code sent to the Lua console
+    #[serde(default = "SandboxConfig::console", deserialize_with = "SandboxConfig::deserialize_console")]
+    pub console: SandboxConfig,
+
+    /// Sandbox for scripts bundled with OpenZT mods (one environment per mod)
+    #[serde(default = "SandboxConfig::mods", deserialize_with = "SandboxConfig::deserialize_mods")]
+    pub mods: SandboxConfig,
+
+    /// Time waiting Lua jobs may run each sim tick, in milliseconds (default: 5)
+    #[serde(default = "default_tick_budget_ms")]
+    pub tick_budget_ms: u64,
+}
+
+/// What code in a sandboxed Lua environment may use
+///
+/// Fields missing from a `[scripting.console]` or `[scripting.mods]` table keep that sandbox's defaults.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SandboxConfig {
+    /// Standard library modules: base, coroutine, string, table, math, bit, os (clock and date functions only)
+    pub stdlib: Vec<StdModule>,
+
+    /// Groups of OpenZT functions: read_world, modify_world, modify_economy, modify_config, debug_memory
+    pub capabilities: Vec<Capability>,
+
+    /// Lua instructions one console command or mod callback may run (0 = unlimited)
+    pub instruction_limit: u64,
+
+    /// Memory one console command or mod callback may allocate, in MB (0 = unlimited)
+    pub memory_limit_mb: u64,
+}
+
+/// A `SandboxConfig` table as written, before it's merged over the sandbox's defaults
+#[derive(Deserialize)]
+struct PartialSandboxConfig {
+    stdlib: Option<Vec<StdModule>>,
+    capabilities: Option<Vec<Capability>>,
+    instruction_limit: Option<u64>,
+    memory_limit_mb: Option<u64>,
+}
+
+impl PartialSandboxConfig {
+    fn merge_over(self, defaults: SandboxConfig) -> SandboxConfig {
+        SandboxConfig {
+            stdlib: self.stdlib.unwrap_or(defaults.stdlib),
+            capabilities: self.capabilities.unwrap_or(defaults.capabilities),
+            instruction_limit: self.instruction_limit.unwrap_or(defaults.instruction_limit),
+            memory_limit_mb: self.memory_limit_mb.unwrap_or(defaults.memory_limit_mb),
+        }
+    }
+}
+
+impl SandboxConfig {
+    /// The console is a local developer tool, so it can use everything by default
+    fn console() -> Self {
+        SandboxConfig {
+            stdlib: default_stdlib(),
+            capabilities: vec![
+                Capability::ReadWorld,
+                Capability::ModifyWorld,
+                Capability::ModifyEconomy,
+                Capability::ModifyConfig,
+                Capability::DebugMemory,
+            ],
+            instruction_limit: 100_000_000,
+            memory_limit_mb: 0,
+        }
+    }
+
+    /// Mod scripts can't touch engine internals or config files, and are held to a budget every tick
+    fn mods() -> Self {
+        SandboxConfig {
+            stdlib: default_stdlib(),
+            capabilities: vec![Capability::ReadWorld, Capability::ModifyWorld, Capability::ModifyEconomy],
+            instruction_limit: 10_000_000,
+            memory_limit_mb: 64,
+        }
+    }
+
+    fn deserialize_console<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(PartialSandboxConfig::deserialize(deserializer)?.merge_over(Self::console()))
+    }
+
+    fn deserialize_mods<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(PartialSandboxConfig::deserialize(deserializer)?.merge_over(Self::mods()))
+    }
+}
+
+fn default_stdlib() -> Vec<StdModule> {
+    StdModule::ALL.to_vec()
+}
+
+fn default_tick_budget_ms() -> u64 {
+    5
+}
+
 fn default_true() -> bool {
     true
 }
@@ -226,6 +398,10 @@ impl Default for OpenZTConfig {
             },
             resource_cache: ResourceCacheConfig::default(),
             expansions: ExpansionConfig::default(),
+            mod_options: IndexMap::new(),
+            scripting: ScriptingConfig::default(),
+            game: GameConfig::default(),
+            bugfixes: BugfixConfig::default(),
         }
     }
 }
@@ -260,6 +436,16 @@ impl Default for ResourceCacheConfig {
     }
 }
 
+impl Default for ScriptingConfig {
+    fn default() -> Self {
+        ScriptingConfig {
+            console: SandboxConfig::console(),
+            mods: SandboxConfig::mods(),
+            tick_budget_ms: default_tick_budget_ms(),
+        }
+    }
+}
+
 impl Default for ExpansionConfig {
     fn default() -> Self {
         ExpansionConfig {
@@ -305,6 +491,7 @@ fn load_openzt_config_from_disk() -> OpenZTConfig {
                     let has_logging = toml_value.get("logging").is_some();
                     let has_resource_cache = toml_value.get("resource_cache").is_some();
                     let has_expansions = toml_value.get("expansions").is_some();
+                    let has_scripting = toml_value.get("scripting").is_some();
 
                     // Check if all fields exist within sections
                     let mod_loading_complete = if let Some(mod_loading) = toml_value.get("mod_loading") {
@@ -334,7 +521,7 @@ fn load_openzt_config_from_disk() -> OpenZTConfig {
                     // No field-level validation needed for expansions since it's a free-form HashMap
 
                     // Update needed if sections missing or fields incomplete
-                    !has_mod_loading || !has_logging || !has_resource_cache || !has_expansions
+                    !has_mod_loading || !has_logging || !has_resource_cache || !has_expansions || !has_scripting
                         || !mod_loading_complete || !logging_complete || !resource_cache_complete
                 }
                 Err(_) => false, // If we can't parse as Value, the full parse will fail below
@@ -676,4 +863,59 @@ level = "debug"
         assert_eq!(parsed.logging.level, LogLevel::Debug);
         assert!(parsed.logging.log_to_file); // default
     }
+
+    #[test]
+    fn test_scripting_section() {
+        let parsed: OpenZTConfig = toml::from_str("").unwrap();
+        assert!(parsed.scripting.console.capabilities.contains(&Capability::DebugMemory));
+        assert!(!parsed.scripting.mods.capabilities.contains(&Capability::DebugMemory));
+        assert_eq!(parsed.scripting.mods.instruction_limit, 10_000_000);
+        assert_eq!(parsed.scripting.tick_budget_ms, 5);
+
+        let config_str = r#"
+[scripting]
+tick_budget_ms = 2
+
+[scripting.mods]
+stdlib = ["base", "string"]
+capabilities = ["read_world"]
+memory_limit_mb = 16
+"#;
+        let parsed: OpenZTConfig = toml::from_str(config_str).unwrap();
+        assert_eq!(parsed.scripting.mods.stdlib, vec![StdModule::Base, StdModule::String]);
+        assert_eq!(parsed.scripting.mods.capabilities, vec![Capability::ReadWorld]);
+        assert_eq!(parsed.scripting.mods.memory_limit_mb, 16);
+        assert_eq!(parsed.scripting.mods.instruction_limit, 10_000_000);
+        assert_eq!(parsed.scripting.tick_budget_ms, 2);
+        // The console section is missing entirely, so it keeps its defaults
+        assert_eq!(parsed.scripting.console, OpenZTConfig::default().scripting.console);
+
+        assert!(toml::from_str::<OpenZTConfig>("[scripting.console]\nstdlib = [\"io\"]").is_err());
+    }
+
+    #[test]
+    fn test_partial_scripting_tables_keep_sandbox_defaults() {
+        let config_str = r#"
+[scripting.console]
+instruction_limit = 0
+
+[scripting.mods]
+memory_limit_mb = 32
+"#;
+        let parsed: OpenZTConfig = toml::from_str(config_str).unwrap();
+        let defaults = OpenZTConfig::default().scripting;
+
+        assert_eq!(parsed.scripting.mods.memory_limit_mb, 32);
+        assert_eq!(parsed.scripting.mods.capabilities, defaults.mods.capabilities);
+        assert_eq!(parsed.scripting.mods.instruction_limit, defaults.mods.instruction_limit);
+        assert_eq!(parsed.scripting.mods.stdlib, defaults.mods.stdlib);
+
+        assert_eq!(parsed.scripting.console.instruction_limit, 0);
+        assert_eq!(parsed.scripting.console.capabilities, defaults.console.capabilities);
+        assert_eq!(parsed.scripting.console.memory_limit_mb, defaults.console.memory_limit_mb);
+
+        // Each table merges over its own sandbox's defaults, not the other one's
+        let parsed: OpenZTConfig = toml::from_str("[scripting.mods]\nstdlib = [\"base\"]").unwrap();
+        assert!(!parsed.scripting.mods.capabilities.contains(&Capability::DebugMemory));
+    }
 }
@@ -2,17 +2,24 @@ pub(crate) mod habitats_locations;
 pub(crate) mod extensions;
 pub(crate) mod legacy_attributes;
 pub(crate) mod loading;
+pub mod mod_options;
+pub(crate) mod patch_generator;
+pub mod patch_report;
 pub mod patches;
+pub(crate) mod scripts;
 
 pub use crate::resource_manager::openzt_mods::{
-    habitats_locations::{get_location_habitat_ids, get_location_or_habitat_by_id},
-    loading::{discover_mods, get_mod_ids, get_num_mod_ids, load_open_zt_mod},
+    habitats_locations::get_location_habitat_ids,
+    loading::{get_mod_ids, get_num_mod_ids, load_open_zt_mod},
 };
+// Only the resource manager's hooks use these
+#[cfg(target_os = "windows")]
+pub use crate::resource_manager::openzt_mods::{habitats_locations::get_location_or_habitat_by_id, loading::discover_mods};
 
 // Re-export items needed for integration tests
-#[cfg(feature = "integration-tests")]
+#[cfg(any(test, feature = "integration-tests"))]
 pub use crate::resource_manager::openzt_mods::{
-    habitats_locations::{get_habitat_id, get_location_id},
+    habitats_locations::get_habitat_id,
     loading::{
         clear_load_tracker,
         get_load_events,
@@ -282,7 +282,7 @@ pub fn list_extensions_with_tag(tag: &str) -> Vec<String> {
         .collect()
 }
 
-#[cfg(feature = "integration-tests")]
+#[cfg(any(test, feature = "integration-tests"))]
 pub fn clear_extensions() {
     EXTENSION_STORAGE.lock().unwrap().clear();
     EXTENSION_BY_BASE.lock().unwrap().clear();
@@ -290,7 +290,7 @@ pub fn clear_extensions() {
 }
 
 impl ExtensionRegistry {
-    #[cfg(feature = "integration-tests")]
+    #[cfg(any(test, feature = "integration-tests"))]
     pub fn clear(&mut self) {
         self.tags.clear();
         self.attributes.clear();
@@ -338,7 +338,7 @@ fn guess_entity_type_from_vtable(vtable: u32) -> Option<LegacyEntityType> {
 /// * `None` - If the entity type cannot be determined
 ///
 /// # Example
-/// ```rust
+/// ```ignore
 /// if let Some(base) = get_entity_base(entity_ptr) {
 ///     println!("Entity base: {}", base); // "legacy.scenery.statue"
 /// }
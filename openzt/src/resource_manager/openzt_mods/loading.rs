@@ -17,7 +17,11 @@ use crate::{
     mods,
     resource_manager::{
         lazyresourcemap::add_ztfile,
-        openzt_mods::habitats_locations::add_location_or_habitat,
+        openzt_mods::{
+            habitats_locations::add_location_or_habitat,
+            mod_options::{register_mod_options, validate_option_declarations},
+            scripts::load_mod_scripts,
+        },
         ztd::ZtdArchive,
         ztfile::{ZTFile, ZTFileType},
     },
@@ -42,6 +46,14 @@ pub fn get_mod_ids() -> Vec<String> {
     binding.iter().cloned().collect()
 }
 
+/// Versions of loaded OpenZT mods, used by `mod_version` patch conditions
+static MOD_VERSIONS: LazyLock<Mutex<HashMap<String, mods::Version>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Get the version of a loaded OpenZT mod
+pub fn get_mod_version(mod_id: &str) -> Option<mods::Version> {
+    MOD_VERSIONS.lock().unwrap().get(mod_id).cloned()
+}
+
 /// Discover all OpenZT mods from .ztd archives without loading them
 ///
 /// Returns a map of mod_id -> (archive_name, Meta) for all mods found in the resource paths
@@ -144,7 +156,7 @@ fn read_meta_from_archive(archive_path: &Path) -> anyhow::Result<Option<mods::Me
 }
 
 // === Load Order Tracking (for integration tests) ===
-#[cfg(feature = "integration-tests")]
+#[cfg(any(test, feature = "integration-tests"))]
 #[derive(Debug, Clone)]
 pub struct LoadEvent {
     pub mod_id: String,
@@ -153,15 +165,15 @@ pub struct LoadEvent {
     pub timestamp: std::time::Instant,
 }
 
-#[cfg(feature = "integration-tests")]
+#[cfg(any(test, feature = "integration-tests"))]
 pub static LOAD_ORDER_TRACKER: LazyLock<Mutex<Vec<LoadEvent>>> = LazyLock::new(|| Mutex::new(Vec::new()));
 
-#[cfg(feature = "integration-tests")]
+#[cfg(any(test, feature = "integration-tests"))]
 pub fn clear_load_tracker() {
     LOAD_ORDER_TRACKER.lock().unwrap().clear();
 }
 
-#[cfg(feature = "integration-tests")]
+#[cfg(any(test, feature = "integration-tests"))]
 pub fn get_load_events() -> Vec<LoadEvent> {
     LOAD_ORDER_TRACKER.lock().unwrap().clone()
 }
@@ -217,9 +229,14 @@ fn load_open_zt_mod_internal(
 
     let mod_id = meta.mod_id().to_string();
 
+    validate_option_declarations(meta.options())
+        .with_context(|| format!("Invalid [options] in meta.toml of {}", mod_id))?;
+
     if !add_new_mod_id(&mod_id) {
         return Err(anyhow!("Mod already loaded: {}", mod_id));
     }
+    MOD_VERSIONS.lock().unwrap().insert(mod_id.clone(), meta.version().clone());
+    register_mod_options(&mod_id, meta.options());
 
     // Create span for the entire loading process
     let mod_name = meta.name().to_string();
@@ -281,7 +298,7 @@ fn load_open_zt_mod_internal(
         );
 
         // Track loading order for integration tests
-        #[cfg(feature = "integration-tests")]
+        #[cfg(any(test, feature = "integration-tests"))]
         {
             let event = LoadEvent {
                 mod_id: mod_id.clone(),
@@ -302,13 +319,17 @@ fn load_open_zt_mod_internal(
         if let Some(patches) = file_info.mod_def.patches() {
             let patch_meta = file_info.mod_def.patch_meta().as_ref().cloned().unwrap_or_default();
             info!("Found {} patches in {}", patches.len(), file_info.filename);
-            if let Err(e) = super::patches::apply_patches(&patch_meta, patches, resource, &mod_id) {
+            let patch_groups = file_info.mod_def.patch_groups().as_ref();
+            if let Err(e) = super::patches::apply_patches_with_groups(&patch_meta, patches, patch_groups, resource, &mod_id) {
                 error!("Failed to apply patches from {}: {}", file_info.filename, e);
                 return Err(e);
             }
         }
     }
 
+    // Scripts run once the mod's defs are in place
+    load_mod_scripts(&mod_id, meta.scripts(), &file_map);
+
     Ok(meta.ztd_type().clone())
 }
 
@@ -359,7 +380,7 @@ pub fn load_open_zt_mod(archive: &mut ZtdArchive, resource: &Path) -> anyhow::Re
 }
 
 /// Load an OpenZT mod from an in-memory file map (for testing)
-#[cfg(feature = "integration-tests")]
+#[cfg(any(test, feature = "integration-tests"))]
 pub fn load_open_zt_mod_from_memory(
     file_map: HashMap<String, Box<[u8]>>,
     mod_name: &str,
@@ -0,0 +1,345 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+};
+
+use anyhow::{anyhow, bail};
+use indexmap::IndexMap;
+use tracing::warn;
+
+use crate::{
+    mods::{ModOption, ModOptionKind},
+    resource_manager::mod_config::{get_openzt_config, save_openzt_config, OpenZTConfig},
+};
+
+/// Options declared by each loaded mod, keyed by mod ID then option name
+static MOD_OPTIONS: LazyLock<Mutex<HashMap<String, IndexMap<String, ModOption>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Check that every option declared in a mod's meta.toml is usable
+///
+/// # Returns
+/// * `Err` if bounds are inverted, an enum has no values, or a default is out of range
+pub fn validate_option_declarations(options: &IndexMap<String, ModOption>) -> anyhow::Result<()> {
+    for (name, option) in options {
+        match &option.kind {
+            ModOptionKind::Int { min: Some(min), max: Some(max), .. } if min > max => {
+                bail!("Option '{}' has min {} greater than max {}", name, min, max)
+            }
+            ModOptionKind::Float { min: Some(min), max: Some(max), .. } if min > max => {
+                bail!("Option '{}' has min {} greater than max {}", name, min, max)
+            }
+            ModOptionKind::Enum { values, .. } if values.is_empty() => bail!("Option '{}' is an enum with no values", name),
+            _ => {}
+        }
+        validate_option_value(name, option, &default_option_value(option)).map_err(|e| anyhow!("Invalid default: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Register the options declared by a loaded mod
+pub fn register_mod_options(mod_id: &str, options: &IndexMap<String, ModOption>) {
+    if options.is_empty() {
+        return;
+    }
+    MOD_OPTIONS.lock().unwrap().insert(mod_id.to_string(), options.clone());
+}
+
+/// Get the options declared by a mod, if it has any
+pub fn get_mod_option_declarations(mod_id: &str) -> Option<IndexMap<String, ModOption>> {
+    MOD_OPTIONS.lock().unwrap().get(mod_id).cloned()
+}
+
+fn get_declaration(mod_id: &str, name: &str) -> anyhow::Result<ModOption> {
+    let options = MOD_OPTIONS.lock().unwrap();
+    let declared = options.get(mod_id).ok_or_else(|| anyhow!("Mod '{}' is not loaded or declares no options", mod_id))?;
+    declared
+        .get(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("Mod '{}' has no option '{}' (available: {})", mod_id, name, declared.keys().cloned().collect::<Vec<_>>().join(", ")))
+}
+
+/// Get the current value of a mod option
+///
+/// Returns the player's choice from openzt.toml, or the declared default if the
+/// choice is unset or no longer valid.
+pub fn get_mod_option(mod_id: &str, name: &str) -> anyhow::Result<toml::Value> {
+    let option = get_declaration(mod_id, name)?;
+    Ok(option_value_from_config(&get_openzt_config(), mod_id, name, &option))
+}
+
+/// Set a mod option and save it to openzt.toml
+///
+/// # Arguments
+/// * `mod_id` - The mod declaring the option
+/// * `name` - The option name
+/// * `value` - The new value, as it would be written in TOML (quotes optional for strings)
+///
+/// # Returns
+/// * `Ok(toml::Value)` - The value that was stored
+/// * `Err` if the option doesn't exist or the value is the wrong type or out of range
+pub fn set_mod_option(mod_id: &str, name: &str, value: &str) -> anyhow::Result<toml::Value> {
+    let option = get_declaration(mod_id, name)?;
+    let value = parse_option_value(name, &option, value)?;
+
+    let mut config = get_openzt_config();
+    config.mod_options.entry(mod_id.to_string()).or_default().insert(name.to_string(), value.clone());
+    save_openzt_config(&config, false)?;
+    Ok(value)
+}
+
+/// Read an option's value from a config, falling back to its default
+fn option_value_from_config(config: &OpenZTConfig, mod_id: &str, name: &str, option: &ModOption) -> toml::Value {
+    let Some(stored) = config.mod_options.get(mod_id).and_then(|options| options.get(name)) else {
+        return default_option_value(option);
+    };
+    match validate_option_value(name, option, stored) {
+        Ok(value) => value,
+        Err(e) => {
+            warn!("Ignoring invalid value in openzt.toml for mod '{}': {}. Using default.", mod_id, e);
+            default_option_value(option)
+        }
+    }
+}
+
+/// The declared default of an option
+pub fn default_option_value(option: &ModOption) -> toml::Value {
+    match &option.kind {
+        ModOptionKind::Bool { default } => toml::Value::Boolean(*default),
+        ModOptionKind::Int { default, .. } => toml::Value::Integer(*default),
+        ModOptionKind::Float { default, .. } => toml::Value::Float(*default),
+        ModOptionKind::Enum { default, .. } | ModOptionKind::String { default } => toml::Value::String(default.clone()),
+    }
+}
+
+/// Check a value against an option's type and bounds
+///
+/// Integers are accepted for float options and converted.
+///
+/// # Returns
+/// * `Ok(toml::Value)` - The value, normalised to the option's type
+/// * `Err` describing why the value was rejected
+pub fn validate_option_value(name: &str, option: &ModOption, value: &toml::Value) -> anyhow::Result<toml::Value> {
+    match (&option.kind, value) {
+        (ModOptionKind::Bool { .. }, toml::Value::Boolean(_)) => Ok(value.clone()),
+        (ModOptionKind::Int { min, max, .. }, toml::Value::Integer(v)) => {
+            check_bounds(name, *v, *min, *max)?;
+            Ok(value.clone())
+        }
+        (ModOptionKind::Float { min, max, .. }, toml::Value::Float(_) | toml::Value::Integer(_)) => {
+            let v = match value {
+                toml::Value::Integer(i) => *i as f64,
+                _ => value.as_float().unwrap_or_default(),
+            };
+            check_bounds(name, v, *min, *max)?;
+            Ok(toml::Value::Float(v))
+        }
+        (ModOptionKind::Enum { values, .. }, toml::Value::String(v)) => {
+            if !values.contains(v) {
+                bail!("Option '{}' must be one of [{}], got '{}'", name, values.join(", "), v);
+            }
+            Ok(value.clone())
+        }
+        (ModOptionKind::String { .. }, toml::Value::String(_)) => Ok(value.clone()),
+        (kind, _) => bail!("Option '{}' expects {}, got {}", name, kind_name(kind), value),
+    }
+}
+
+/// Parse a value typed by the player (e.g. from the console) for an option
+///
+/// Strings may be given with or without quotes.
+pub fn parse_option_value(name: &str, option: &ModOption, input: &str) -> anyhow::Result<toml::Value> {
+    let input = input.trim();
+    let value = match &option.kind {
+        ModOptionKind::Bool { .. } => match input {
+            "true" => toml::Value::Boolean(true),
+            "false" => toml::Value::Boolean(false),
+            _ => bail!("Option '{}' expects true or false, got '{}'", name, input),
+        },
+        ModOptionKind::Int { .. } => toml::Value::Integer(
+            input
+                .parse()
+                .map_err(|_| anyhow!("Option '{}' expects an integer, got '{}'", name, input))?,
+        ),
+        ModOptionKind::Float { .. } => toml::Value::Float(
+            input
+                .parse()
+                .map_err(|_| anyhow!("Option '{}' expects a number, got '{}'", name, input))?,
+        ),
+        ModOptionKind::Enum { .. } | ModOptionKind::String { .. } => {
+            let unquoted = input.strip_prefix('"').and_then(|s| s.strip_suffix('"')).unwrap_or(input);
+            toml::Value::String(unquoted.to_string())
+        }
+    };
+    validate_option_value(name, option, &value)
+}
+
+/// Format an option value for display or variable substitution (strings unquoted)
+pub fn format_option_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn check_bounds<T: PartialOrd + std::fmt::Display + Copy>(name: &str, value: T, min: Option<T>, max: Option<T>) -> anyhow::Result<()> {
+    let too_low = min.is_some_and(|min| value < min);
+    let too_high = max.is_some_and(|max| value > max);
+    if too_low || too_high {
+        match (min, max) {
+            (Some(min), Some(max)) => bail!("Option '{}' must be between {} and {}, got {}", name, min, max, value),
+            (Some(min), None) => bail!("Option '{}' must be at least {}, got {}", name, min, value),
+            (None, Some(max)) => bail!("Option '{}' must be at most {}, got {}", name, max, value),
+            (None, None) => {}
+        }
+    }
+    Ok(())
+}
+
+fn kind_name(kind: &ModOptionKind) -> &'static str {
+    match kind {
+        ModOptionKind::Bool { .. } => "a boolean",
+        ModOptionKind::Int { .. } => "an integer",
+        ModOptionKind::Float { .. } => "a number",
+        ModOptionKind::Enum { .. } => "one of its enum values",
+        ModOptionKind::String { .. } => "a string",
+    }
+}
+
+/// Describe a mod's options and their current values, one per line
+pub fn describe_mod_options(mod_id: &str) -> anyhow::Result<String> {
+    let options = get_mod_option_declarations(mod_id).ok_or_else(|| anyhow!("Mod '{}' is not loaded or declares no options", mod_id))?;
+    let config = get_openzt_config();
+
+    let mut result = String::new();
+    for (name, option) in &options {
+        let value = option_value_from_config(&config, mod_id, name, option);
+        let bounds = match &option.kind {
+            ModOptionKind::Int { min, max, .. } => format_bounds(*min, *max),
+            ModOptionKind::Float { min, max, .. } => format_bounds(*min, *max),
+            ModOptionKind::Enum { values, .. } => format!(" [{}]", values.join(", ")),
+            _ => String::new(),
+        };
+        result.push_str(&format!("{} = {}{}", name, format_option_value(&value), bounds));
+        if !option.description.is_empty() {
+            result.push_str(&format!(" - {}", option.description));
+        }
+        result.push('\n');
+    }
+    Ok(result)
+}
+
+fn format_bounds<T: std::fmt::Display>(min: Option<T>, max: Option<T>) -> String {
+    match (min, max) {
+        (None, None) => String::new(),
+        (min, max) => format!(
+            " [{}..{}]",
+            min.map(|v| v.to_string()).unwrap_or_default(),
+            max.map(|v| v.to_string()).unwrap_or_default()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn option(kind: ModOptionKind) -> ModOption {
+        ModOption {
+            description: String::new(),
+            kind,
+        }
+    }
+
+    fn difficulty() -> ModOption {
+        option(ModOptionKind::Int {
+            default: 3,
+            min: Some(1),
+            max: Some(5),
+        })
+    }
+
+    #[test]
+    fn test_validate_int_bounds() {
+        let opt = difficulty();
+        assert_eq!(validate_option_value("difficulty", &opt, &toml::Value::Integer(5)).unwrap(), toml::Value::Integer(5));
+
+        let err = validate_option_value("difficulty", &opt, &toml::Value::Integer(12)).unwrap_err();
+        assert_eq!(err.to_string(), "Option 'difficulty' must be between 1 and 5, got 12");
+
+        let err = validate_option_value("difficulty", &opt, &toml::Value::String("hard".to_string())).unwrap_err();
+        assert!(err.to_string().contains("expects an integer"));
+    }
+
+    #[test]
+    fn test_validate_float_accepts_integers() {
+        let opt = option(ModOptionKind::Float {
+            default: 1.0,
+            min: Some(0.5),
+            max: None,
+        });
+        assert_eq!(validate_option_value("scale", &opt, &toml::Value::Integer(2)).unwrap(), toml::Value::Float(2.0));
+
+        let err = validate_option_value("scale", &opt, &toml::Value::Float(0.1)).unwrap_err();
+        assert_eq!(err.to_string(), "Option 'scale' must be at least 0.5, got 0.1");
+    }
+
+    #[test]
+    fn test_parse_option_value() {
+        let theme = option(ModOptionKind::Enum {
+            default: "jungle".to_string(),
+            values: vec!["jungle".to_string(), "desert".to_string()],
+        });
+        assert_eq!(parse_option_value("theme", &theme, "\"desert\"").unwrap(), toml::Value::String("desert".to_string()));
+        assert!(parse_option_value("theme", &theme, "arctic").unwrap_err().to_string().contains("must be one of [jungle, desert]"));
+
+        let hardcore = option(ModOptionKind::Bool { default: false });
+        assert_eq!(parse_option_value("hardcore", &hardcore, "true").unwrap(), toml::Value::Boolean(true));
+        assert!(parse_option_value("hardcore", &hardcore, "yes").is_err());
+
+        assert!(parse_option_value("difficulty", &difficulty(), "0").is_err());
+    }
+
+    #[test]
+    fn test_validate_declarations() {
+        let mut options = IndexMap::new();
+        options.insert("difficulty".to_string(), difficulty());
+        assert!(validate_option_declarations(&options).is_ok());
+
+        options.insert(
+            "broken".to_string(),
+            option(ModOptionKind::Int {
+                default: 9,
+                min: Some(1),
+                max: Some(5),
+            }),
+        );
+        assert!(validate_option_declarations(&options).is_err());
+
+        let mut options = IndexMap::new();
+        options.insert(
+            "theme".to_string(),
+            option(ModOptionKind::Enum {
+                default: "jungle".to_string(),
+                values: Vec::new(),
+            }),
+        );
+        assert!(validate_option_declarations(&options).is_err());
+    }
+
+    #[test]
+    fn test_value_from_config_falls_back_to_default() {
+        let opt = difficulty();
+        let mut config = OpenZTConfig::default();
+        assert_eq!(option_value_from_config(&config, "test.mod", "difficulty", &opt), toml::Value::Integer(3));
+
+        config
+            .mod_options
+            .entry("test.mod".to_string())
+            .or_default()
+            .insert("difficulty".to_string(), toml::Value::Integer(4));
+        assert_eq!(option_value_from_config(&config, "test.mod", "difficulty", &opt), toml::Value::Integer(4));
+
+        config.mod_options["test.mod"].insert("difficulty".to_string(), toml::Value::Integer(99));
+        assert_eq!(option_value_from_config(&config, "test.mod", "difficulty", &opt), toml::Value::Integer(3));
+    }
+}
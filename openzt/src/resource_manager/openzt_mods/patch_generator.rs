@@ -0,0 +1,537 @@
+use std::path::Path;
+
+use anyhow::Context;
+use indexmap::IndexMap;
+use openzt_configparser::ini::Ini;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::{
+    mods::{
+        AddSectionPatch,
+        AppendValuePatch,
+        AppendValuesPatch,
+        ClearSectionPatch,
+        OnExists,
+        Patch,
+        RemoveKeyPatch,
+        RemoveKeysPatch,
+        RemoveSectionPatch,
+        SetKeyPatch,
+        SetKeysPatch,
+    },
+    resource_manager::lazyresourcemap::get_file,
+};
+
+// ============================================================================
+// Patch Generation (INI diff -> [patches])
+// ============================================================================
+
+/// Collects generated patches and hands out unique, readable patch names
+struct PatchSetBuilder {
+    /// Prefix for every patch name, derived from the target file stem (e.g. "blckbuck")
+    prefix: String,
+    target: String,
+    patches: IndexMap<String, Patch>,
+}
+
+impl PatchSetBuilder {
+    fn new(target: &str) -> Self {
+        let stem = Path::new(target)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or(target);
+
+        PatchSetBuilder {
+            prefix: slugify(stem),
+            target: target.to_string(),
+            patches: IndexMap::new(),
+        }
+    }
+
+    /// Insert a patch named `<file>_<operation>_<parts...>`, adding a numeric suffix on collision
+    fn push(&mut self, operation: &str, parts: &[&str], patch: Patch) {
+        let mut name = format!("{}_{}", self.prefix, operation);
+        for part in parts {
+            name.push('_');
+            name.push_str(&slugify(part));
+        }
+
+        let mut unique_name = name.clone();
+        let mut suffix = 2;
+        while self.patches.contains_key(&unique_name) {
+            unique_name = format!("{}_{}", name, suffix);
+            suffix += 1;
+        }
+
+        self.patches.insert(unique_name, patch);
+    }
+
+    fn push_removed_keys(&mut self, section: &str, mut keys: Vec<String>) {
+        match keys.len() {
+            0 => {}
+            1 => {
+                let key = keys.remove(0);
+                self.push("remove_key", &[section, &key], Patch::RemoveKey(RemoveKeyPatch {
+                    target: self.target.clone(),
+                    section: section.to_string(),
+                    key: key.clone(),
+                    condition: None,
+                }));
+            }
+            _ => {
+                self.push("remove_keys", &[section], Patch::RemoveKeys(RemoveKeysPatch {
+                    target: self.target.clone(),
+                    section: section.to_string(),
+                    keys,
+                    condition: None,
+                }));
+            }
+        }
+    }
+
+    fn push_set_keys(&mut self, section: &str, mut keys: IndexMap<String, String>) {
+        match keys.len() {
+            0 => {}
+            1 => {
+                let (key, value) = keys.swap_remove_index(0).unwrap();
+                self.push("set_key", &[section, &key], Patch::SetKey(SetKeyPatch {
+                    target: self.target.clone(),
+                    section: section.to_string(),
+                    key: key.clone(),
+                    value,
+                    condition: None,
+                }));
+            }
+            _ => {
+                self.push("set_keys", &[section], Patch::SetKeys(SetKeysPatch {
+                    target: self.target.clone(),
+                    section: section.to_string(),
+                    keys,
+                    condition: None,
+                }));
+            }
+        }
+    }
+
+    fn push_appended_values(&mut self, section: &str, key: String, mut values: Vec<String>) {
+        match values.len() {
+            0 => {}
+            1 => {
+                self.push("append_value", &[section, &key], Patch::AppendValue(AppendValuePatch {
+                    target: self.target.clone(),
+                    section: section.to_string(),
+                    key: key.clone(),
+                    value: values.remove(0),
+                    condition: None,
+                }));
+            }
+            _ => {
+                self.push("append_values", &[section, &key], Patch::AppendValues(AppendValuesPatch {
+                    target: self.target.clone(),
+                    section: section.to_string(),
+                    key: key.clone(),
+                    values,
+                    condition: None,
+                }));
+            }
+        }
+    }
+}
+
+/// Lowercase a name and replace anything that isn't alphanumeric with '_'
+fn slugify(input: &str) -> String {
+    let mut slug = String::with_capacity(input.len());
+    for ch in input.chars() {
+        if ch.is_ascii_alphanumeric() {
+            slug.push(ch.to_ascii_lowercase());
+        } else if !slug.ends_with('_') {
+            slug.push('_');
+        }
+    }
+    slug.trim_matches('_').to_string()
+}
+
+/// Get the values of a key, failing for bare keys (no '=') which patches cannot express
+fn key_values<'a>(section: &str, key: &str, values: &'a Option<Vec<String>>) -> anyhow::Result<&'a [String]> {
+    match values {
+        Some(values) if !values.is_empty() => {
+            if values.iter().any(|v| v.contains('{')) {
+                warn!("Key '[{}]{}' contains '{{', which will be treated as a patch variable when applied", section, key);
+            }
+            Ok(values)
+        }
+        _ => anyhow::bail!("Key '[{}]{}' has no value, bare keys cannot be expressed as patches", section, key),
+    }
+}
+
+/// Generate patches for a section that only exists in the modified file
+fn diff_new_section(
+    builder: &mut PatchSetBuilder,
+    section: &str,
+    modified_keys: &IndexMap<String, Option<Vec<String>>>,
+) -> anyhow::Result<()> {
+    let mut keys = IndexMap::new();
+    let mut appends = Vec::new();
+
+    for (key, values) in modified_keys {
+        let values = key_values(section, key, values)?;
+        keys.insert(key.clone(), values[0].clone());
+        if values.len() > 1 {
+            appends.push((key.clone(), values[1..].to_vec()));
+        }
+    }
+
+    builder.push("add_section", &[section], Patch::AddSection(AddSectionPatch {
+        target: builder.target.clone(),
+        section: section.to_string(),
+        keys,
+        on_exists: OnExists::Error,
+        condition: None,
+    }));
+
+    for (key, values) in appends {
+        builder.push_appended_values(section, key, values);
+    }
+
+    Ok(())
+}
+
+/// Generate patches for a section present in both files
+///
+/// Removals are emitted before sets, and sets before appends, so that a repeated key
+/// that was rewritten is first reset to its new first value and then extended.
+fn diff_existing_section(
+    builder: &mut PatchSetBuilder,
+    section: &str,
+    base_keys: &IndexMap<String, Option<Vec<String>>>,
+    modified_keys: &IndexMap<String, Option<Vec<String>>>,
+) -> anyhow::Result<()> {
+    if modified_keys.is_empty() {
+        if !base_keys.is_empty() {
+            builder.push("clear_section", &[section], Patch::ClearSection(ClearSectionPatch {
+                target: builder.target.clone(),
+                section: section.to_string(),
+                condition: None,
+            }));
+        }
+        return Ok(());
+    }
+
+    let removed: Vec<String> = base_keys.keys().filter(|key| !modified_keys.contains_key(*key)).cloned().collect();
+    let mut set = IndexMap::new();
+    let mut appends = Vec::new();
+
+    for (key, values) in modified_keys {
+        let values = key_values(section, key, values)?;
+
+        match base_keys.get(key) {
+            Some(Some(base_values)) if base_values.as_slice() == values => {}
+            Some(Some(base_values)) if !base_values.is_empty() && values.starts_with(base_values) => {
+                // Values were only added to the end of a repeated key
+                appends.push((key.clone(), values[base_values.len()..].to_vec()));
+            }
+            _ => {
+                set.insert(key.clone(), values[0].clone());
+                if values.len() > 1 {
+                    appends.push((key.clone(), values[1..].to_vec()));
+                }
+            }
+        }
+    }
+
+    builder.push_removed_keys(section, removed);
+    builder.push_set_keys(section, set);
+    for (key, values) in appends {
+        builder.push_appended_values(section, key, values);
+    }
+
+    Ok(())
+}
+
+/// Generate the minimal set of patches that turn `base` into `modified`
+///
+/// Applying the returned patches in order to `target` (when it matches `base`) reproduces the keys and
+/// values of `modified`. Key order within a section and comments are not preserved.
+///
+/// # Arguments
+/// * `target` - Resource path the patches should target (e.g. "animals/blckbuck.ai")
+/// * `base` - The original INI file
+/// * `modified` - The edited copy of the INI file
+///
+/// # Returns
+/// * `Ok(IndexMap<String, Patch>)` - Ordered patches, keyed by generated patch name
+/// * `Err(_)` if the modified file contains something patches cannot express (e.g. a key without a value)
+pub fn generate_patches(target: &str, base: &Ini, modified: &Ini) -> anyhow::Result<IndexMap<String, Patch>> {
+    let mut builder = PatchSetBuilder::new(target);
+    let base_map = base.get_map_ref();
+    let modified_map = modified.get_map_ref();
+
+    for section in base_map.keys() {
+        if !modified_map.contains_key(section) {
+            builder.push("remove_section", &[section], Patch::RemoveSection(RemoveSectionPatch {
+                target: target.to_string(),
+                section: section.clone(),
+                condition: None,
+            }));
+        }
+    }
+
+    for (section, modified_keys) in modified_map {
+        match base_map.get(section) {
+            Some(base_keys) => diff_existing_section(&mut builder, section, base_keys, modified_keys)?,
+            None => diff_new_section(&mut builder, section, modified_keys)?,
+        }
+    }
+
+    info!("Generated {} patches for '{}'", builder.patches.len(), target);
+    Ok(builder.patches)
+}
+
+/// Serialize patches as a `[patches]` TOML document that can be dropped into a mod's defs/ folder
+pub fn patches_to_toml(patches: &IndexMap<String, Patch>) -> anyhow::Result<String> {
+    #[derive(Serialize)]
+    struct PatchFile<'a> {
+        patches: &'a IndexMap<String, Patch>,
+    }
+
+    toml::to_string_pretty(&PatchFile { patches }).context("Failed to serialize patches to TOML")
+}
+
+/// Parse INI text the same way the patch engine does
+fn parse_ini(content: String, name: &str) -> anyhow::Result<Ini> {
+    let mut ini = Ini::new_cs();
+    ini.set_comment_symbols(&[';', '#', ':']);
+    ini.read(content)
+        .map_err(|e| anyhow::anyhow!("Failed to parse INI '{}': {}", name, e))?;
+    Ok(ini)
+}
+
+/// Generate patch TOML that turns the currently loaded `target` resource into the file at `modified_path`
+///
+/// # Arguments
+/// * `target` - Resource path of the base file in the resource system
+/// * `modified_path` - Path on disk to the edited copy of the file
+///
+/// # Returns
+/// * `Ok(String)` - `[patches]` TOML document
+/// * `Err(_)` if either file can't be read or parsed, or the diff can't be expressed as patches
+pub fn generate_patches_for_resource(target: &str, modified_path: &Path) -> anyhow::Result<String> {
+    let (_, base_data) = get_file(target)
+        .ok_or_else(|| anyhow::anyhow!("Target file '{}' not found in resource system", target))?;
+    let base = parse_ini(crate::encoding_utils::decode_game_text(&base_data), target)?;
+
+    let modified_data = std::fs::read(modified_path)
+        .with_context(|| format!("Failed to read modified file '{}'", modified_path.display()))?;
+    let modified = parse_ini(crate::encoding_utils::decode_game_text(&modified_data), &modified_path.display().to_string())?;
+
+    let patches = generate_patches(target, &base, &modified)?;
+    patches_to_toml(&patches)
+}
+
+// ============================================================================
+// Unit Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, ffi::CString};
+
+    use super::*;
+    use crate::{
+        mods::ModDefinition,
+        resource_manager::{
+            openzt_mods::patches::{apply_single_patch_shadow, load_ini_from_shadow, ShadowResources, ShadowScope, SubstitutionContext},
+            ztfile::{ZTFile, ZTFileType},
+        },
+    };
+
+    const TARGET: &str = "animals/blckbuck.ai";
+
+    fn ini(content: &str) -> Ini {
+        parse_ini(content.to_string(), "test").unwrap()
+    }
+
+    /// Apply patches to `base` through the shadow patch engine and return the resulting INI
+    fn apply(base: &Ini, patches: &IndexMap<String, Patch>) -> Ini {
+        let mut shadow = ShadowResources::new(&HashSet::new(), ShadowScope::PatchFile).unwrap();
+        let content = base.writes();
+        let content_len = content.len() as u32;
+        shadow.update_file(TARGET, ZTFile::Text(CString::new(content).unwrap(), ZTFileType::Ai, content_len));
+
+        let context = SubstitutionContext {
+            current_mod_id: "test_mod".to_string(),
+        };
+        for (name, patch) in patches {
+            apply_single_patch_shadow(patch, Path::new(""), name, &context, &mut shadow).unwrap();
+        }
+
+        load_ini_from_shadow(TARGET, &shadow).unwrap()
+    }
+
+    fn assert_round_trip(base: &str, modified: &str) -> IndexMap<String, Patch> {
+        let base = ini(base);
+        let modified = ini(modified);
+        let patches = generate_patches(TARGET, &base, &modified).unwrap();
+
+        let result = apply(&base, &patches);
+        assert_eq!(result.get_map_ref(), modified.get_map_ref(), "patched base should match modified file");
+
+        // The TOML output should deserialize back into the same patches
+        let toml_str = patches_to_toml(&patches).unwrap();
+        let mod_def: ModDefinition = toml::from_str(&toml_str).unwrap();
+        let parsed = mod_def.patches().as_ref().expect("patches should be present");
+        assert_eq!(parsed.keys().collect::<Vec<_>>(), patches.keys().collect::<Vec<_>>());
+        let result = apply(&base, parsed);
+        assert_eq!(result.get_map_ref(), modified.get_map_ref(), "patches parsed from TOML should match modified file");
+
+        patches
+    }
+
+    const BASE: &str = "\
+[Characteristics/Integers]
+cPrefIdle = 10
+cHungerThreshold = 50
+cSpeed = 4
+
+[Characteristics/Strings]
+cFoodType = grass
+
+[Animations]
+walk = walk
+run = run
+
+[Members]
+cBiome = 1
+cBiome = 2
+";
+
+    #[test]
+    fn test_identical_files_produce_no_patches() {
+        let patches = assert_round_trip(BASE, BASE);
+        assert!(patches.is_empty());
+    }
+
+    #[test]
+    fn test_single_changed_key_uses_set_key() {
+        let modified = BASE.replace("cSpeed = 4", "cSpeed = 6");
+        let patches = assert_round_trip(BASE, &modified);
+
+        assert_eq!(patches.len(), 1);
+        match patches.get("blckbuck_set_key_characteristics_integers_cspeed") {
+            Some(Patch::SetKey(p)) => {
+                assert_eq!(p.section, "Characteristics/Integers");
+                assert_eq!(p.key, "cSpeed");
+                assert_eq!(p.value, "6");
+            }
+            other => panic!("Expected SetKey patch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_multiple_changed_keys_use_set_keys() {
+        let modified = BASE.replace("cSpeed = 4", "cSpeed = 6").replace("cPrefIdle = 10", "cPrefIdle = 2");
+        let patches = assert_round_trip(BASE, &modified);
+
+        assert_eq!(patches.len(), 1);
+        match patches.values().next() {
+            Some(Patch::SetKeys(p)) => assert_eq!(p.keys.len(), 2),
+            other => panic!("Expected SetKeys patch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_removed_keys() {
+        let modified = BASE.replace("cSpeed = 4\n", "");
+        let patches = assert_round_trip(BASE, &modified);
+        assert!(matches!(patches.values().next(), Some(Patch::RemoveKey(_))));
+
+        let modified = modified.replace("cPrefIdle = 10\n", "");
+        let patches = assert_round_trip(BASE, &modified);
+        assert!(matches!(patches.values().next(), Some(Patch::RemoveKeys(p)) if p.keys.len() == 2));
+    }
+
+    #[test]
+    fn test_added_and_removed_sections() {
+        let modified = BASE.replace("[Animations]\nwalk = walk\nrun = run\n", "") + "\n[NewSection]\nkey = value\nlist = a\nlist = b\n";
+        let patches = assert_round_trip(BASE, &modified);
+
+        assert!(patches.values().any(|p| matches!(p, Patch::RemoveSection(p) if p.section == "Animations")));
+        assert!(patches.values().any(|p| matches!(p, Patch::AddSection(p) if p.section == "NewSection" && p.keys.len() == 2)));
+        assert!(patches.values().any(|p| matches!(p, Patch::AppendValue(p) if p.key == "list" && p.value == "b")));
+    }
+
+    #[test]
+    fn test_emptied_section_is_removed() {
+        // The parser drops sections without keys, so an emptied section reads as a removed one
+        let modified = BASE.replace("walk = walk\nrun = run\n", "");
+        let patches = assert_round_trip(BASE, &modified);
+
+        assert_eq!(patches.len(), 1);
+        assert!(matches!(patches.values().next(), Some(Patch::RemoveSection(p)) if p.section == "Animations"));
+    }
+
+    #[test]
+    fn test_values_appended_to_repeated_key() {
+        let modified = BASE.replace("cBiome = 2\n", "cBiome = 2\ncBiome = 3\ncBiome = 4\n");
+        let patches = assert_round_trip(BASE, &modified);
+
+        assert_eq!(patches.len(), 1);
+        match patches.values().next() {
+            Some(Patch::AppendValues(p)) => assert_eq!(p.values, vec!["3".to_string(), "4".to_string()]),
+            other => panic!("Expected AppendValues patch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rewritten_repeated_key() {
+        // Not a pure append, so the key is reset to its first value and the rest appended
+        let modified = BASE.replace("cBiome = 1\ncBiome = 2\n", "cBiome = 5\ncBiome = 2\ncBiome = 7\n");
+        assert_round_trip(BASE, &modified);
+
+        let modified = BASE.replace("cBiome = 1\ncBiome = 2\n", "cBiome = 2\n");
+        assert_round_trip(BASE, &modified);
+    }
+
+    #[test]
+    fn test_mixed_changes_round_trip() {
+        let modified = "\
+[Characteristics/Integers]
+cPrefIdle = 10
+cHungerThreshold = 75
+cNewKey = 1
+
+[Members]
+cBiome = 1
+cBiome = 2
+cBiome = 9
+
+[Extra]
+a = 1
+";
+        assert_round_trip(BASE, modified);
+    }
+
+    #[test]
+    fn test_bare_key_is_rejected() {
+        let base = ini("[Section]\nkey = value\n");
+        let modified = ini("[Section]\nkey = value\nbare_key\n");
+        let result = generate_patches(TARGET, &base, &modified);
+        assert!(result.unwrap_err().to_string().contains("bare keys"));
+    }
+
+    #[test]
+    fn test_patch_names_are_unique() {
+        let mut builder = PatchSetBuilder::new("ui/My File.ini");
+        let patch = Patch::RemoveSection(RemoveSectionPatch {
+            target: "ui/My File.ini".to_string(),
+            section: "A".to_string(),
+            condition: None,
+        });
+        builder.push("remove_section", &["A B"], patch.clone());
+        builder.push("remove_section", &["a-b"], patch);
+
+        let names: Vec<_> = builder.patches.keys().cloned().collect();
+        assert_eq!(names, vec!["my_file_remove_section_a_b", "my_file_remove_section_a_b_2"]);
+    }
+}
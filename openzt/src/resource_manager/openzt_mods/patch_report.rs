@@ -0,0 +1,353 @@
+use std::{
+    fmt::Write as _,
+    path::PathBuf,
+    sync::{LazyLock, Mutex},
+};
+
+use anyhow::Context;
+use indexmap::IndexMap;
+use serde::Serialize;
+use tracing::info;
+
+use super::patches::{get_patch_condition, get_patch_operation, get_patch_target, ConditionOutcome};
+use crate::mods::Patch;
+
+/// File name of the persisted patch report, written next to openzt.log
+const PATCH_REPORT_FILE_NAME: &str = "openzt_patch_report.json";
+
+/// Final status of a single patch
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PatchStatus {
+    /// Patch was applied and kept
+    Applied,
+
+    /// Patch wasn't applied because its condition, or the file-level condition, failed
+    Skipped,
+
+    /// Patch or its condition returned an error
+    Failed,
+
+    /// Patch was applied, but a patch group or patch file rollback undid it
+    RolledBack,
+}
+
+/// Outcome of a single patch
+#[derive(Serialize, Debug, Clone)]
+pub struct PatchReportEntry {
+    pub name: String,
+    pub operation: String,
+    pub target: String,
+
+    /// Patch group the patch was applied in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+
+    /// Result of the patch's condition, `None` if it has none or it wasn't evaluated
+    pub condition_passed: Option<bool>,
+
+    /// Which check failed, if the patch was skipped by its condition or the file-level condition
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub condition_failure: Option<String>,
+
+    pub status: PatchStatus,
+
+    /// Error followed by its causes, outermost first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub error_chain: Vec<String>,
+}
+
+/// Outcome of applying a patch group
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PatchGroupOutcome {
+    /// Every patch in the group was applied (or skipped by its condition) and kept
+    Committed,
+
+    /// A patch failed, so every change made by the group was rolled back
+    RolledBack { failed_patch: String, error: String },
+
+    /// The group was committed, but the patch file it belongs to was rolled back afterwards
+    Discarded,
+}
+
+/// Result of applying a single patch group
+#[derive(Serialize, Debug, Clone)]
+pub struct PatchGroupReport {
+    pub group: String,
+
+    /// Group this one was applied as a fallback for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_for: Option<String>,
+
+    pub outcome: PatchGroupOutcome,
+
+    /// Patches applied before the group committed or rolled back
+    pub applied_patches: Vec<String>,
+
+    /// Patches skipped because their condition failed
+    pub skipped_patches: Vec<String>,
+}
+
+/// Every patch and patch group outcome for a single mod, in the order they were applied
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ModPatchReport {
+    pub mod_id: String,
+    pub patches: Vec<PatchReportEntry>,
+    pub groups: Vec<PatchGroupReport>,
+}
+
+impl ModPatchReport {
+    /// Number of patches with the given status
+    pub fn count(&self, status: PatchStatus) -> usize {
+        self.patches.iter().filter(|p| p.status == status).count()
+    }
+
+    /// Human readable summary, one line per patch and group
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "Patch report for {}: {} applied, {} skipped, {} failed, {} rolled back\n",
+            self.mod_id,
+            self.count(PatchStatus::Applied),
+            self.count(PatchStatus::Skipped),
+            self.count(PatchStatus::Failed),
+            self.count(PatchStatus::RolledBack),
+        );
+
+        for patch in &self.patches {
+            let status = match patch.status {
+                PatchStatus::Applied => "applied",
+                PatchStatus::Skipped => "skipped",
+                PatchStatus::Failed => "failed",
+                PatchStatus::RolledBack => "rolled back",
+            };
+            let _ = write!(summary, "  [{}] {} ({} {})", status, patch.name, patch.operation, patch.target);
+            if let Some(group) = &patch.group {
+                let _ = write!(summary, " in group {}", group);
+            }
+            if let Some(reason) = &patch.condition_failure {
+                let _ = write!(summary, ": condition failed at {}", reason);
+            }
+            if !patch.error_chain.is_empty() {
+                let _ = write!(summary, ": {}", patch.error_chain.join(": "));
+            }
+            summary.push('\n');
+        }
+
+        for group in &self.groups {
+            let _ = match &group.outcome {
+                PatchGroupOutcome::Committed => writeln!(summary, "  group {}: committed", group.group),
+                PatchGroupOutcome::Discarded => writeln!(summary, "  group {}: committed, then discarded with its patch file", group.group),
+                PatchGroupOutcome::RolledBack { failed_patch, error } => {
+                    writeln!(summary, "  group {}: rolled back at patch '{}': {}", group.group, failed_patch, error)
+                }
+            };
+        }
+
+        summary
+    }
+}
+
+/// Collects patch outcomes while a single patch file is applied
+///
+/// Outcomes are only stored once the file finishes, since a file-level rollback
+/// changes the final status of patches that were already applied.
+#[derive(Default)]
+pub(crate) struct PatchFileReport {
+    pub(crate) patches: Vec<PatchReportEntry>,
+    pub(crate) groups: Vec<PatchGroupReport>,
+}
+
+impl PatchFileReport {
+    /// Record the outcome of a patch
+    ///
+    /// # Arguments
+    /// * `patch_name` - Name of the patch
+    /// * `patch` - The patch itself
+    /// * `group` - Patch group the patch was applied in, if any
+    /// * `condition` - Result of evaluating the condition, `None` if it wasn't evaluated
+    /// * `status` - Status of the patch
+    /// * `error` - Error returned by the patch or its condition
+    pub(crate) fn record(
+        &mut self,
+        patch_name: &str,
+        patch: &Patch,
+        group: Option<&str>,
+        condition: Option<&ConditionOutcome>,
+        status: PatchStatus,
+        error: Option<&anyhow::Error>,
+    ) {
+        self.patches.push(PatchReportEntry {
+            name: patch_name.to_string(),
+            operation: get_patch_operation(patch).to_string(),
+            target: get_patch_target(patch).to_string(),
+            group: group.map(str::to_string),
+            condition_passed: get_patch_condition(patch).as_ref().and(condition.map(ConditionOutcome::passed)),
+            condition_failure: match condition {
+                Some(ConditionOutcome::Failed(reason)) => Some(reason.clone()),
+                _ => None,
+            },
+            status,
+            error_chain: error.map(|e| e.chain().map(|cause| cause.to_string()).collect()).unwrap_or_default(),
+        });
+    }
+
+    /// Mark patches recorded since `start` that were applied as rolled back
+    pub(crate) fn roll_back_since(&mut self, start: usize) {
+        for patch in self.patches[start..].iter_mut().filter(|p| p.status == PatchStatus::Applied) {
+            patch.status = PatchStatus::RolledBack;
+        }
+    }
+
+    /// Store this file's outcomes in the mod's report
+    ///
+    /// # Arguments
+    /// * `mod_id` - Mod the patch file belongs to
+    /// * `file_rolled_back` - Whether the file's shadow was discarded, undoing everything applied
+    pub(crate) fn finish(mut self, mod_id: &str, file_rolled_back: bool) {
+        if file_rolled_back {
+            self.roll_back_since(0);
+            for group in self.groups.iter_mut().filter(|g| g.outcome == PatchGroupOutcome::Committed) {
+                group.outcome = PatchGroupOutcome::Discarded;
+            }
+        }
+
+        let mut reports = PATCH_REPORTS.lock().unwrap();
+        let report = reports.entry(mod_id.to_string()).or_insert_with(|| ModPatchReport {
+            mod_id: mod_id.to_string(),
+            ..Default::default()
+        });
+        report.patches.extend(self.patches);
+        report.groups.extend(self.groups);
+    }
+}
+
+static PATCH_REPORTS: LazyLock<Mutex<IndexMap<String, ModPatchReport>>> = LazyLock::new(|| Mutex::new(IndexMap::new()));
+
+/// Get the patch report for a mod
+///
+/// # Returns
+/// * `Some(ModPatchReport)` - Every patch outcome recorded for the mod
+/// * `None` if the mod hasn't applied any patches
+pub fn get_patch_report(mod_id: &str) -> Option<ModPatchReport> {
+    PATCH_REPORTS.lock().unwrap().get(mod_id).cloned()
+}
+
+/// Get the patch reports for every mod, in load order
+pub fn get_patch_reports() -> Vec<ModPatchReport> {
+    PATCH_REPORTS.lock().unwrap().values().cloned().collect()
+}
+
+/// Path of the persisted patch report
+pub fn get_patch_report_path() -> PathBuf {
+    crate::util::get_base_path().join(PATCH_REPORT_FILE_NAME)
+}
+
+/// Write every mod's patch report to `openzt_patch_report.json`
+pub fn save_patch_report() -> anyhow::Result<()> {
+    let path = get_patch_report_path();
+    let json = serde_json::to_string_pretty(&get_patch_reports()).context("Failed to serialize patch report")?;
+    std::fs::write(&path, json).with_context(|| format!("Failed to write patch report to {}", path.display()))?;
+
+    info!("Saved patch report to {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mods::{KeyCheck, PatchCondition, SetKeyPatch};
+
+    fn set_key(condition: Option<PatchCondition>) -> Patch {
+        Patch::SetKey(SetKeyPatch {
+            target: "animals/blckbuck.ai".to_string(),
+            section: "Section".to_string(),
+            key: "Key".to_string(),
+            value: "Value".to_string(),
+            condition,
+        })
+    }
+
+    #[test]
+    fn test_record_entry_fields() {
+        let mut report = PatchFileReport::default();
+        let error = anyhow::anyhow!("root cause").context("outer error");
+        report.record("p1", &set_key(None), Some("group"), Some(&ConditionOutcome::Passed), PatchStatus::Failed, Some(&error));
+
+        let entry = &report.patches[0];
+        assert_eq!(entry.name, "p1");
+        assert_eq!(entry.operation, "set_key");
+        assert_eq!(entry.target, "animals/blckbuck.ai");
+        assert_eq!(entry.group, Some("group".to_string()));
+        assert_eq!(entry.condition_passed, None, "Patches without a condition have no condition result");
+        assert_eq!(entry.error_chain, vec!["outer error".to_string(), "root cause".to_string()]);
+
+        let condition = PatchCondition {
+            key_exists: Some(KeyCheck {
+                section: "Section".to_string(),
+                key: "Key".to_string(),
+            }),
+            ..Default::default()
+        };
+        let outcome = ConditionOutcome::Failed("key_exists: key '[Section]Key' does not exist".to_string());
+        report.record("p2", &set_key(Some(condition)), None, Some(&outcome), PatchStatus::Skipped, None);
+        assert_eq!(report.patches[1].condition_passed, Some(false));
+        assert_eq!(report.patches[1].condition_failure, Some("key_exists: key '[Section]Key' does not exist".to_string()));
+        assert!(report.patches[1].error_chain.is_empty());
+    }
+
+    #[test]
+    fn test_file_rollback_updates_statuses() {
+        let mod_id = "test.patch_report.rollback";
+        let mut report = PatchFileReport::default();
+        report.record("applied", &set_key(None), None, Some(&ConditionOutcome::Passed), PatchStatus::Applied, None);
+        report.record("skipped", &set_key(None), None, None, PatchStatus::Skipped, None);
+        report.groups.push(PatchGroupReport {
+            group: "group".to_string(),
+            fallback_for: None,
+            outcome: PatchGroupOutcome::Committed,
+            applied_patches: vec!["applied".to_string()],
+            skipped_patches: Vec::new(),
+        });
+        report.finish(mod_id, true);
+
+        let stored = get_patch_report(mod_id).expect("report should be stored");
+        assert_eq!(stored.patches[0].status, PatchStatus::RolledBack);
+        assert_eq!(stored.patches[1].status, PatchStatus::Skipped);
+        assert_eq!(stored.groups[0].outcome, PatchGroupOutcome::Discarded);
+        assert!(stored.summary().contains("0 applied, 1 skipped, 0 failed, 1 rolled back"));
+    }
+
+    #[test]
+    fn test_reports_accumulate_per_mod() {
+        let mod_id = "test.patch_report.accumulate";
+        for name in ["first", "second"] {
+            let mut report = PatchFileReport::default();
+            report.record(name, &set_key(None), None, Some(&ConditionOutcome::Passed), PatchStatus::Applied, None);
+            report.finish(mod_id, false);
+        }
+
+        let stored = get_patch_report(mod_id).expect("report should be stored");
+        let names: Vec<_> = stored.patches.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["first", "second"]);
+        assert!(get_patch_report("test.patch_report.missing").is_none());
+    }
+
+    #[test]
+    fn test_report_json_format() {
+        let mut report = ModPatchReport {
+            mod_id: "test.mod".to_string(),
+            ..Default::default()
+        };
+        let mut file_report = PatchFileReport::default();
+        file_report.record("p1", &set_key(None), None, None, PatchStatus::Failed, Some(&anyhow::anyhow!("boom")));
+        report.patches = file_report.patches;
+
+        let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["mod_id"], "test.mod");
+        assert_eq!(json["patches"][0]["status"], "failed");
+        assert_eq!(json["patches"][0]["operation"], "set_key");
+        assert_eq!(json["patches"][0]["error_chain"][0], "boom");
+        assert!(json["patches"][0].get("group").is_none());
+    }
+}
@@ -4,6 +4,7 @@ use std::str;
 
 use anyhow::{self, Context};
 use openzt_configparser::ini::{Ini, MergeMode as IniMergeMode};
+use regex::Regex;
 use tracing::{error, info, warn};
 
 use crate::{
@@ -17,9 +18,11 @@ use crate::{
         ErrorHandling,
         MergeMode,
         MergePatch,
+        NumericCheck,
         OnExists,
         Patch,
         PatchCondition,
+        PatchGroup,
         PatchMeta,
         RemoveKeyPatch,
         RemoveKeysPatch,
@@ -31,7 +34,15 @@ use crate::{
     },
     resource_manager::{
         lazyresourcemap::{add_ztfile, check_file, get_file, remove_resource},
-        openzt_mods::{get_mod_ids, habitats_locations::{get_habitat_id, get_location_id}, legacy_attributes::{get_legacy_attribute_with_subtype, LegacyEntityType}},
+        mod_config::get_openzt_config,
+        openzt_mods::{
+            get_mod_ids,
+            habitats_locations::{get_habitat_id, get_location_id},
+            legacy_attributes::{get_legacy_attribute_with_subtype, legacy_entity_exists, LegacyEntityType},
+            loading::get_mod_version,
+            mod_options::{format_option_value, get_mod_option, get_mod_option_declarations, parse_option_value},
+            patch_report::{PatchFileReport, PatchGroupOutcome, PatchGroupReport, PatchStatus},
+        },
         ztfile::{modify_ztfile_as_animation, ZTFile, ZTFileType},
     },
     string_registry::get_string_from_registry,
@@ -48,6 +59,7 @@ enum VariableType {
     Location,
     String,
     Legacy,  // NEW: Legacy Zoo Tycoon entity attributes
+    Option,
 }
 
 /// Parsed variable reference from {variable} syntax
@@ -86,6 +98,7 @@ pub struct SubstitutionContext {
 /// * "habitat.swamp" → ParsedVariable { var_type: Habitat, mod_id: None, identifier: "swamp" }
 /// * "lunar.location.moon" → ParsedVariable { var_type: Location, mod_id: Some("lunar"), identifier: "moon" }
 /// * "string.9500" → ParsedVariable { var_type: String, mod_id: None, identifier: "9500" }
+/// * "option.difficulty" → ParsedVariable { var_type: Option, mod_id: None, identifier: "difficulty" }
 fn parse_variable(var_str: &str) -> anyhow::Result<ParsedVariable> {
     let parts: Vec<&str> = var_str.split('.').collect();
 
@@ -96,7 +109,8 @@ fn parse_variable(var_str: &str) -> anyhow::Result<ParsedVariable> {
                 "habitat" => VariableType::Habitat,
                 "location" => VariableType::Location,
                 "string" => VariableType::String,
-                _ => anyhow::bail!("Invalid variable type '{}': expected 'habitat', 'location', or 'string'", parts[0]),
+                "option" => VariableType::Option,
+                _ => anyhow::bail!("Invalid variable type '{}': expected 'habitat', 'location', 'string', or 'option'", parts[0]),
             };
 
             Ok(ParsedVariable {
@@ -127,7 +141,8 @@ fn parse_variable(var_str: &str) -> anyhow::Result<ParsedVariable> {
                     "habitat" => VariableType::Habitat,
                     "location" => VariableType::Location,
                     "string" => VariableType::String,
-                    _ => anyhow::bail!("Invalid variable type '{}': expected 'habitat', 'location', or 'string'", parts[1]),
+                    "option" => VariableType::Option,
+                    _ => anyhow::bail!("Invalid variable type '{}': expected 'habitat', 'location', 'string', or 'option'", parts[1]),
                 };
 
                 Ok(ParsedVariable {
@@ -248,6 +263,11 @@ fn resolve_variable(var: &ParsedVariable, context: &SubstitutionContext) -> anyh
             get_string_from_registry(string_id)
                 .map_err(|_| anyhow::anyhow!("String ID {} not found in registry", string_id))
         }
+        VariableType::Option => {
+            let mod_id = var.mod_id.as_deref().unwrap_or(&context.current_mod_id);
+
+            get_mod_option(mod_id, &var.identifier).map(|value| format_option_value(&value))
+        }
         VariableType::Legacy => {
             // NEW: Resolve legacy entity attribute
             let parts = var.legacy_parts.as_ref()
@@ -356,6 +376,16 @@ pub struct ShadowResources {
 
     /// Scope of this shadow (for logging)
     scope: ShadowScope,
+
+    /// Nested savepoints, innermost last
+    savepoints: Vec<ShadowSavepoint>,
+}
+
+/// Snapshot of a shadow's state, restored when its savepoint is rolled back
+struct ShadowSavepoint {
+    files: HashMap<String, ZTFile>,
+    new_files: HashSet<String>,
+    deleted_files: HashSet<String>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -365,6 +395,9 @@ pub enum ShadowScope {
 
     /// Shadow for entire mod
     Mod,
+
+    /// Shadow for a single patch group
+    PatchGroup,
 }
 
 impl ShadowResources {
@@ -416,6 +449,7 @@ impl ShadowResources {
             new_files,
             deleted_files: HashSet::new(),
             scope,
+            savepoints: Vec::new(),
         })
     }
 
@@ -499,6 +533,49 @@ impl ShadowResources {
         self.files.contains_key(path) || check_file(path)
     }
 
+    /// Start a nested savepoint
+    ///
+    /// Changes made after this call can be undone with `rollback_savepoint` without
+    /// losing earlier changes to the shadow.
+    pub fn savepoint(&mut self) {
+        self.savepoints.push(ShadowSavepoint {
+            files: self.files.clone(),
+            new_files: self.new_files.clone(),
+            deleted_files: self.deleted_files.clone(),
+        });
+    }
+
+    /// Undo every change made since the most recent savepoint, and remove that savepoint
+    ///
+    /// # Returns
+    /// * `Ok(())` if the shadow was restored
+    /// * `Err(_)` if there is no open savepoint
+    pub fn rollback_savepoint(&mut self) -> anyhow::Result<()> {
+        let savepoint = self.savepoints.pop()
+            .ok_or_else(|| anyhow::anyhow!("No savepoint to roll back to"))?;
+
+        self.files = savepoint.files;
+        self.new_files = savepoint.new_files;
+        self.deleted_files = savepoint.deleted_files;
+        Ok(())
+    }
+
+    /// Keep every change made since the most recent savepoint, and remove that savepoint
+    ///
+    /// # Returns
+    /// * `Ok(())` if the savepoint was released
+    /// * `Err(_)` if there is no open savepoint
+    pub fn release_savepoint(&mut self) -> anyhow::Result<()> {
+        self.savepoints.pop()
+            .map(|_| ())
+            .ok_or_else(|| anyhow::anyhow!("No savepoint to release"))
+    }
+
+    /// Number of open savepoints
+    pub fn savepoint_depth(&self) -> usize {
+        self.savepoints.len()
+    }
+
     /// Commit shadow to main resource system (success case)
     ///
     /// This writes all shadow files to the main resource system.
@@ -552,7 +629,7 @@ impl ShadowResources {
 /// # Returns
 /// * `Ok(Ini)` - Parsed INI file
 /// * `Err(_)` if file not found or not parseable as INI
-fn load_ini_from_shadow(path: &str, shadow: &ShadowResources) -> anyhow::Result<Ini> {
+pub(crate) fn load_ini_from_shadow(path: &str, shadow: &ShadowResources) -> anyhow::Result<Ini> {
     let file = shadow.get_file(path)
         .ok_or_else(|| anyhow::anyhow!("File '{}' not found", path))?;
 
@@ -1736,7 +1813,7 @@ fn apply_single_patch_direct(
 /// # Returns
 /// * `Ok(())` if the patch was applied successfully
 /// * `Err(_)` if the patch failed
-fn apply_single_patch_shadow(
+pub(crate) fn apply_single_patch_shadow(
     patch: &Patch,
     mod_path: &Path,
     patch_name: &str,
@@ -1776,95 +1853,269 @@ fn is_mod_loaded(mod_id: &str) -> bool {
     loaded_mods.iter().any(|id| id == mod_id)
 }
 
-/// Evaluate patch-level conditions that require target file access
+/// Result of evaluating a patch condition
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ConditionOutcome {
+    Passed,
+
+    /// The condition failed, with a description of the check that failed (e.g. "any[1].mod_loaded: ...")
+    Failed(String),
+}
+
+impl ConditionOutcome {
+    pub(crate) fn passed(&self) -> bool {
+        matches!(self, ConditionOutcome::Passed)
+    }
+}
+
+/// Evaluate a patch condition, including any nested all/any/not conditions
 ///
 /// # Arguments
 /// * `condition` - The condition to evaluate
 /// * `default_target` - Default target file path (used if condition.target is not specified)
 /// * `patch_name` - Name of the patch (for logging)
+/// * `context` - Current mod context, used by `option_equals`
 ///
 /// # Returns
-/// * `Ok(true)` if all conditions pass
-/// * `Ok(false)` if any condition fails
-/// * `Err(_)` if there's an error evaluating conditions
+/// * `Ok(ConditionOutcome::Passed)` if there is no condition or every check passes
+/// * `Ok(ConditionOutcome::Failed(_))` describing the first check that failed
+/// * `Err(_)` if the condition can't be evaluated (e.g. invalid regex or missing target)
 fn evaluate_patch_condition_with_target(
     condition: &Option<PatchCondition>,
     default_target: &str,
     patch_name: &str,
-) -> anyhow::Result<bool> {
+    context: &SubstitutionContext,
+) -> anyhow::Result<ConditionOutcome> {
     let Some(cond) = condition else {
-        return Ok(true);
+        return Ok(ConditionOutcome::Passed);
     };
 
-    // Use condition.target if specified, otherwise use default_target
+    let outcome = evaluate_condition_tree(cond, default_target, "", patch_name, context)?;
+    if let ConditionOutcome::Failed(reason) = &outcome {
+        info!("Patch '{}': condition failed - {}", patch_name, reason);
+    }
+    Ok(outcome)
+}
+
+/// Join a condition path and a check name for failure messages (e.g. "any[1]" + "mod_loaded")
+fn condition_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+/// Evaluate a single level of a condition tree
+///
+/// Checks are evaluated in a fixed order and the first failure is returned. Nested
+/// conditions inherit this level's target unless they set their own.
+///
+/// # Arguments
+/// * `cond` - The condition to evaluate
+/// * `default_target` - Target inherited from the patch or parent condition
+/// * `path` - Path of this condition in the tree, used in failure messages
+/// * `patch_name` - Name of the patch (for logging)
+/// * `context` - Current mod context, used by `option_equals`
+fn evaluate_condition_tree(
+    cond: &PatchCondition,
+    default_target: &str,
+    path: &str,
+    patch_name: &str,
+    context: &SubstitutionContext,
+) -> anyhow::Result<ConditionOutcome> {
     let target = cond.target.as_deref().unwrap_or(default_target);
+    let fail = |name: &str, reason: String| Ok(ConditionOutcome::Failed(format!("{}: {}", condition_path(path, name), reason)));
 
-    // First check mod_loaded condition (doesn't require target)
+    // Checks that don't need the target file
     if let Some(required_mod) = &cond.mod_loaded {
         if !is_mod_loaded(required_mod) {
-            info!("Patch '{}': skipping - required mod '{}' not loaded", patch_name, required_mod);
-            return Ok(false);
+            return fail("mod_loaded", format!("mod '{}' is not loaded", required_mod));
         }
     }
 
-    // Check key_exists condition
-    if let Some(key_check) = &cond.key_exists {
-        // Check if target file exists
-        if !check_file(target) {
-            warn!("Patch '{}': cannot evaluate key_exists condition - target file '{}' not found",
-                  patch_name, target);
-            return Ok(false);
+    if let Some(check) = &cond.mod_version {
+        match get_mod_version(&check.mod_id) {
+            None => return fail("mod_version", format!("mod '{}' is not loaded", check.mod_id)),
+            Some(loaded) if !check.op.compare(&loaded, &check.version) => {
+                return fail("mod_version", format!("mod '{}' version {} is not {} {}", check.mod_id, loaded, check.op, check.version));
+            }
+            Some(_) => {}
         }
+    }
 
-        // Try to load as INI file
-        match load_ini_from_resources(target) {
-            Ok(ini) => {
-                if ini.get(&key_check.section, &key_check.key).is_none() {
-                    info!("Patch '{}': skipping - key '[{}]{}' does not exist in '{}'",
-                          patch_name, key_check.section, key_check.key, target);
-                    return Ok(false);
-                }
+    if let Some(file) = &cond.file_exists {
+        if !check_file(file) {
+            return fail("file_exists", format!("file '{}' not found", file));
+        }
+    }
+
+    if let Some(check) = &cond.entity_exists {
+        let entity_type = check.entity_type.parse::<LegacyEntityType>()
+            .with_context(|| format!("Invalid entity type in {}", condition_path(path, "entity_exists")))?;
+        if !legacy_entity_exists(entity_type, &check.name) {
+            return fail("entity_exists", format!("legacy entity '{}.{}' not found", check.entity_type, check.name));
+        }
+    }
+
+    if let Some(check) = &cond.setting_equals {
+        let actual = get_openzt_setting(&check.setting)?;
+        if actual.as_deref() != Some(check.value.as_str()) {
+            return fail("setting_equals", format!("setting '{}' is {:?}, expected '{}'", check.setting, actual, check.value));
+        }
+    }
+
+    if let Some(check) = &cond.option_equals {
+        let mod_id = check.mod_id.as_deref().unwrap_or(&context.current_mod_id);
+        let option = get_mod_option_declarations(mod_id)
+            .and_then(|options| options.get(&check.option).cloned())
+            .ok_or_else(|| anyhow::anyhow!("{}: mod '{}' has no option '{}'", condition_path(path, "option_equals"), mod_id, check.option))?;
+        let expected = parse_option_value(&check.option, &option, &check.value)
+            .with_context(|| format!("Invalid value in {}", condition_path(path, "option_equals")))?;
+        let actual = get_mod_option(mod_id, &check.option)?;
+        if actual != expected {
+            return fail(
+                "option_equals",
+                format!("option '{}.{}' is '{}', expected '{}'", mod_id, check.option, format_option_value(&actual), check.value),
+            );
+        }
+    }
+
+    // Checks on the target file
+    let needs_target = cond.key_exists.is_some()
+        || cond.value_equals.is_some()
+        || cond.value_matches.is_some()
+        || cond.value_gt.is_some()
+        || cond.value_lt.is_some();
+
+    if needs_target {
+        if target.is_empty() {
+            let location = if path.is_empty() { "Top-level condition" } else { path };
+            return Err(anyhow::anyhow!("{} with key_exists/value_* checks requires 'target' field", location));
+        }
+
+        let ini = match load_condition_target(target) {
+            Ok(ini) => ini,
+            Err(reason) => {
+                warn!("Patch '{}': cannot evaluate condition - {}", patch_name, reason);
+                return fail("target", reason);
             }
-            Err(e) => {
-                warn!("Patch '{}': cannot evaluate key_exists condition - failed to load target '{}': {}",
-                      patch_name, target, e);
-                return Ok(false);
+        };
+
+        if let Some(check) = &cond.key_exists {
+            if ini.get(&check.section, &check.key).is_none() {
+                return fail("key_exists", format!("key '[{}]{}' does not exist in '{}'", check.section, check.key, target));
+            }
+        }
+
+        if let Some(check) = &cond.value_equals {
+            let actual = ini.get(&check.section, &check.key);
+            if actual.as_deref() != Some(check.value.as_str()) {
+                return fail("value_equals", format!("key '[{}]{}' is {:?}, expected '{}'", check.section, check.key, actual, check.value));
+            }
+        }
+
+        if let Some(check) = &cond.value_matches {
+            let regex = Regex::new(&check.value)
+                .with_context(|| format!("Invalid regex in {}", condition_path(path, "value_matches")))?;
+            let actual = ini.get(&check.section, &check.key);
+            if !actual.as_deref().is_some_and(|value| regex.is_match(value)) {
+                return fail("value_matches", format!("key '[{}]{}' is {:?}, which does not match '{}'", check.section, check.key, actual, check.value));
+            }
+        }
+
+        if let Some(check) = &cond.value_gt {
+            match read_numeric_value(&ini, check) {
+                Ok(actual) if actual > check.value => {}
+                Ok(actual) => return fail("value_gt", format!("key '[{}]{}' is {}, not greater than {}", check.section, check.key, actual, check.value)),
+                Err(reason) => return fail("value_gt", reason),
+            }
+        }
+
+        if let Some(check) = &cond.value_lt {
+            match read_numeric_value(&ini, check) {
+                Ok(actual) if actual < check.value => {}
+                Ok(actual) => return fail("value_lt", format!("key '[{}]{}' is {}, not less than {}", check.section, check.key, actual, check.value)),
+                Err(reason) => return fail("value_lt", reason),
             }
         }
     }
 
-    // Check value_equals condition
-    if let Some(value_check) = &cond.value_equals {
-        // Check if target file exists
-        if !check_file(target) {
-            warn!("Patch '{}': cannot evaluate value_equals condition - target file '{}' not found",
-                  patch_name, target);
-            return Ok(false);
+    // Nested conditions
+    if let Some(children) = &cond.all {
+        for (i, child) in children.iter().enumerate() {
+            let outcome = evaluate_condition_tree(child, target, &condition_path(path, &format!("all[{}]", i)), patch_name, context)?;
+            if !outcome.passed() {
+                return Ok(outcome);
+            }
         }
+    }
 
-        // Try to load as INI file
-        match load_ini_from_resources(target) {
-            Ok(ini) => {
-                let actual_value = ini.get(&value_check.section, &value_check.key);
-                if actual_value.as_deref() != Some(&value_check.value) {
-                    info!("Patch '{}': skipping - key '[{}]{}' value does not equal '{}' (actual: {:?})",
-                          patch_name, value_check.section, value_check.key, value_check.value, actual_value);
-                    return Ok(false);
+    if let Some(children) = &cond.any {
+        let mut reasons = Vec::new();
+        for (i, child) in children.iter().enumerate() {
+            match evaluate_condition_tree(child, target, &condition_path(path, &format!("any[{}]", i)), patch_name, context)? {
+                ConditionOutcome::Passed => {
+                    reasons.clear();
+                    break;
                 }
+                ConditionOutcome::Failed(reason) => reasons.push(reason),
             }
-            Err(e) => {
-                warn!("Patch '{}': cannot evaluate value_equals condition - failed to load target '{}': {}",
-                      patch_name, target, e);
-                return Ok(false);
-            }
+        }
+        if !reasons.is_empty() || children.is_empty() {
+            return fail("any", format!("no nested condition passed ({})", reasons.join("; ")));
         }
     }
 
-    Ok(true)
+    if let Some(child) = &cond.not {
+        if evaluate_condition_tree(child, target, &condition_path(path, "not"), patch_name, context)?.passed() {
+            return fail("not", "nested condition passed".to_string());
+        }
+    }
+
+    Ok(ConditionOutcome::Passed)
+}
+
+/// Load the target file of a condition, or describe why it can't be checked
+fn load_condition_target(target: &str) -> Result<Ini, String> {
+    if !check_file(target) {
+        return Err(format!("target file '{}' not found", target));
+    }
+    load_ini_from_resources(target).map_err(|e| format!("failed to load target '{}': {}", target, e))
+}
+
+/// Read a key as a number for value_gt/value_lt, or describe why it can't be compared
+fn read_numeric_value(ini: &Ini, check: &NumericCheck) -> Result<f64, String> {
+    let actual = ini.get(&check.section, &check.key)
+        .ok_or_else(|| format!("key '[{}]{}' does not exist", check.section, check.key))?;
+    actual.trim().parse::<f64>()
+        .map_err(|_| format!("key '[{}]{}' value '{}' is not a number", check.section, check.key, actual))
+}
+
+/// Look up a setting in openzt.toml by its dotted path (e.g. "resource_cache.enabled")
+///
+/// # Returns
+/// * `Ok(Some(String))` - The setting's value, strings unquoted
+/// * `Ok(None)` if the setting doesn't exist
+fn get_openzt_setting(setting: &str) -> anyhow::Result<Option<String>> {
+    let config = toml::Value::try_from(get_openzt_config()).context("Failed to read openzt.toml settings")?;
+
+    let mut value = &config;
+    for part in setting.split('.') {
+        match value.get(part) {
+            Some(next) => value = next,
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(match value {
+        toml::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }))
 }
 
 /// Get the target file path from a patch (for condition evaluation)
-fn get_patch_target(patch: &Patch) -> &str {
+pub(crate) fn get_patch_target(patch: &Patch) -> &str {
     match patch {
         Patch::Replace(p) => &p.target,
         Patch::Merge(p) => &p.target,
@@ -1883,7 +2134,7 @@ fn get_patch_target(patch: &Patch) -> &str {
 }
 
 /// Get the condition from a patch (for condition evaluation)
-fn get_patch_condition(patch: &Patch) -> &Option<PatchCondition> {
+pub(crate) fn get_patch_condition(patch: &Patch) -> &Option<PatchCondition> {
     match patch {
         Patch::Replace(p) => &p.condition,
         Patch::Merge(p) => &p.condition,
@@ -1901,12 +2152,38 @@ fn get_patch_condition(patch: &Patch) -> &Option<PatchCondition> {
     }
 }
 
-/// Result of applying a single patch
-#[derive(Debug, Clone, PartialEq)]
-enum PatchResult {
-    Success,
-    Skipped,           // Condition failed or warning situation
-    Error(String),     // Error occurred
+/// Get the operation name of a patch, as written in `operation = "..."`
+pub(crate) fn get_patch_operation(patch: &Patch) -> &'static str {
+    match patch {
+        Patch::Replace(_) => "replace",
+        Patch::Merge(_) => "merge",
+        Patch::Delete(_) => "delete",
+        Patch::SetPalette(_) => "set_palette",
+        Patch::SetKey(_) => "set_key",
+        Patch::SetKeys(_) => "set_keys",
+        Patch::AppendValue(_) => "append_value",
+        Patch::AppendValues(_) => "append_values",
+        Patch::RemoveKey(_) => "remove_key",
+        Patch::RemoveKeys(_) => "remove_keys",
+        Patch::AddSection(_) => "add_section",
+        Patch::ClearSection(_) => "clear_section",
+        Patch::RemoveSection(_) => "remove_section",
+    }
+}
+
+/// Record every patch in a file as skipped because the file-level condition failed
+fn finish_skipped_patch_file(
+    patches: &indexmap::IndexMap<String, Patch>,
+    plan: &PatchGroupPlan,
+    mut report: PatchFileReport,
+    mod_id: &str,
+    reason: &str,
+) {
+    let outcome = ConditionOutcome::Failed(format!("patch_meta.condition.{}", reason));
+    for (patch_name, patch) in patches {
+        report.record(patch_name, patch, plan.group_of(patch_name), Some(&outcome), PatchStatus::Skipped, None);
+    }
+    report.finish(mod_id, false);
 }
 
 /// Apply patches directly without shadow (continue mode)
@@ -1917,14 +2194,16 @@ enum PatchResult {
 /// # Arguments
 /// * `patch_meta` - Patch metadata containing error handling and file-level conditions
 /// * `patches` - Ordered map of patches to apply (order is preserved via IndexMap)
+/// * `patch_groups` - Optional patch groups, each applied atomically through its own shadow
 /// * `mod_path` - Path to the current mod being loaded
 /// * `current_mod_id` - The ID of the current mod (for variable substitution)
 ///
 /// # Returns
-/// * `Ok(())` always (errors are logged but don't stop execution)
+/// * `Ok(())` unless a patch group with on_error=abort/abort_mod fails (other errors are logged)
 fn apply_patches_direct(
     patch_meta: &PatchMeta,
     patches: &indexmap::IndexMap<String, Patch>,
+    patch_groups: Option<&indexmap::IndexMap<String, PatchGroup>>,
     mod_path: &Path,
     current_mod_id: &str,
 ) -> anyhow::Result<()> {
@@ -1937,60 +2216,73 @@ fn apply_patches_direct(
     info!("Applying patch file with {} patches (on_error: continue)",
           patches.len());
 
-    // Evaluate top-level conditions
+    let mut plan = PatchGroupPlan::new(patch_groups, patches)?;
+    let mut report = PatchFileReport::default();
+
+    // Evaluate top-level conditions (file-based checks need an explicit target)
     if let Some(top_level_condition) = &patch_meta.condition {
-        // Check mod_loaded at file level
-        if let Some(required_mod) = &top_level_condition.mod_loaded {
-            if !is_mod_loaded(required_mod) {
-                warn!("Patch file skipped - required mod '{}' not loaded", required_mod);
-                return Ok(());
-            }
+        let target = top_level_condition.target.as_deref().unwrap_or("");
+        if let ConditionOutcome::Failed(reason) = evaluate_condition_tree(top_level_condition, target, "", "top-level", &context)? {
+            warn!("Patch file skipped - top-level condition failed: {}", reason);
+            finish_skipped_patch_file(patches, &plan, report, current_mod_id, &reason);
+            return Ok(());
         }
+    }
 
-        // Check key_exists and value_equals with target
-        if top_level_condition.key_exists.is_some() || top_level_condition.value_equals.is_some() {
-            let Some(target) = &top_level_condition.target else {
-                return Err(anyhow::anyhow!(
-                    "Top-level condition with key_exists/value_equals requires 'target' field"
-                ));
-            };
+    // Apply patches in order
+    for (patch_name, patch) in patches {
+        if let Some(group_name) = plan.group_of(patch_name) {
+            if !plan.start(group_name) {
+                continue;
+            }
 
-            // Use existing evaluation function with target
-            if !evaluate_patch_condition_with_target(&Some(top_level_condition.clone()), target, "top-level")? {
-                warn!("Patch file skipped - top-level conditions failed");
-                return Ok(());
+            // Groups get their own shadow so they stay atomic even though this file is applied directly.
+            // Nothing is cloned up front; the shadow falls back to main resources for reads.
+            let mut group_shadow = ShadowResources::new(&HashSet::new(), ShadowScope::PatchGroup)?;
+            match apply_patch_group(group_name, None, &plan, patches, mod_path, &context, &mut group_shadow, &mut report) {
+                Ok(_) => group_shadow.commit()?,
+                Err(e) => {
+                    group_shadow.discard();
+                    report.finish(current_mod_id, false);
+                    return Err(e);
+                }
             }
+            continue;
         }
-    }
 
-    // Apply patches in order
-    for (patch_name, patch) in patches {
         info!("Processing patch '{}'", patch_name);
 
         // Evaluate patch-level conditions
         let target = get_patch_target(patch);
         let condition = get_patch_condition(patch);
 
-        match evaluate_patch_condition_with_target(condition, target, patch_name) {
-            Ok(true) => {
+        match evaluate_patch_condition_with_target(condition, target, patch_name, &context) {
+            Ok(outcome @ ConditionOutcome::Passed) => {
                 // Condition passed, apply patch
                 let result = apply_single_patch_direct(patch, mod_path, patch_name, &context);
 
-                if let Err(e) = result {
-                    error!("Patch '{}' failed: {}. Continuing.", patch_name, e);
+                match result {
+                    Ok(()) => report.record(patch_name, patch, None, Some(&outcome), PatchStatus::Applied, None),
+                    Err(e) => {
+                        error!("Patch '{}' failed: {}. Continuing.", patch_name, e);
+                        report.record(patch_name, patch, None, Some(&outcome), PatchStatus::Failed, Some(&e));
+                    }
                 }
             }
-            Ok(false) => {
+            Ok(outcome @ ConditionOutcome::Failed(_)) => {
                 // Condition failed, skip patch
                 info!("Patch '{}': skipping (condition failed)", patch_name);
+                report.record(patch_name, patch, None, Some(&outcome), PatchStatus::Skipped, None);
             }
             Err(e) => {
                 // Error evaluating condition
                 error!("Patch '{}': error evaluating condition: {}. Continuing.", patch_name, e);
+                report.record(patch_name, patch, None, None, PatchStatus::Failed, Some(&e));
             }
         }
     }
 
+    report.finish(current_mod_id, false);
     info!("Patch application complete (continue mode)");
     Ok(())
 }
@@ -2004,15 +2296,18 @@ fn apply_patches_direct(
 /// # Arguments
 /// * `patch_meta` - Patch metadata containing error handling and file-level conditions
 /// * `patches` - Ordered map of patches to apply (order is preserved via IndexMap)
+/// * `patch_groups` - Optional patch groups, each applied inside a savepoint of the file's shadow
 /// * `mod_path` - Path to the current mod being loaded
 /// * `current_mod_id` - The ID of the current mod (for variable substitution)
 ///
 /// # Returns
 /// * `Ok(())` if all patches succeeded and shadow was committed
-/// * `Err(_)` if any patch failed (shadow is automatically discarded)
+/// * `Err(_)` if any ungrouped patch failed, or a group failed with on_error=abort/abort_mod
+///   (shadow is automatically discarded)
 fn apply_patches_with_shadow(
     patch_meta: &PatchMeta,
     patches: &indexmap::IndexMap<String, Patch>,
+    patch_groups: Option<&indexmap::IndexMap<String, PatchGroup>>,
     mod_path: &Path,
     current_mod_id: &str,
 ) -> anyhow::Result<()> {
@@ -2024,29 +2319,16 @@ fn apply_patches_with_shadow(
     info!("Applying patch file with {} patches (on_error: {:?})",
           patches.len(), patch_meta.on_error);
 
-    // Evaluate top-level conditions
-    if let Some(top_level_condition) = &patch_meta.condition {
-        // Check mod_loaded at file level
-        if let Some(required_mod) = &top_level_condition.mod_loaded {
-            if !is_mod_loaded(required_mod) {
-                warn!("Patch file skipped - required mod '{}' not loaded", required_mod);
-                return Ok(());
-            }
-        }
-
-        // Check key_exists and value_equals with target
-        if top_level_condition.key_exists.is_some() || top_level_condition.value_equals.is_some() {
-            let Some(target) = &top_level_condition.target else {
-                return Err(anyhow::anyhow!(
-                    "Top-level condition with key_exists/value_equals requires 'target' field"
-                ));
-            };
+    let mut plan = PatchGroupPlan::new(patch_groups, patches)?;
+    let mut report = PatchFileReport::default();
 
-            // Use existing evaluation function with target
-            if !evaluate_patch_condition_with_target(&Some(top_level_condition.clone()), target, "top-level")? {
-                warn!("Patch file skipped - top-level conditions failed");
-                return Ok(());
-            }
+    // Evaluate top-level conditions (file-based checks need an explicit target)
+    if let Some(top_level_condition) = &patch_meta.condition {
+        let target = top_level_condition.target.as_deref().unwrap_or("");
+        if let ConditionOutcome::Failed(reason) = evaluate_condition_tree(top_level_condition, target, "", "top-level", &context)? {
+            warn!("Patch file skipped - top-level condition failed: {}", reason);
+            finish_skipped_patch_file(patches, &plan, report, current_mod_id, &reason);
+            return Ok(());
         }
     }
 
@@ -2063,38 +2345,60 @@ fn apply_patches_with_shadow(
 
     // Apply patches to shadow
     for (patch_name, patch) in patches {
+        if let Some(group_name) = plan.group_of(patch_name) {
+            if !plan.start(group_name) {
+                continue;
+            }
+
+            if let Err(e) = apply_patch_group(group_name, None, &plan, patches, mod_path, &context, &mut shadow, &mut report) {
+                error!("{}. Rolling back.", e);
+                shadow.discard();
+                report.finish(current_mod_id, true);
+                return Err(e);
+            }
+            continue;
+        }
+
         info!("Processing patch '{}'", patch_name);
 
         // Evaluate patch-level conditions
         let target = get_patch_target(patch);
         let condition = get_patch_condition(patch);
 
-        match evaluate_patch_condition_with_target(condition, target, patch_name) {
-            Ok(true) => {
+        match evaluate_patch_condition_with_target(condition, target, patch_name, &context) {
+            Ok(outcome @ ConditionOutcome::Passed) => {
                 // Condition passed, apply patch to shadow
                 let result = apply_single_patch_shadow(patch, mod_path, patch_name, &context, &mut shadow);
 
                 if let Err(e) = result {
                     error!("Patch '{}' failed: {}. Rolling back.", patch_name, e);
+                    report.record(patch_name, patch, None, Some(&outcome), PatchStatus::Failed, Some(&e));
                     shadow.discard();
+                    report.finish(current_mod_id, true);
                     return Err(e);
                 }
+                report.record(patch_name, patch, None, Some(&outcome), PatchStatus::Applied, None);
             }
-            Ok(false) => {
+            Ok(outcome @ ConditionOutcome::Failed(_)) => {
                 // Condition failed, skip patch
                 info!("Patch '{}': skipping (condition failed)", patch_name);
+                report.record(patch_name, patch, None, Some(&outcome), PatchStatus::Skipped, None);
             }
             Err(e) => {
                 // Error evaluating condition
                 error!("Patch '{}': error evaluating condition: {}. Rolling back.", patch_name, e);
+                report.record(patch_name, patch, None, None, PatchStatus::Failed, Some(&e));
                 shadow.discard();
+                report.finish(current_mod_id, true);
                 return Err(e);
             }
         }
     }
 
     // All patches succeeded - commit shadow to main resources
-    shadow.commit()?;
+    let committed = shadow.commit();
+    report.finish(current_mod_id, committed.is_err());
+    committed?;
 
     info!("All patches applied successfully and committed");
     Ok(())
@@ -2120,18 +2424,241 @@ pub fn apply_patches(
     patches: &indexmap::IndexMap<String, Patch>,
     mod_path: &Path,
     current_mod_id: &str,
+) -> anyhow::Result<()> {
+    apply_patches_with_groups(patch_meta, patches, None, mod_path, current_mod_id)
+}
+
+/// Apply all patches, applying each patch group atomically
+///
+/// Same as `apply_patches`, but patches listed in `patch_groups` are applied together at the
+/// position of the group's first patch, and rolled back together if any of them fails.
+/// Outcomes of every patch and group are recorded in the mod's patch report.
+///
+/// # Arguments
+/// * `patch_meta` - Patch metadata containing error handling and file-level conditions
+/// * `patches` - Ordered map of patches to apply (order is preserved via IndexMap)
+/// * `patch_groups` - Optional `[patch_groups]` from the same defs file
+/// * `mod_path` - Path to the current mod being loaded
+/// * `current_mod_id` - The ID of the current mod (for variable substitution)
+///
+/// # Returns
+/// * `Ok(())` if patches were applied successfully
+/// * `Err(_)` if the patch groups are invalid, on_error=abort or on_error=abort_mod and an
+///   ungrouped patch failed, or a group with on_error=abort/abort_mod failed
+pub fn apply_patches_with_groups(
+    patch_meta: &PatchMeta,
+    patches: &indexmap::IndexMap<String, Patch>,
+    patch_groups: Option<&indexmap::IndexMap<String, PatchGroup>>,
+    mod_path: &Path,
+    current_mod_id: &str,
 ) -> anyhow::Result<()> {
     // Route based on error handling mode
     match patch_meta.on_error {
         ErrorHandling::Continue => {
             // Direct mode - no shadow, patches applied directly
-            apply_patches_direct(patch_meta, patches, mod_path, current_mod_id)
+            apply_patches_direct(patch_meta, patches, patch_groups, mod_path, current_mod_id)
         }
         ErrorHandling::Abort | ErrorHandling::AbortMod => {
             // Shadow mode - patches applied to shadow, committed on success
-            apply_patches_with_shadow(patch_meta, patches, mod_path, current_mod_id)
+            apply_patches_with_shadow(patch_meta, patches, patch_groups, mod_path, current_mod_id)
+        }
+    }
+}
+
+// ============================================================================
+// Patch Groups
+// ============================================================================
+
+/// Validated patch groups for a single patch file
+struct PatchGroupPlan<'a> {
+    groups: Option<&'a indexmap::IndexMap<String, PatchGroup>>,
+
+    /// Patch name -> name of the group it belongs to
+    patch_to_group: HashMap<&'a str, &'a str>,
+
+    /// Groups that are only applied as another group's fallback
+    fallback_only: HashSet<&'a str>,
+
+    /// Groups that have already been applied in this file
+    started: HashSet<&'a str>,
+}
+
+impl<'a> PatchGroupPlan<'a> {
+    /// Validate patch groups against the patches in the same file
+    ///
+    /// # Returns
+    /// * `Ok(PatchGroupPlan)` - Plan for applying the groups
+    /// * `Err(_)` if a group is empty, references an unknown patch or group, shares a patch
+    ///   with another group, or is part of a fallback cycle
+    fn new(groups: Option<&'a indexmap::IndexMap<String, PatchGroup>>, patches: &indexmap::IndexMap<String, Patch>) -> anyhow::Result<Self> {
+        let mut patch_to_group = HashMap::new();
+        let mut fallback_only = HashSet::new();
+
+        for (group_name, group) in groups.into_iter().flatten() {
+            if group.patches.is_empty() {
+                anyhow::bail!("Patch group '{}' has no patches", group_name);
+            }
+
+            for patch_name in &group.patches {
+                if !patches.contains_key(patch_name) {
+                    anyhow::bail!("Patch group '{}' references unknown patch '{}'", group_name, patch_name);
+                }
+                if let Some(other) = patch_to_group.insert(patch_name.as_str(), group_name.as_str()) {
+                    anyhow::bail!("Patch '{}' is in both patch group '{}' and '{}'", patch_name, other, group_name);
+                }
+            }
+
+            if let Some(fallback) = &group.fallback_group {
+                if !groups.is_some_and(|g| g.contains_key(fallback)) {
+                    anyhow::bail!("Patch group '{}' has unknown fallback_group '{}'", group_name, fallback);
+                }
+                fallback_only.insert(fallback.as_str());
+            }
+        }
+
+        // Fallback chains must end, otherwise a failing group could retry forever
+        for (group_name, mut group) in groups.into_iter().flatten() {
+            let mut visited = HashSet::from([group_name.as_str()]);
+            while let Some(fallback) = &group.fallback_group {
+                if !visited.insert(fallback.as_str()) {
+                    anyhow::bail!("Patch group '{}' has a fallback_group cycle through '{}'", group_name, fallback);
+                }
+                group = &groups.unwrap()[fallback];
+            }
+        }
+
+        Ok(PatchGroupPlan {
+            groups,
+            patch_to_group,
+            fallback_only,
+            started: HashSet::new(),
+        })
+    }
+
+    /// Get the group a patch belongs to, if any
+    fn group_of(&self, patch_name: &str) -> Option<&'a str> {
+        self.patch_to_group.get(patch_name).copied()
+    }
+
+    /// Check whether a group should be applied now, marking it as started
+    ///
+    /// Groups are applied once, at the position of their first patch in `[patches]`.
+    /// Fallback-only groups are never applied this way.
+    fn start(&mut self, group_name: &'a str) -> bool {
+        !self.fallback_only.contains(group_name) && self.started.insert(group_name)
+    }
+
+    fn group(&self, group_name: &str) -> &'a PatchGroup {
+        &self.groups.expect("patch group plan has no groups")[group_name]
+    }
+}
+
+/// Apply a patch group to a shadow inside its own savepoint
+///
+/// If any patch fails, the group's changes are rolled back and its fallback group (if any) is
+/// applied in its place. If that doesn't recover, the group's `on_error` decides whether the rest
+/// of the patch file should continue.
+///
+/// # Arguments
+/// * `group_name` - Name of the group to apply
+/// * `fallback_for` - Name of the group this one replaces, if applied as a fallback
+/// * `plan` - Validated patch groups for this file
+/// * `patches` - All patches in the file
+/// * `mod_path` - Path to the current mod being loaded
+/// * `context` - Substitution context for variable resolution
+/// * `shadow` - Shadow resources to apply the group to
+/// * `report` - Report for the patch file, receives every patch and group outcome including fallbacks
+///
+/// # Returns
+/// * `Ok(true)` if the group was committed to the shadow
+/// * `Ok(false)` if the group was rolled back and the file should continue
+/// * `Err(_)` if the group was rolled back and its on_error is abort or abort_mod
+#[allow(clippy::too_many_arguments)]
+fn apply_patch_group(
+    group_name: &str,
+    fallback_for: Option<&str>,
+    plan: &PatchGroupPlan,
+    patches: &indexmap::IndexMap<String, Patch>,
+    mod_path: &Path,
+    context: &SubstitutionContext,
+    shadow: &mut ShadowResources,
+    report: &mut PatchFileReport,
+) -> anyhow::Result<bool> {
+    let group = plan.group(group_name);
+    info!("Applying patch group '{}' with {} patches (on_error: {:?})", group_name, group.patches.len(), group.on_error);
+
+    let mut group_report = PatchGroupReport {
+        group: group_name.to_string(),
+        fallback_for: fallback_for.map(str::to_string),
+        outcome: PatchGroupOutcome::Committed,
+        applied_patches: Vec::new(),
+        skipped_patches: Vec::new(),
+    };
+
+    shadow.savepoint();
+    let first_entry = report.patches.len();
+
+    let mut failure = None;
+    for patch_name in &group.patches {
+        let patch = &patches[patch_name];
+        let target = get_patch_target(patch);
+        let condition = get_patch_condition(patch);
+
+        match evaluate_patch_condition_with_target(condition, target, patch_name, context) {
+            Ok(outcome @ ConditionOutcome::Passed) => match apply_single_patch_shadow(patch, mod_path, patch_name, context, shadow) {
+                Ok(()) => {
+                    report.record(patch_name, patch, Some(group_name), Some(&outcome), PatchStatus::Applied, None);
+                    group_report.applied_patches.push(patch_name.clone());
+                }
+                Err(e) => {
+                    report.record(patch_name, patch, Some(group_name), Some(&outcome), PatchStatus::Failed, Some(&e));
+                    failure = Some((patch_name.clone(), e));
+                    break;
+                }
+            },
+            Ok(outcome @ ConditionOutcome::Failed(_)) => {
+                info!("Patch '{}': skipping (condition failed)", patch_name);
+                report.record(patch_name, patch, Some(group_name), Some(&outcome), PatchStatus::Skipped, None);
+                group_report.skipped_patches.push(patch_name.clone());
+            }
+            Err(e) => {
+                report.record(patch_name, patch, Some(group_name), None, PatchStatus::Failed, Some(&e));
+                failure = Some((patch_name.clone(), e));
+                break;
+            }
+        }
+    }
+
+    let Some((failed_patch, e)) = failure else {
+        shadow.release_savepoint()?;
+        info!("Patch group '{}' committed ({} applied, {} skipped)",
+              group_name, group_report.applied_patches.len(), group_report.skipped_patches.len());
+        report.groups.push(group_report);
+        return Ok(true);
+    };
+
+    shadow.rollback_savepoint()?;
+    report.roll_back_since(first_entry);
+    error!("Patch group '{}': patch '{}' failed: {}. Rolled back group.", group_name, failed_patch, e);
+    group_report.outcome = PatchGroupOutcome::RolledBack {
+        failed_patch: failed_patch.clone(),
+        error: e.to_string(),
+    };
+    report.groups.push(group_report);
+
+    if let Some(fallback) = &group.fallback_group {
+        info!("Patch group '{}': applying fallback group '{}'", group_name, fallback);
+        if apply_patch_group(fallback, Some(group_name), plan, patches, mod_path, context, shadow, report)? {
+            return Ok(false);
         }
     }
+
+    match group.on_error {
+        ErrorHandling::Continue => Ok(false),
+        ErrorHandling::Abort | ErrorHandling::AbortMod => Err(anyhow::anyhow!(
+            "Patch group '{}' failed at patch '{}': {}", group_name, failed_patch, e
+        )),
+    }
 }
 
 // ============================================================================
@@ -2182,6 +2709,18 @@ mod tests {
         assert_eq!(result.identifier, "moon");
     }
 
+    #[test]
+    fn test_parse_variable_option() {
+        let result = parse_variable("option.difficulty").unwrap();
+        assert_eq!(result.var_type, VariableType::Option);
+        assert_eq!(result.mod_id, None);
+        assert_eq!(result.identifier, "difficulty");
+
+        let result = parse_variable("lunar.option.difficulty").unwrap();
+        assert_eq!(result.var_type, VariableType::Option);
+        assert_eq!(result.mod_id, Some("lunar".to_string()));
+    }
+
     #[test]
     fn test_parse_variable_invalid_syntax_too_few_parts() {
         let result = parse_variable("habitat");
@@ -2494,4 +3033,351 @@ mod tests {
         assert_eq!(legacy_parts.attribute, "name_id");
     }
 
+    // =========================================================================
+    // Tests for shadow savepoints and patch groups
+    // =========================================================================
+
+    fn text_file(content: &str) -> ZTFile {
+        let content_len = content.len() as u32;
+        ZTFile::Text(std::ffi::CString::new(content).unwrap(), ZTFileType::Ini, content_len)
+    }
+
+    fn file_content(shadow: &ShadowResources, path: &str) -> String {
+        match shadow.files.get(path) {
+            Some(ZTFile::Text(content, _, _)) => content.to_str().unwrap().to_string(),
+            other => panic!("Expected text file for '{}', got {:?}", path, other.is_some()),
+        }
+    }
+
+    fn set_key(target: &str, value: &str) -> Patch {
+        Patch::SetKey(SetKeyPatch {
+            target: target.to_string(),
+            section: "Section".to_string(),
+            key: "Key".to_string(),
+            value: value.to_string(),
+            condition: None,
+        })
+    }
+
+    fn group(patches: &[&str], on_error: ErrorHandling, fallback_group: Option<&str>) -> PatchGroup {
+        PatchGroup {
+            patches: patches.iter().map(|p| p.to_string()).collect(),
+            on_error,
+            fallback_group: fallback_group.map(str::to_string),
+        }
+    }
+
+    fn test_context() -> SubstitutionContext {
+        SubstitutionContext {
+            current_mod_id: "test_mod".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_shadow_savepoint_rollback_restores_state() {
+        let mut shadow = ShadowResources::new(&HashSet::new(), ShadowScope::PatchFile).unwrap();
+        shadow.update_file("a.ini", text_file("[Section]\nKey=1\n"));
+
+        shadow.savepoint();
+        shadow.update_file("a.ini", text_file("[Section]\nKey=2\n"));
+        shadow.update_file("b.ini", text_file("[Section]\n"));
+        shadow.delete_file("c.ini");
+        assert_eq!(shadow.savepoint_depth(), 1);
+
+        shadow.rollback_savepoint().unwrap();
+        assert_eq!(shadow.savepoint_depth(), 0);
+        assert_eq!(file_content(&shadow, "a.ini"), "[Section]\nKey=1\n");
+        assert!(!shadow.files.contains_key("b.ini"));
+        assert!(!shadow.deleted_files.contains("c.ini"));
+    }
+
+    #[test]
+    fn test_shadow_nested_savepoints() {
+        let mut shadow = ShadowResources::new(&HashSet::new(), ShadowScope::PatchFile).unwrap();
+
+        shadow.savepoint();
+        shadow.update_file("outer.ini", text_file("outer"));
+        shadow.savepoint();
+        shadow.update_file("inner.ini", text_file("inner"));
+
+        // Rolling back the inner savepoint keeps the outer change
+        shadow.rollback_savepoint().unwrap();
+        assert!(shadow.files.contains_key("outer.ini"));
+        assert!(!shadow.files.contains_key("inner.ini"));
+
+        shadow.release_savepoint().unwrap();
+        assert!(shadow.files.contains_key("outer.ini"));
+
+        assert!(shadow.release_savepoint().is_err(), "No savepoint should be left to release");
+        assert!(shadow.rollback_savepoint().is_err(), "No savepoint should be left to roll back");
+    }
+
+    #[test]
+    fn test_patch_group_plan_validation() {
+        let mut patches = indexmap::IndexMap::new();
+        patches.insert("p1".to_string(), set_key("a.ini", "1"));
+        patches.insert("p2".to_string(), set_key("a.ini", "2"));
+
+        let cases: Vec<(Vec<(&str, PatchGroup)>, &str)> = vec![
+            (vec![("g", group(&[], ErrorHandling::Continue, None))], "has no patches"),
+            (vec![("g", group(&["missing"], ErrorHandling::Continue, None))], "unknown patch"),
+            (
+                vec![("g1", group(&["p1"], ErrorHandling::Continue, None)), ("g2", group(&["p1"], ErrorHandling::Continue, None))],
+                "in both patch group",
+            ),
+            (vec![("g", group(&["p1"], ErrorHandling::Continue, Some("missing")))], "unknown fallback_group"),
+            (
+                vec![("g1", group(&["p1"], ErrorHandling::Continue, Some("g2"))), ("g2", group(&["p2"], ErrorHandling::Continue, Some("g1")))],
+                "cycle",
+            ),
+        ];
+
+        for (groups, expected) in cases {
+            let groups: indexmap::IndexMap<String, PatchGroup> = groups.into_iter().map(|(n, g)| (n.to_string(), g)).collect();
+            let error = PatchGroupPlan::new(Some(&groups), &patches).err().expect("plan should be invalid");
+            assert!(error.to_string().contains(expected), "Expected '{}' in error: {}", expected, error);
+        }
+    }
+
+    #[test]
+    fn test_patch_group_plan_starts_groups_once() {
+        let mut patches = indexmap::IndexMap::new();
+        patches.insert("p1".to_string(), set_key("a.ini", "1"));
+        patches.insert("p2".to_string(), set_key("a.ini", "2"));
+        patches.insert("p3".to_string(), set_key("a.ini", "3"));
+
+        let mut groups = indexmap::IndexMap::new();
+        groups.insert("main".to_string(), group(&["p1", "p2"], ErrorHandling::Continue, Some("fallback")));
+        groups.insert("fallback".to_string(), group(&["p3"], ErrorHandling::Continue, None));
+
+        let mut plan = PatchGroupPlan::new(Some(&groups), &patches).unwrap();
+        assert_eq!(plan.group_of("p2"), Some("main"));
+        assert!(plan.start("main"));
+        assert!(!plan.start("main"), "A group should only be started once");
+        assert!(!plan.start("fallback"), "Fallback-only groups should not start on their own");
+    }
+
+    #[test]
+    fn test_patch_group_commits() {
+        let mut patches = indexmap::IndexMap::new();
+        patches.insert("p1".to_string(), set_key("a.ini", "1"));
+        let mut groups = indexmap::IndexMap::new();
+        groups.insert("main".to_string(), group(&["p1"], ErrorHandling::Abort, None));
+
+        let plan = PatchGroupPlan::new(Some(&groups), &patches).unwrap();
+        let mut shadow = ShadowResources::new(&HashSet::new(), ShadowScope::PatchFile).unwrap();
+        shadow.update_file("a.ini", text_file("[Section]\nKey=0\n"));
+        let mut report = PatchFileReport::default();
+
+        let committed = apply_patch_group("main", None, &plan, &patches, Path::new(""), &test_context(), &mut shadow, &mut report).unwrap();
+        assert!(committed);
+        assert_eq!(shadow.savepoint_depth(), 0);
+        assert!(file_content(&shadow, "a.ini").contains("Key=1"));
+        assert_eq!(report.groups.len(), 1);
+        assert_eq!(report.groups[0].outcome, PatchGroupOutcome::Committed);
+        assert_eq!(report.groups[0].applied_patches, vec!["p1".to_string()]);
+        assert_eq!(report.patches[0].status, PatchStatus::Applied);
+        assert_eq!(report.patches[0].group, Some("main".to_string()));
+    }
+
+    #[test]
+    fn test_patch_group_rolls_back_and_applies_fallback() {
+        let mut patches = indexmap::IndexMap::new();
+        patches.insert("p1".to_string(), set_key("a.ini", "1"));
+        patches.insert("p2".to_string(), set_key("missing.ini", "2"));
+        patches.insert("p3".to_string(), set_key("a.ini", "3"));
+        let mut groups = indexmap::IndexMap::new();
+        groups.insert("main".to_string(), group(&["p1", "p2"], ErrorHandling::Abort, Some("fallback")));
+        groups.insert("fallback".to_string(), group(&["p3"], ErrorHandling::Continue, None));
+
+        let plan = PatchGroupPlan::new(Some(&groups), &patches).unwrap();
+        let mut shadow = ShadowResources::new(&HashSet::new(), ShadowScope::PatchFile).unwrap();
+        shadow.update_file("a.ini", text_file("[Section]\nKey=0\n"));
+        let mut report = PatchFileReport::default();
+
+        // The fallback recovers, so the abort on_error of the main group doesn't apply
+        let committed = apply_patch_group("main", None, &plan, &patches, Path::new(""), &test_context(), &mut shadow, &mut report).unwrap();
+        assert!(!committed);
+        assert!(file_content(&shadow, "a.ini").contains("Key=3"), "p1 should be rolled back and p3 applied");
+
+        assert_eq!(report.groups.len(), 2);
+        assert!(matches!(&report.groups[0].outcome, PatchGroupOutcome::RolledBack { failed_patch, .. } if failed_patch == "p2"));
+        assert_eq!(report.groups[0].applied_patches, vec!["p1".to_string()]);
+        assert_eq!(report.groups[1].group, "fallback");
+        assert_eq!(report.groups[1].fallback_for, Some("main".to_string()));
+        assert_eq!(report.groups[1].outcome, PatchGroupOutcome::Committed);
+
+        let statuses: Vec<_> = report.patches.iter().map(|p| (p.name.as_str(), p.status)).collect();
+        assert_eq!(statuses, vec![("p1", PatchStatus::RolledBack), ("p2", PatchStatus::Failed), ("p3", PatchStatus::Applied)]);
+    }
+
+    #[test]
+    fn test_patch_group_on_error() {
+        let mut patches = indexmap::IndexMap::new();
+        patches.insert("p1".to_string(), set_key("a.ini", "1"));
+        patches.insert("p2".to_string(), set_key("missing.ini", "2"));
+
+        for (on_error, should_abort) in [(ErrorHandling::Continue, false), (ErrorHandling::Abort, true), (ErrorHandling::AbortMod, true)] {
+            let mut groups = indexmap::IndexMap::new();
+            groups.insert("main".to_string(), group(&["p1", "p2"], on_error.clone(), None));
+
+            let plan = PatchGroupPlan::new(Some(&groups), &patches).unwrap();
+            let mut shadow = ShadowResources::new(&HashSet::new(), ShadowScope::PatchFile).unwrap();
+            shadow.update_file("a.ini", text_file("[Section]\nKey=0\n"));
+            let mut report = PatchFileReport::default();
+
+            let result = apply_patch_group("main", None, &plan, &patches, Path::new(""), &test_context(), &mut shadow, &mut report);
+            assert_eq!(result.is_err(), should_abort, "Unexpected result for on_error {:?}", on_error);
+            assert!(file_content(&shadow, "a.ini").contains("Key=0"), "Group should be rolled back for on_error {:?}", on_error);
+            assert_eq!(shadow.savepoint_depth(), 0);
+        }
+    }
+
+    // =========================================================================
+    // Tests for nested patch conditions
+    // =========================================================================
+
+    fn evaluate(condition_toml: &str) -> anyhow::Result<ConditionOutcome> {
+        let condition: PatchCondition = toml::from_str(condition_toml).unwrap();
+        let context = SubstitutionContext {
+            current_mod_id: "test.mod".to_string(),
+        };
+        evaluate_patch_condition_with_target(&Some(condition), "", "test_patch", &context)
+    }
+
+    fn failure(outcome: ConditionOutcome) -> String {
+        match outcome {
+            ConditionOutcome::Failed(reason) => reason,
+            ConditionOutcome::Passed => panic!("Expected condition to fail"),
+        }
+    }
+
+    #[test]
+    fn test_condition_leaf_failure_is_reported() {
+        let reason = failure(evaluate(r#"mod_loaded = "test.not_loaded""#).unwrap());
+        assert_eq!(reason, "mod_loaded: mod 'test.not_loaded' is not loaded");
+
+        let reason = failure(evaluate(r#"file_exists = "test/not_a_file.ini""#).unwrap());
+        assert_eq!(reason, "file_exists: file 'test/not_a_file.ini' not found");
+
+        let reason = failure(evaluate(r#"mod_version = { mod_id = "test.not_loaded", version = "1.0.0" }"#).unwrap());
+        assert!(reason.starts_with("mod_version:"), "Unexpected reason: {}", reason);
+    }
+
+    #[test]
+    fn test_condition_not() {
+        assert_eq!(evaluate(r#"not = { mod_loaded = "test.not_loaded" }"#).unwrap(), ConditionOutcome::Passed);
+
+        let reason = failure(evaluate(r#"not = { not = { mod_loaded = "test.not_loaded" } }"#).unwrap());
+        assert_eq!(reason, "not: nested condition passed");
+    }
+
+    #[test]
+    fn test_condition_any_reports_every_branch() {
+        let reason = failure(
+            evaluate(
+                r#"
+                [[any]]
+                mod_loaded = "test.a"
+
+                [[any]]
+                file_exists = "test/b.ini"
+                "#,
+            )
+            .unwrap(),
+        );
+        assert_eq!(
+            reason,
+            "any: no nested condition passed (any[0].mod_loaded: mod 'test.a' is not loaded; any[1].file_exists: file 'test/b.ini' not found)"
+        );
+
+        let outcome = evaluate(
+            r#"
+            [[any]]
+            mod_loaded = "test.a"
+
+            [[any]]
+            not = { mod_loaded = "test.b" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(outcome, ConditionOutcome::Passed);
+    }
+
+    #[test]
+    fn test_condition_all_reports_nested_path() {
+        let reason = failure(
+            evaluate(
+                r#"
+                [[all]]
+                not = { mod_loaded = "test.a" }
+
+                [[all]]
+                [all.not]
+                not = { mod_loaded = "test.b" }
+                "#,
+            )
+            .unwrap(),
+        );
+        assert_eq!(reason, "all[1].not: nested condition passed");
+    }
+
+    #[test]
+    fn test_condition_option_equals() {
+        let meta: crate::mods::Meta = toml::from_str(
+            r#"
+            name = "options"
+            description = "test"
+            authors = ["test"]
+            mod_id = "test.options"
+            version = "1.0.0"
+
+            [options.theme]
+            type = "enum"
+            default = "jungle"
+            values = ["jungle", "desert"]
+            "#,
+        )
+        .unwrap();
+        crate::resource_manager::openzt_mods::mod_options::register_mod_options("test.options", meta.options());
+
+        let outcome = evaluate(r#"option_equals = { mod_id = "test.options", option = "theme", value = "jungle" }"#).unwrap();
+        assert_eq!(outcome, ConditionOutcome::Passed);
+
+        let reason = failure(evaluate(r#"option_equals = { mod_id = "test.options", option = "theme", value = "desert" }"#).unwrap());
+        assert_eq!(reason, "option_equals: option 'test.options.theme' is 'jungle', expected 'desert'");
+
+        // Values outside the declared choices and unknown options are errors, not failures
+        assert!(evaluate(r#"option_equals = { mod_id = "test.options", option = "theme", value = "arctic" }"#).is_err());
+        assert!(evaluate(r#"option_equals = { mod_id = "test.options", option = "missing", value = "1" }"#).is_err());
+    }
+
+    #[test]
+    fn test_condition_errors() {
+        // File checks need a target when there is no patch target to inherit
+        let error = evaluate("value_gt = { section = \"S\", key = \"K\", value = 1.0 }").unwrap_err();
+        assert!(error.to_string().contains("requires 'target'"), "Unexpected error: {}", error);
+
+        let error = evaluate(r#"all = [{ value_lt = { section = "S", key = "K", value = 1.0 } }]"#).unwrap_err();
+        assert!(error.to_string().starts_with("all[0] with"), "Unexpected error: {}", error);
+
+        let error = evaluate(r#"entity_exists = { type = "spaceships", name = "x" }"#).unwrap_err();
+        assert!(error.to_string().contains("entity_exists"), "Unexpected error: {}", error);
+    }
+
+    #[test]
+    fn test_read_numeric_value() {
+        let mut ini = Ini::new_cs();
+        ini.read("[S]\nint = 5\nfloat = 2.5\ntext = fast\n".to_string()).unwrap();
+        let check = |key: &str| NumericCheck {
+            section: "S".to_string(),
+            key: key.to_string(),
+            value: 0.0,
+        };
+
+        assert_eq!(read_numeric_value(&ini, &check("int")), Ok(5.0));
+        assert_eq!(read_numeric_value(&ini, &check("float")), Ok(2.5));
+        assert!(read_numeric_value(&ini, &check("text")).unwrap_err().contains("not a number"));
+        assert!(read_numeric_value(&ini, &check("missing")).unwrap_err().contains("does not exist"));
+    }
 }
@@ -0,0 +1,437 @@
+//! Lua scripts bundled with OpenZT mods
+//!
+//! Mods list their scripts in meta.toml (`scripts = ["scripts/main.lua"]`). A mod's scripts
+//! share one sandboxed environment, configured by `[scripting.mods]` in openzt.toml, and run
+//! right after the mod's defs are applied. Scripts register lifecycle callbacks with `on_load(fn)`,
+//! `on_game_start(fn)`, `on_tick(fn)`, `on_save(fn)` and `on_entity_placed(fn)`, and can subscribe
+//! to other game events with `events.subscribe` (see [`crate::events`]). `state` holds the mod's
+//! data for the loaded zoo, see [`crate::zoo_state`].
+//!
+//! Callbacks run in mod load order. Errors are logged against the mod that caused them: a
+//! script that fails disables that mod's scripts, and a callback that fails is disabled,
+//! while every other mod keeps running.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use anyhow::{anyhow, Context};
+use mlua::{Function, IntoLuaMulti, Lua, Table};
+#[cfg(target_os = "windows")]
+use openzt_detour_macro::detour_mod;
+use tracing::{error, info, warn};
+
+use crate::{
+    events::{self, Event, EventKind, Propagation},
+    resource_manager::{
+        mod_config::{get_openzt_config, SandboxConfig},
+        openzt_mods::extensions::get_entity_base,
+    },
+    scripting::sandbox::{with_limits, ExecutionLimits},
+    zoo_state::ZooStateRef,
+};
+
+/// Lifecycle events mod scripts can register callbacks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LifecycleHook {
+    /// All mods have been loaded
+    Load,
+    /// A new or saved game has started
+    GameStart,
+    /// The simulation advanced, called with the elapsed milliseconds
+    Tick,
+    /// The zoo is being saved
+    Save,
+    /// An entity was placed on the map, called with its address and its base (e.g. `legacy.animals.elephant`) if known
+    EntityPlaced,
+}
+
+impl LifecycleHook {
+    pub const ALL: [LifecycleHook; 5] = [
+        LifecycleHook::Load,
+        LifecycleHook::GameStart,
+        LifecycleHook::Tick,
+        LifecycleHook::Save,
+        LifecycleHook::EntityPlaced,
+    ];
+
+    /// Name of the function scripts call to register a callback for this hook
+    pub fn lua_name(&self) -> &'static str {
+        match self {
+            LifecycleHook::Load => "on_load",
+            LifecycleHook::GameStart => "on_game_start",
+            LifecycleHook::Tick => "on_tick",
+            LifecycleHook::Save => "on_save",
+            LifecycleHook::EntityPlaced => "on_entity_placed",
+        }
+    }
+}
+
+struct Callback {
+    id: u64,
+    hook: LifecycleHook,
+    /// Position of the owning mod in the load order
+    load_index: usize,
+    mod_id: String,
+    function: Function,
+}
+
+#[derive(Default)]
+struct Callbacks {
+    next_id: u64,
+    entries: Vec<Callback>,
+}
+
+impl Callbacks {
+    fn insert(&mut self, hook: LifecycleHook, load_index: usize, mod_id: &str, function: Function) {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        // Callbacks registered late (e.g. from on_load) still run in mod load order
+        let position = self.entries.partition_point(|callback| callback.load_index <= load_index);
+        self.entries.insert(
+            position,
+            Callback {
+                id,
+                hook,
+                load_index,
+                mod_id: mod_id.to_string(),
+                function,
+            },
+        );
+    }
+}
+
+/// Mod scripts and the lifecycle callbacks they registered
+#[derive(Default)]
+pub struct ScriptRegistry {
+    callbacks: Arc<Mutex<Callbacks>>,
+    /// Mods that have run scripts, in load order
+    mods: Mutex<Vec<String>>,
+}
+
+impl ScriptRegistry {
+    /// Run a mod's scripts in its environment
+    ///
+    /// Mods must be loaded in the resolved load order, which is the order their callbacks run in.
+    ///
+    /// # Arguments
+    /// * `lua` - The Lua state to run the scripts in
+    /// * `env` - The mod's sandboxed environment, shared by all its scripts
+    /// * `limits` - Budget for each script
+    /// * `mod_id` - The mod the scripts belong to
+    /// * `scripts` - Path and source of each script, in the order declared in meta.toml
+    ///
+    /// # Returns
+    /// * `Ok(())` - Every script ran
+    /// * `Err` - A script failed; callbacks the mod already registered are removed
+    pub fn load_mod_scripts(&self, lua: &Lua, env: Table, limits: &ExecutionLimits, mod_id: &str, scripts: &[(String, String)]) -> anyhow::Result<()> {
+        let load_index = {
+            let mut mods = self.mods.lock().unwrap();
+            mods.push(mod_id.to_string());
+            mods.len() - 1
+        };
+
+        let result = self.run_scripts(lua, env, limits, mod_id, load_index, scripts);
+        if result.is_err() {
+            self.callbacks.lock().unwrap().entries.retain(|callback| callback.mod_id != mod_id);
+        }
+        result
+    }
+
+    fn run_scripts(&self, lua: &Lua, env: Table, limits: &ExecutionLimits, mod_id: &str, load_index: usize, scripts: &[(String, String)]) -> anyhow::Result<()> {
+        self.add_mod_functions(lua, &env, mod_id, load_index)
+            .with_context(|| format!("Failed to create script environment for {}", mod_id))?;
+
+        let limits = limits.owned_by(mod_id);
+        for (path, source) in scripts {
+            with_limits(lua, &limits, || {
+                lua.load(source.as_str())
+                    .set_name(format!("@{}/{}", mod_id, path))
+                    .set_environment(env.clone())
+                    .exec()
+            })
+            .with_context(|| format!("Error running {}", path))?;
+        }
+        Ok(())
+    }
+
+    /// Add `mod_id`, a `print` that logs and the hook registration functions to a mod's environment
+    fn add_mod_functions(&self, lua: &Lua, env: &Table, mod_id: &str, load_index: usize) -> mlua::Result<()> {
+        env.set("mod_id", mod_id)?;
+
+        let log_mod_id = mod_id.to_string();
+        env.set(
+            "print",
+            lua.create_function(move |_, args: mlua::Variadic<mlua::Value>| {
+                let parts: Vec<String> = args.iter().map(|arg| arg.to_string().unwrap_or_default()).collect();
+                info!("[{}] {}", log_mod_id, parts.join("\t"));
+                Ok(())
+            })?,
+        )?;
+
+        for hook in LifecycleHook::ALL {
+            let callbacks = Arc::clone(&self.callbacks);
+            let mod_id = mod_id.to_string();
+            env.set(
+                hook.lua_name(),
+                lua.create_function(move |_, function: Function| {
+                    callbacks.lock().unwrap().insert(hook, load_index, &mod_id, function);
+                    Ok(())
+                })?,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Call every callback registered for `hook`, in mod load order
+    ///
+    /// Each callback gets its own budget of `limits`, and runs as its mod. A callback that errors
+    /// is logged against its mod and disabled, the rest still run.
+    pub fn dispatch(&self, lua: &Lua, limits: &ExecutionLimits, hook: LifecycleHook, args: impl IntoLuaMulti + Clone) {
+        // Callbacks may register more callbacks, so don't hold the lock while calling them
+        let targets: Vec<(u64, String, Function)> = self
+            .callbacks
+            .lock()
+            .unwrap()
+            .entries
+            .iter()
+            .filter(|callback| callback.hook == hook)
+            .map(|callback| (callback.id, callback.mod_id.clone(), callback.function.clone()))
+            .collect();
+
+        for (id, mod_id, function) in targets {
+            if let Err(err) = with_limits(lua, &limits.owned_by(&mod_id), || function.call::<()>(args.clone())) {
+                error!("{} callback of mod '{}' failed and has been disabled: {}", hook.lua_name(), mod_id, err);
+                self.callbacks.lock().unwrap().entries.retain(|callback| callback.id != id);
+            }
+        }
+    }
+
+    /// Mods with a callback for `hook`, in the order they are called
+    pub fn callback_mods(&self, hook: LifecycleHook) -> Vec<String> {
+        self.callbacks
+            .lock()
+            .unwrap()
+            .entries
+            .iter()
+            .filter(|callback| callback.hook == hook)
+            .map(|callback| callback.mod_id.clone())
+            .collect()
+    }
+}
+
+static MOD_SCRIPTS: LazyLock<ScriptRegistry> = LazyLock::new(ScriptRegistry::default);
+
+static MOD_SANDBOX: LazyLock<SandboxConfig> = LazyLock::new(|| get_openzt_config().scripting.mods);
+
+/// Run the scripts a mod declares in meta.toml
+///
+/// Errors are logged rather than returned so a broken script doesn't stop the mod's defs,
+/// or any other mod, from loading.
+pub fn load_mod_scripts(mod_id: &str, script_paths: &[String], file_map: &HashMap<String, Box<[u8]>>) {
+    for file_name in file_map.keys() {
+        if file_name.starts_with("scripts/") && file_name.to_lowercase().ends_with(".lua") && !script_paths.contains(file_name) {
+            warn!("Script {} in mod '{}' is not listed in meta.toml and will not run", file_name, mod_id);
+        }
+    }
+
+    if script_paths.is_empty() {
+        return;
+    }
+
+    let scripts = match read_scripts(script_paths, file_map) {
+        Ok(scripts) => scripts,
+        Err(err) => {
+            error!("Scripts for mod '{}' were not loaded: {:#}", mod_id, err);
+            return;
+        }
+    };
+
+    let lua = crate::scripting::lua_handle();
+    let env = match crate::scripting::create_sandbox(&lua, &MOD_SANDBOX) {
+        Ok(env) => env,
+        Err(err) => {
+            error!("Scripts for mod '{}' were not loaded: failed to create sandbox: {}", mod_id, err);
+            return;
+        }
+    };
+    if let Err(err) = env.set("state", ZooStateRef::new(mod_id)) {
+        error!("Scripts for mod '{}' were not loaded: failed to add zoo state: {}", mod_id, err);
+        return;
+    }
+    match MOD_SCRIPTS.load_mod_scripts(&lua, env, &ExecutionLimits::from(&*MOD_SANDBOX), mod_id, &scripts) {
+        Ok(()) => info!("Loaded {} script(s) for mod '{}'", scripts.len(), mod_id),
+        Err(err) => error!("Scripts for mod '{}' failed and have been disabled: {:#}", mod_id, err),
+    }
+}
+
+/// Read the declared scripts, which must be `.lua` files under `scripts/`
+fn read_scripts(script_paths: &[String], file_map: &HashMap<String, Box<[u8]>>) -> anyhow::Result<Vec<(String, String)>> {
+    script_paths
+        .iter()
+        .map(|path| {
+            if !path.starts_with("scripts/") || !path.to_lowercase().ends_with(".lua") {
+                return Err(anyhow!("Script '{}' must be a .lua file in scripts/", path));
+            }
+            let source = file_map.get(path).ok_or_else(|| anyhow!("Script '{}' not found in mod", path))?;
+            Ok((path.clone(), String::from_utf8_lossy(source).into_owned()))
+        })
+        .collect()
+}
+
+/// Call every mod script callback registered for `hook`
+pub fn dispatch(hook: LifecycleHook, args: impl IntoLuaMulti + Clone) {
+    MOD_SCRIPTS.dispatch(&crate::scripting::lua_handle(), &ExecutionLimits::from(&*MOD_SANDBOX), hook, args);
+}
+
+#[cfg(target_os = "windows")]
+#[detour_mod]
+pub mod mod_script_hooks {
+    use openzt_detour::gen::ztgamemgr::{SAVE, START};
+
+    use super::{dispatch, LifecycleHook};
+
+    #[detour(START)]
+    unsafe extern "fastcall" fn zt_game_mgr_start(this_ptr: i32) {
+        unsafe { START_DETOUR.call(this_ptr) };
+        dispatch(LifecycleHook::GameStart, ());
+    }
+
+    #[detour(SAVE)]
+    unsafe extern "thiscall" fn zt_game_mgr_save(this_ptr: u32, stream: u32) -> u32 {
+        dispatch(LifecycleHook::Save, ());
+        unsafe { SAVE_DETOUR.call(this_ptr, stream) }
+    }
+}
+
+pub fn init() {
+    #[cfg(target_os = "windows")]
+    unsafe {
+        if mod_script_hooks::init_detours().is_err() {
+            error!("Failed to initialize mod script detours");
+        }
+    }
+
+    events::subscribe(EventKind::EntityPlaced, 0, |event| {
+        if let Event::EntityPlaced { entity } = event {
+            dispatch(LifecycleHook::EntityPlaced, (*entity, get_entity_base(*entity)));
+        }
+        Propagation::Continue
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A Lua state with a global `record(value)` that appends to the returned list
+    fn recording_lua() -> (Lua, Arc<Mutex<Vec<String>>>) {
+        let lua = Lua::new();
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&records);
+        let record = lua
+            .create_function(move |_, value: String| {
+                sink.lock().unwrap().push(value);
+                Ok(())
+            }).unwrap();
+        lua.globals().set("record", record).unwrap();
+        (lua, records)
+    }
+
+    /// Load `source` as a mod's only script, in an environment that sees the plain globals
+    fn load(registry: &ScriptRegistry, lua: &Lua, mod_id: &str, source: &str) -> anyhow::Result<()> {
+        let env = lua.create_table().unwrap();
+        let metatable = lua.create_table().unwrap();
+        metatable.set("__index", lua.globals()).unwrap();
+        env.set_metatable(Some(metatable)).unwrap();
+        let scripts = vec![("scripts/main.lua".to_string(), source.to_string())];
+        registry.load_mod_scripts(lua, env, &ExecutionLimits::default(), mod_id, &scripts)
+    }
+
+    #[test]
+    fn test_callbacks_run_in_load_order() {
+        let (lua, records) = recording_lua();
+        let registry = ScriptRegistry::default();
+
+        load(&registry, &lua, "first", "on_load(function() on_tick(function(ms) record(mod_id .. ':' .. ms) end) end)").unwrap();
+        load(&registry, &lua, "second", "on_tick(function(ms) record(mod_id .. ':' .. ms) end)").unwrap();
+
+        registry.dispatch(&lua, &ExecutionLimits::default(), LifecycleHook::Load, ());
+        // first registered its tick callback after second, but still runs first
+        assert_eq!(registry.callback_mods(LifecycleHook::Tick), vec!["first", "second"]);
+
+        registry.dispatch(&lua, &ExecutionLimits::default(), LifecycleHook::Tick, 16);
+        assert_eq!(*records.lock().unwrap(), vec!["first:16", "second:16"]);
+    }
+
+    #[test]
+    fn test_mod_environments_are_separate() {
+        let (lua, records) = recording_lua();
+        let registry = ScriptRegistry::default();
+
+        load(&registry, &lua, "first", "shared = 'first'").unwrap();
+        load(&registry, &lua, "second", "record(tostring(shared))").unwrap();
+
+        assert_eq!(*records.lock().unwrap(), vec!["nil"]);
+        assert!(lua.globals().get::<Option<String>>("shared").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_failing_script_is_contained() {
+        let (lua, records) = recording_lua();
+        let registry = ScriptRegistry::default();
+
+        let err = load(&registry, &lua, "broken", "on_save(function() record('broken') end)\nerror('oops')").unwrap_err();
+        assert!(format!("{:#}", err).contains("oops"));
+        load(&registry, &lua, "working", "on_save(function() record('working') end)").unwrap();
+
+        registry.dispatch(&lua, &ExecutionLimits::default(), LifecycleHook::Save, ());
+        assert_eq!(*records.lock().unwrap(), vec!["working"]);
+    }
+
+    #[test]
+    fn test_failing_callback_is_disabled() {
+        let (lua, records) = recording_lua();
+        let registry = ScriptRegistry::default();
+
+        load(&registry, &lua, "broken", "on_game_start(function() error('oops') end)").unwrap();
+        load(&registry, &lua, "working", "on_game_start(function() record('started') end)").unwrap();
+
+        registry.dispatch(&lua, &ExecutionLimits::default(), LifecycleHook::GameStart, ());
+        registry.dispatch(&lua, &ExecutionLimits::default(), LifecycleHook::GameStart, ());
+        assert_eq!(*records.lock().unwrap(), vec!["started", "started"]);
+        assert_eq!(registry.callback_mods(LifecycleHook::GameStart), vec!["working"]);
+    }
+
+    #[test]
+    fn test_runaway_callback_is_stopped() {
+        let (lua, records) = recording_lua();
+        let registry = ScriptRegistry::default();
+        let limits = ExecutionLimits {
+            instructions: Some(100_000),
+            ..Default::default()
+        };
+
+        load(&registry, &lua, "runaway", "on_tick(function() while true do end end)").unwrap();
+        load(&registry, &lua, "working", "on_tick(function() record('tick') end)").unwrap();
+
+        registry.dispatch(&lua, &limits, LifecycleHook::Tick, 16);
+        assert_eq!(*records.lock().unwrap(), vec!["tick"]);
+        assert_eq!(registry.callback_mods(LifecycleHook::Tick), vec!["working"]);
+    }
+
+    #[test]
+    fn test_read_scripts() {
+        let file_map: HashMap<String, Box<[u8]>> = HashMap::from([("scripts/main.lua".to_string(), b"print('hi')".to_vec().into_boxed_slice())]);
+
+        let scripts = read_scripts(&["scripts/main.lua".to_string()], &file_map).unwrap();
+        assert_eq!(scripts, vec![("scripts/main.lua".to_string(), "print('hi')".to_string())]);
+
+        assert!(read_scripts(&["scripts/missing.lua".to_string()], &file_map).is_err());
+        assert!(read_scripts(&["defs/main.lua".to_string()], &file_map).is_err());
+        assert!(read_scripts(&["scripts/main.toml".to_string()], &file_map).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+//! Storage for the contents of the resources in the resource map
+//!
+//! The game reads a resource through a [`BFResourcePtr`], whose pointers are 32 bits, so inside the game each
+//! resource is stored as one ([`BFResourceStore`]) and its handle is the `BFResourcePtr`'s address. Nothing
+//! else needs that layout, on 64 bit hosts [`MemoryStore`] keeps the contents in a map instead, so mod loading,
+//! patching and the resource map can run under plain `cargo test`.
+
+#[cfg(not(target_pointer_width = "32"))]
+use std::sync::LazyLock;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+};
+
+#[cfg(target_pointer_width = "32")]
+use super::bfresourcemgr::BFResourcePtr;
+
+/// Contents of resources, referred to by the handles the resource map keeps
+pub trait ResourceStore: Send + Sync {
+    /// Stores a resource's contents, `zip_name` is the archive they're reported to come from
+    fn insert(&self, zip_name: &str, resource_name: &str, contents: Box<[u8]>) -> anyhow::Result<u32>;
+
+    /// The archive name and a copy of the contents
+    fn read(&self, handle: u32) -> (String, Box<[u8]>);
+
+    fn content_size(&self, handle: u32) -> u32;
+
+    /// Replaces the contents, the handle stays valid as the game may still hold it
+    fn replace(&self, handle: u32, contents: Box<[u8]>);
+
+    /// Frees the contents, the handle is invalid afterwards
+    fn remove(&self, handle: u32);
+}
+
+/// Resources as [`BFResourcePtr`]s the game can read, handles are their addresses
+///
+/// The contents are allocated with a trailing nul, which text readers expect and doesn't count in
+/// `content_size`, so they can be freed knowing only the `BFResourcePtr`.
+#[cfg(target_pointer_width = "32")]
+pub struct BFResourceStore;
+
+#[cfg(target_pointer_width = "32")]
+impl BFResourceStore {
+    fn resource(handle: u32) -> &'static mut BFResourcePtr {
+        unsafe { &mut *(handle as *mut BFResourcePtr) }
+    }
+
+    fn alloc_contents(contents: Box<[u8]>) -> u32 {
+        let mut data = contents.into_vec();
+        data.push(0);
+        Box::into_raw(data.into_boxed_slice()) as *mut u8 as u32
+    }
+
+    fn free_contents(resource: &BFResourcePtr) {
+        let data = std::ptr::slice_from_raw_parts_mut(resource.data_ptr as *mut u8, resource.content_size as usize + 1);
+        drop(unsafe { Box::from_raw(data) });
+    }
+}
+
+#[cfg(target_pointer_width = "32")]
+impl ResourceStore for BFResourceStore {
+    fn insert(&self, zip_name: &str, resource_name: &str, contents: Box<[u8]>) -> anyhow::Result<u32> {
+        use std::ffi::CString;
+
+        use anyhow::Context;
+
+        let bf_zip_name = CString::new(zip_name).with_context(|| format!("Error converting zip name to CString: {}", zip_name))?;
+        let bf_resource_name =
+            CString::new(resource_name).with_context(|| format!("Error converting resource name to CString: {}", resource_name))?;
+
+        let content_size = contents.len() as u32;
+        let resource = Box::new(BFResourcePtr {
+            num_refs: 100, // We set this very high to prevent the game from unloading the resource
+            bf_zip_name: bf_zip_name.into(),
+            bf_resource_name: bf_resource_name.into(),
+            data_ptr: Self::alloc_contents(contents),
+            content_size,
+        });
+        Ok(Box::into_raw(resource) as u32)
+    }
+
+    fn read(&self, handle: u32) -> (String, Box<[u8]>) {
+        use crate::util::ZTString;
+
+        let resource = Self::resource(handle);
+        let contents = unsafe { std::slice::from_raw_parts(resource.data_ptr as *const u8, resource.content_size as usize) };
+        (resource.bf_zip_name.copy_to_string(), contents.into())
+    }
+
+    fn content_size(&self, handle: u32) -> u32 {
+        Self::resource(handle).content_size
+    }
+
+    fn replace(&self, handle: u32, contents: Box<[u8]>) {
+        let resource = Self::resource(handle);
+        Self::free_contents(resource);
+        resource.content_size = contents.len() as u32;
+        resource.data_ptr = Self::alloc_contents(contents);
+    }
+
+    fn remove(&self, handle: u32) {
+        let resource = unsafe { Box::from_raw(handle as *mut BFResourcePtr) };
+        Self::free_contents(&resource);
+    }
+}
+
+/// Archive name and contents
+type StoredResource = (String, Box<[u8]>);
+
+/// Resources kept in a map, for targets where the game's 32 bit pointers can't address them
+#[derive(Default)]
+pub struct MemoryStore {
+    next_handle: AtomicU32,
+    resources: Mutex<HashMap<u32, StoredResource>>,
+}
+
+impl MemoryStore {
+    fn with_resource<R>(&self, handle: u32, f: impl FnOnce(&mut StoredResource) -> R) -> R {
+        let mut resources = self.resources.lock().unwrap();
+        let resource = resources.get_mut(&handle).unwrap_or_else(|| panic!("Invalid resource handle {:#x}", handle));
+        f(resource)
+    }
+}
+
+impl ResourceStore for MemoryStore {
+    fn insert(&self, zip_name: &str, _resource_name: &str, contents: Box<[u8]>) -> anyhow::Result<u32> {
+        // Handles start at 1 so 0 is never one
+        let handle = self.next_handle.fetch_add(1, Ordering::Relaxed) + 1;
+        self.resources.lock().unwrap().insert(handle, (zip_name.to_string(), contents));
+        Ok(handle)
+    }
+
+    fn read(&self, handle: u32) -> (String, Box<[u8]>) {
+        self.with_resource(handle, |(zip_name, contents)| (zip_name.clone(), contents.clone()))
+    }
+
+    fn content_size(&self, handle: u32) -> u32 {
+        self.with_resource(handle, |(_, contents)| contents.len() as u32)
+    }
+
+    fn replace(&self, handle: u32, contents: Box<[u8]>) {
+        self.with_resource(handle, |resource| resource.1 = contents)
+    }
+
+    fn remove(&self, handle: u32) {
+        self.resources.lock().unwrap().remove(&handle);
+    }
+}
+
+#[cfg(target_pointer_width = "32")]
+static STORE: BFResourceStore = BFResourceStore;
+
+#[cfg(not(target_pointer_width = "32"))]
+static STORE: LazyLock<MemoryStore> = LazyLock::new(MemoryStore::default);
+
+/// The store the resource map uses, [`BFResourceStore`] wherever its pointers fit in 32 bits
+pub fn store() -> &'static dyn ResourceStore {
+    #[cfg(target_pointer_width = "32")]
+    return &STORE;
+    #[cfg(not(target_pointer_width = "32"))]
+    return &*STORE;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_store(store: &dyn ResourceStore) {
+        let handle = store.insert("zip::./test.ztd", "animals/test.ai", b"[Habitat]\n".as_slice().into()).unwrap();
+        assert_eq!(store.content_size(handle), 10);
+        assert_eq!(store.read(handle), ("zip::./test.ztd".to_string(), b"[Habitat]\n".as_slice().into()));
+
+        store.replace(handle, Box::new([]));
+        assert_eq!(store.content_size(handle), 0);
+        store.replace(handle, b"[Members]\ncBiome = 1\n".as_slice().into());
+        assert_eq!(store.read(handle).1.as_ref(), b"[Members]\ncBiome = 1\n");
+
+        let other = store.insert("<disabled>", "animals/other.ai", Box::new([])).unwrap();
+        assert_ne!(handle, other);
+        store.remove(handle);
+        assert_eq!(store.read(other), ("<disabled>".to_string(), Box::new([]) as Box<[u8]>));
+        store.remove(other);
+    }
+
+    #[test]
+    fn test_memory_store() {
+        check_store(&MemoryStore::default());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid resource handle")]
+    fn test_memory_store_removed_handle() {
+        let store = MemoryStore::default();
+        let handle = store.insert("zip::./test.ztd", "animals/test.ai", Box::new([1, 2, 3])).unwrap();
+        store.remove(handle);
+        store.read(handle);
+    }
+
+    #[cfg(target_pointer_width = "32")]
+    #[test]
+    fn test_bf_resource_store() {
+        check_store(&BFResourceStore);
+    }
+}
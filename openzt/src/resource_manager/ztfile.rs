@@ -5,8 +5,8 @@ use openzt_configparser::ini::{Ini, WriteOptions};
 
 use crate::{
     animation::Animation,
-    resource_manager::{bfresourcemgr::BFResourcePtr, lazyresourcemap::get_file_ptr},
-    util::{get_from_memory, save_to_memory, ZTString},
+    resource_manager::{bfresourcemgr::BFResourcePtr, lazyresourcemap::get_file_ptr, resource_store::store},
+    util::ZTString,
 };
 
 #[derive(Debug, Clone)]
@@ -143,6 +143,20 @@ impl From<BFResourcePtr> for ZTFile {
 }
 
 impl ZTFile {
+    pub fn file_type(&self) -> ZTFileType {
+        match self {
+            ZTFile::Text(_, type_, _) | ZTFile::RawBytes(_, type_, _) => *type_,
+        }
+    }
+
+    /// The file's bytes, without the nul terminating text
+    pub fn into_contents(self) -> Box<[u8]> {
+        match self {
+            ZTFile::Text(data, _, _) => data.into_bytes().into_boxed_slice(),
+            ZTFile::RawBytes(data, _, _) => data,
+        }
+    }
+
     pub fn builder() -> ZTFileBuilder<false, false, false, false> {
         ZTFileBuilder {
             file_name: None,
@@ -228,16 +242,15 @@ impl ZTFileBuilder<true, true, true, true> {
     }
 }
 
+/// Rewrites a loaded resource's contents in place, the game may already hold its handle
 pub fn modify_ztfile<F>(file_name: &str, modifier: F) -> anyhow::Result<()>
 where
-    F: Fn(&mut BFResourcePtr) -> anyhow::Result<()>,
+    F: Fn(&[u8]) -> anyhow::Result<Box<[u8]>>,
 {
-    let bf_resource_ptr_ptr = get_file_ptr(file_name).ok_or_else(|| anyhow!("File not found: {}", file_name))?;
-    let mut bf_resource_ptr = get_from_memory::<BFResourcePtr>(bf_resource_ptr_ptr);
+    let handle = get_file_ptr(file_name).ok_or_else(|| anyhow!("File not found: {}", file_name))?;
+    let (_, contents) = store().read(handle);
 
-    modifier(&mut bf_resource_ptr)?;
-
-    save_to_memory::<BFResourcePtr>(bf_resource_ptr_ptr, bf_resource_ptr.clone());
+    store().replace(handle, modifier(&contents)?);
 
     Ok(())
 }
@@ -246,10 +259,8 @@ pub fn modify_ztfile_as_ini<F>(file_name: &str, modifier: F) -> anyhow::Result<(
 where
     F: Fn(&mut Ini) -> anyhow::Result<()>,
 {
-    modify_ztfile(file_name, |file: &mut BFResourcePtr| {
-        let c_string = unsafe { CString::from_raw(file.data_ptr as *mut i8) };
-        let bytes = c_string.as_bytes();
-        let decoded_string = crate::encoding_utils::decode_game_text(bytes);
+    modify_ztfile(file_name, |contents| {
+        let decoded_string = crate::encoding_utils::decode_game_text(contents);
         let mut cfg = Ini::new_cs();
         cfg.set_comment_symbols(&[';', '#', ':']);
 
@@ -261,11 +272,9 @@ where
         write_options.space_around_delimiters = true;
         write_options.blank_lines_between_sections = 1;
         let new_string = cfg.pretty_writes(&write_options);
-        file.content_size = new_string.len() as u32;
 
         let new_c_string = CString::new(new_string).with_context(|| format!("Error converting ini to CString after modifying {}", file_name))?;
-        file.data_ptr = new_c_string.into_raw() as u32;
-        Ok(())
+        Ok(new_c_string.into_bytes().into_boxed_slice())
     })
 }
 
@@ -273,58 +282,13 @@ pub fn modify_ztfile_as_animation<F>(file_name: &str, modifier: F) -> anyhow::Re
 where
     F: Fn(&mut Animation) -> anyhow::Result<()>,
 {
-    modify_ztfile(file_name, |file: &mut BFResourcePtr| {
-        let data_vec: Box<[u8]> = unsafe { Box::from_raw(slice::from_raw_parts_mut(file.data_ptr as *mut _, file.content_size as usize)) };
-        let mut animation = Animation::parse(&data_vec)?;
+    modify_ztfile(file_name, |contents| {
+        let mut animation = Animation::parse(contents)?;
 
         modifier(&mut animation)?;
 
-        let (new_animation_bytes, length) = animation.write()?;
-        let boxed_slice = new_animation_bytes.into_boxed_slice();
-        let data_ptr = boxed_slice.as_ptr() as u32;
-        std::mem::forget(boxed_slice);
-        file.data_ptr = data_ptr;
-        file.content_size = length as u32;
-        Ok(())
+        let (mut new_animation_bytes, length) = animation.write()?;
+        new_animation_bytes.truncate(length);
+        Ok(new_animation_bytes.into_boxed_slice())
     })
 }
-
-pub fn ztfile_to_raw_resource(path: &str, file_name: String, ztfile: ZTFile) -> anyhow::Result<(String, ZTFileType, u32)> {
-    let mut ztd_path = path.to_string();
-    ztd_path = ztd_path.replace('\\', "/").replace("./", "zip::./");
-    let lowercase_filename = file_name.to_lowercase();
-
-    let bf_zip_name = CString::new(ztd_path.clone())
-        .with_context(|| format!("Error converting zip name to CString: {}", ztd_path))?;
-
-    let bf_resource_name = CString::new(lowercase_filename.clone())
-        .with_context(|| format!("Error converting resource name to CString: {}", lowercase_filename))?;
-
-    match ztfile {
-        ZTFile::Text(data, type_, length) => {
-            let ptr = data.into_raw() as u32;
-            let resource_ptr = Box::into_raw(Box::new(BFResourcePtr {
-                num_refs: 100, // We set this very high to prevent the game from unloading the resource
-                bf_zip_name: bf_zip_name.into(),
-                bf_resource_name: bf_resource_name.into(),
-                data_ptr: ptr,
-                content_size: length,
-            }));
-
-            Ok((lowercase_filename, type_, resource_ptr as _))
-        }
-        ZTFile::RawBytes(data, type_, length) => {
-            let ptr = data.as_ptr() as u32;
-            std::mem::forget(data);
-            let resource_ptr = Box::into_raw(Box::new(BFResourcePtr {
-                num_refs: 100, // We set this very high to prevent the game from unloading the resource
-                bf_zip_name: bf_zip_name.into(),
-                bf_resource_name: bf_resource_name.into(),
-                data_ptr: ptr,
-                content_size: length,
-            }));
-
-        Ok((lowercase_filename, type_, resource_ptr as _))
-        }
-    }
-}
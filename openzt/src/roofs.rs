@@ -5,61 +5,36 @@ use tracing::info;
 
 use crate::resource_manager::openzt_mods::extensions::{register_tag, EntityScope, list_extensions_with_tag, get_extension};
 use crate::resource_manager::openzt_mods::legacy_attributes::LegacyEntityType;
-use crate::runtime_state;
 use crate::shortcuts::{Ctrl, R};
-use crate::util::get_from_memory;
+use crate::util::{get_from_memory, save_to_memory};
 use crate::ztworldmgr::read_zt_world_mgr_from_global;
+use crate::zoo_state::{self, OPENZT_NAMESPACE};
 
-#[cfg(target_os = "windows")]
-use openzt_detour_macro::detour_mod;
-
-/// Detour module for PLACE_ENTITY_ON_MAP_1
+/// Post handler for PLACE_ENTITY_ON_MAP
 ///
-/// This detour ensures that newly placed roof entities are hidden
-/// if the roofs_hidden state is true.
-#[cfg(target_os = "windows")]
-#[detour_mod]
-pub mod hooks_place_entity {
-    use super::*;
-    use openzt_detour::gen::ztmapview::PLACE_ENTITY_ON_MAP_1;
-
-    /// Detour for PLACE_ENTITY_ON_MAP_1
-    ///
-    /// After placing an entity, checks if it's a roof and hides it if needed.
-    /// The second parameter (entity_ptr) is the BFEntity that was just placed.
-    #[detour(PLACE_ENTITY_ON_MAP_1)]
-    unsafe extern "thiscall" fn place_entity_on_map_detour(
-        _this: u32,
-        entity_ptr: u32,
-        _pos: f32,
-        _rotation: i32,
-    ) -> u32 {
-        // Call the original function first to place the entity
-        let result = PLACE_ENTITY_ON_MAP_1_DETOUR.call(_this, entity_ptr, _pos, _rotation);
-
-        // Only proceed if placement succeeded and we have a valid entity pointer
-        if result != 0 && entity_ptr != 0 {
-            // Check if roofs are currently hidden
-            if runtime_state::get_bool("roofs_hidden") {
-                // Check if this entity has the "roof" tag
-                if let Some(base) = crate::resource_manager::openzt_mods::extensions::get_entity_base(entity_ptr) {
-                    let roof_extensions = list_extensions_with_tag("roof");
-                    for ext_key in &roof_extensions {
-                        if let Some(record) = get_extension(ext_key) {
-                            if record.base == base {
-                                // This is a roof entity, hide it
-                                let visible_ptr = (entity_ptr + 0x13f) as *mut u8;
-                                *visible_ptr = 0;
-                                info!("Auto-hid newly placed roof entity: {} (ptr: 0x{:x})", base, entity_ptr);
-                                break;
-                            }
-                        }
+/// Ensures that newly placed roof entities are hidden if the roofs_hidden state is true.
+/// The second parameter (entity_ptr) is the BFEntity that was just placed.
+fn hide_placed_roof(&(_, entity_ptr, _, _): &(u32, u32, f32, i32), result: &mut u32) {
+    // Only proceed if placement succeeded and we have a valid entity pointer
+    if *result == 0 || entity_ptr == 0 {
+        return;
+    }
+    // Check if roofs are currently hidden
+    if zoo_state::get_bool(OPENZT_NAMESPACE, "roofs_hidden") {
+        // Check if this entity has the "roof" tag
+        if let Some(base) = &crate::resource_manager::openzt_mods::extensions::get_entity_base(entity_ptr) {
+            let roof_extensions = list_extensions_with_tag("roof");
+            for ext_key in &roof_extensions {
+                if let Some(record) = get_extension(ext_key) {
+                    if &record.base == base {
+                        // This is a roof entity, hide it
+                        save_to_memory::<u8>(entity_ptr + 0x13f, 0);
+                        info!("Auto-hid newly placed roof entity: {} (ptr: 0x{:x})", base, entity_ptr);
+                        break;
                     }
                 }
             }
         }
-
-        result
     }
 }
 
@@ -125,10 +100,7 @@ pub fn hide_roofs() {
                     // Check if this entity type has the roof tag
                     if roof_bases.contains(&base) {
                         // Set visible flag to 0 (hidden) at offset 0x13f
-                        unsafe {
-                            let visible_ptr = (entity_ptr + 0x13f) as *mut u8;
-                            *visible_ptr = 0;
-                        }
+                        save_to_memory::<u8>(entity_ptr + 0x13f, 0);
                         hidden_count += 1;
                         info!("Hid roof entity: {} (ptr: 0x{:x})", base, entity_ptr);
                     }
@@ -186,10 +158,7 @@ pub fn show_roofs() {
         if entity_ptr != 0 {
             if let Some(base) = crate::resource_manager::openzt_mods::extensions::get_entity_base(entity_ptr) {
                 if roof_bases.contains(&base) {
-                    unsafe {
-                        let visible_ptr = (entity_ptr + 0x13f) as *mut u8;
-                        *visible_ptr = 1;  // Set visible
-                    }
+                    save_to_memory::<u8>(entity_ptr + 0x13f, 1);  // Set visible
                     shown_count += 1;
                 }
             }
@@ -204,10 +173,11 @@ pub fn show_roofs() {
 /// Toggle roof visibility
 ///
 /// This function toggles the visibility of all roof-tagged entities.
-/// It uses the runtime state store to track the current state and calls
-/// the appropriate hide/show function.
+/// The current state is kept in the zoo state store, so it is saved with the zoo,
+/// and the appropriate hide/show function is called.
 pub fn toggle_roofs() {
-    let is_hidden = runtime_state::toggle_bool("roofs_hidden");
+    let is_hidden = !zoo_state::get_bool(OPENZT_NAMESPACE, "roofs_hidden");
+    zoo_state::set(OPENZT_NAMESPACE, "roofs_hidden", is_hidden);
 
     if is_hidden {
         info!("Toggling roofs: HIDING");
@@ -232,13 +202,8 @@ pub fn init() {
         Err(e) => tracing::error!("Failed to register roof tag: {}", e),
     }
 
-    // Initialize the placement detour (Windows only)
-    #[cfg(target_os = "windows")]
-    if let Err(e) = unsafe { hooks_place_entity::init_detours() } {
-        tracing::error!("Failed to initialize roofs placement detour: {}", e);
-    } else {
-        info!("Initialized roofs placement detour");
-    }
+    // Hide roofs as they're placed
+    crate::hook_chains::PLACE_ENTITY_ON_MAP.post("roofs", 0, hide_placed_roof);
 
     // Register Ctrl+R shortcut to toggle roof visibility
     crate::shortcut!(
@@ -1,69 +1,93 @@
+use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::LazyLock;
 use std::sync::Mutex;
-use mlua::Lua;
+use mlua::{IntoLua, Lua, Table};
 use tracing::info;
 
+use crate::resource_manager::mod_config::{get_openzt_config, SandboxConfig};
 use crate::resource_manager::openzt_mods::legacy_attributes::{self, LegacyEntityType};
 use crate::resource_manager::openzt_mods::extensions;
 
+pub mod api_docs;
+pub mod sandbox;
+pub mod scheduler;
+pub mod userdata;
+
+pub use api_docs::{LuaType, LuaTypes};
+use api_docs::LuaFunctionMeta;
+pub use sandbox::Capability;
+use sandbox::ExecutionLimits;
+
 /// Macro to simplify registering Lua functions
 ///
+/// The fourth argument is the [`Capability`] group the function belongs to, which decides
+/// which sandboxed environments can call it.
+///
 /// # Usage
 /// ```rust
 /// use openztlib::lua_fn;
 /// 
 /// // No arguments
-/// lua_fn!("my_func", "Does something", "my_func()", || {
+/// lua_fn!("my_func", "Does something", "my_func()", ReadWorld, || {
 ///     Ok("result")
 /// });
 ///
 /// // Single argument
-/// lua_fn!("my_func", "Does something", "my_func(arg)", |arg: String| {
+/// lua_fn!("my_func", "Does something", "my_func(arg)", ReadWorld, |arg: String| {
 ///     Ok(format!("Got: {}", arg))
 /// });
 ///
 /// // Multiple arguments
-/// lua_fn!("my_func", "Does something", "my_func(a, b)", |a: u32, b: String| {
+/// lua_fn!("my_func", "Does something", "my_func(a, b)", ModifyWorld, |a: u32, b: String| {
 ///     Ok(format!("{}: {}", a, b))
 /// });
 ///
 /// // Optional argument
-/// lua_fn!("my_func", "Does something", "my_func([opt])", |opt: Option<String>| {
+/// lua_fn!("my_func", "Does something", "my_func([opt])", ReadWorld, |opt: Option<String>| {
 ///     Ok(opt.unwrap_or_else(|| "default".to_string()))
 /// });
 /// ```
 #[macro_export]
 macro_rules! lua_fn {
     // No arguments
-    ($name:expr, $desc:expr, $sig:expr, || $body:block) => {
+    ($name:expr, $desc:expr, $sig:expr, $cap:ident, || $body:block) => {{
+        let function: fn(&::mlua::Lua, ()) -> ::mlua::Result<_> = |_, ()| $body;
         $crate::scripting::add_lua_function(
             $name,
             $desc,
             $sig,
-            |lua| lua.create_function(|_, ()| $body).unwrap()
+            $crate::scripting::Capability::$cap,
+            $crate::scripting::LuaTypes::new().returns_of(function),
+            move |lua| lua.create_function(function).unwrap()
         ).unwrap()
-    };
+    }};
 
     // Single argument
-    ($name:expr, $desc:expr, $sig:expr, |$arg:ident : $arg_ty:ty| $body:block) => {
+    ($name:expr, $desc:expr, $sig:expr, $cap:ident, |$arg:ident : $arg_ty:ty| $body:block) => {{
+        let function: fn(&::mlua::Lua, $arg_ty) -> ::mlua::Result<_> = |_, $arg: $arg_ty| $body;
         $crate::scripting::add_lua_function(
             $name,
             $desc,
             $sig,
-            |lua| lua.create_function(|_, $arg: $arg_ty| $body).unwrap()
+            $crate::scripting::Capability::$cap,
+            $crate::scripting::LuaTypes::new().param::<$arg_ty>(stringify!($arg)).returns_of(function),
+            move |lua| lua.create_function(function).unwrap()
         ).unwrap()
-    };
+    }};
 
     // Multiple arguments (2+)
-    ($name:expr, $desc:expr, $sig:expr, |$($arg:ident : $arg_ty:ty),+ $(,)?| $body:block) => {
+    ($name:expr, $desc:expr, $sig:expr, $cap:ident, |$($arg:ident : $arg_ty:ty),+ $(,)?| $body:block) => {{
+        let function: fn(&::mlua::Lua, ($($arg_ty),+)) -> ::mlua::Result<_> = |_, ($($arg),+): ($($arg_ty),+)| $body;
         $crate::scripting::add_lua_function(
             $name,
             $desc,
             $sig,
-            |lua| lua.create_function(|_, ($($arg),+): ($($arg_ty),+)| $body).unwrap()
+            $crate::scripting::Capability::$cap,
+            $crate::scripting::LuaTypes::new()$(.param::<$arg_ty>(stringify!($arg)))+.returns_of(function),
+            move |lua| lua.create_function(function).unwrap()
         ).unwrap()
-    };
+    }};
 }
 
 static LUA_CONTEXT: LazyLock<Mutex<Lua>> = LazyLock::new(|| Mutex::new(Lua::new()));
@@ -72,42 +96,176 @@ static LUA_CONTEXT: LazyLock<Mutex<Lua>> = LazyLock::new(|| Mutex::new(Lua::new(
 static LUA_FUNCTION_METADATA: LazyLock<Mutex<Vec<LuaFunctionMeta>>> =
     LazyLock::new(|| Mutex::new(Vec::new()));
 
-struct LuaFunctionMeta {
-    name: String,
-    description: String,
-    signature: String,
-}
-
-/// Registers a Lua function with metadata for help()
+/// Registers a Lua function with metadata for help() and the generated API reference
+///
+/// Functions are registered in the Lua globals, which only serve as the source for
+/// sandboxed environments; see [`sandbox::create_environment`]. A dotted name such as
+/// `world.entities` registers the function in the `world` table. [`lua_fn!`](crate::lua_fn)
+/// fills in `types` from the closure, other callers spell them out:
+///
+/// ```rust
+/// use openztlib::scripting::{add_lua_function, Capability, LuaTypes};
+///
+/// add_lua_function(
+///     "greet",
+///     "Greets someone",
+///     "greet([name])",
+///     Capability::Core,
+///     LuaTypes::new().param::<Option<String>>("name").returns::<String>(),
+///     |lua| lua.create_function(|_, name: Option<String>| Ok(format!("Hello {}", name.unwrap_or_default()))).unwrap(),
+/// ).unwrap();
+/// ```
 pub fn add_lua_function(
     name: &str,
     description: &str,
     signature: &str,
-    func_closure: fn(&Lua) -> mlua::Function
+    capability: Capability,
+    types: LuaTypes,
+    func_closure: impl FnOnce(&Lua) -> mlua::Function
 ) -> Result<(), mlua::Error> {
     // Add metadata
     LUA_FUNCTION_METADATA.lock().unwrap().push(LuaFunctionMeta {
         name: name.to_string(),
         description: description.to_string(),
         signature: signature.to_string(),
+        capability,
+        types,
     });
 
     // Register function in Lua global scope
     let lua = LUA_CONTEXT.lock().unwrap();
     let func = func_closure(&lua);
-    let globals = lua.globals();
-    globals.set(name, func)
+    sandbox::set_path(&lua, &lua.globals(), name, func)
+}
+
+/// A handle to the shared Lua state, for running code without holding the context lock
+///
+/// Lua calls back into Rust functions that may themselves need the state, so console
+/// commands and mod script callbacks are run through a handle instead of the locked context.
+pub(crate) fn lua_handle() -> Lua {
+    LUA_CONTEXT.lock().unwrap().clone()
+}
+
+/// Create a sandboxed environment with the stdlib modules and capabilities allowed by `config`
+pub(crate) fn create_sandbox(lua: &Lua, config: &SandboxConfig) -> mlua::Result<Table> {
+    let functions: Vec<(String, Capability)> = LUA_FUNCTION_METADATA
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|meta| (meta.name.clone(), meta.capability))
+        .collect();
+    sandbox::create_environment(lua, config, &functions)
+}
+
+/// The console's environment, kept between commands so variables persist
+static CONSOLE_SANDBOX: LazyLock<(Table, ExecutionLimits)> = LazyLock::new(|| {
+    let config = get_openzt_config().scripting.console;
+    let env = create_sandbox(&lua_handle(), &config).expect("Failed to create console sandbox");
+    (env, ExecutionLimits::from(&config))
+});
+
+/// Evaluates console code in the console sandbox
+///
+/// The code runs as a job, so it can wait across ticks; in that case the result says which job it became.
+fn eval_console(code: &str) -> mlua::Result<mlua::Value> {
+    // Not holding the context lock, functions called from the console may run mod script callbacks
+    let lua = lua_handle();
+    let (env, limits) = &*CONSOLE_SANDBOX;
+    let chunk = |source: &str| lua.load(source.to_string()).set_name("=console").set_environment(env.clone()).into_function();
+    // Like Chunk::eval, try the code as an expression first so `get_date()` returns its result
+    let function = chunk(&format!("return {}", code)).or_else(|_| chunk(code))?;
+    let name = format!("console: {}", code.lines().next().unwrap_or_default());
+    match scheduler::start(&lua, &name, function, mlua::MultiValue::new(), limits)? {
+        scheduler::JobStart::Finished(values) => Ok(values.into_iter().next().unwrap_or(mlua::Value::Nil)),
+        scheduler::JobStart::Waiting(id) => format!("Started job {}, see jobs()", id).into_lua(&lua),
+    }
 }
 
 /// Executes Lua code and returns the result as a string
 pub fn execute_lua(code: &str) -> Result<String, String> {
-    let lua = LUA_CONTEXT.lock().unwrap();
-    match lua.load(code).eval::<mlua::Value>() {
+    match eval_console(code) {
         Ok(value) => Ok(lua_value_to_string(&value)),
         Err(e) => Err(format!("Lua error: {}", e))
     }
 }
 
+/// Executes Lua code and returns the result as JSON
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` - The chunk's return value, see [`lua_value_to_json`]
+/// * `Err(mlua::Error)` - The Lua error, see [`split_lua_error`]
+pub fn execute_lua_json(code: &str) -> Result<serde_json::Value, mlua::Error> {
+    eval_console(code).map(|value| lua_value_to_json(&value))
+}
+
+/// Split a Lua error into its message and stack traceback, if it has one
+pub fn split_lua_error(error: &mlua::Error) -> (String, Option<String>) {
+    match error {
+        mlua::Error::CallbackError { traceback, cause } => (cause.to_string(), Some(traceback.clone())),
+        other => {
+            let message = other.to_string();
+            match message.split_once("\nstack traceback:") {
+                Some((message, traceback)) => (message.to_string(), Some(format!("stack traceback:{}", traceback))),
+                None => (message, None),
+            }
+        }
+    }
+}
+
+/// Tables nested deeper than this are cut off, which also stops reference cycles
+const MAX_JSON_DEPTH: usize = 32;
+
+/// Converts a Lua value to JSON
+///
+/// * nil becomes null, integers and floats stay distinct JSON numbers (non-finite floats become strings)
+/// * Tables with only the keys 1..n become arrays, other tables become objects with string keys
+/// * Functions, userdata and threads become strings such as "<function>"
+/// * Errors become `{"error": message, "traceback": ...}`
+pub fn lua_value_to_json(value: &mlua::Value) -> serde_json::Value {
+    lua_value_to_json_at_depth(value, 0)
+}
+
+fn lua_value_to_json_at_depth(value: &mlua::Value, depth: usize) -> serde_json::Value {
+    use serde_json::Value as Json;
+
+    match value {
+        mlua::Value::Nil => Json::Null,
+        mlua::Value::Boolean(b) => Json::Bool(*b),
+        mlua::Value::Integer(i) => Json::from(*i),
+        mlua::Value::Number(n) => serde_json::Number::from_f64(*n).map(Json::Number).unwrap_or_else(|| Json::String(n.to_string())),
+        mlua::Value::String(s) => Json::String(s.to_string_lossy().to_string()),
+        mlua::Value::Table(t) => {
+            if depth >= MAX_JSON_DEPTH {
+                return Json::String("<table: max depth reached>".to_string());
+            }
+            let pairs: Vec<(mlua::Value, mlua::Value)> = t.pairs::<mlua::Value, mlua::Value>().flatten().collect();
+
+            // A sequence 1..n (including the empty table) is an array
+            let len = t.raw_len();
+            if pairs.len() == len && pairs.iter().all(|(k, _)| matches!(k, mlua::Value::Integer(i) if *i >= 1 && *i as usize <= len)) {
+                return Json::Array((1..=len).map(|i| lua_value_to_json_at_depth(&t.raw_get(i).unwrap_or(mlua::Value::Nil), depth + 1)).collect());
+            }
+
+            let map = pairs
+                .iter()
+                .map(|(k, v)| {
+                    let key = match k {
+                        mlua::Value::String(s) => s.to_string_lossy().to_string(),
+                        other => lua_value_to_string(other),
+                    };
+                    (key, lua_value_to_json_at_depth(v, depth + 1))
+                })
+                .collect();
+            Json::Object(map)
+        }
+        mlua::Value::Error(e) => {
+            let (message, traceback) = split_lua_error(e);
+            serde_json::json!({ "error": message, "traceback": traceback })
+        }
+        other => Json::String(lua_value_to_string(other)),
+    }
+}
+
 /// Converts a Lua value to a string representation
 fn lua_value_to_string(value: &mlua::Value) -> String {
     match value {
@@ -132,7 +290,8 @@ fn lua_value_to_string(value: &mlua::Value) -> String {
         },
         mlua::Value::Function(_) => "<function>".to_string(),
         mlua::Value::Thread(_) => "<thread>".to_string(),
-        mlua::Value::UserData(_) => "<userdata>".to_string(),
+        // Userdata with a __tostring metamethod, such as entities, describe themselves
+        mlua::Value::UserData(_) => value.to_string().unwrap_or_else(|_| "<userdata>".to_string()),
         mlua::Value::LightUserData(_) => "<lightuserdata>".to_string(),
         mlua::Value::Error(e) => format!("<error: {}>", e),
         _ => "<unknown>".to_string(),
@@ -141,9 +300,35 @@ fn lua_value_to_string(value: &mlua::Value) -> String {
 
 pub fn init() {
     info!("Initializing Lua scripting");
+    register_lua_functions();
+    scheduler::init();
+}
+
+/// Registers the Lua functions of every module, without installing the detours their init functions do
+#[cfg(test)]
+pub(crate) fn register_all_lua_functions() {
+    register_lua_functions();
+    scheduler::init();
+    crate::events::register_lua_functions();
+    crate::zoo_state::register_lua_functions();
+    crate::string_registry::register_lua_functions();
+    crate::settings::register_lua_functions();
+    crate::game_memory::init();
+    crate::detours::init();
+    crate::binary_patch::init();
+    crate::expansions::register_lua_functions();
+    crate::resource_manager::commands::init_commands();
+    crate::ztui::init();
+    crate::ztworldmgr::register_lua_functions();
+    crate::bfentitytype::init();
+    crate::ztadvterrainmgr::init();
+    crate::ztgamemgr::init();
+    crate::zthabitatmgr::register_lua_functions();
+}
 
+fn register_lua_functions() {
     // Register the continue() function
-    lua_fn!("click_continue", "Clicks the continue button", "continue()", || {
+    lua_fn!("click_continue", "Clicks the continue button", "continue()", ModifyWorld, || {
         unsafe {
             openzt_detour::gen::ztui::CLICK_CONTINUE.original()();
         }
@@ -151,7 +336,7 @@ pub fn init() {
     });
 
     // Register the help() function
-    lua_fn!("help", "Lists available Lua functions or searches by keyword", "help([search_term])", |search: Option<String>| {
+    lua_fn!("help", "Lists available Lua functions or searches by keyword", "help([search_term])", Core, |search: Option<String>| {
         let metadata = LUA_FUNCTION_METADATA.lock().unwrap();
         let filtered: Vec<&LuaFunctionMeta> = match &search {
             Some(term) => metadata.iter()
@@ -170,16 +355,27 @@ pub fn init() {
 
         let mut result = String::new();
         for meta in filtered {
-            result.push_str(&format!("{} - {}\n  Usage: {}\n\n",
-                meta.name, meta.description, meta.signature));
+            result.push_str(&format!("{} - {}\n  Usage: {}\n  Capability: {}\n\n",
+                meta.name, meta.description, meta.signature, meta.capability.as_str()));
         }
         Ok(result)
     });
 
+    // Register the list_lua_functions() function, used by openzt-console for tab completion
+    lua_fn!("list_lua_functions", "Returns a table of registered Lua functions with their signatures", "list_lua_functions()", Core, || {
+        let metadata = LUA_FUNCTION_METADATA.lock().unwrap();
+        let functions: Vec<HashMap<&str, String>> = metadata
+            .iter()
+            .map(|meta| HashMap::from([("name", meta.name.clone()), ("signature", meta.signature.clone())]))
+            .collect();
+        Ok(functions)
+    });
+
     // Register the get_legacy_attribute() function
     lua_fn!("get_legacy_attribute",
         "Get a legacy entity attribute (name_id currently supported)",
         "get_legacy_attribute(entity_type, entity_name, [subtype], attribute)",
+        ReadWorld,
         |entity_type: String, entity_name: String, args: mlua::Variadic<String>| {
             // Parse variadic args: either (subtype, attribute) or just (attribute)
             let (subtype, attribute) = if args.len() == 2 {
@@ -208,6 +404,7 @@ pub fn init() {
     lua_fn!("list_legacy_entities",
         "List all legacy entities (optionally filtered by type)",
         "list_legacy_entities([entity_type])",
+        ReadWorld,
         |entity_type: Option<String>| {
             use crate::resource_manager::openzt_mods::legacy_attributes::LEGACY_ATTRIBUTES_MAP;
 
@@ -331,6 +528,7 @@ pub fn init() {
     lua_fn!("list_legacy_types",
         "List all available legacy entity types with counts",
         "list_legacy_types()",
+        ReadWorld,
         || {
             use crate::resource_manager::openzt_mods::legacy_attributes::LEGACY_ATTRIBUTES_MAP;
 
@@ -374,6 +572,7 @@ pub fn init() {
     lua_fn!("get_extension",
         "Get extension data by extension key (e.g., 'animals.elephant')",
         "get_extension(extension_key)",
+        ReadWorld,
         |extension_key: String| {
             match extensions::get_extension(&extension_key) {
                 Some(record) => {
@@ -404,6 +603,7 @@ pub fn init() {
     lua_fn!("get_extension_by_base",
         "Get extension data by base entity (e.g., 'legacy.animals.elephant')",
         "get_extension_by_base(base)",
+        ReadWorld,
         |base: String| {
             match extensions::get_extension_by_base(&base) {
                 Some(record) => {
@@ -434,6 +634,7 @@ pub fn init() {
     lua_fn!("get_extension_tags",
         "Get tags for an extension by key",
         "get_extension_tags(extension_key)",
+        ReadWorld,
         |extension_key: String| {
             match extensions::get_entity_tags(&extension_key) {
                 Ok(tags) => Ok(if tags.is_empty() { "(no tags)".to_string() } else { tags.join(", ") }),
@@ -446,6 +647,7 @@ pub fn init() {
     lua_fn!("get_extension_attribute",
         "Get a specific attribute for an extension",
         "get_extension_attribute(extension_key, attribute_key)",
+        ReadWorld,
         |extension_key: String, attribute_key: String| {
             match extensions::get_entity_attribute(&extension_key, &attribute_key) {
                 Ok(Some(value)) => Ok(value),
@@ -459,6 +661,7 @@ pub fn init() {
     lua_fn!("extension_has_tag",
         "Check if an extension has a specific tag",
         "extension_has_tag(extension_key, tag)",
+        ReadWorld,
         |extension_key: String, tag: String| {
             match extensions::entity_has_tag(&extension_key, &tag) {
                 Ok(has_tag) => Ok(if has_tag { "true" } else { "false" }.to_string()),
@@ -471,6 +674,7 @@ pub fn init() {
     lua_fn!("list_extensions_with_tag",
         "List all extensions that have a specific tag",
         "list_extensions_with_tag(tag)",
+        ReadWorld,
         |tag: String| {
             let exts = extensions::list_extensions_with_tag(&tag);
             Ok(if exts.is_empty() {
@@ -485,6 +689,7 @@ pub fn init() {
     lua_fn!("list_registered_tags",
         "List all registered tags (optionally filtered by entity type)",
         "list_registered_tags([entity_type])",
+        ReadWorld,
         |entity_type: Option<String>| {
             use crate::resource_manager::openzt_mods::extensions::EXTENSION_REGISTRY;
 
@@ -519,6 +724,7 @@ pub fn init() {
     lua_fn!("list_registered_attributes",
         "List all registered attributes (optionally filtered by entity type)",
         "list_registered_attributes([entity_type])",
+        ReadWorld,
         |entity_type: Option<String>| {
             use crate::resource_manager::openzt_mods::extensions::EXTENSION_REGISTRY;
 
@@ -553,9 +759,69 @@ pub fn init() {
     lua_fn!("hide_roofs",
         "Hide all entities tagged with 'roof'",
         "hide_roofs()",
+        ModifyWorld,
         || {
             crate::roofs::hide_roofs();
             Ok(("Roofs hidden".to_string(), None::<String>))
         }
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn eval_json(code: &str) -> serde_json::Value {
+        let lua = Lua::new();
+        lua_value_to_json(&lua.load(code).eval::<mlua::Value>().unwrap())
+    }
+
+    #[test]
+    fn test_lua_value_to_json_scalars() {
+        assert_eq!(eval_json("return nil"), json!(null));
+        assert_eq!(eval_json("return true"), json!(true));
+        assert_eq!(eval_json("return 42"), json!(42));
+        assert_eq!(eval_json("return 1.5"), json!(1.5));
+        assert_eq!(eval_json("return 'hi'"), json!("hi"));
+        assert_eq!(eval_json("return 1/0"), json!("inf"));
+        assert_eq!(eval_json("return print"), json!("<function>"));
+    }
+
+    #[test]
+    fn test_lua_value_to_json_tables() {
+        assert_eq!(eval_json("return {3, 2, 1}"), json!([3, 2, 1]));
+        assert_eq!(eval_json("return {}"), json!([]));
+        assert_eq!(eval_json("return {a = 1, b = {c = {true}}}"), json!({"a": 1, "b": {"c": [true]}}));
+        // Sparse or mixed tables are objects with stringified keys
+        assert_eq!(eval_json("return {[1] = 'a', [3] = 'c'}"), json!({"1": "a", "3": "c"}));
+        assert_eq!(eval_json("return {'a', x = 'y'}"), json!({"1": "a", "x": "y"}));
+    }
+
+    #[test]
+    fn test_lua_value_to_json_cycles_are_cut_off() {
+        let value = eval_json("local t = {} t.self = t return t");
+        let mut depth = 0;
+        let mut current = &value;
+        while let Some(next) = current.get("self") {
+            current = next;
+            depth += 1;
+        }
+        assert_eq!(depth, MAX_JSON_DEPTH);
+        assert_eq!(current, &json!("<table: max depth reached>"));
+    }
+
+    #[test]
+    fn test_split_lua_error() {
+        let lua = Lua::new();
+        let error = lua.load("error('boom')").exec().unwrap_err();
+        let (message, _) = split_lua_error(&error);
+        assert!(message.contains("boom"));
+        assert!(!message.contains("stack traceback"));
+
+        let (message, traceback) = split_lua_error(&mlua::Error::RuntimeError("bad\nstack traceback:\n\t[C]: in ?".to_string()));
+        assert_eq!(message, "runtime error: bad");
+        assert_eq!(traceback.as_deref(), Some("stack traceback:\n\t[C]: in ?"));
+    }
+}
@@ -0,0 +1,427 @@
+//! Lua types of registered functions, and the API reference generated from them
+//!
+//! [`lua_fn!`](crate::lua_fn) records the names and types of a function's parameters and what it
+//! returns, functions registered with [`add_lua_function`](super::add_lua_function) directly spell
+//! them out with [`LuaTypes`]. From that, [`markdown`] renders the API reference and [`stubs`]
+//! renders LuaLS/EmmyLua annotations (`openzt.d.lua`) for editor autocompletion. Both are
+//! committed in `docs/lua`, `cargo test` fails if they are out of date and regenerates them when
+//! `UPDATE_LUA_DOCS=1` is set.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use mlua::{Lua, MultiValue, Variadic};
+
+use super::Capability;
+
+/// A Rust type that functions registered in Lua take or return
+pub trait LuaType {
+    /// The type in LuaLS annotation syntax, such as `integer` or `string[]`
+    fn lua_type() -> String;
+
+    /// Whether the value can be nil, so the argument can be left out
+    fn optional() -> bool {
+        false
+    }
+
+    /// Whether the value takes all remaining arguments or results
+    fn variadic() -> bool {
+        false
+    }
+}
+
+macro_rules! lua_type {
+    ($lua_type:literal: $($ty:ty),+) => {
+        $(
+            impl LuaType for $ty {
+                fn lua_type() -> String {
+                    $lua_type.to_string()
+                }
+            }
+        )+
+    };
+}
+
+lua_type!("boolean": bool);
+lua_type!("integer": i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+lua_type!("number": f32, f64);
+lua_type!("string": String, &str, mlua::String);
+lua_type!("table": mlua::Table);
+lua_type!("function": mlua::Function);
+lua_type!("any": mlua::Value);
+
+impl<T: LuaType> LuaType for Option<T> {
+    fn lua_type() -> String {
+        T::lua_type()
+    }
+
+    fn optional() -> bool {
+        true
+    }
+}
+
+impl<T: LuaType> LuaType for Vec<T> {
+    fn lua_type() -> String {
+        format!("{}[]", T::lua_type())
+    }
+}
+
+impl<K: LuaType, V: LuaType> LuaType for HashMap<K, V> {
+    fn lua_type() -> String {
+        format!("table<{}, {}>", K::lua_type(), V::lua_type())
+    }
+}
+
+impl<K: LuaType, V: LuaType> LuaType for BTreeMap<K, V> {
+    fn lua_type() -> String {
+        format!("table<{}, {}>", K::lua_type(), V::lua_type())
+    }
+}
+
+impl<T: LuaType> LuaType for Variadic<T> {
+    fn lua_type() -> String {
+        T::lua_type()
+    }
+
+    fn variadic() -> bool {
+        true
+    }
+}
+
+impl LuaType for MultiValue {
+    fn lua_type() -> String {
+        "any".to_string()
+    }
+
+    fn variadic() -> bool {
+        true
+    }
+}
+
+/// The results of a registered function: nothing, one value or a tuple of values
+pub trait LuaReturns {
+    /// The result types in LuaLS annotation syntax
+    fn lua_returns() -> Vec<String>;
+}
+
+/// A single result, `string?` if it can be nil and `any ...` if it is variadic
+fn result_type<T: LuaType>() -> String {
+    if T::variadic() {
+        format!("{} ...", T::lua_type())
+    } else if T::optional() {
+        format!("{}?", T::lua_type())
+    } else {
+        T::lua_type()
+    }
+}
+
+impl<T: LuaType> LuaReturns for T {
+    fn lua_returns() -> Vec<String> {
+        vec![result_type::<T>()]
+    }
+}
+
+impl LuaReturns for () {
+    fn lua_returns() -> Vec<String> {
+        Vec::new()
+    }
+}
+
+impl<A: LuaType, B: LuaType> LuaReturns for (A, B) {
+    fn lua_returns() -> Vec<String> {
+        vec![result_type::<A>(), result_type::<B>()]
+    }
+}
+
+impl<A: LuaType, B: LuaType, C: LuaType> LuaReturns for (A, B, C) {
+    fn lua_returns() -> Vec<String> {
+        vec![result_type::<A>(), result_type::<B>(), result_type::<C>()]
+    }
+}
+
+/// A parameter of a registered function
+#[derive(Debug, Clone, PartialEq)]
+pub struct LuaParam {
+    pub name: String,
+    pub lua_type: String,
+    pub optional: bool,
+    pub variadic: bool,
+}
+
+impl LuaParam {
+    pub fn of<T: LuaType>(name: &str) -> Self {
+        LuaParam {
+            name: name.to_string(),
+            lua_type: T::lua_type(),
+            optional: T::optional(),
+            variadic: T::variadic(),
+        }
+    }
+}
+
+/// The parameters and results of a registered function
+///
+/// `returns` is `None` until the results are known, which [`validate`] reports.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LuaTypes {
+    pub params: Vec<LuaParam>,
+    pub returns: Option<Vec<String>>,
+}
+
+impl LuaTypes {
+    pub fn new() -> Self {
+        LuaTypes::default()
+    }
+
+    /// Adds a parameter of type `T`
+    pub fn param<T: LuaType>(mut self, name: &str) -> Self {
+        self.params.push(LuaParam::of::<T>(name));
+        self
+    }
+
+    /// Sets the results to those of `R`
+    pub fn returns<R: LuaReturns>(mut self) -> Self {
+        self.returns = Some(R::lua_returns());
+        self
+    }
+
+    /// Sets the results to those of `function`, used by [`lua_fn!`](crate::lua_fn) where they are inferred
+    pub fn returns_of<A, R: LuaReturns>(self, _function: fn(&Lua, A) -> mlua::Result<R>) -> Self {
+        self.returns::<R>()
+    }
+
+    /// Sets the results in LuaLS annotation syntax, for when the Rust type says too little, such as an iterator
+    pub fn returns_lua(mut self, types: &[&str]) -> Self {
+        self.returns = Some(types.iter().map(|t| t.to_string()).collect());
+        self
+    }
+}
+
+/// A registered function, shown by help() and in the generated reference
+pub(crate) struct LuaFunctionMeta {
+    pub(crate) name: String,
+    pub(crate) description: String,
+    pub(crate) signature: String,
+    pub(crate) capability: Capability,
+    pub(crate) types: LuaTypes,
+}
+
+/// Lists what is missing from the registered functions' metadata, empty if nothing is
+pub(crate) fn validate(functions: &[LuaFunctionMeta]) -> Vec<String> {
+    let mut problems = Vec::new();
+    for function in functions {
+        if function.description.trim().is_empty() {
+            problems.push(format!("{} has no description", function.name));
+        }
+        if function.types.returns.is_none() {
+            problems.push(format!("{} has no return type", function.name));
+        }
+        for param in &function.types.params {
+            if param.name.is_empty() || param.lua_type.is_empty() {
+                problems.push(format!("{} has a parameter without a name or type", function.name));
+            }
+        }
+    }
+    problems
+}
+
+/// Types that LuaLS knows without a `---@class` declaration
+const BUILTIN_TYPES: &[&str] = &["any", "boolean", "fun", "function", "integer", "nil", "number", "string", "table", "thread"];
+
+/// The parameter list of a function definition, such as `a, b, ...`
+fn param_list(types: &LuaTypes) -> String {
+    types
+        .params
+        .iter()
+        .map(|param| if param.variadic { "..." } else { param.name.as_str() })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn sorted(functions: &[LuaFunctionMeta]) -> Vec<&LuaFunctionMeta> {
+    let mut sorted: Vec<&LuaFunctionMeta> = functions.iter().collect();
+    sorted.sort_by(|a, b| a.name.cmp(&b.name));
+    sorted
+}
+
+/// Renders the Markdown API reference
+pub(crate) fn markdown(functions: &[LuaFunctionMeta]) -> String {
+    let mut out = String::from("# OpenZT Lua API\n\n");
+    out.push_str("<!-- Generated by `cargo test` from the registered Lua functions, do not edit -->\n\n");
+    out.push_str("Functions are only available in sandboxes granted their capability, see `[scripting]` in `openzt.toml`.\n");
+
+    for function in sorted(functions) {
+        out.push_str(&format!("\n## `{}`\n\n{}\n\n", function.name, function.description));
+        out.push_str(&format!("```lua\n{}\n```\n\n", function.signature));
+        if !function.types.params.is_empty() {
+            out.push_str("| Parameter | Type |\n| --- | --- |\n");
+            for param in &function.types.params {
+                let name = if param.variadic { "..." } else { param.name.as_str() };
+                let optional = if param.optional { " (optional)" } else { "" };
+                out.push_str(&format!("| `{}` | `{}`{} |\n", name, param.lua_type, optional));
+            }
+            out.push('\n');
+        }
+        let returns = match &function.types.returns {
+            Some(returns) if returns.is_empty() => "nothing".to_string(),
+            Some(returns) => returns.iter().map(|t| format!("`{}`", t)).collect::<Vec<_>>().join(", "),
+            None => "unknown".to_string(),
+        };
+        out.push_str(&format!("**Returns:** {}  \n**Capability:** `{}`\n", returns, function.capability.as_str()));
+    }
+    out
+}
+
+/// Renders LuaLS/EmmyLua annotation stubs for the registered functions
+pub(crate) fn stubs(functions: &[LuaFunctionMeta]) -> String {
+    let mut out = String::from("---@meta openzt\n");
+    out.push_str("-- Generated by `cargo test` from the registered Lua functions, do not edit\n");
+
+    // Userdata types the functions take or return
+    let mut classes = BTreeSet::new();
+    for function in functions {
+        let returns = function.types.returns.iter().flatten();
+        for lua_type in function.types.params.iter().map(|param| &param.lua_type).chain(returns) {
+            classes.extend(
+                lua_type
+                    .split(|c: char| !c.is_alphanumeric() && c != '_')
+                    .filter(|word| word.chars().next().is_some_and(char::is_alphabetic) && !BUILTIN_TYPES.contains(word))
+                    .map(str::to_string),
+            );
+        }
+    }
+    for class in classes {
+        out.push_str(&format!("\n---@class {}\n", class));
+    }
+
+    // Tables holding dotted names such as world.entities
+    let tables: BTreeSet<&str> = functions.iter().filter_map(|function| function.name.rsplit_once('.').map(|(table, _)| table)).collect();
+    for table in tables {
+        out.push_str(&format!("\n{} = {{}}\n", table));
+    }
+
+    for function in sorted(functions) {
+        out.push('\n');
+        for line in function.description.lines() {
+            out.push_str(&format!("---{}\n", line));
+        }
+        out.push_str(&format!("---\n---Capability: {}\n", function.capability.as_str()));
+        for param in &function.types.params {
+            if param.variadic {
+                out.push_str(&format!("---@param ... {}\n", param.lua_type));
+            } else {
+                let optional = if param.optional { "?" } else { "" };
+                out.push_str(&format!("---@param {}{} {}\n", param.name, optional, param.lua_type));
+            }
+        }
+        for result in function.types.returns.iter().flatten() {
+            out.push_str(&format!("---@return {}\n", result));
+        }
+        out.push_str(&format!("function {}({}) end\n", function.name, param_list(&function.types)));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn meta(name: &str, description: &str, types: LuaTypes) -> LuaFunctionMeta {
+        LuaFunctionMeta {
+            name: name.to_string(),
+            description: description.to_string(),
+            signature: format!("{}()", name),
+            capability: Capability::ReadWorld,
+            types,
+        }
+    }
+
+    #[test]
+    fn test_types() {
+        assert_eq!(
+            LuaTypes::new().param::<u32>("id").param::<Option<String>>("name").param::<Variadic<f32>>("rest").params,
+            vec![
+                LuaParam { name: "id".to_string(), lua_type: "integer".to_string(), optional: false, variadic: false },
+                LuaParam { name: "name".to_string(), lua_type: "string".to_string(), optional: true, variadic: false },
+                LuaParam { name: "rest".to_string(), lua_type: "number".to_string(), optional: false, variadic: true },
+            ]
+        );
+        assert_eq!(LuaTypes::new().returns::<()>().returns, Some(vec![]));
+        assert_eq!(LuaTypes::new().returns::<Vec<String>>().returns, Some(vec!["string[]".to_string()]));
+        assert_eq!(
+            LuaTypes::new().returns::<(Option<String>, Option<String>)>().returns,
+            Some(vec!["string?".to_string(), "string?".to_string()])
+        );
+        assert_eq!(LuaTypes::new().returns::<HashMap<String, bool>>().returns, Some(vec!["table<string, boolean>".to_string()]));
+        assert_eq!(LuaTypes::new().returns::<MultiValue>().returns, Some(vec!["any ...".to_string()]));
+    }
+
+    #[test]
+    fn test_returns_inferred_by_lua_fn() {
+        let function: fn(&Lua, u32) -> mlua::Result<_> = |_, id| Ok((id > 0, Some(id.to_string())));
+        assert_eq!(
+            LuaTypes::new().returns_of(function).returns,
+            Some(vec!["boolean".to_string(), "string?".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let functions = vec![
+            meta("ok", "Fine", LuaTypes::new().returns::<()>()),
+            meta("undocumented", " ", LuaTypes::new().returns::<()>()),
+            meta("untyped", "No return type", LuaTypes::new()),
+        ];
+        assert_eq!(validate(&functions), vec!["undocumented has no description", "untyped has no return type"]);
+    }
+
+    #[test]
+    fn test_stubs() {
+        let functions = vec![
+            meta("zoo_state", "Returns the state", LuaTypes::new().param::<String>("namespace").returns_lua(&["ZooState"])),
+            meta(
+                "world.entities",
+                "Iterates over entities",
+                LuaTypes::new().param::<Option<String>>("class").returns_lua(&["fun(): ZTEntity?"]),
+            ),
+            meta("sel_type", "Selects", LuaTypes::new().param::<Variadic<String>>("args").returns::<(Option<String>, Option<String>)>()),
+        ];
+        let stubs = stubs(&functions);
+        assert!(stubs.starts_with("---@meta openzt\n"));
+        assert!(stubs.contains("\n---@class ZTEntity\n"));
+        assert!(stubs.contains("\n---@class ZooState\n"));
+        assert!(stubs.contains("\nworld = {}\n"));
+        assert!(stubs.contains("---@param class? string\n---@return fun(): ZTEntity?\nfunction world.entities(class) end\n"));
+        assert!(stubs.contains("---@param ... string\n---@return string?\n---@return string?\nfunction sel_type(...) end\n"));
+        // Sorted by name
+        assert!(stubs.find("function sel_type").unwrap() < stubs.find("function world.entities").unwrap());
+
+        let markdown = markdown(&functions);
+        assert!(markdown.contains("## `world.entities`\n\nIterates over entities\n"));
+        assert!(markdown.contains("| `class` | `string` (optional) |\n"));
+        assert!(markdown.contains("**Returns:** `string?`, `string?`  \n**Capability:** `read_world`\n"));
+    }
+
+    /// Checks every function OpenZT registers is documented and typed, and that `docs/lua` matches them
+    #[test]
+    fn test_registered_functions_are_documented() {
+        crate::scripting::register_all_lua_functions();
+        let functions = crate::scripting::LUA_FUNCTION_METADATA.lock().unwrap();
+        assert!(!functions.is_empty());
+        let problems = validate(&functions);
+        assert!(problems.is_empty(), "Registered Lua functions are missing metadata:\n{}", problems.join("\n"));
+
+        let docs = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../docs/lua");
+        let update = std::env::var_os("UPDATE_LUA_DOCS").is_some();
+        for (file, contents) in [("api.md", markdown(&functions)), ("openzt.d.lua", stubs(&functions))] {
+            let path = docs.join(file);
+            if std::fs::read_to_string(&path).ok().as_deref() == Some(contents.as_str()) {
+                continue;
+            }
+            assert!(update, "docs/lua/{} is out of date, run `UPDATE_LUA_DOCS=1 cargo test -p openzt api_docs` to regenerate it", file);
+            std::fs::create_dir_all(&docs).unwrap();
+            std::fs::write(&path, contents).unwrap();
+        }
+    }
+}
@@ -0,0 +1,527 @@
+//! Sandboxed Lua environments
+//!
+//! The console and mod scripts share one Lua state but never run in its globals. Each gets an
+//! environment table holding only the standard library modules and OpenZT function groups
+//! ([`Capability`]) its `[scripting]` config in openzt.toml allows. Code run in a sandbox can
+//! also be held to an instruction, time and memory budget with [`with_limits`].
+//!
+//! Function names may be dotted, such as `world.entities`, in which case they are placed in a
+//! table (`world`) that is also copied per environment.
+
+use std::{
+    cell::{Cell, RefCell},
+    time::{Duration, Instant},
+};
+
+use mlua::{Function, HookTriggers, IntoLua, Lua, Table, VmState};
+use serde::{Deserialize, Serialize};
+
+use crate::resource_manager::mod_config::SandboxConfig;
+
+/// A group of registered Lua functions that is granted to an environment as a whole
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Help and introspection, always granted
+    Core,
+    /// Read entities, resources, settings and mod data
+    ReadWorld,
+    /// Change entities, settings, UI state or loaded resources
+    ModifyWorld,
+    /// Change the zoo's cash or unlock content
+    ModifyEconomy,
+    /// Change game settings, openzt.toml or write files
+    ModifyConfig,
+    /// Raw pointers, vtables and other engine internals
+    DebugMemory,
+}
+
+impl Capability {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Capability::Core => "core",
+            Capability::ReadWorld => "read_world",
+            Capability::ModifyWorld => "modify_world",
+            Capability::ModifyEconomy => "modify_economy",
+            Capability::ModifyConfig => "modify_config",
+            Capability::DebugMemory => "debug_memory",
+        }
+    }
+}
+
+/// A standard library module an environment may use
+///
+/// Only functions that can't reach the file system, the process or other environments are
+/// exposed: `base` has no `load`, `loadfile`, `dofile`, `require` or `getfenv`/`setfenv`,
+/// and `os` only has the clock and date functions. `io`, `package`, `debug`, `ffi` and `jit`
+/// are never available.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum StdModule {
+    Base,
+    Coroutine,
+    String,
+    Table,
+    Math,
+    Bit,
+    Os,
+}
+
+const BASE_FUNCTIONS: &[&str] = &[
+    "_VERSION",
+    "assert",
+    "error",
+    "getmetatable",
+    "ipairs",
+    "next",
+    "pairs",
+    "pcall",
+    "print",
+    "rawequal",
+    "rawget",
+    "rawlen",
+    "rawset",
+    "select",
+    "setmetatable",
+    "tonumber",
+    "tostring",
+    "type",
+    "unpack",
+    "xpcall",
+];
+
+const OS_FUNCTIONS: &[&str] = &["clock", "date", "difftime", "time"];
+
+impl StdModule {
+    pub const ALL: [StdModule; 7] = [
+        StdModule::Base,
+        StdModule::Coroutine,
+        StdModule::String,
+        StdModule::Table,
+        StdModule::Math,
+        StdModule::Bit,
+        StdModule::Os,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            StdModule::Base => "_G",
+            StdModule::Coroutine => "coroutine",
+            StdModule::String => "string",
+            StdModule::Table => "table",
+            StdModule::Math => "math",
+            StdModule::Bit => "bit",
+            StdModule::Os => "os",
+        }
+    }
+
+    /// Copy this module's safe functions from the real globals into `env`
+    ///
+    /// Module tables are copied rather than shared so one environment can't replace
+    /// functions used by another.
+    fn install(&self, lua: &Lua, globals: &Table, env: &Table) -> mlua::Result<()> {
+        match self {
+            StdModule::Base => copy_fields(globals, env, BASE_FUNCTIONS.iter().copied()),
+            StdModule::Os => {
+                let os = lua.create_table()?;
+                copy_fields(&globals.get(self.name())?, &os, OS_FUNCTIONS.iter().copied())?;
+                env.set(self.name(), os)
+            }
+            _ => {
+                let Some(module) = globals.get::<Option<Table>>(self.name())? else {
+                    return Ok(());
+                };
+                let copy = lua.create_table()?;
+                for pair in module.pairs::<mlua::Value, mlua::Value>() {
+                    let (key, value) = pair?;
+                    copy.set(key, value)?;
+                }
+                env.set(self.name(), copy)
+            }
+        }
+    }
+}
+
+fn copy_fields<'a>(from: &Table, to: &Table, names: impl Iterator<Item = &'a str>) -> mlua::Result<()> {
+    for name in names {
+        to.set(name, from.get::<mlua::Value>(name)?)?;
+    }
+    Ok(())
+}
+
+/// Build a new environment with the stdlib modules and capabilities allowed by `config`
+///
+/// # Arguments
+/// * `lua` - The Lua state, whose globals hold the full stdlib and every registered function
+/// * `config` - The sandbox settings from openzt.toml
+/// * `functions` - Name and capability of every registered function
+///
+/// Functions outside the granted capabilities are replaced by stubs that raise an error
+/// naming the missing capability.
+pub fn create_environment(lua: &Lua, config: &SandboxConfig, functions: &[(String, Capability)]) -> mlua::Result<Table> {
+    protect_string_metatable(lua)?;
+    let globals = lua.globals();
+    let env = lua.create_table()?;
+
+    for module in &config.stdlib {
+        module.install(lua, &globals, &env)?;
+    }
+
+    for (name, capability) in functions {
+        if *capability == Capability::Core || config.capabilities.contains(capability) {
+            set_path(lua, &env, name, get_path(&globals, name)?)?;
+        } else {
+            let message = missing_capability(&format!("{}()", name), *capability);
+            set_path(lua, &env, name, lua.create_function(move |_, _: mlua::MultiValue| Err::<(), _>(mlua::Error::runtime(&message)))?)?;
+        }
+    }
+
+    env.set("_G", &env)?;
+    Ok(env)
+}
+
+/// Hide the metatable every string shares from `getmetatable`
+///
+/// Its `__index` is the real `string` table behind method calls such as `s:upper()`, so without
+/// this one environment could replace string methods used by all the others.
+fn protect_string_metatable(lua: &Lua) -> mlua::Result<()> {
+    match lua.type_metatable::<mlua::String>() {
+        Some(metatable) => metatable.raw_set("__metatable", false),
+        None => Ok(()),
+    }
+}
+
+/// Set `value` at a possibly dotted `name` in `table`, creating intermediate tables
+pub(crate) fn set_path(lua: &Lua, table: &Table, name: &str, value: impl IntoLua) -> mlua::Result<()> {
+    let Some((parent, field)) = name.rsplit_once('.') else {
+        return table.set(name, value);
+    };
+    let mut current = table.clone();
+    for part in parent.split('.') {
+        current = match current.get::<Option<Table>>(part)? {
+            Some(next) => next,
+            None => {
+                let next = lua.create_table()?;
+                current.set(part, &next)?;
+                next
+            }
+        };
+    }
+    current.set(field, value)
+}
+
+/// Get the value at a possibly dotted `name` in `table`, nil if any part is missing
+fn get_path(table: &Table, name: &str) -> mlua::Result<mlua::Value> {
+    let mut parts = name.split('.');
+    let mut value = table.get::<mlua::Value>(parts.next().unwrap_or_default())?;
+    for part in parts {
+        value = match value {
+            mlua::Value::Table(table) => table.get(part)?,
+            _ => return Ok(mlua::Value::Nil),
+        };
+    }
+    Ok(value)
+}
+
+fn missing_capability(action: &str, capability: Capability) -> String {
+    format!("{} requires the '{}' capability, which this environment does not have", action, capability.as_str())
+}
+
+/// Budget and grants for a single run of sandboxed code, such as a console command or a mod callback
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecutionLimits {
+    pub instructions: Option<u64>,
+    pub memory_bytes: Option<usize>,
+    /// Wall-clock time the code may run
+    pub duration: Option<Duration>,
+    /// Capabilities checked by [`require_capability`], `None` grants all of them
+    pub capabilities: Option<Vec<Capability>>,
+    /// Id of the mod the code belongs to, `None` for the console
+    pub owner: Option<String>,
+}
+
+impl ExecutionLimits {
+    /// These limits for code belonging to mod `mod_id`
+    pub fn owned_by(&self, mod_id: &str) -> ExecutionLimits {
+        ExecutionLimits {
+            owner: Some(mod_id.to_string()),
+            ..self.clone()
+        }
+    }
+}
+
+impl From<&SandboxConfig> for ExecutionLimits {
+    fn from(config: &SandboxConfig) -> Self {
+        ExecutionLimits {
+            instructions: (config.instruction_limit > 0).then_some(config.instruction_limit),
+            memory_bytes: (config.memory_limit_mb > 0).then_some(config.memory_limit_mb as usize * 1024 * 1024),
+            duration: None,
+            capabilities: Some(config.capabilities.clone()),
+            owner: None,
+        }
+    }
+}
+
+/// How often the instruction hook runs
+const HOOK_INTERVAL: u32 = 1000;
+
+thread_local! {
+    /// Instructions the running code has left, `None` when it is unlimited
+    static INSTRUCTIONS_LEFT: Cell<Option<u64>> = const { Cell::new(None) };
+    /// When the running code runs out of time, `None` when it is unlimited
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+    /// Limits of the running code, `None` outside of [`with_limits`]
+    static CURRENT_LIMITS: RefCell<Option<ExecutionLimits>> = const { RefCell::new(None) };
+}
+
+/// Error unless the running code was granted `capability`
+///
+/// Function capabilities are enforced when an environment is built, but userdata is shared
+/// between environments, so field setters and other writes check at the time they run.
+///
+/// # Arguments
+/// * `action` - What is being attempted, for the error message, e.g. "setting ZTEntity.visible"
+pub fn require_capability(capability: Capability, action: &str) -> mlua::Result<()> {
+    CURRENT_LIMITS.with_borrow(|limits| match limits.as_ref().and_then(|limits| limits.capabilities.as_ref()) {
+        Some(granted) if capability != Capability::Core && !granted.contains(&capability) => Err(mlua::Error::runtime(missing_capability(action, capability))),
+        _ => Ok(()),
+    })
+}
+
+/// Limits of the running code, unlimited outside of [`with_limits`]
+///
+/// Used to run callbacks registered by sandboxed code, such as event handlers, under the same
+/// limits as the code that registered them.
+pub fn current_limits() -> ExecutionLimits {
+    CURRENT_LIMITS.with_borrow(|limits| limits.clone().unwrap_or_default())
+}
+
+/// Run `f` with `limits` applied to any Lua code it executes
+///
+/// Running out of instructions or time raises a Lua error, as does allocating more than the memory
+/// limit on top of what the state already used. Nested calls use their own limits and restore
+/// the outer ones when they return.
+pub fn with_limits<R>(lua: &Lua, limits: &ExecutionLimits, f: impl FnOnce() -> mlua::Result<R>) -> mlua::Result<R> {
+    if limits.instructions.is_some() || limits.duration.is_some() {
+        disable_jit(lua)?;
+        lua.set_global_hook(HookTriggers::new().every_nth_instruction(HOOK_INTERVAL), |_, _| {
+            if DEADLINE.get().is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(mlua::Error::runtime("time limit exceeded"));
+            }
+            INSTRUCTIONS_LEFT.with(|left| match left.get() {
+                Some(remaining) if remaining < HOOK_INTERVAL as u64 => {
+                    left.set(Some(0));
+                    Err(mlua::Error::runtime("instruction limit exceeded"))
+                }
+                Some(remaining) => {
+                    left.set(Some(remaining - HOOK_INTERVAL as u64));
+                    Ok(VmState::Continue)
+                }
+                None => Ok(VmState::Continue),
+            })
+        })?;
+    }
+    let outer_instructions = INSTRUCTIONS_LEFT.replace(limits.instructions);
+    let outer_deadline = DEADLINE.replace(limits.duration.map(|duration| Instant::now() + duration));
+    let outer_limits = CURRENT_LIMITS.replace(Some(limits.clone()));
+
+    // Not every Lua build supports memory limits, in which case only instructions are limited
+    let outer_memory = limits.memory_bytes.and_then(|bytes| lua.set_memory_limit(lua.used_memory() + bytes).ok());
+
+    let result = f();
+
+    INSTRUCTIONS_LEFT.set(outer_instructions);
+    DEADLINE.set(outer_deadline);
+    CURRENT_LIMITS.set(outer_limits);
+    if let Some(limit) = outer_memory {
+        let _ = lua.set_memory_limit(limit);
+    }
+    result
+}
+
+/// Code compiled by the LuaJIT JIT never calls hooks, so it can't be limited
+///
+/// Sandboxes don't expose `jit`, so scripts can't turn it back on.
+fn disable_jit(lua: &Lua) -> mlua::Result<()> {
+    let Some(jit) = lua.globals().get::<Option<Table>>("jit")? else {
+        return Ok(());
+    };
+    if jit.get::<Function>("status")?.call::<bool>(())? {
+        jit.get::<Function>("off")?.call::<()>(())?;
+        jit.get::<Function>("flush")?.call::<()>(())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(stdlib: &[StdModule], capabilities: &[Capability]) -> SandboxConfig {
+        SandboxConfig {
+            stdlib: stdlib.to_vec(),
+            capabilities: capabilities.to_vec(),
+            instruction_limit: 0,
+            memory_limit_mb: 0,
+        }
+    }
+
+    fn eval(lua: &Lua, env: &Table, code: &str) -> mlua::Result<mlua::Value> {
+        lua.load(code).set_environment(env.clone()).eval()
+    }
+
+    #[test]
+    fn test_stdlib_allowlist() {
+        let lua = Lua::new();
+        let env = create_environment(&lua, &config(&[StdModule::Base, StdModule::String, StdModule::Os], &[]), &[]).unwrap();
+
+        assert_eq!(eval(&lua, &env, "return string.upper(tostring(1))").unwrap().as_string().unwrap(), "1");
+        assert!(matches!(eval(&lua, &env, "return os.time()").unwrap(), mlua::Value::Integer(_) | mlua::Value::Number(_)));
+
+        for blocked in ["io", "require", "loadfile", "dofile", "load", "loadstring", "package", "debug", "math", "os.execute", "os.getenv"] {
+            assert!(eval(&lua, &env, &format!("return {}", blocked)).unwrap().is_nil(), "{} should not be available", blocked);
+        }
+    }
+
+    #[test]
+    fn test_environments_do_not_share_modules() {
+        let lua = Lua::new();
+        let sandbox = config(&[StdModule::String], &[]);
+        let first = create_environment(&lua, &sandbox, &[]).unwrap();
+        let second = create_environment(&lua, &sandbox, &[]).unwrap();
+
+        eval(&lua, &first, "string.upper = nil; leaked = true").unwrap();
+        assert!(eval(&lua, &second, "return string.upper").unwrap().is_function());
+        assert!(eval(&lua, &second, "return leaked").unwrap().is_nil());
+        assert!(lua.globals().get::<mlua::Value>("leaked").unwrap().is_nil());
+    }
+
+    #[test]
+    fn test_environments_do_not_share_string_methods() {
+        let lua = Lua::new();
+        let sandbox = config(&[StdModule::Base, StdModule::String], &[]);
+        let first = create_environment(&lua, &sandbox, &[]).unwrap();
+        let second = create_environment(&lua, &sandbox, &[]).unwrap();
+
+        assert_eq!(eval(&lua, &first, "return getmetatable('')").unwrap(), mlua::Value::Boolean(false));
+        assert!(eval(&lua, &first, "getmetatable('').__index.upper = function() return 'hijacked' end").is_err());
+        assert!(eval(&lua, &first, "rawset(getmetatable(''), '__index', {})").is_err());
+        assert!(eval(&lua, &first, "setmetatable('', {})").is_err());
+
+        eval(&lua, &first, "string.upper = function() return 'hijacked' end").unwrap();
+        assert_eq!(eval(&lua, &second, "return ('abc'):upper()").unwrap().as_string().unwrap(), "ABC");
+        assert_eq!(eval(&lua, &first, "return ('abc'):upper()").unwrap().as_string().unwrap(), "ABC");
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let lua = Lua::new();
+        for name in ["help", "zoostats", "add_cash"] {
+            lua.globals().set(name, lua.create_function(move |_, ()| Ok(name)).unwrap()).unwrap();
+        }
+        let functions = vec![
+            ("help".to_string(), Capability::Core),
+            ("zoostats".to_string(), Capability::ReadWorld),
+            ("add_cash".to_string(), Capability::ModifyEconomy),
+        ];
+        let env = create_environment(&lua, &config(&[], &[Capability::ReadWorld]), &functions).unwrap();
+
+        assert_eq!(eval(&lua, &env, "return help()").unwrap().as_string().unwrap(), "help");
+        assert_eq!(eval(&lua, &env, "return zoostats()").unwrap().as_string().unwrap(), "zoostats");
+        let err = eval(&lua, &env, "return add_cash(1000)").unwrap_err();
+        assert!(err.to_string().contains("add_cash() requires the 'modify_economy' capability"));
+    }
+
+    #[test]
+    fn test_dotted_names() {
+        let lua = Lua::new();
+        set_path(&lua, &lua.globals(), "world.entities", lua.create_function(|_, ()| Ok("entities")).unwrap()).unwrap();
+        set_path(&lua, &lua.globals(), "world.clear", lua.create_function(|_, ()| Ok("clear")).unwrap()).unwrap();
+        let functions = vec![
+            ("world.entities".to_string(), Capability::ReadWorld),
+            ("world.clear".to_string(), Capability::ModifyWorld),
+        ];
+        let first = create_environment(&lua, &config(&[], &[Capability::ReadWorld]), &functions).unwrap();
+        let second = create_environment(&lua, &config(&[], &[Capability::ReadWorld]), &functions).unwrap();
+
+        assert_eq!(eval(&lua, &first, "return world.entities()").unwrap().as_string().unwrap(), "entities");
+        let err = eval(&lua, &first, "return world.clear()").unwrap_err();
+        assert!(err.to_string().contains("world.clear() requires the 'modify_world' capability"));
+
+        eval(&lua, &first, "world.entities = nil").unwrap();
+        assert!(eval(&lua, &second, "return world.entities").unwrap().is_function());
+    }
+
+    #[test]
+    fn test_require_capability() {
+        let lua = Lua::new();
+        let limits = ExecutionLimits {
+            capabilities: Some(vec![Capability::ReadWorld]),
+            ..Default::default()
+        };
+
+        // Rust code outside a sandbox may do anything
+        require_capability(Capability::ModifyWorld, "setting x").unwrap();
+
+        with_limits(&lua, &limits, || {
+            require_capability(Capability::ReadWorld, "reading x")?;
+            require_capability(Capability::Core, "help")?;
+            let err = require_capability(Capability::ModifyWorld, "setting x").unwrap_err();
+            assert!(err.to_string().contains("setting x requires the 'modify_world' capability"));
+            assert_eq!(current_limits(), limits);
+            Ok(())
+        })
+        .unwrap();
+        require_capability(Capability::ModifyWorld, "setting x").unwrap();
+        assert_eq!(current_limits(), ExecutionLimits::default());
+    }
+
+    #[test]
+    fn test_instruction_limit() {
+        let lua = Lua::new();
+        let limits = ExecutionLimits {
+            instructions: Some(100_000),
+            ..Default::default()
+        };
+
+        let err = with_limits(&lua, &limits, || lua.load("while true do end").exec()).unwrap_err();
+        assert!(err.to_string().contains("instruction limit exceeded"));
+
+        // The budget is per run, and unlimited code is unaffected afterwards
+        with_limits(&lua, &limits, || lua.load("for i = 1, 1000 do end").exec()).unwrap();
+        lua.load("for i = 1, 1000000 do end").exec().unwrap();
+    }
+
+    #[test]
+    fn test_time_limit() {
+        let lua = Lua::new();
+        let limits = ExecutionLimits {
+            duration: Some(Duration::from_millis(20)),
+            ..Default::default()
+        };
+
+        let err = with_limits(&lua, &limits, || lua.load("while true do end").exec()).unwrap_err();
+        assert!(err.to_string().contains("time limit exceeded"));
+        with_limits(&lua, &limits, || lua.load("for i = 1, 1000 do end").exec()).unwrap();
+    }
+
+    #[test]
+    fn test_config_limits() {
+        let mut sandbox = config(&[], &[]);
+        assert_eq!(ExecutionLimits::from(&sandbox).instructions, None);
+        assert_eq!(ExecutionLimits::from(&sandbox).memory_bytes, None);
+
+        sandbox.instruction_limit = 5000;
+        sandbox.memory_limit_mb = 2;
+        assert_eq!(
+            ExecutionLimits::from(&sandbox),
+            ExecutionLimits {
+                instructions: Some(5000),
+                memory_bytes: Some(2 * 1024 * 1024),
+                duration: None,
+                capabilities: Some(Vec::new()),
+                owner: None,
+            }
+        );
+    }
+}
@@ -0,0 +1,471 @@
+//! Lua jobs that wait across game ticks
+//!
+//! Console commands and code passed to `spawn(fn)` run as jobs: coroutines that can call
+//! `wait_ticks(n)`, `wait_game_days(n)` or `wait_until(predicate)` to suspend until the condition
+//! holds. A job that never waits finishes immediately, like any other code. Waiting jobs are
+//! resumed from the sim update hook, in turns, until the tick's time budget (`tick_budget_ms`
+//! under `[scripting]` in openzt.toml) is spent; jobs that miss their turn run first next tick.
+//! A single resume may also run for at most the budget, so a job that doesn't wait often enough
+//! is cancelled rather than stalling the simulation.
+//!
+//! Each resume runs under the sandbox limits of the code that started the job. Jobs belong to the
+//! mod that started them, and mods can only list and cancel their own jobs; the console manages
+//! every job.
+
+use std::{
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use mlua::{Function, Lua, MultiValue, Thread, ThreadStatus};
+use tracing::{error, info};
+
+use super::{
+    add_lua_function, lua_handle,
+    sandbox::{current_limits, with_limits, ExecutionLimits},
+    Capability, LuaTypes,
+};
+use crate::{lua_fn, resource_manager::mod_config::get_openzt_config, ztgamemgr::game_day};
+
+pub type JobId = u64;
+
+/// The wait functions, which check they are called from a job and yield what to wait for
+const WAITERS: &str = r#"
+local yield, running, type, error = coroutine.yield, coroutine.running, type, error
+local function waiter(name, kind, expected)
+    return function(value)
+        local co, main = running()
+        if not co or main then error(name .. "() can only be called from a job, see spawn()", 2) end
+        if type(value) ~= expected then error(name .. "() expects a " .. expected .. ", got " .. type(value), 2) end
+        return yield(kind, value)
+    end
+end
+return {
+    wait_ticks = waiter("wait_ticks", "ticks", "number"),
+    wait_game_days = waiter("wait_game_days", "days", "number"),
+    wait_until = waiter("wait_until", "until", "function"),
+}
+"#;
+
+/// What a suspended job is waiting for
+#[derive(Clone)]
+enum Wait {
+    /// Resume on the tick with this number
+    Tick(u64),
+    /// Resume `days` game days after `from`, which is filled in once a game is running
+    Days { days: i64, from: Option<i64> },
+    /// Resume once the predicate returns true
+    Until(Function),
+}
+
+#[derive(Clone)]
+struct Job {
+    id: JobId,
+    name: String,
+    thread: Thread,
+    /// Limits of the code that started the job, including the mod it belongs to
+    limits: ExecutionLimits,
+    wait: Wait,
+}
+
+impl Job {
+    /// Whether code running as mod `caller` may list and cancel the job, `None` is the console
+    fn managed_by(&self, caller: Option<&str>) -> bool {
+        caller.is_none() || self.limits.owner.as_deref() == caller
+    }
+}
+
+/// How far a job got when it was started
+#[derive(Debug)]
+pub enum JobStart {
+    /// The job ran to completion without waiting
+    Finished(MultiValue),
+    /// The job is waiting and will be resumed on a later tick
+    Waiting(JobId),
+}
+
+enum Step {
+    Waiting(Wait),
+    Finished(MultiValue),
+}
+
+#[derive(Default)]
+struct Jobs {
+    next_id: JobId,
+    /// Ticks run so far
+    tick: u64,
+    /// The job resumed last, the next tick starts with the one after it
+    last_resumed: JobId,
+    entries: Vec<Job>,
+}
+
+#[derive(Default)]
+pub struct Scheduler {
+    jobs: Mutex<Jobs>,
+}
+
+impl Scheduler {
+    /// Run `function` as a job until it first waits or finishes
+    ///
+    /// # Arguments
+    /// * `name` - Shown by `jobs()` and in errors, e.g. the code's source
+    /// * `today` - The current game day, see [`game_day`]
+    pub fn start(&self, lua: &Lua, name: &str, function: Function, args: MultiValue, limits: &ExecutionLimits, today: Option<i64>) -> mlua::Result<JobStart> {
+        let thread = lua.create_thread(function)?;
+        let tick = self.jobs.lock().unwrap().tick;
+        match resume(lua, &thread, args, limits, tick, today)? {
+            Step::Finished(values) => Ok(JobStart::Finished(values)),
+            Step::Waiting(wait) => {
+                let mut jobs = self.jobs.lock().unwrap();
+                jobs.next_id += 1;
+                let id = jobs.next_id;
+                jobs.entries.push(Job { id, name: name.to_string(), thread, limits: limits.clone(), wait });
+                Ok(JobStart::Waiting(id))
+            }
+        }
+    }
+
+    /// Resume the jobs whose wait is over, until `budget` is spent
+    pub fn tick(&self, lua: &Lua, today: Option<i64>, budget: Duration) {
+        let started = Instant::now();
+        let (tick, jobs) = {
+            let mut jobs = self.jobs.lock().unwrap();
+            for job in &mut jobs.entries {
+                if let Wait::Days { from: from @ None, .. } = &mut job.wait {
+                    *from = today;
+                }
+            }
+            // Jobs run from a snapshot, as they may start or cancel jobs themselves
+            let last_resumed = jobs.last_resumed;
+            let (earlier, later): (Vec<&Job>, Vec<&Job>) = jobs.entries.iter().partition(|job| job.id <= last_resumed);
+            let snapshot: Vec<Job> = later.into_iter().chain(earlier).cloned().collect();
+            (jobs.tick, snapshot)
+        };
+
+        for job in jobs {
+            if started.elapsed() >= budget {
+                break;
+            }
+            if !self.jobs.lock().unwrap().entries.iter().any(|entry| entry.id == job.id) {
+                continue;
+            }
+            let limits = ExecutionLimits {
+                duration: Some(budget),
+                ..job.limits.clone()
+            };
+            let ready = match &job.wait {
+                Wait::Tick(target) => tick >= *target,
+                Wait::Days { days, from } => matches!((today, from), (Some(today), Some(from)) if today >= from + days),
+                Wait::Until(predicate) => match with_limits(lua, &limits, || predicate.call::<bool>(())) {
+                    Ok(ready) => ready,
+                    Err(err) => {
+                        self.fail(&job, err);
+                        continue;
+                    }
+                },
+            };
+            if !ready {
+                continue;
+            }
+
+            self.jobs.lock().unwrap().last_resumed = job.id;
+            match resume(lua, &job.thread, MultiValue::new(), &limits, tick, today) {
+                Ok(Step::Waiting(wait)) => {
+                    if let Some(entry) = self.jobs.lock().unwrap().entries.iter_mut().find(|entry| entry.id == job.id) {
+                        entry.wait = wait;
+                    }
+                }
+                Ok(Step::Finished(_)) => {
+                    self.cancel(job.id, None);
+                    info!("Job {} ({}) finished", job.id, job.name);
+                }
+                Err(err) => self.fail(&job, err),
+            }
+        }
+        self.jobs.lock().unwrap().tick += 1;
+    }
+
+    fn fail(&self, job: &Job, err: mlua::Error) {
+        self.cancel(job.id, None);
+        error!("Job {} ({}) failed and was cancelled: {}", job.id, job.name, err);
+    }
+
+    /// Stop a job, returns false if `caller` has none with `id`
+    ///
+    /// # Arguments
+    /// * `caller` - The mod asking, only its own jobs can be cancelled; `None` for the console, which can cancel any
+    pub fn cancel(&self, id: JobId, caller: Option<&str>) -> bool {
+        let mut jobs = self.jobs.lock().unwrap();
+        let before = jobs.entries.len();
+        jobs.entries.retain(|job| job.id != id || !job.managed_by(caller));
+        jobs.entries.len() != before
+    }
+
+    /// One line per waiting job `caller` manages, with its id, name and what it is waiting for
+    pub fn list(&self, caller: Option<&str>) -> Vec<String> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.entries
+            .iter()
+            .filter(|job| job.managed_by(caller))
+            .map(|job| {
+                let wait = match &job.wait {
+                    Wait::Tick(target) => format!("{} ticks", target.saturating_sub(jobs.tick)),
+                    Wait::Days { days, from: None } => format!("{} game days", days),
+                    Wait::Days { days, from: Some(from) } => format!("{} game days from day {}", days, from),
+                    Wait::Until(_) => "a condition".to_string(),
+                };
+                format!("{}: {} (waiting for {})", job.id, job.name, wait)
+            })
+            .collect()
+    }
+}
+
+/// Resume `thread` under `limits`, and work out what it waits for if it yielded
+fn resume(lua: &Lua, thread: &Thread, args: MultiValue, limits: &ExecutionLimits, tick: u64, today: Option<i64>) -> mlua::Result<Step> {
+    let values = with_limits(lua, limits, || thread.resume::<MultiValue>(args))?;
+    if thread.status() != ThreadStatus::Resumable {
+        return Ok(Step::Finished(values));
+    }
+    let mut values = values.into_iter();
+    let count = |value: Option<mlua::Value>| match value {
+        // mlua::Integer is only i64 on 64 bit targets
+        #[allow(clippy::useless_conversion)]
+        Some(mlua::Value::Integer(n)) => i64::from(n),
+        Some(mlua::Value::Number(n)) => n.ceil() as i64,
+        _ => 1,
+    };
+    let wait = match (values.next(), values.next()) {
+        (Some(mlua::Value::String(kind)), Some(mlua::Value::Function(predicate))) if kind == "until" => Wait::Until(predicate),
+        (Some(mlua::Value::String(kind)), value) if kind == "days" => Wait::Days { days: count(value).max(0), from: today },
+        (Some(mlua::Value::String(kind)), value) if kind == "ticks" => Wait::Tick(tick + count(value).max(1) as u64),
+        // A bare coroutine.yield() waits for the next tick
+        _ => Wait::Tick(tick + 1),
+    };
+    Ok(Step::Waiting(wait))
+}
+
+static SCHEDULER: LazyLock<Scheduler> = LazyLock::new(Scheduler::default);
+
+static TICK_BUDGET: LazyLock<Duration> = LazyLock::new(|| Duration::from_millis(get_openzt_config().scripting.tick_budget_ms));
+
+/// Run `function` as a job on the global scheduler, see [`Scheduler::start`]
+pub fn start(lua: &Lua, name: &str, function: Function, args: MultiValue, limits: &ExecutionLimits) -> mlua::Result<JobStart> {
+    SCHEDULER.start(lua, name, function, args, limits, game_day())
+}
+
+/// Resume waiting jobs, called every sim tick
+pub fn tick() {
+    SCHEDULER.tick(&lua_handle(), game_day(), *TICK_BUDGET);
+}
+
+fn waiter(lua: &Lua, name: &str) -> Function {
+    let waiters: mlua::Table = lua.load(WAITERS).set_name("=scheduler").eval().unwrap();
+    waiters.get(name).unwrap()
+}
+
+pub fn init() {
+    add_lua_function(
+        "wait_ticks",
+        "Suspends the running job for a number of sim ticks",
+        "wait_ticks(n)",
+        Capability::Core,
+        LuaTypes::new().param::<f64>("n").returns::<()>(),
+        |lua| waiter(lua, "wait_ticks"),
+    )
+    .unwrap();
+    add_lua_function(
+        "wait_game_days",
+        "Suspends the running job for a number of in-game days",
+        "wait_game_days(n)",
+        Capability::Core,
+        LuaTypes::new().param::<f64>("n").returns::<()>(),
+        |lua| waiter(lua, "wait_game_days"),
+    )
+    .unwrap();
+    add_lua_function(
+        "wait_until",
+        "Suspends the running job until predicate() returns true, checked every tick",
+        "wait_until(predicate)",
+        Capability::Core,
+        LuaTypes::new().param::<Function>("predicate").returns::<()>(),
+        |lua| waiter(lua, "wait_until"),
+    )
+    .unwrap();
+
+    // spawn(fn, ...) - runs fn as a job with the caller's sandbox limits
+    add_lua_function(
+        "spawn",
+        "Runs a function as a job that can wait, returns the job id",
+        "spawn(fn, ...)",
+        Capability::Core,
+        LuaTypes::new().param::<Function>("fn").param::<MultiValue>("args").returns::<Option<JobId>>(),
+        |lua| {
+            lua.create_function(|lua, (function, args): (Function, MultiValue)| {
+                let info = function.info();
+                let name = format!("{}:{}", info.short_src.unwrap_or_default(), info.line_defined.unwrap_or_default());
+                match start(lua, &name, function, args, &current_limits())? {
+                    JobStart::Waiting(id) => Ok(Some(id)),
+                    JobStart::Finished(_) => Ok(None),
+                }
+            })
+            .unwrap()
+        },
+    )
+    .unwrap();
+
+    lua_fn!("jobs", "Lists the jobs waiting to be resumed", "jobs()", Core, || {
+        let jobs = SCHEDULER.list(current_limits().owner.as_deref());
+        Ok(if jobs.is_empty() { "No jobs waiting".to_string() } else { jobs.join("\n") })
+    });
+
+    lua_fn!("cancel", "Cancels a waiting job, returns false if there is none with that id", "cancel(job_id)", Core, |id: JobId| {
+        Ok(SCHEDULER.cancel(id, current_limits().owner.as_deref()))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    const BUDGET: Duration = Duration::from_secs(5);
+
+    /// A Lua state with the wait functions and a global `record(value)` that appends to the returned list
+    fn job_lua() -> (Lua, Arc<Mutex<Vec<String>>>) {
+        let lua = Lua::new();
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&records);
+        let record = lua
+            .create_function(move |_, value: String| {
+                sink.lock().unwrap().push(value);
+                Ok(())
+            })
+            .unwrap();
+        lua.globals().set("record", record).unwrap();
+        for name in ["wait_ticks", "wait_game_days", "wait_until"] {
+            lua.globals().set(name, waiter(&lua, name)).unwrap();
+        }
+        (lua, records)
+    }
+
+    fn start_code(scheduler: &Scheduler, lua: &Lua, code: &str, today: Option<i64>) -> mlua::Result<JobStart> {
+        let function = lua.load(code).into_function()?;
+        scheduler.start(lua, "test", function, MultiValue::new(), &ExecutionLimits::default(), today)
+    }
+
+    #[test]
+    fn test_finishes_without_waiting() {
+        let (lua, _) = job_lua();
+        let scheduler = Scheduler::default();
+        let JobStart::Finished(values) = start_code(&scheduler, &lua, "return 1 + 1", None).unwrap() else {
+            panic!("job should have finished");
+        };
+        assert_eq!(values.into_iter().next(), Some(mlua::Value::Integer(2)));
+        assert!(scheduler.list(None).is_empty());
+    }
+
+    #[test]
+    fn test_wait_ticks() {
+        let (lua, records) = job_lua();
+        let scheduler = Scheduler::default();
+        let start = start_code(&scheduler, &lua, "for i = 1, 3 do record('step ' .. i) wait_ticks(2) end", None).unwrap();
+        assert!(matches!(start, JobStart::Waiting(1)));
+        assert_eq!(scheduler.list(None), vec!["1: test (waiting for 2 ticks)"]);
+
+        for _ in 0..6 {
+            scheduler.tick(&lua, None, BUDGET);
+        }
+        assert_eq!(*records.lock().unwrap(), vec!["step 1", "step 2", "step 3"]);
+        assert_eq!(scheduler.list(None).len(), 1);
+        scheduler.tick(&lua, None, BUDGET);
+        assert!(scheduler.list(None).is_empty());
+    }
+
+    #[test]
+    fn test_wait_game_days_and_until() {
+        let (lua, records) = job_lua();
+        let scheduler = Scheduler::default();
+        start_code(&scheduler, &lua, "wait_game_days(3) record('days')", None).unwrap();
+        lua.globals().set("ready", false).unwrap();
+        start_code(&scheduler, &lua, "wait_until(function() return ready end) record('until')", None).unwrap();
+
+        // Days only count once a game is running
+        scheduler.tick(&lua, None, BUDGET);
+        scheduler.tick(&lua, Some(100), BUDGET);
+        scheduler.tick(&lua, Some(102), BUDGET);
+        assert!(records.lock().unwrap().is_empty());
+        lua.globals().set("ready", true).unwrap();
+        scheduler.tick(&lua, Some(103), BUDGET);
+        assert_eq!(*records.lock().unwrap(), vec!["days", "until"]);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let (lua, records) = job_lua();
+        let scheduler = Scheduler::default();
+        start_code(&scheduler, &lua, "while true do record('tick') wait_ticks(1) end", None).unwrap();
+        scheduler.tick(&lua, None, BUDGET);
+        scheduler.tick(&lua, None, BUDGET);
+        assert!(scheduler.cancel(1, None));
+        assert!(!scheduler.cancel(1, None));
+        scheduler.tick(&lua, None, BUDGET);
+        assert_eq!(*records.lock().unwrap(), vec!["tick", "tick"]);
+    }
+
+    #[test]
+    fn test_mods_only_manage_their_own_jobs() {
+        let (lua, _) = job_lua();
+        let scheduler = Scheduler::default();
+        let function = |code: &str| lua.load(code).into_function().unwrap();
+        for (name, mod_id) in [("first", "mod.a"), ("second", "mod.b")] {
+            let limits = ExecutionLimits::default().owned_by(mod_id);
+            scheduler.start(&lua, name, function("wait_ticks(10)"), MultiValue::new(), &limits, None).unwrap();
+        }
+
+        assert_eq!(scheduler.list(Some("mod.a")), vec!["1: first (waiting for 10 ticks)"]);
+        assert!(!scheduler.cancel(2, Some("mod.a")));
+        assert_eq!(scheduler.list(None).len(), 2);
+        assert!(scheduler.cancel(1, Some("mod.a")));
+        // The console manages every job
+        assert!(scheduler.cancel(2, None));
+        assert!(scheduler.list(None).is_empty());
+    }
+
+    #[test]
+    fn test_errors() {
+        let (lua, records) = job_lua();
+        let scheduler = Scheduler::default();
+        let err = start_code(&scheduler, &lua, "wait_ticks('soon')", None).unwrap_err();
+        assert!(err.to_string().contains("wait_ticks() expects a number, got string"));
+        let err = lua.load("wait_ticks(1)").exec().unwrap_err();
+        assert!(err.to_string().contains("wait_ticks() can only be called from a job"));
+
+        start_code(&scheduler, &lua, "wait_ticks(1) error('boom')", None).unwrap();
+        start_code(&scheduler, &lua, "wait_ticks(1) record('still running')", None).unwrap();
+        scheduler.tick(&lua, None, BUDGET);
+        scheduler.tick(&lua, None, BUDGET);
+        assert_eq!(*records.lock().unwrap(), vec!["still running"]);
+        assert!(scheduler.list(None).is_empty());
+    }
+
+    #[test]
+    fn test_tick_budget() {
+        let (lua, records) = job_lua();
+        let scheduler = Scheduler::default();
+        let budget = Duration::from_millis(25);
+        start_code(&scheduler, &lua, "wait_ticks(1) record('slow') local t = os.clock() while os.clock() - t < 1 do end record('never')", None).unwrap();
+        start_code(&scheduler, &lua, "wait_ticks(1) record('quick')", None).unwrap();
+
+        // The slow job runs past the budget and is cancelled, which leaves no time for the quick one
+        scheduler.tick(&lua, None, budget);
+        scheduler.tick(&lua, None, budget);
+        assert_eq!(*records.lock().unwrap(), vec!["slow"]);
+        assert_eq!(scheduler.list(None), vec!["2: test (waiting for 0 ticks)"]);
+
+        // It goes first on the next tick instead
+        scheduler.tick(&lua, None, budget);
+        assert_eq!(*records.lock().unwrap(), vec!["slow", "quick"]);
+        assert!(scheduler.list(None).is_empty());
+    }
+}
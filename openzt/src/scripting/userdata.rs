@@ -0,0 +1,195 @@
+//! Helpers for exposing game structs to Lua as userdata
+//!
+//! Game objects are exposed as small handles holding their address, so every field access
+//! reads the live value from memory. Fields come from the `FieldAccessorAsString` derive: reads
+//! go through `get_field` and writes through `set_field`, with values converted between the
+//! derive's strings and Lua numbers, booleans and strings. A handle is checked against the game's
+//! current list of such objects before each access, as Lua may keep it after the object is freed.
+
+use field_accessor_as_string_trait::FieldAccessorAsStringTrait;
+use mlua::{Function, IntoLua, Lua};
+
+use super::{sandbox::require_capability, Capability};
+
+/// Convert a value returned by `get_field` to the matching Lua type
+fn field_to_lua(lua: &Lua, value: String) -> mlua::Result<mlua::Value> {
+    if let Ok(boolean) = value.parse::<bool>() {
+        return Ok(mlua::Value::Boolean(boolean));
+    }
+    if let Ok(integer) = value.parse::<mlua::Integer>() {
+        return Ok(mlua::Value::Integer(integer));
+    }
+    if let Ok(number) = value.parse::<f64>() {
+        return Ok(mlua::Value::Number(number));
+    }
+    value.into_lua(lua)
+}
+
+/// Convert a Lua value to a string `set_field` can parse for a field currently holding `current`
+///
+/// Booleans and numbers are interchangeable, as the game stores many flags as integers.
+fn lua_to_field(current: &str, value: mlua::Value) -> mlua::Result<String> {
+    let is_bool = current.parse::<bool>().is_ok();
+    Ok(match value {
+        mlua::Value::Boolean(b) if is_bool => b.to_string(),
+        mlua::Value::Boolean(b) => (b as u8).to_string(),
+        mlua::Value::Integer(i) if is_bool => (i != 0).to_string(),
+        mlua::Value::Integer(i) => i.to_string(),
+        mlua::Value::Number(n) if is_bool => (n != 0.0).to_string(),
+        // Whole numbers are written without a fraction so integer fields can parse them
+        mlua::Value::Number(n) if n.fract() == 0.0 && n.abs() < mlua::Integer::MAX as f64 => (n as i64).to_string(),
+        mlua::Value::Number(n) => n.to_string(),
+        mlua::Value::String(s) => s.to_str()?.to_string(),
+        other => return Err(mlua::Error::runtime(format!("expected a number, boolean or string, got {}", other.type_name()))),
+    })
+}
+
+/// Read field `name` of `target` as a Lua value
+///
+/// # Arguments
+/// * `type_name` - The type shown in errors, e.g. "ZTEntity"
+pub fn get_field<T: FieldAccessorAsStringTrait + ?Sized>(lua: &Lua, type_name: &str, target: &T, name: &str) -> mlua::Result<mlua::Value> {
+    if !target.is_field(name) {
+        return Err(mlua::Error::runtime(format!("{} has no field '{}'", type_name, name)));
+    }
+    field_to_lua(lua, target.get_field(name).map_err(mlua::Error::runtime)?)
+}
+
+/// Set field `name` of `target` from a Lua value
+///
+/// Writing to the game requires the `modify_world` capability.
+///
+/// # Arguments
+/// * `type_name` - The type shown in errors, e.g. "ZTEntity"
+/// * `settable` - Fields that are safe to change, `None` if they all are
+pub fn set_field<T: FieldAccessorAsStringTrait + ?Sized>(
+    type_name: &str,
+    target: &mut T,
+    settable: Option<&[&str]>,
+    name: &str,
+    value: mlua::Value,
+) -> mlua::Result<()> {
+    require_capability(Capability::ModifyWorld, &format!("setting {}.{}", type_name, name))?;
+    if !target.is_field(name) {
+        return Err(mlua::Error::runtime(format!("{} has no field '{}'", type_name, name)));
+    }
+    if settable.is_some_and(|settable| !settable.contains(&name)) {
+        return Err(mlua::Error::runtime(format!("{}.{} is read-only", type_name, name)));
+    }
+    let current = target.get_field(name).map_err(mlua::Error::runtime)?;
+    let value = lua_to_field(&current, value)?;
+    target.set_field(name, &value).map_err(|err| mlua::Error::runtime(format!("Failed to set {}.{}: {}", type_name, name, err)))
+}
+
+/// The error raised when a handle's object is no longer in the game
+///
+/// # Arguments
+/// * `type_name` - The type shown in errors, e.g. "ZTEntity"
+pub fn stale_handle(type_name: &str, address: u32) -> mlua::Error {
+    mlua::Error::runtime(format!("{} at {:#x} no longer exists", type_name, address))
+}
+
+/// A Lua iterator function over `items`, for use in a generic `for` loop
+///
+/// The items are collected up front, so the iterator is unaffected by entities being added or
+/// removed while the loop runs.
+pub fn iterator<T: IntoLua + Send + 'static>(lua: &Lua, items: Vec<T>) -> mlua::Result<Function> {
+    let mut items = items.into_iter();
+    lua.create_function_mut(move |_, ()| Ok(items.next()))
+}
+
+#[cfg(test)]
+mod tests {
+    use field_accessor_as_string::FieldAccessorAsString;
+    use mlua::{MetaMethod, UserData, UserDataMethods};
+
+    use super::*;
+    use crate::scripting::sandbox::{with_limits, ExecutionLimits};
+
+    #[derive(Debug, Default, FieldAccessorAsString)]
+    struct Animal {
+        name: String,
+        age: u32,
+        happiness: f32,
+        visible: u8,
+        sick: bool,
+    }
+
+    #[derive(Clone, Copy)]
+    struct AnimalRef(usize);
+
+    static ANIMALS: std::sync::Mutex<Vec<Animal>> = std::sync::Mutex::new(Vec::new());
+
+    impl UserData for AnimalRef {
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_method("is_old", |_, this, ()| Ok(ANIMALS.lock().unwrap()[this.0].age > 10));
+            methods.add_meta_method(MetaMethod::Index, |lua, this, name: String| get_field(lua, "Animal", &ANIMALS.lock().unwrap()[this.0], &name));
+            methods.add_meta_method(MetaMethod::NewIndex, |_, this, (name, value): (String, mlua::Value)| {
+                set_field("Animal", &mut ANIMALS.lock().unwrap()[this.0], Some(&["age", "visible", "sick"]), &name, value)
+            });
+        }
+    }
+
+    fn eval(lua: &Lua, code: &str) -> mlua::Result<mlua::Value> {
+        lua.load(code).eval()
+    }
+
+    #[test]
+    fn test_animal_userdata() {
+        *ANIMALS.lock().unwrap() = vec![
+            Animal { name: "Lion".to_string(), age: 12, happiness: 87.5, ..Default::default() },
+            Animal { name: "Zebra".to_string(), age: 3, happiness: 40.0, ..Default::default() },
+        ];
+        let lua = Lua::new();
+        lua.globals()
+            .set("animals", lua.create_function(|lua, ()| iterator(lua, vec![AnimalRef(0), AnimalRef(1)])).unwrap())
+            .unwrap();
+
+        // Fields and methods both resolve, and iterators work in a for loop
+        let names = eval(&lua, "local names = {} for a in animals() do if a:is_old() then table.insert(names, a.name) end end return table.concat(names, ',')").unwrap();
+        assert_eq!(names.as_string().unwrap(), "Lion");
+        assert_eq!(eval(&lua, "local a = animals()() return a.happiness").unwrap(), mlua::Value::Number(87.5));
+        assert_eq!(eval(&lua, "local a = animals()() return a.sick").unwrap(), mlua::Value::Boolean(false));
+
+        eval(&lua, "local a = animals()() a.age = 13 a.visible = true a.sick = 1").unwrap();
+        let animals = ANIMALS.lock().unwrap();
+        assert_eq!((animals[0].age, animals[0].visible, animals[0].sick), (13, 1, true));
+        drop(animals);
+
+        let err = eval(&lua, "animals()().name = 'Tiger'").unwrap_err();
+        assert!(err.to_string().contains("Animal.name is read-only"));
+        let err = eval(&lua, "return animals()().colour").unwrap_err();
+        assert!(err.to_string().contains("Animal has no field 'colour'"));
+        let err = eval(&lua, "animals()().age = 'old'").unwrap_err();
+        assert!(err.to_string().contains("Failed to set Animal.age"));
+    }
+
+    #[test]
+    fn test_set_field_requires_modify_world() {
+        let mut animal = Animal::default();
+        let lua = Lua::new();
+        let read_only = ExecutionLimits {
+            capabilities: Some(vec![Capability::ReadWorld]),
+            ..Default::default()
+        };
+
+        let err = with_limits(&lua, &read_only, || set_field("Animal", &mut animal, None, "age", mlua::Value::Integer(5))).unwrap_err();
+        assert!(err.to_string().contains("setting Animal.age requires the 'modify_world' capability"));
+        assert_eq!(animal.age, 0);
+    }
+
+    #[test]
+    fn test_field_conversion() {
+        let lua = Lua::new();
+        assert_eq!(field_to_lua(&lua, "42".to_string()).unwrap(), mlua::Value::Integer(42));
+        assert_eq!(field_to_lua(&lua, "-1.5".to_string()).unwrap(), mlua::Value::Number(-1.5));
+        assert_eq!(field_to_lua(&lua, "true".to_string()).unwrap(), mlua::Value::Boolean(true));
+        assert_eq!(field_to_lua(&lua, "Lion".to_string()).unwrap().as_string().unwrap(), "Lion");
+
+        assert_eq!(lua_to_field("7", mlua::Value::Number(3.0)).unwrap(), "3");
+        assert_eq!(lua_to_field("7", mlua::Value::Number(3.5)).unwrap(), "3.5");
+        assert_eq!(lua_to_field("0", mlua::Value::Boolean(true)).unwrap(), "1");
+        assert_eq!(lua_to_field("false", mlua::Value::Integer(2)).unwrap(), "true");
+        assert!(lua_to_field("0", mlua::Value::Nil).is_err());
+    }
+}
@@ -1,6 +1,8 @@
 use crate::command_console::CommandError;
 use crate::lua_fn;
+#[cfg(target_os = "windows")]
 use tracing::error;
+#[cfg(target_os = "windows")]
 use openzt_detour_macro::detour_mod;
 
 mod ai;
@@ -61,6 +63,7 @@ fn command_list_settings(args: Vec<&str>) -> Result<String, CommandError> {
     Ok(categories.to_string())
 }
 
+#[cfg(target_os = "windows")]
 #[detour_mod]
 mod zoo_ini_loading {
     use tracing::info;
@@ -77,8 +80,17 @@ mod zoo_ini_loading {
 
 
 pub fn init() {
+    register_lua_functions();
+
+    #[cfg(target_os = "windows")]
+    if unsafe { zoo_ini_loading::init_detours() }.is_err() {
+        error!("Error initialising load ini detours");
+    };
+}
+
+pub(crate) fn register_lua_functions() {
     // get_setting(section, key) - two string args
-    lua_fn!("get_setting", "Gets a setting value", "get_setting(section, key)", |section: String, key: String| {
+    lua_fn!("get_setting", "Gets a setting value", "get_setting(section, key)", ReadWorld, |section: String, key: String| {
         match command_get_setting(vec![&section, &key]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
@@ -86,7 +98,7 @@ pub fn init() {
     });
 
     // set_setting(section, key, value) - three string args
-    lua_fn!("set_setting", "Sets a setting value", "set_setting(section, key, value)", |section: String, key: String, value: String| {
+    lua_fn!("set_setting", "Sets a setting value", "set_setting(section, key, value)", ModifyConfig, |section: String, key: String, value: String| {
         match command_set_setting(vec![&section, &key, &value]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
@@ -94,7 +106,7 @@ pub fn init() {
     });
 
     // list_settings([category]) - optional string arg
-    lua_fn!("list_settings", "Lists available settings, optionally filtered by category", "list_settings([category])", |category: Option<String>| {
+    lua_fn!("list_settings", "Lists available settings, optionally filtered by category", "list_settings([category])", ReadWorld, |category: Option<String>| {
         match category {
             Some(cat) => {
                 match command_list_settings(vec![&cat]) {
@@ -110,8 +122,4 @@ pub fn init() {
             }
         }
     });
-
-    if unsafe { zoo_ini_loading::init_detours() }.is_err() {
-        error!("Error initialising load ini detours");
-    };
 }
\ No newline at end of file
@@ -1,12 +1,14 @@
+use openzt_detour::{game_address, game_build::GameAddress};
+
 use super::util::{Address, Setting, GettableSettable};
 
 
-const ZTAIMGR_ADDRESS_PTR: u32 = 0x00638098;
+const ZTAIMGR_ADDRESS_PTR: GameAddress = game_address!(CompleteCollection: 0x00638098);
 
 const SHOW_BUILDING_AI_INFO: Setting<bool> = Setting {
     header: "AI",
     key: "ShowBuildingAIInfo",
-    address: Address::Global(0x00638fc8),
+    address: Address::Global(game_address!(CompleteCollection: 0x00638fc8)),
     default: false,
 };
 const SHOW_AI_INFO: Setting<bool> = Setting {
@@ -1,8 +1,10 @@
+use openzt_detour::{game_address, game_build::GameAddress};
+
 use super::util::{Address, Setting, GettableSettable};
 
-pub const EXE_LOCATION_ADDRESS: u32 = 0x0064BEDC;
-pub const EXE_LOCATION_ADDRESS_2: u32 = 0x0064BED8;
-pub const EXE_LOCATION_ADDRESS_3: u32 = 0x0064A800;
+pub const EXE_LOCATION_ADDRESS: GameAddress = game_address!(CompleteCollection: 0x0064BEDC);
+pub const EXE_LOCATION_ADDRESS_2: GameAddress = game_address!(CompleteCollection: 0x0064BED8);
+pub const EXE_LOCATION_ADDRESS_3: GameAddress = game_address!(CompleteCollection: 0x0064A800);
 
 pub fn get_settings() -> Vec<Box<dyn GettableSettable>> {
     vec![
@@ -18,37 +20,37 @@ pub fn get_settings() -> Vec<Box<dyn GettableSettable>> {
 const LOG_CUTTOFF: Setting<i32> = Setting {
     header: "Debug",
     key: "logCutoff",
-    address: Address::Global(0x0063804c),
+    address: Address::Global(game_address!(CompleteCollection: 0x0063804c)),
     default: 9,
 };
 const SEND_DEBUGGER: Setting<bool> = Setting {
     header: "Debug",
     key: "sendDebugger",
-    address: Address::Global(0x00643e44),
+    address: Address::Global(game_address!(CompleteCollection: 0x00643e44)),
     default: false,
 };
 const SEND_LOG_FILE: Setting<bool> = Setting {
     header: "Debug",
     key: "sendLogFile",
-    address: Address::Global(0x00643e48),
+    address: Address::Global(game_address!(CompleteCollection: 0x00643e48)),
     default: false,
 };
 const SEND_MESSAGE_BOX: Setting<bool> = Setting {
     header: "Debug",
     key: "sendMessageBox",
-    address: Address::Global(0x00643e4a),
+    address: Address::Global(game_address!(CompleteCollection: 0x00643e4a)),
     default: false,
 };
 const DELTA_LOG_0: Setting<bool> = Setting {
     header: "Debug",
     key: "deltaLog0",
-    address: Address::Global(0x00638054),
+    address: Address::Global(game_address!(CompleteCollection: 0x00638054)),
     default: false,
 };
 const DELTA_LOG_1: Setting<bool> = Setting {
     header: "Debug",
     key: "deltaLog1",
-    address: Address::Global(0x0064bd7c),
+    address: Address::Global(game_address!(CompleteCollection: 0x0064bd7c)),
     default: false,
 };
 
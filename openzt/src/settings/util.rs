@@ -1,4 +1,5 @@
 use openzt_configparser::ini::Ini;
+use openzt_detour::game_build::GameAddress;
 
 use crate::util::{get_from_memory, save_to_memory};
 
@@ -11,8 +12,9 @@ pub struct Setting<T> {
 }
 
 pub enum Address {
-    Global(u32),
-    Indirect(u32, u32),
+    Global(GameAddress),
+    /// A pointer to an object, and the offset of the setting in it
+    Indirect(GameAddress, u32),
 }
 
 pub trait GettableSettable {
@@ -25,16 +27,16 @@ pub trait GettableSettable {
 impl<T> Setting<T> {
     fn write(&self, value: T) {
         let address = match self.address {
-            Address::Global(address) => address,
-            Address::Indirect(base, offset) => get_from_memory::<u32>(base) + offset,
+            Address::Global(address) => address.get(),
+            Address::Indirect(base, offset) => get_from_memory::<u32>(base.get()) + offset,
         };
         save_to_memory::<T>(address, value);
     }
 
     fn read(&self) -> T {
         let address = match self.address {
-            Address::Global(address) => address,
-            Address::Indirect(base, offset) => get_from_memory::<u32>(base) + offset,
+            Address::Global(address) => address.get(),
+            Address::Indirect(base, offset) => get_from_memory::<u32>(base.get()) + offset,
         };
         get_from_memory::<T>(address)
     } 
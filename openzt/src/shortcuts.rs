@@ -1,14 +1,15 @@
 //! Keyboard shortcut registration system.
 //!
 //! Modules can independently register shortcuts with key+modifier combinations.
-//! The HANDLE_KEY_DOWN detour checks matches before calling the original function.
+//! The HANDLE_KEY_DOWN detour checks matches before calling the original function, and publishes
+//! keys no shortcut handled as `KeyPressed` events.
 //!
 //! # Typestate Pattern
 //!
 //! Shortcuts use a typestate pattern for type-safe modifier construction.
 //! Multiple ergonomic syntaxes are supported:
 //!
-//! ```rust
+//! ```ignore
 //! use openzt::shortcuts::{Key, Ctrl, Shift, Alt, VkKey, R};
 //! use windows::Win32::UI::Input::KeyboardAndMouse::*;
 //!
@@ -32,7 +33,85 @@
 use std::marker::PhantomData;
 use std::ops::Add;
 use std::sync::LazyLock;
+#[cfg(target_os = "windows")]
 use windows::Win32::UI::Input::KeyboardAndMouse::*;
+#[cfg(not(target_os = "windows"))]
+use key_codes::*;
+
+/// The virtual key codes of `windows::Win32::UI::Input::KeyboardAndMouse`, which doesn't build off Windows
+#[cfg(not(target_os = "windows"))]
+mod key_codes {
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct VIRTUAL_KEY(pub u16);
+
+    pub const VK_BACK: VIRTUAL_KEY = VIRTUAL_KEY(0x08);
+    pub const VK_TAB: VIRTUAL_KEY = VIRTUAL_KEY(0x09);
+    pub const VK_RETURN: VIRTUAL_KEY = VIRTUAL_KEY(0x0d);
+    pub const VK_SHIFT: VIRTUAL_KEY = VIRTUAL_KEY(0x10);
+    pub const VK_CONTROL: VIRTUAL_KEY = VIRTUAL_KEY(0x11);
+    pub const VK_MENU: VIRTUAL_KEY = VIRTUAL_KEY(0x12);
+    pub const VK_ESCAPE: VIRTUAL_KEY = VIRTUAL_KEY(0x1b);
+    pub const VK_SPACE: VIRTUAL_KEY = VIRTUAL_KEY(0x20);
+    pub const VK_PRIOR: VIRTUAL_KEY = VIRTUAL_KEY(0x21);
+    pub const VK_NEXT: VIRTUAL_KEY = VIRTUAL_KEY(0x22);
+    pub const VK_END: VIRTUAL_KEY = VIRTUAL_KEY(0x23);
+    pub const VK_HOME: VIRTUAL_KEY = VIRTUAL_KEY(0x24);
+    pub const VK_LEFT: VIRTUAL_KEY = VIRTUAL_KEY(0x25);
+    pub const VK_UP: VIRTUAL_KEY = VIRTUAL_KEY(0x26);
+    pub const VK_RIGHT: VIRTUAL_KEY = VIRTUAL_KEY(0x27);
+    pub const VK_DOWN: VIRTUAL_KEY = VIRTUAL_KEY(0x28);
+    pub const VK_INSERT: VIRTUAL_KEY = VIRTUAL_KEY(0x2d);
+    pub const VK_DELETE: VIRTUAL_KEY = VIRTUAL_KEY(0x2e);
+    pub const VK_0: VIRTUAL_KEY = VIRTUAL_KEY(0x30);
+    pub const VK_1: VIRTUAL_KEY = VIRTUAL_KEY(0x31);
+    pub const VK_2: VIRTUAL_KEY = VIRTUAL_KEY(0x32);
+    pub const VK_3: VIRTUAL_KEY = VIRTUAL_KEY(0x33);
+    pub const VK_4: VIRTUAL_KEY = VIRTUAL_KEY(0x34);
+    pub const VK_5: VIRTUAL_KEY = VIRTUAL_KEY(0x35);
+    pub const VK_6: VIRTUAL_KEY = VIRTUAL_KEY(0x36);
+    pub const VK_7: VIRTUAL_KEY = VIRTUAL_KEY(0x37);
+    pub const VK_8: VIRTUAL_KEY = VIRTUAL_KEY(0x38);
+    pub const VK_9: VIRTUAL_KEY = VIRTUAL_KEY(0x39);
+    pub const VK_A: VIRTUAL_KEY = VIRTUAL_KEY(0x41);
+    pub const VK_B: VIRTUAL_KEY = VIRTUAL_KEY(0x42);
+    pub const VK_C: VIRTUAL_KEY = VIRTUAL_KEY(0x43);
+    pub const VK_D: VIRTUAL_KEY = VIRTUAL_KEY(0x44);
+    pub const VK_E: VIRTUAL_KEY = VIRTUAL_KEY(0x45);
+    pub const VK_F: VIRTUAL_KEY = VIRTUAL_KEY(0x46);
+    pub const VK_G: VIRTUAL_KEY = VIRTUAL_KEY(0x47);
+    pub const VK_H: VIRTUAL_KEY = VIRTUAL_KEY(0x48);
+    pub const VK_I: VIRTUAL_KEY = VIRTUAL_KEY(0x49);
+    pub const VK_J: VIRTUAL_KEY = VIRTUAL_KEY(0x4a);
+    pub const VK_K: VIRTUAL_KEY = VIRTUAL_KEY(0x4b);
+    pub const VK_L: VIRTUAL_KEY = VIRTUAL_KEY(0x4c);
+    pub const VK_M: VIRTUAL_KEY = VIRTUAL_KEY(0x4d);
+    pub const VK_N: VIRTUAL_KEY = VIRTUAL_KEY(0x4e);
+    pub const VK_O: VIRTUAL_KEY = VIRTUAL_KEY(0x4f);
+    pub const VK_P: VIRTUAL_KEY = VIRTUAL_KEY(0x50);
+    pub const VK_Q: VIRTUAL_KEY = VIRTUAL_KEY(0x51);
+    pub const VK_R: VIRTUAL_KEY = VIRTUAL_KEY(0x52);
+    pub const VK_S: VIRTUAL_KEY = VIRTUAL_KEY(0x53);
+    pub const VK_T: VIRTUAL_KEY = VIRTUAL_KEY(0x54);
+    pub const VK_U: VIRTUAL_KEY = VIRTUAL_KEY(0x55);
+    pub const VK_V: VIRTUAL_KEY = VIRTUAL_KEY(0x56);
+    pub const VK_W: VIRTUAL_KEY = VIRTUAL_KEY(0x57);
+    pub const VK_X: VIRTUAL_KEY = VIRTUAL_KEY(0x58);
+    pub const VK_Y: VIRTUAL_KEY = VIRTUAL_KEY(0x59);
+    pub const VK_Z: VIRTUAL_KEY = VIRTUAL_KEY(0x5a);
+    pub const VK_F1: VIRTUAL_KEY = VIRTUAL_KEY(0x70);
+    pub const VK_F2: VIRTUAL_KEY = VIRTUAL_KEY(0x71);
+    pub const VK_F3: VIRTUAL_KEY = VIRTUAL_KEY(0x72);
+    pub const VK_F4: VIRTUAL_KEY = VIRTUAL_KEY(0x73);
+    pub const VK_F5: VIRTUAL_KEY = VIRTUAL_KEY(0x74);
+    pub const VK_F6: VIRTUAL_KEY = VIRTUAL_KEY(0x75);
+    pub const VK_F7: VIRTUAL_KEY = VIRTUAL_KEY(0x76);
+    pub const VK_F8: VIRTUAL_KEY = VIRTUAL_KEY(0x77);
+    pub const VK_F9: VIRTUAL_KEY = VIRTUAL_KEY(0x78);
+    pub const VK_F10: VIRTUAL_KEY = VIRTUAL_KEY(0x79);
+    pub const VK_F11: VIRTUAL_KEY = VIRTUAL_KEY(0x7a);
+    pub const VK_F12: VIRTUAL_KEY = VIRTUAL_KEY(0x7b);
+}
 
 // ============================================================================
 // Typestate Pattern for Shortcut Construction
@@ -192,7 +271,7 @@ impl<const CTRL: bool, const ALT: bool, const SHIFT: bool, const KEY: i32>
 ///
 /// # Example
 ///
-/// ```rust
+/// ```ignore
 /// use openzt::shortcuts::{Ctrl, Shift, R};
 ///
 /// // Shift-only: produces PartialShortcut (not registerable)
@@ -488,7 +567,7 @@ impl<const CTRL: bool, const ALT: bool, const SHIFT: bool, const KEY: i32> Regis
 ///
 /// # Example
 ///
-/// ```rust
+/// ```ignore
 /// use openzt::shortcuts::{Key, Ctrl, Shift};
 ///
 /// register_shortcut(
@@ -567,10 +646,9 @@ pub fn register_shortcut<S: Registerable>(
 ///
 /// Returns the callback function if a match is found, None otherwise.
 pub fn check_shortcuts(key_code: i32) -> Option<fn()> {
-    // GetAsyncKeyState returns i16, check high bit (bit 15) for key state
-    let ctrl_pressed = unsafe { GetAsyncKeyState(VK_CONTROL.0 as i32) as u16 } & 0x8000 != 0;
-    let shift_pressed = unsafe { GetAsyncKeyState(VK_SHIFT.0 as i32) as u16 } & 0x8000 != 0;
-    let alt_pressed = unsafe { GetAsyncKeyState(VK_MENU.0 as i32) as u16 } & 0x8000 != 0;
+    let ctrl_pressed = is_key_down(VK_CONTROL);
+    let shift_pressed = is_key_down(VK_SHIFT);
+    let alt_pressed = is_key_down(VK_MENU);
 
     let registry = SHORTCUT_REGISTRY.lock().unwrap();
     registry
@@ -587,6 +665,18 @@ pub fn check_shortcuts(key_code: i32) -> Option<fn()> {
         })
 }
 
+#[cfg(target_os = "windows")]
+fn is_key_down(key: VIRTUAL_KEY) -> bool {
+    // GetAsyncKeyState returns i16, check high bit (bit 15) for key state
+    unsafe { GetAsyncKeyState(key.0 as i32) as u16 & 0x8000 != 0 }
+}
+
+/// Without a keyboard to poll no modifier is held
+#[cfg(not(target_os = "windows"))]
+fn is_key_down(_key: VIRTUAL_KEY) -> bool {
+    false
+}
+
 /// List all registered shortcuts as a formatted string.
 pub fn list_shortcuts() -> String {
     let registry = SHORTCUT_REGISTRY.lock().unwrap();
@@ -628,7 +718,7 @@ pub fn list_shortcuts() -> String {
 ///
 /// # Example
 ///
-/// ```rust
+/// ```ignore
 /// shortcut!(
 ///     "ztgamemgr",
 ///     "Add $10,000 to budget",
@@ -653,10 +743,13 @@ macro_rules! shortcut {
     };
 }
 
+#[cfg(target_os = "windows")]
 use openzt_detour_macro::detour_mod;
+#[cfg(target_os = "windows")]
 use openzt_detour::gen::ztapp::HANDLE_KEY_DOWN;
 use tracing::info;
 
+#[cfg(target_os = "windows")]
 #[detour_mod]
 pub mod zoo_shortcuts {
     use super::*;
@@ -667,12 +760,16 @@ pub mod zoo_shortcuts {
             callback();
             return 0; // Don't call original function
         }
+        if !crate::events::publish(crate::events::Event::KeyPressed { key: param_1 }) {
+            return 0;
+        }
         unsafe { HANDLE_KEY_DOWN_DETOUR.call(param_1) }
     }
 }
 
 pub fn init() {
     info!("Initializing keyboard shortcut system");
+    #[cfg(target_os = "windows")]
     if let Err(e) = unsafe { zoo_shortcuts::init_detours() } {
         tracing::error!("Error initializing shortcut detours: {}", e);
     }
@@ -2,6 +2,8 @@ use std::sync::Mutex;
 
 use std::sync::LazyLock;
 use std::collections::HashMap;
+use openzt_detour::{game_address, game_build::GameAddress, gen::bfapp::LOAD_STRING};
+#[cfg(target_os = "windows")]
 use openzt_detour_macro::detour_mod;
 use tracing::info;
 
@@ -10,7 +12,7 @@ use crate::lua_fn;
 
 const STRING_REGISTRY_ID_OFFSET: u32 = 100_000;
 
-const GLOBAL_BFAPP: u32 = 0x00638148;
+const GLOBAL_BFAPP: GameAddress = game_address!(CompleteCollection: 0x00638148);
 
 static STRING_REGISTRY: LazyLock<Mutex<Vec<String>>> = LazyLock::new(|| Mutex::new(Vec::new()));
 
@@ -75,14 +77,14 @@ fn command_get_string(args: Vec<&str>) -> Result<String, CommandError> {
     }
     let string_id = args[0].parse::<u32>()?;
 
-    let bfapp_load_string: extern "thiscall" fn(u32, u32, u32) -> u32 = unsafe { std::mem::transmute(0x00404e0a) };
+    let bfapp_load_string = unsafe { LOAD_STRING.original() };
 
     if let Ok(string) = get_string_from_registry(string_id) {
         Ok(format!("OpenZT: {}", string))
     } else {
         info!("String not in registry, calling ZT");
         let buffer = &mut [0u8; 200];
-        let length = bfapp_load_string(GLOBAL_BFAPP, string_id, buffer.as_mut_ptr() as u32);
+        let length = unsafe { bfapp_load_string(GLOBAL_BFAPP.get(), string_id, buffer.as_mut_ptr() as u32) };
         if length == 0 {
             return Err(Into::into("String not found"));
         }
@@ -91,6 +93,7 @@ fn command_get_string(args: Vec<&str>) -> Result<String, CommandError> {
     }
 }
 
+#[cfg(target_os = "windows")]
 #[detour_mod]
 pub mod zoo_string {
     use tracing::info;
@@ -145,12 +148,16 @@ pub mod zoo_string {
 }
 
 pub fn init() {
+    #[cfg(target_os = "windows")]
     if unsafe { zoo_string::init_detours() }.is_err() {
         info!("Failed to initialize string_registry detours");
     }
+    register_lua_functions();
+}
 
+pub(crate) fn register_lua_functions() {
     // get_string(id) - single u32 arg
-    lua_fn!("get_string", "Retrieves game string by ID (from OpenZT registry or game)", "get_string(id)", |id: u32| {
+    lua_fn!("get_string", "Retrieves game string by ID (from OpenZT registry or game)", "get_string(id)", ReadWorld, |id: u32| {
         let id_str = id.to_string();
         match command_get_string(vec![&id_str]) {
             Ok(result) => Ok((Some(result), None::<String>)),
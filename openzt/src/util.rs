@@ -1,37 +1,26 @@
-use std::{ffi::{c_char, CString, CStr}, fmt, mem::transmute, path::PathBuf, ptr, marker};
+use std::{ffi::{c_char, CString, CStr}, fmt, mem::size_of, path::PathBuf, ptr, marker};
 
-#[cfg(target_os = "windows")]
-use windows::Win32::System::Memory::{VirtualProtect, PAGE_EXECUTE_READWRITE, PAGE_PROTECTION_FLAGS};
+use crate::game_memory;
+
+// Memory is read and written through the current thread's backend, the game's own memory unless a test installed
+//  a FakeMemory, see game_memory::with_memory
 
 // TODO: Test replacing most uses of get_from_memory with map_from_memory : Unclear if we need mem::forget each reference afterwards?
 pub fn map_from_memory<T>(address: u32) -> &'static mut T {
-    unsafe { transmute::<u32, &mut T>(address) }
+    unsafe { &mut *(game_memory::host_ptr(address, size_of::<T>()) as *mut T) }
 }
 
 pub fn get_from_memory<T>(address: u32) -> T {
-    unsafe { ptr::read(address as *const T) }
+    unsafe { ptr::read_unaligned(game_memory::host_ptr(address, size_of::<T>()) as *const T) }
 }
 
 pub fn checked_get_from_memory<T: Checkable>(address: u32) -> anyhow::Result<T> {
     T::check(address)?;
-    Ok(unsafe { ptr::read(address as *const T) })
+    Ok(get_from_memory(address))
 }
 
 pub fn save_to_memory<T>(address: u32, value: T) {
-    unsafe { ptr::write(address as *mut T, value) };
-}
-
-#[cfg(target_os = "windows")]
-pub fn save_to_protected_memory<T>(address: u32, value: T) -> anyhow::Result<()> {
-    unsafe {
-        {
-            let mut old_protect: PAGE_PROTECTION_FLAGS = PAGE_PROTECTION_FLAGS(0);
-            VirtualProtect(address as *mut _, std::mem::size_of::<T>(), PAGE_EXECUTE_READWRITE, &mut old_protect)?;
-            ptr::write(address as *mut _, value);
-            VirtualProtect(address as *mut _, std::mem::size_of::<T>(), old_protect, &mut old_protect)?;
-        }
-    }
-    Ok(())
+    unsafe { ptr::write_unaligned(game_memory::host_ptr(address, size_of::<T>()) as *mut T, value) };
 }
 
 pub fn get_base_path() -> PathBuf {
@@ -46,19 +35,6 @@ pub fn get_ini_path() -> PathBuf {
     exe_location
 }
 
-#[cfg(target_os = "windows")]
-pub fn patch_nop(address: u32) -> anyhow::Result<()> {
-    unsafe {
-        {
-            let mut old_protect: PAGE_PROTECTION_FLAGS = PAGE_PROTECTION_FLAGS(0);
-            VirtualProtect(address as *mut _, 1, PAGE_EXECUTE_READWRITE, &mut old_protect)?;
-            ptr::write(address as *mut _, 0x90u8);
-            VirtualProtect(address as *mut _, 1, old_protect, &mut old_protect)?;
-        }
-    }
-    Ok(())
-}
-
 pub fn get_string_from_memory_with_size(address: u32, size: u32) -> String {
     get_string_from_memory_bounded(address, address + size, address + size)
 }
@@ -146,7 +122,7 @@ impl ZTString for ZTBufferString {
     }
 
     fn get_cstr(&self) -> &CStr {
-        unsafe { CStr::from_ptr(self.start_ptr as *const c_char) }
+        unsafe { CStr::from_ptr(game_memory::host_ptr(self.start_ptr, 1) as *const c_char) }
     }
 
     fn copy_to_string(&self) -> String {
@@ -182,7 +158,7 @@ impl ZTString for ZTBoundedString {
     }
 
     fn get_cstr(&self) -> &CStr {
-        unsafe { CStr::from_ptr(self.start_ptr as *const c_char) }
+        unsafe { CStr::from_ptr(game_memory::host_ptr(self.start_ptr, 1) as *const c_char) }
     }
 
     fn copy_to_string(&self) -> String {
@@ -1,12 +1,16 @@
+#[cfg(target_os = "windows")]
 use openzt_detour_macro::detour_mod;
+#[cfg(target_os = "windows")]
 use tracing::error;
 
 pub fn init() {
+    #[cfg(target_os = "windows")]
     if unsafe { bf_version_info::init_detours() }.is_err() {
         error!("Failed to initialize bf_version_info detours");
     }
 }
 
+#[cfg(target_os = "windows")]
 #[detour_mod]
 mod bf_version_info {
     use crate::util::{get_from_memory, get_string_from_memory, save_string_to_memory, save_to_memory};
@@ -0,0 +1,591 @@
+//! Per-zoo state that is saved and loaded with the zoo
+//!
+//! A typed key-value store (booleans, integers, floats, strings and tables) namespaced by mod id;
+//! OpenZT's own state lives in the `openzt` namespace. When the game saves a zoo the store is
+//! written to a sidecar file next to the save (`mygame.zoo` gets `mygame.openzt.json`), loading
+//! the save reads it back, and starting a new zoo clears it.
+//!
+//! Every namespace records the schema version of its data. Registering a schema with
+//! [`register_schema`] migrates data saved by an older version as soon as it is loaded.
+//!
+//! Lua code uses `zoo_state(namespace)` from the console, while mod scripts get `state` bound to
+//! their own mod id. Changing another mod's namespace needs the `modify_config` capability:
+//!
+//! ```lua
+//! state:set("visits", (state:get("visits") or 0) + 1)
+//! state:on_migrate(2, function(old_version, state) state:set("visits", 0) end)
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use anyhow::{anyhow, Context};
+use mlua::{FromLua, IntoLua, Lua, MetaMethod, UserData, UserDataMethods};
+#[cfg(target_os = "windows")]
+use openzt_detour_macro::detour_mod;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::{
+    lua_fn,
+    scripting::{
+        sandbox::{current_limits, require_capability, with_limits},
+        Capability, LuaType,
+    },
+};
+
+/// Namespace of OpenZT's own state
+pub const OPENZT_NAMESPACE: &str = "openzt";
+
+/// Version of the sidecar file layout, files written by a newer OpenZT are not loaded
+const FORMAT_VERSION: u32 = 1;
+
+/// Lua tables nested deeper than this are rejected
+const MAX_TABLE_DEPTH: usize = 16;
+
+/// A value in the store
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum StateValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    /// Integer keys of Lua tables are stored as strings and restored as integers
+    Table(BTreeMap<String, StateValue>),
+}
+
+impl StateValue {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            StateValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn from_lua_at_depth(value: mlua::Value, depth: usize) -> mlua::Result<StateValue> {
+        Ok(match value {
+            mlua::Value::Boolean(value) => StateValue::Bool(value),
+            // mlua::Integer is only i64 on 64 bit targets
+            #[allow(clippy::useless_conversion)]
+            mlua::Value::Integer(value) => StateValue::Int(i64::from(value)),
+            mlua::Value::Number(value) => StateValue::Float(value),
+            mlua::Value::String(value) => StateValue::String(value.to_str()?.to_string()),
+            mlua::Value::Table(table) if depth < MAX_TABLE_DEPTH => {
+                let mut entries = BTreeMap::new();
+                for pair in table.pairs::<mlua::Value, mlua::Value>() {
+                    let (key, value) = pair?;
+                    let key = match key {
+                        mlua::Value::String(key) => key.to_str()?.to_string(),
+                        mlua::Value::Integer(key) => key.to_string(),
+                        other => return Err(mlua::Error::runtime(format!("zoo state table keys must be strings or integers, got {}", other.type_name()))),
+                    };
+                    entries.insert(key, StateValue::from_lua_at_depth(value, depth + 1)?);
+                }
+                StateValue::Table(entries)
+            }
+            mlua::Value::Table(_) => return Err(mlua::Error::runtime(format!("zoo state tables can be nested at most {} deep", MAX_TABLE_DEPTH))),
+            other => return Err(mlua::Error::runtime(format!("zoo state can't store a {}", other.type_name()))),
+        })
+    }
+}
+
+impl From<bool> for StateValue {
+    fn from(value: bool) -> Self {
+        StateValue::Bool(value)
+    }
+}
+
+impl From<i64> for StateValue {
+    fn from(value: i64) -> Self {
+        StateValue::Int(value)
+    }
+}
+
+impl From<f64> for StateValue {
+    fn from(value: f64) -> Self {
+        StateValue::Float(value)
+    }
+}
+
+impl From<&str> for StateValue {
+    fn from(value: &str) -> Self {
+        StateValue::String(value.to_string())
+    }
+}
+
+impl From<String> for StateValue {
+    fn from(value: String) -> Self {
+        StateValue::String(value)
+    }
+}
+
+impl FromLua for StateValue {
+    fn from_lua(value: mlua::Value, _: &Lua) -> mlua::Result<Self> {
+        StateValue::from_lua_at_depth(value, 0)
+    }
+}
+
+impl IntoLua for StateValue {
+    fn into_lua(self, lua: &Lua) -> mlua::Result<mlua::Value> {
+        match self {
+            StateValue::Bool(value) => value.into_lua(lua),
+            StateValue::Int(value) => match mlua::Integer::try_from(value) {
+                Ok(value) => value.into_lua(lua),
+                Err(_) => (value as f64).into_lua(lua),
+            },
+            StateValue::Float(value) => value.into_lua(lua),
+            StateValue::String(value) => value.into_lua(lua),
+            StateValue::Table(entries) => {
+                let table = lua.create_table()?;
+                for (key, value) in entries {
+                    match key.parse::<mlua::Integer>() {
+                        Ok(index) => table.raw_set(index, value)?,
+                        Err(_) => table.raw_set(key, value)?,
+                    }
+                }
+                table.into_lua(lua)
+            }
+        }
+    }
+}
+
+/// The state of one mod
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Namespace {
+    /// Schema version the values were written with
+    pub version: u32,
+    pub values: BTreeMap<String, StateValue>,
+}
+
+/// Everything stored for the loaded zoo, as written to the sidecar file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ZooState {
+    format: u32,
+    namespaces: BTreeMap<String, Namespace>,
+}
+
+impl Default for ZooState {
+    fn default() -> Self {
+        ZooState { format: FORMAT_VERSION, namespaces: BTreeMap::new() }
+    }
+}
+
+impl ZooState {
+    fn parse(contents: &str) -> anyhow::Result<ZooState> {
+        let state: ZooState = serde_json::from_str(contents)?;
+        if state.format > FORMAT_VERSION {
+            return Err(anyhow!("written by a newer version of OpenZT (format {}, this version reads up to {})", state.format, FORMAT_VERSION));
+        }
+        Ok(state)
+    }
+}
+
+/// Upgrades a namespace's data from the version it was saved with
+pub type Migration = Arc<dyn Fn(u32) -> anyhow::Result<()> + Send + Sync>;
+
+struct Schema {
+    version: u32,
+    migrate: Migration,
+}
+
+static STATE: LazyLock<Mutex<ZooState>> = LazyLock::new(|| Mutex::new(ZooState::default()));
+
+static SCHEMAS: LazyLock<Mutex<HashMap<String, Schema>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The save the current zoo was loaded from or last saved to
+static CURRENT_ZOO: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// The file the player picked in the save dialog, until the save it was picked for finishes
+static SAVE_FILE_NAME: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+pub fn get(namespace: &str, key: &str) -> Option<StateValue> {
+    STATE.lock().unwrap().namespaces.get(namespace)?.values.get(key).cloned()
+}
+
+/// Get a boolean, false if it is missing or not a boolean
+pub fn get_bool(namespace: &str, key: &str) -> bool {
+    get(namespace, key).and_then(|value| value.as_bool()).unwrap_or(false)
+}
+
+pub fn set(namespace: &str, key: &str, value: impl Into<StateValue>) {
+    let version = SCHEMAS.lock().unwrap().get(namespace).map_or(0, |schema| schema.version);
+    let mut state = STATE.lock().unwrap();
+    let namespace = state.namespaces.entry(namespace.to_string()).or_insert_with(|| Namespace { version, values: BTreeMap::new() });
+    namespace.values.insert(key.to_string(), value.into());
+}
+
+pub fn remove(namespace: &str, key: &str) -> Option<StateValue> {
+    STATE.lock().unwrap().namespaces.get_mut(namespace)?.values.remove(key)
+}
+
+pub fn keys(namespace: &str) -> Vec<String> {
+    STATE.lock().unwrap().namespaces.get(namespace).map(|namespace| namespace.values.keys().cloned().collect()).unwrap_or_default()
+}
+
+/// Schema version of the stored data, `None` if the namespace has no data
+pub fn version(namespace: &str) -> Option<u32> {
+    STATE.lock().unwrap().namespaces.get(namespace).map(|namespace| namespace.version)
+}
+
+/// Declare the current schema version of a namespace
+///
+/// Whenever data saved with an older version is loaded, `migrate` is called with that version
+/// to upgrade it through [`get`] and [`set`], after which the data is marked as `version`.
+/// A failed migration is logged and leaves the data at its old version.
+pub fn register_schema(namespace: &str, version: u32, migrate: impl Fn(u32) -> anyhow::Result<()> + Send + Sync + 'static) {
+    SCHEMAS.lock().unwrap().insert(namespace.to_string(), Schema { version, migrate: Arc::new(migrate) });
+    // The zoo may already be loaded, e.g. when registering from the console
+    migrate_namespace(namespace);
+}
+
+fn migrate_namespace(namespace: &str) {
+    let Some((version, migrate)) = SCHEMAS.lock().unwrap().get(namespace).map(|schema| (schema.version, Arc::clone(&schema.migrate))) else {
+        return;
+    };
+    let Some(saved_version) = self::version(namespace) else {
+        return;
+    };
+    if saved_version > version {
+        warn!("Zoo state for '{}' was saved by a newer version (schema {}, expected {}), leaving it as is", namespace, saved_version, version);
+        return;
+    }
+    if saved_version == version {
+        return;
+    }
+    // Not holding the lock, migrations use get and set
+    match migrate(saved_version) {
+        Ok(()) => {
+            if let Some(namespace) = STATE.lock().unwrap().namespaces.get_mut(namespace) {
+                namespace.version = version;
+            }
+            info!("Migrated zoo state for '{}' from schema {} to {}", namespace, saved_version, version);
+        }
+        Err(err) => error!("Failed to migrate zoo state for '{}' from schema {} to {}: {:#}", namespace, saved_version, version, err),
+    }
+}
+
+/// Replace the store, then migrate every namespace with a registered schema
+fn replace_state(state: ZooState) {
+    let namespaces: Vec<String> = state.namespaces.keys().cloned().collect();
+    *STATE.lock().unwrap() = state;
+    for namespace in namespaces {
+        migrate_namespace(&namespace);
+    }
+}
+
+/// The sidecar file for a save, `mygame.zoo` -> `mygame.openzt.json`
+pub fn sidecar_path(zoo: &Path) -> PathBuf {
+    zoo.with_extension("openzt.json")
+}
+
+/// Load the state saved alongside `zoo`, or start empty if there is none
+pub fn load(zoo: &Path) -> anyhow::Result<()> {
+    let path = sidecar_path(zoo);
+    let state = match fs::read_to_string(&path) {
+        Ok(contents) => ZooState::parse(&contents).with_context(|| format!("Failed to read {}", path.display()))?,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => ZooState::default(),
+        Err(err) => return Err(err).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    replace_state(state);
+    Ok(())
+}
+
+/// Write the state next to `zoo`, or remove a stale sidecar if there is nothing to save
+pub fn save(zoo: &Path) -> anyhow::Result<()> {
+    let path = sidecar_path(zoo);
+    let state = STATE.lock().unwrap().clone();
+    if state.namespaces.values().all(|namespace| namespace.values.is_empty()) {
+        if path.exists() {
+            fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+        }
+        return Ok(());
+    }
+    fs::write(&path, serde_json::to_string_pretty(&state)?).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Forget all state, for a new zoo
+pub fn clear() {
+    replace_state(ZooState::default());
+}
+
+/// The save a file name from the save dialog refers to, relative names are in the game's directory
+/// and the game adds `.zoo` when the player leaves the extension off
+fn zoo_save_path(base: &Path, file_name: &str) -> PathBuf {
+    let mut zoo = base.join(file_name);
+    if zoo.extension().is_none() {
+        zoo.set_extension("zoo");
+    }
+    zoo
+}
+
+/// The save a finished save wrote, the file picked in the save dialog or else the current zoo's
+fn saved_zoo() -> Option<PathBuf> {
+    SAVE_FILE_NAME.lock().unwrap().take().or_else(|| CURRENT_ZOO.lock().unwrap().clone())
+}
+
+/// A namespace of the store as Lua userdata
+pub struct ZooStateRef(String);
+
+impl ZooStateRef {
+    pub fn new(namespace: &str) -> Self {
+        ZooStateRef(namespace.to_string())
+    }
+
+    /// Mods may change their own namespace, any other needs `modify_config`
+    fn check_writable(&self, action: &str) -> mlua::Result<()> {
+        if current_limits().owner.as_deref() == Some(self.0.as_str()) {
+            return Ok(());
+        }
+        require_capability(Capability::ModifyConfig, &format!("{} in zoo state '{}'", action, self.0))
+    }
+}
+
+impl LuaType for ZooStateRef {
+    fn lua_type() -> String {
+        "ZooState".to_string()
+    }
+}
+
+impl UserData for ZooStateRef {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("get", |_, this, key: String| Ok(get(&this.0, &key)));
+        // Setting nil removes the key
+        methods.add_method("set", |_, this, (key, value): (String, Option<StateValue>)| {
+            this.check_writable("set()")?;
+            match value {
+                Some(value) => set(&this.0, &key, value),
+                None => {
+                    remove(&this.0, &key);
+                }
+            }
+            Ok(())
+        });
+        methods.add_method("keys", |_, this, ()| Ok(keys(&this.0)));
+        methods.add_method("version", |_, this, ()| Ok(version(&this.0)));
+        methods.add_method("on_migrate", |lua, this, (version, migrate): (u32, mlua::Function)| {
+            this.check_writable("on_migrate()")?;
+            let (lua, namespace, limits) = (lua.clone(), this.0.clone(), current_limits());
+            register_schema(&this.0, version, move |from| {
+                with_limits(&lua, &limits, || migrate.call::<()>((from, ZooStateRef::new(&namespace)))).map_err(|err| anyhow!("{}", err))
+            });
+            Ok(())
+        });
+        methods.add_meta_method(MetaMethod::ToString, |_, this, ()| Ok(format!("ZooState({})", this.0)));
+    }
+}
+
+#[cfg(target_os = "windows")]
+#[detour_mod]
+pub mod zoo_state_hooks {
+    use openzt_detour::gen::{
+        standalone::GET_SAVE_FILE_NAME_A,
+        ztui_gameopts::{LOAD_FILE, SAVE_GAME, START_NEW_GAME},
+    };
+    use tracing::{error, info, warn};
+
+    use super::{clear, load, save, saved_zoo, zoo_save_path, CURRENT_ZOO, SAVE_FILE_NAME};
+    use crate::util::{get_base_path, get_from_memory, get_string_from_memory};
+
+    #[detour(LOAD_FILE)]
+    unsafe extern "cdecl" fn zt_load_file(file_name_ptr: u32) -> u32 {
+        // The state is loaded first so mod scripts see it when the game starts
+        if file_name_ptr != 0 {
+            let zoo = get_base_path().join(get_string_from_memory(file_name_ptr));
+            match load(&zoo) {
+                Ok(()) => info!("Loaded zoo state for {}", zoo.display()),
+                Err(err) => {
+                    error!("Zoo state was not loaded: {:#}", err);
+                    clear();
+                }
+            }
+            *CURRENT_ZOO.lock().unwrap() = Some(zoo);
+        }
+        unsafe { LOAD_FILE_DETOUR.call(file_name_ptr) }
+    }
+
+    // The OPENFILENAMEA the save dialog fills in, lpstrFile holds the path the player picked
+    #[detour(GET_SAVE_FILE_NAME_A)]
+    unsafe extern "stdcall" fn zt_get_save_file_name(open_file_name_ptr: u32) -> bool {
+        let picked = unsafe { GET_SAVE_FILE_NAME_A_DETOUR.call(open_file_name_ptr) };
+        if picked {
+            let file_name_ptr = get_from_memory::<u32>(open_file_name_ptr + 0x1c);
+            *SAVE_FILE_NAME.lock().unwrap() = Some(zoo_save_path(&get_base_path(), &get_string_from_memory(file_name_ptr)));
+        }
+        picked
+    }
+
+    #[detour(SAVE_GAME)]
+    unsafe extern "stdcall" fn zt_save_game() -> u32 {
+        let result = unsafe { SAVE_GAME_DETOUR.call() };
+
+        match saved_zoo() {
+            Some(zoo) => {
+                if let Err(err) = save(&zoo) {
+                    error!("Zoo state was not saved: {:#}", err);
+                }
+                *CURRENT_ZOO.lock().unwrap() = Some(zoo);
+            }
+            None => warn!("Zoo state was not saved: the game didn't say which file it saved to"),
+        }
+        result
+    }
+
+    #[detour(START_NEW_GAME)]
+    unsafe extern "stdcall" fn zt_start_new_game() {
+        clear();
+        *CURRENT_ZOO.lock().unwrap() = None;
+        unsafe { START_NEW_GAME_DETOUR.call() };
+    }
+}
+
+pub fn init() {
+    #[cfg(target_os = "windows")]
+    if let Err(e) = unsafe { zoo_state_hooks::init_detours() } {
+        error!("Error initializing zoo state detours: {}", e);
+    }
+    register_lua_functions();
+}
+
+pub(crate) fn register_lua_functions() {
+    lua_fn!("zoo_state", "Returns the state saved with the zoo for a namespace (mod id)", "zoo_state(namespace)", ReadWorld, |namespace: String| {
+        Ok(ZooStateRef::new(&namespace))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scripting::sandbox::ExecutionLimits;
+
+    /// The store is global, so every test uses its own namespace
+    fn lua_with_state(namespace: &str) -> Lua {
+        let lua = Lua::new();
+        lua.globals().set("state", ZooStateRef::new(namespace)).unwrap();
+        lua
+    }
+
+    #[test]
+    fn test_values_round_trip_through_lua() {
+        let lua = lua_with_state("test.lua");
+        lua.load("state:set('flag', true) state:set('count', 3) state:set('ratio', 0.5) state:set('name', 'Zoo') state:set('list', {10, 20, nested = {x = 1}})")
+            .exec()
+            .unwrap();
+        assert_eq!(get("test.lua", "flag"), Some(StateValue::Bool(true)));
+        assert_eq!(get("test.lua", "count"), Some(StateValue::Int(3)));
+        assert_eq!(get("test.lua", "ratio"), Some(StateValue::Float(0.5)));
+        assert_eq!(get("test.lua", "name"), Some(StateValue::from("Zoo")));
+
+        let check = "local list = state:get('list') return list[1] + list[2] + list.nested.x, #list";
+        let (sum, len): (i64, i64) = lua.load(check).eval().unwrap();
+        assert_eq!((sum, len), (31, 2));
+
+        lua.load("state:set('flag', nil)").exec().unwrap();
+        assert_eq!(keys("test.lua"), vec!["count", "list", "name", "ratio"]);
+        assert!(lua.load("state:set('f', function() end)").exec().unwrap_err().to_string().contains("can't store a function"));
+        assert!(lua.load("local t = {} t[t] = 1 state:set('t', t)").exec().unwrap_err().to_string().contains("keys must be strings or integers"));
+    }
+
+    #[test]
+    fn test_mods_only_write_their_own_namespace() {
+        let lua = Lua::new();
+        lua.globals().set("own", ZooStateRef::new("test.own")).unwrap();
+        lua.globals().set("other", ZooStateRef::new("test.other")).unwrap();
+        let mod_limits = ExecutionLimits {
+            capabilities: Some(vec![Capability::ReadWorld, Capability::ModifyWorld]),
+            ..Default::default()
+        }
+        .owned_by("test.own");
+        let run = |limits: &ExecutionLimits, code: &str| with_limits(&lua, limits, || lua.load(code).exec());
+
+        run(&mod_limits, "own:set('visits', 1)").unwrap();
+        assert_eq!(get("test.own", "visits"), Some(StateValue::Int(1)));
+
+        for code in ["other:set('visits', 1)", "other:set('visits', nil)", "other:on_migrate(2, function() end)"] {
+            let err = run(&mod_limits, code).unwrap_err();
+            assert!(err.to_string().contains("requires the 'modify_config' capability"), "{}: {}", code, err);
+        }
+        assert!(keys("test.other").is_empty());
+        // Reading another mod's state is still allowed
+        run(&mod_limits, "assert(other:get('visits') == nil)").unwrap();
+
+        let console_limits = ExecutionLimits {
+            capabilities: Some(vec![Capability::ReadWorld, Capability::ModifyConfig]),
+            ..Default::default()
+        };
+        run(&console_limits, "other:set('visits', 2)").unwrap();
+        assert_eq!(get("test.other", "visits"), Some(StateValue::Int(2)));
+    }
+
+    #[test]
+    fn test_sidecar_format() {
+        let mut state = ZooState::default();
+        let mut values = BTreeMap::new();
+        values.insert("hidden".to_string(), StateValue::Bool(true));
+        values.insert("count".to_string(), StateValue::Int(2));
+        values.insert("ratio".to_string(), StateValue::Float(2.0));
+        state.namespaces.insert("openzt".to_string(), Namespace { version: 1, values });
+
+        let parsed = ZooState::parse(&serde_json::to_string(&state).unwrap()).unwrap();
+        assert_eq!(parsed, state);
+        let err = ZooState::parse(r#"{"format": 99, "namespaces": {}}"#).unwrap_err();
+        assert!(err.to_string().contains("newer version of OpenZT"));
+        assert_eq!(sidecar_path(Path::new("saves/mygame.zoo")), PathBuf::from("saves/mygame.openzt.json"));
+    }
+
+    #[test]
+    fn test_migration() {
+        let saved = ZooState::parse(r#"{"format": 1, "namespaces": {"test.migrate": {"version": 1, "values": {"visits": 4}}}}"#).unwrap();
+        let lua = lua_with_state("test.migrate");
+        lua.load("state:on_migrate(2, function(old_version, state) state:set('visits', state:get('visits') * 10) state:set('from', old_version) end)")
+            .exec()
+            .unwrap();
+
+        // Registering doesn't touch a namespace with no data, loading older data migrates it
+        assert_eq!(version("test.migrate"), None);
+        STATE.lock().unwrap().namespaces.extend(saved.namespaces);
+        migrate_namespace("test.migrate");
+        assert_eq!(get("test.migrate", "visits"), Some(StateValue::Int(40)));
+        assert_eq!(get("test.migrate", "from"), Some(StateValue::Int(1)));
+        assert_eq!(version("test.migrate"), Some(2));
+
+        // Current data is left alone, and new namespaces start at the registered version
+        migrate_namespace("test.migrate");
+        assert_eq!(get("test.migrate", "visits"), Some(StateValue::Int(40)));
+        register_schema("test.new", 3, |_| Err(anyhow!("should not run")));
+        set("test.new", "key", "value");
+        assert_eq!(version("test.new"), Some(3));
+    }
+
+    #[test]
+    fn test_failed_migration_keeps_version() {
+        STATE.lock().unwrap().namespaces.insert("test.failing".to_string(), Namespace { version: 1, values: BTreeMap::new() });
+        register_schema("test.failing", 2, |_| Err(anyhow!("boom")));
+        assert_eq!(version("test.failing"), Some(1));
+    }
+
+    #[test]
+    fn test_saved_zoo() {
+        let dir = std::env::temp_dir().join(format!("openzt_zoo_state_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        assert_eq!(zoo_save_path(&dir, "saves\\mygame"), dir.join("saves\\mygame.zoo"));
+        assert_eq!(zoo_save_path(&dir, "mygame.zoo"), dir.join("mygame.zoo"));
+
+        // A save without the dialog goes to the current zoo, one with it to the file picked
+        *CURRENT_ZOO.lock().unwrap() = Some(dir.join("old.zoo"));
+        assert_eq!(saved_zoo(), Some(dir.join("old.zoo")));
+        *SAVE_FILE_NAME.lock().unwrap() = Some(dir.join("new.zoo"));
+        assert_eq!(saved_zoo(), Some(dir.join("new.zoo")));
+        assert_eq!(saved_zoo(), Some(dir.join("old.zoo")));
+        *CURRENT_ZOO.lock().unwrap() = None;
+
+        set("test.files", "saved", true);
+        save(&dir.join("old.zoo")).unwrap();
+        assert!(dir.join("old.openzt.json").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,5 +1,6 @@
 use std::{fmt, fmt::Display};
 
+use openzt_detour::{game_address, game_build::GameAddress};
 use tracing::info;
 
 use crate::{
@@ -8,7 +9,7 @@ use crate::{
     util::{get_from_memory, ZTBufferString},
 };
 
-const GLOBAL_ZTADVTERRAINMGR_ADDRESS: u32 = 0x00638058;
+const GLOBAL_ZTADVTERRAINMGR_ADDRESS: GameAddress = game_address!(CompleteCollection: 0x00638058);
 const BFTERRAINTYPEINFO_SIZE: usize = 0x30;
 
 #[derive(Debug)]
@@ -79,7 +80,7 @@ struct BFTerrainTypeInfo {
 }
 
 fn read_ztadvterrainmgr_raw_from_memory() -> ZTAdvTerrainMgr_raw {
-    get_from_memory(get_from_memory::<u32>(GLOBAL_ZTADVTERRAINMGR_ADDRESS))
+    get_from_memory(get_from_memory::<u32>(GLOBAL_ZTADVTERRAINMGR_ADDRESS.get()))
 }
 
 fn read_ztadvterrainmgr_from_memory() -> ZTAdvTerrainMgr {
@@ -102,7 +103,7 @@ fn command_get_bfterraintypeinfo(_args: Vec<&str>) -> Result<String, CommandErro
 
 pub fn init() {
     // list_bfterraintypeinfo() - no args
-    lua_fn!("list_bfterraintypeinfo", "Lists terrain type info", "list_bfterraintypeinfo()", || {
+    lua_fn!("list_bfterraintypeinfo", "Lists terrain type info", "list_bfterraintypeinfo()", ReadWorld, || {
         match command_get_bfterraintypeinfo(vec![]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
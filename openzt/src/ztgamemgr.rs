@@ -1,38 +1,44 @@
+use std::sync::Mutex;
+
+use openzt_detour::{game_address, game_build::GameAddress};
+use game_struct_layout::game_struct;
 use tracing::info;
 
 use crate::{
     command_console::CommandError,
+    events::{publish, Event},
     lua_fn,
-    util::get_from_memory,
+    util::{get_from_memory, save_to_memory},
 };
 
-const GLOBAL_ZTGAMEMGR_ADDRESS: u32 = 0x00638048;
+const GLOBAL_ZTGAMEMGR_ADDRESS: GameAddress = game_address!(CompleteCollection: 0x00638048);
 
 /// ZTGameMgr struct
+#[game_struct]
+#[size(0x25a4)]
 #[derive(Debug)]
 #[repr(C)]
 struct ZTGameMgr {
-    pad1: [u8; 0x0C],
-    cash: f32,                     // 0x0C
-    pad2: [u8; 0x30 - 0x10],       // 0x0C
-    num_animals: u16,              // 0x30
-    pad3: [u8; 0x38 - 0x32],       // 0x30
-    num_species: u16,              // 0x38
-    pad4: [u8; 0x3C - 0x3A],       // 0x38
-    num_tired_guests: u16,         // 0x3C
-    pad5: [u8; 0x40 - 0x3E],       // 0x3C
-    num_hungry_guests: u16,        // 0x40
-    pad6: [u8; 0x44 - 0x42],       // 0x40
-    num_thirst_guests: u16,        // 0x44
-    pad7: [u8; 0x48 - 0x46],       // 0x44
-    num_guests_restroom_need: u16, // 0x48
-    pad8: [u8; 0x54 - 0x4A],       // 0x48
-    num_guests: u16,               // 0x54
-    pad9: [u8; 0x1160 - 0x56],     // 0x54
-    zoo_admission_cost: f32,       // 0x1160
-    pad10: [u8; 0x1194 - 0x1164],  // 0x1160
-    date: Systemtime,              // 0x1194
-    pad11: [u8; 0x1400],           // 0x1194
+    #[offset(0x0C)]
+    cash: f32,
+    #[offset(0x30)]
+    num_animals: u16,
+    #[offset(0x38)]
+    num_species: u16,
+    #[offset(0x3C)]
+    num_tired_guests: u16,
+    #[offset(0x40)]
+    num_hungry_guests: u16,
+    #[offset(0x44)]
+    num_thirst_guests: u16,
+    #[offset(0x48)]
+    num_guests_restroom_need: u16,
+    #[offset(0x54)]
+    num_guests: u16,
+    #[offset(0x1160)]
+    zoo_admission_cost: f32,
+    #[offset(0x1194)]
+    date: Systemtime,
     // TODO: Below
     // admissions_income_by_month: get_from_memory::<[f32; 12]>(zt_game_mgr_prt + 0x254),
     // concessions_benefit_by_month: get_from_memory::<[f32; 12]>(zt_game_mgr_prt + 0x29c),
@@ -64,16 +70,14 @@ impl ZTGameMgr {
     /// enables or disables dev mode
     fn enable_dev_mode(enable: bool) {
         let enable_dev_mode_address = 0x63858A;
-        unsafe {
-            *(enable_dev_mode_address as *mut bool) = enable;
-        }
+        save_to_memory(enable_dev_mode_address, enable);
     }
 
     /// returns the instance of the ZTGameMgr struct
     fn instance() -> Option<&'static mut ZTGameMgr> {
         unsafe {
             // get the pointer to the ZTGameMgr instance
-            let ptr = get_from_memory::<*mut ZTGameMgr>(GLOBAL_ZTGAMEMGR_ADDRESS);
+            let ptr = get_from_memory::<*mut ZTGameMgr>(GLOBAL_ZTGAMEMGR_ADDRESS.get());
 
             // is pointer null
             if !ptr.is_null() {
@@ -86,6 +90,46 @@ impl ZTGameMgr {
     }
 }
 
+/// Days since 1970-01-01 of the in-game date, `None` while no zoo is loaded
+pub fn game_day() -> Option<i64> {
+    let date = &ZTGameMgr::instance()?.date;
+    if date.w_month == 0 {
+        return None;
+    }
+    // Days from civil algorithm, shifting the year to start in March so leap days come last
+    let (month, day) = (date.w_month as i64, date.w_day as i64);
+    let year = date.w_year as i64 - (month <= 2) as i64;
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146_097 + day_of_era - 719_468)
+}
+
+/// The (year, month) seen by the last call to [`publish_date_changes`]
+static LAST_DATE: Mutex<Option<(u16, u16)>> = Mutex::new(None);
+
+/// Publishes `MonthChanged` and `YearChanged` events when the in-game date rolls over, called every sim tick
+///
+/// Only a single month step counts as a rollover, so loading a zoo doesn't publish an event.
+pub fn publish_date_changes() {
+    let Some(ztgamemgr) = ZTGameMgr::instance() else {
+        return;
+    };
+    let date = (ztgamemgr.date.w_year, ztgamemgr.date.w_month);
+    let Some((last_year, last_month)) = LAST_DATE.lock().unwrap().replace(date) else {
+        return;
+    };
+    let (year, month) = date;
+    if year as u32 * 12 + month as u32 != last_year as u32 * 12 + last_month as u32 + 1 {
+        return;
+    }
+    publish(Event::MonthChanged { year, month });
+    if year != last_year {
+        publish(Event::YearChanged { year });
+    }
+}
+
 /// a command that prints the SYSTEMTIME struct in memory in a human-readable format
 /// usage: `get_date`
 pub fn command_get_date_str(_args: Vec<&str>) -> Result<String, CommandError> {
@@ -125,7 +169,7 @@ pub fn command_zoostats(_args: Vec<&str>) -> Result<String, CommandError> {
 /// registers the Lua functions
 pub fn init() {
     // get_date() - no args
-    lua_fn!("get_date", "Returns current in-game date/time", "get_date()", || {
+    lua_fn!("get_date", "Returns current in-game date/time", "get_date()", ReadWorld, || {
         match command_get_date_str(vec![]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
@@ -133,7 +177,7 @@ pub fn init() {
     });
 
     // add_cash(amount) - single f32 arg
-    lua_fn!("add_cash", "Adds cash to player's budget", "add_cash(amount)", |amount: f32| {
+    lua_fn!("add_cash", "Adds cash to player's budget", "add_cash(amount)", ModifyEconomy, |amount: f32| {
         let amount_str = amount.to_string();
         match command_add_cash(vec![&amount_str]) {
             Ok(result) => Ok((Some(result), None::<String>)),
@@ -142,7 +186,7 @@ pub fn init() {
     });
 
     // enable_dev_mode(enabled) - bool arg
-    lua_fn!("enable_dev_mode", "Enables/disables developer mode", "enable_dev_mode(true/false)", |enabled: bool| {
+    lua_fn!("enable_dev_mode", "Enables/disables developer mode", "enable_dev_mode(true/false)", ModifyEconomy, |enabled: bool| {
         let enabled_str = enabled.to_string();
         match command_enable_dev_mode(vec![&enabled_str]) {
             Ok(result) => Ok((Some(result), None::<String>)),
@@ -151,7 +195,7 @@ pub fn init() {
     });
 
     // zoostats() - no args
-    lua_fn!("zoostats", "Returns zoo statistics", "zoostats()", || {
+    lua_fn!("zoostats", "Returns zoo statistics", "zoostats()", ReadWorld, || {
         match command_zoostats(vec![]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
@@ -1,36 +1,44 @@
 use nt_time::{FileTime, time::OffsetDateTime};
+#[cfg(target_os = "windows")]
 use tracing::info;
 use std::fmt;
+use openzt_detour::{game_address, game_build::GameAddress};
+#[cfg(target_os = "windows")]
 use openzt_detour_macro::detour_mod;
 
+use field_accessor_as_string::FieldAccessorAsString;
+use game_struct_layout::game_struct;
 use getset::{Getters};
+use mlua::{IntoLua, MetaMethod, UserData, UserDataMethods, UserDataRef};
 
 use crate::{
     command_console::CommandError,
     lua_fn,
+    scripting::{add_lua_function, userdata, Capability, LuaType, LuaTypes},
     util::{get_from_memory, ZTArray, ZTBoundedString, ZTString},
-    ztworldmgr::{read_zt_world_mgr_from_global, Direction},
+    ztworldmgr::{read_zt_world_mgr_from_global, BFEntity, Direction, ZTEntityClass, ZTEntityRef},
     ztmapview::BFTile,
 };
 
-pub const GLOBAL_ZTHABITATMGR_ADDRESS: u32 = 0x0063805c;
+pub const GLOBAL_ZTHABITATMGR_ADDRESS: GameAddress = game_address!(CompleteCollection: 0x0063805c);
 
 /// ZTHabitatMgr struct
+#[game_struct]
 #[derive(Debug)]
 #[repr(C)]
 pub struct ZTHabitatMgr {
     vtable: u32,                     // 0x000
-    pad1: [u8; 0x04],                // ----------------------- padding: 4 bytes
-    map_size_x: u32,                 // 0x008
+    #[offset(0x008)]
+    map_size_x: u32,
     map_size_y: u32,                 // 0x00c
     zoo_entrance_x: u32,             // 0x010
     zoo_entrance_y: u32,             // 0x014
-    pad2: [u8; 0x04],                // ----------------------- padding: 4 bytes
-    exhibit_array: ZTArray<ZTHabitat>, // 0x01c (0xc bytes)
+    #[offset(0x01c)]
+    exhibit_array: ZTArray<ZTHabitat>,
     other_array_start: u32,        // 0x028 //TODO: Use ZTArray; Seems to be some kind of mapping from BFTile to ZTHabitat or a ZTHabitat index
     other_array_end: u32,          // 0x02c
     other_array_buffer_end: u32,       // 0x030
-    pad3: [u8; 0x24],                // ----------------------- padding: 36 bytes
+    #[offset(0x058)]
     popularity_scale_factor: f32
 }
 
@@ -92,24 +100,25 @@ impl fmt::Display for ZTHabitatMgr {
 }
 
 pub fn read_zt_habitat_mgr_from_memory() -> ZTHabitatMgr {
-    get_from_memory::<ZTHabitatMgr>(get_from_memory(GLOBAL_ZTHABITATMGR_ADDRESS))
+    get_from_memory::<ZTHabitatMgr>(get_from_memory(GLOBAL_ZTHABITATMGR_ADDRESS.get()))
 }
 
-#[derive(Debug, Getters)]
+#[game_struct]
+#[derive(Debug, Getters, FieldAccessorAsString)]
 #[repr(C)]
 #[get = "pub"]
 pub struct ZTHabitat{
     vtable: u32,                     // 0x000
     zt_show_info_ptr: u32,            // 0x004
-    pad1: [u8; 0x38],                // ----------------------- padding: 60 bytes
-    exhibit_tile_ptr: u32,          // 0x040 // Seems incorrect?
-    pad2: [u8; 0x48],                // ----------------------- padding: 72 bytes
-    entrance_tile_ptr: u32,          // 0x08c
+    #[offset(0x040)]
+    exhibit_tile_ptr: u32,          // Seems incorrect?
+    #[offset(0x08c)]
+    entrance_tile_ptr: u32,
     entrance_rotation: u32,          // 0x090
-    pad3: [u8; 0x58],                // ----------------------- padding: 88 bytes
-    unknown_u32: u32,                // 0x0ec
-    pad4: [u8; 0xc],                // ----------------------- padding: 12 bytes
-    current_donactions: f32,       // 0xfc
+    #[offset(0x0ec)]
+    unknown_u32: u32,
+    #[offset(0xfc)]
+    current_donactions: f32,
     last_donactions: f32,          // 0x100
     total_donactions: f32,         // 0x104
     current_upkeep: f32,          // 0x108
@@ -120,7 +129,7 @@ pub struct ZTHabitat{
     unknown_u32_4: u32,            // 0x11c
     created_timestamp: FileTime,        // 0x120
     unknown_nt_time: FileTime,          // 0x128
-    pad5: [u8; 0x24],                // ----------------------- padding: 36 bytes
+    #[offset(0x154)]
     exhibit_name: ZTBoundedString
 }
 
@@ -201,6 +210,63 @@ impl fmt::Display for ZTHabitat {
     }
 }
 
+/// A habitat, exposed to Lua
+///
+/// Fields are those of [`ZTHabitat`] plus `name`. Habitats are read-only, accessing one that has
+/// since been deleted raises a Lua error.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ZTHabitatRef(u32);
+
+impl ZTHabitatRef {
+    /// The habitat, if it still exists
+    fn habitat(&self) -> mlua::Result<ZTHabitat> {
+        let exhibits = read_zt_habitat_mgr_from_memory().exhibit_array;
+        if !(0..exhibits.len()).any(|i| exhibits.get_ptr(i) == self.0) {
+            return Err(userdata::stale_handle("ZTHabitat", self.0));
+        }
+        Ok(get_from_memory::<ZTHabitat>(self.0))
+    }
+
+    /// Animals standing on one of the habitat's tiles
+    fn animals(&self) -> mlua::Result<Vec<ZTEntityRef>> {
+        let habitat = self.habitat()?;
+        let zthm = read_zt_habitat_mgr_from_memory();
+        Ok(read_zt_world_mgr_from_global()
+            .entity_ptrs()
+            .into_iter()
+            .filter(|ptr| ZTEntityClass::from(get_from_memory::<u32>(*ptr)) == ZTEntityClass::Animal)
+            .filter(|ptr| {
+                get_from_memory::<BFEntity>(*ptr)
+                    .get_tile()
+                    .and_then(|tile| zthm.get_habitat_by_tile(&tile))
+                    .is_some_and(|animal_habitat| animal_habitat == habitat)
+            })
+            .map(ZTEntityRef::new)
+            .collect())
+    }
+}
+
+impl LuaType for ZTHabitatRef {
+    fn lua_type() -> String {
+        "ZTHabitat".to_string()
+    }
+}
+
+impl UserData for ZTHabitatRef {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("animals", |lua, this, ()| userdata::iterator(lua, this.animals()?));
+        methods.add_meta_method(MetaMethod::Index, |lua, this, name: String| match name.as_str() {
+            "name" => this.habitat()?.exhibit_name.copy_to_string().into_lua(lua),
+            _ => userdata::get_field(lua, "ZTHabitat", &this.habitat()?, &name),
+        });
+        methods.add_meta_method(MetaMethod::NewIndex, |_, this, (name, value): (String, mlua::Value)| {
+            userdata::set_field("ZTHabitat", &mut this.habitat()?, Some(&[]), &name, value)
+        });
+        methods.add_meta_method(MetaMethod::ToString, |_, this, ()| Ok(this.habitat()?.to_string()));
+        methods.add_meta_method(MetaMethod::Eq, |_, this, other: UserDataRef<ZTHabitatRef>| Ok(*this == *other));
+    }
+}
+
 fn command_get_zt_habitat_mgr(_args: Vec<&str>) -> Result<String, CommandError> {
     let zt_habitat_mgr = read_zt_habitat_mgr_from_memory();
     Ok(format!("{}", zt_habitat_mgr))
@@ -224,6 +290,7 @@ fn command_get_zt_habitats(_args: Vec<&str>) -> Result<String, CommandError> {
     Ok(result_string)
 }
 
+#[cfg(target_os = "windows")]
 #[detour_mod]
 pub mod hooks_zthabitatmgr {
     use super::*;
@@ -245,8 +312,16 @@ pub mod hooks_zthabitatmgr {
 }
 
 pub fn init() {
+    register_lua_functions();
+    #[cfg(target_os = "windows")]
+    if let Err(e) = unsafe { hooks_zthabitatmgr::init_detours() } {
+        info!("Error initialising zthabitatmgr detours: {}", e);
+    }
+}
+
+pub(crate) fn register_lua_functions() {
     // get_zthabitatmgr() - no args
-    lua_fn!("get_zthabitatmgr", "Returns ZTHabitatMgr debug info", "get_zthabitatmgr()", || {
+    lua_fn!("get_zthabitatmgr", "Returns ZTHabitatMgr debug info", "get_zthabitatmgr()", DebugMemory, || {
         match command_get_zt_habitat_mgr(vec![]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
@@ -254,14 +329,66 @@ pub fn init() {
     });
 
     // list_exhibits() - no args
-    lua_fn!("list_exhibits", "Lists all zoo exhibits/habitats", "list_exhibits()", || {
+    lua_fn!("list_exhibits", "Lists all zoo exhibits/habitats", "list_exhibits()", ReadWorld, || {
         match command_get_zt_habitats(vec![]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
         }
     });
 
-    if let Err(e) = unsafe { hooks_zthabitatmgr::init_detours() } {
-        info!("Error initialising zthabitatmgr detours: {}", e);
+    // world.habitats() - iterator over habitat userdata
+    add_lua_function(
+        "world.habitats",
+        "Iterates over the zoo's exhibits and habitats",
+        "for habitat in world.habitats() do ... end",
+        Capability::ReadWorld,
+        LuaTypes::new().returns_lua(&["fun(): ZTHabitat?"]),
+        |lua| {
+            lua.create_function(|lua, ()| {
+                let exhibits = read_zt_habitat_mgr_from_memory().exhibit_array;
+                let habitats = (0..exhibits.len()).map(|i| ZTHabitatRef(exhibits.get_ptr(i))).collect();
+                userdata::iterator(lua, habitats)
+            })
+            .unwrap()
+        },
+    )
+    .unwrap();
+}
+#[cfg(test)]
+mod tests {
+    use std::{
+        mem::{offset_of, size_of},
+        sync::Arc,
+    };
+
+    use super::*;
+    use crate::game_memory::{with_memory, FakeMemory};
+
+    #[test]
+    fn test_get_habitat() {
+        let memory = Arc::new(FakeMemory::new());
+        let habitat = memory.alloc(size_of::<ZTHabitat>());
+        let name = memory.alloc_c_string("Lion Exhibit");
+        memory.write(habitat + offset_of!(ZTHabitat, exhibit_name) as u32, [name, name + 12]);
+
+        // Each x has an entry of 0xc bytes pointing to its column, where each y has an entry of 0x28 bytes
+        let columns = memory.alloc(3 * 0xc);
+        for x in 0..3 {
+            let column = memory.alloc(3 * 0x28);
+            memory.write(columns + x * 0xc, column);
+            if x == 1 {
+                memory.write(column + 2 * 0x28, habitat);
+            }
+        }
+        let habitat_mgr = memory.alloc(size_of::<ZTHabitatMgr>());
+        memory.write(habitat_mgr + offset_of!(ZTHabitatMgr, other_array_start) as u32, columns);
+
+        with_memory(memory, || {
+            let habitat_mgr = get_from_memory::<ZTHabitatMgr>(habitat_mgr);
+            let habitat = habitat_mgr.get_habitat(1, 2).unwrap();
+            assert_eq!(habitat.exhibit_name.copy_to_string(), "Lion Exhibit");
+            assert!(habitat_mgr.get_habitat(1, 1).is_none());
+            assert!(habitat_mgr.get_habitat(0, 2).is_none());
+        });
     }
-}
\ No newline at end of file
+}
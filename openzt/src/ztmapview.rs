@@ -1,6 +1,8 @@
 use core::fmt;
+use game_struct_layout::game_struct;
 use num_enum::FromPrimitive;
 use tracing::info;
+#[cfg(target_os = "windows")]
 use openzt_detour_macro::detour_mod;
 
 use crate::bfentitytype::{ZTEntityTypeClass, zt_entity_type_class_is};
@@ -38,18 +40,19 @@ use crate::ztworldmgr::{BFEntity, IVec3};
 // }
 
 // TODO: Impl Store for this, create own macro that ignores the padding OR type alias for the padding with a nop impl of Store
+#[game_struct]
+#[size(0x8c)]
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
 pub struct BFTile {
-    padding: [u8; 0x34],
+    #[offset(0x034)]
     pub pos: IVec3, // 0x034 + 0xc
-    padding_2: [u8; 0x40],
-    unknown_byte_1: u8, // 0x080
+    #[offset(0x080)]
+    unknown_byte_1: u8,
     pub unknown_byte_2: u8, // 0x081
     unknown_byte_3: u8, // 0x082
     unknown_byte_4: u8, // 0x083
     unknown_byte_5: u8, // 0x084
-    padding_3: [u8; 0x8], // Full size 0x8c
 }
 
 impl PartialEq for BFTile {
@@ -66,17 +69,11 @@ impl fmt::Display for BFTile {
 
 impl BFTile {
     pub fn new(pos: IVec3, unknown_byte_2: u8) -> Self {
-        BFTile {
-            padding: [0; 0x34],
-            pos,
-            padding_2: [0; 0x40],
-            unknown_byte_1: 0,
-            unknown_byte_2,
-            unknown_byte_3: 0,
-            unknown_byte_4: 0,
-            unknown_byte_5: 0,
-            padding_3: [0; 0x8],
-        }
+        // SAFETY: BFTile is plain data, all zeroes is a valid (empty) tile
+        let mut tile: BFTile = unsafe { std::mem::zeroed() };
+        tile.pos = pos;
+        tile.unknown_byte_2 = unknown_byte_2;
+        tile
     }
 
     pub fn get_local_elevation(&self, pos: IVec3) -> i32 {
@@ -203,6 +200,7 @@ impl BFTile {
     }
 }
 
+#[cfg(target_os = "windows")]
 #[detour_mod]
 pub mod zoo_ztmapview {
     use tracing::{info};
@@ -263,14 +261,17 @@ pub mod zoo_ztmapview {
 }
 
 pub fn init() {
+    #[cfg(target_os = "windows")]
     if let Err(e) = unsafe { zoo_ztmapview::init_detours() } {
         info!("Error initialising zoo_ztmapview detours: {}", e);
     };
 }
 
-pub struct ZTMapView {
-    pad: [u8; 0x5ec] // Not currently using this struct, so just padding it out to the size of the class
-}
+// Not currently using this struct, so just padding it out to the size of the class
+#[game_struct]
+#[size(0x5ec)]
+#[repr(C)]
+pub struct ZTMapView {}
 
 #[derive(Debug, PartialEq, Eq, FromPrimitive, Clone)]
 #[repr(u32)]
@@ -1,5 +1,7 @@
 use std::fmt;
 
+use game_struct_layout::game_struct;
+use openzt_detour::{game_address, game_build::GameAddress};
 use openzt_detour::gen::ztui_general::GET_SELECTED_ENTITY;
 use openzt_detour::gen::bfuimgr::GET_ELEMENT_0;
 use tracing::info;
@@ -11,7 +13,7 @@ use crate::{
     ztworldmgr::read_zt_entity_from_memory,
 };
 
-const BFUIMGR_PTR: u32 = 0x00638de0;
+const BFUIMGR_PTR: GameAddress = game_address!(CompleteCollection: 0x00638de0);
 
 /// UIElementId enum for currently used UI elements
 #[derive(Debug)]
@@ -94,11 +96,11 @@ impl fmt::Display for Sex {
     }
 }
 
-const RANDOM_SEX_STRING_PTR: u32 = 0x0063e420;
+const RANDOM_SEX_STRING_PTR: GameAddress = game_address!(CompleteCollection: 0x0063e420);
 
 pub fn init() {
     // get_selected_entity() - no args
-    lua_fn!("get_selected_entity", "Returns details of the currently selected entity", "get_selected_entity()", || {
+    lua_fn!("get_selected_entity", "Returns details of the currently selected entity", "get_selected_entity()", ReadWorld, || {
         match command_get_selected_entity(vec![]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
@@ -106,7 +108,7 @@ pub fn init() {
     });
 
     // get_element(id) - single u32 arg
-    lua_fn!("get_element", "Returns UI element details by ID", "get_element(id)", |id: u32| {
+    lua_fn!("get_element", "Returns UI element details by ID", "get_element(id)", ReadWorld, |id: u32| {
         let id_str = id.to_string();
         match command_get_element(vec![&id_str]) {
             Ok(result) => Ok((Some(result), None::<String>)),
@@ -115,7 +117,7 @@ pub fn init() {
     });
 
     // get_buy_tab() - no args
-    lua_fn!("get_buy_tab", "Returns the currently active buy tab", "get_buy_tab()", || {
+    lua_fn!("get_buy_tab", "Returns the currently active buy tab", "get_buy_tab()", ReadWorld, || {
         match command_get_current_buy_tab(vec![]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
@@ -123,7 +125,7 @@ pub fn init() {
     });
 
     // ui(callback_name) - single string arg
-    lua_fn!("ui", "Calls a UI callback function", "ui(callback_name)", |callback: String| {
+    lua_fn!("ui", "Calls a UI callback function", "ui(callback_name)", ModifyWorld, |callback: String| {
         match command_call_ui_callback(vec![&callback]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
@@ -147,7 +149,7 @@ fn command_get_element(args: Vec<&str>) -> Result<String, CommandError> {
     }
     let address = args[0].parse()?;
     let get_element_fn = unsafe { GET_ELEMENT_0.original() };
-    let ui_element_addr = unsafe { get_element_fn(BFUIMGR_PTR, address) };
+    let ui_element_addr = unsafe { get_element_fn(BFUIMGR_PTR.get(), address) };
     if ui_element_addr == 0 {
         return Err(Into::into("No element found"));
     }
@@ -156,9 +158,14 @@ fn command_get_element(args: Vec<&str>) -> Result<String, CommandError> {
     Ok(format!("{}", element))
 }
 
+/// Name of the UI element at `ui_element_addr`, as given in the game's UI layout files
+pub fn element_name(ui_element_addr: u32) -> String {
+    get_from_memory::<UIElement>(ui_element_addr).element_name.to_string()
+}
+
 fn get_element(id: UIElementId) -> Option<UIElement> {
     let get_element_fn = unsafe { GET_ELEMENT_0.original() };
-    let ui_element_addr = unsafe { get_element_fn(BFUIMGR_PTR, id as i32) };
+    let ui_element_addr = unsafe { get_element_fn(BFUIMGR_PTR.get(), id as i32) };
     if ui_element_addr == 0 {
         return None;
     }
@@ -247,7 +254,7 @@ pub fn get_selected_sex() -> Option<Sex> {
 }
 
 pub fn get_random_sex() -> Option<Sex> {
-    let string_address = get_from_memory::<u32>(RANDOM_SEX_STRING_PTR);
+    let string_address = get_from_memory::<u32>(RANDOM_SEX_STRING_PTR.get());
     match get_string_from_memory_bounded(string_address, string_address + 4, string_address + 8).as_str() {
         "m" => Some(Sex::Male),
         "f" => Some(Sex::Female),
@@ -271,6 +278,7 @@ pub fn get_selected_entity_type_address() -> u32 {
     get_from_memory(selected_entity + 0x128)
 }
 
+#[game_struct]
 #[derive(Debug)]
 #[repr(C)]
 pub struct UIElement {
@@ -280,8 +288,8 @@ pub struct UIElement {
     unknown_string_1: ZTBufferString,
     string_content: ZTBufferString,
     element_name: ZTBufferString,
-    // 25 unknown u32s
-    padding: [u8; 76],
+    // 19 unknown u32s
+    #[offset(0x7c)]
     state: UIState,
 }
 
@@ -1,23 +1,29 @@
 use std::cmp::max;
 use std::{collections::HashMap, fmt};
 use std::str::FromStr;
+use field_accessor_as_string::FieldAccessorAsString;
+use game_struct_layout::game_struct;
 use getset::Getters;
 use itertools::Itertools;
+use mlua::{IntoLua, MetaMethod, UserData, UserDataMethods, UserDataRef};
 use num_enum::FromPrimitive;
 use tracing::{error, info};
+use openzt_detour::{game_address, game_build::GameAddress};
+#[cfg(target_os = "windows")]
 use openzt_detour_macro::detour_mod;
 
 use crate::{
-    bfentitytype::{read_zt_entity_type_from_memory, BFEntityType, ZTEntityType, ZTSceneryType},
+    bfentitytype::{read_zt_entity_type_from_memory, BFEntityType, BFEntityTypeRef, ZTEntityType, ZTSceneryType},
     command_console::CommandError,
     lua_fn,
+    scripting::{add_lua_function, userdata, Capability, LuaType, LuaTypes},
     util::{get_from_memory, get_string_from_memory, map_from_memory},
 };
 use crate::util::ZTBufferString;
 use crate::ztmapview::BFTile;
 use crate::bfentitytype::ZTEntityTypeClass;
 
-const GLOBAL_ZTWORLDMGR_ADDRESS: u32 = 0x00638040;
+const GLOBAL_ZTWORLDMGR_ADDRESS: GameAddress = game_address!(CompleteCollection: 0x00638040);
 
 #[derive(Debug, PartialEq, Eq, FromPrimitive, Clone)]
 #[repr(u32)]
@@ -98,22 +104,23 @@ impl Rectangle {
 // zt_sub_type: get_string_from_memory(get_from_memory::<u32>(zt_entity_type_ptr + 0xa4)),
 // bf_config_file_ptr: get_from_memory::<u32>(zt_entity_type_ptr + 0x80),
 
-#[derive(Debug, Getters)]
+#[game_struct]
+#[derive(Debug, Getters, FieldAccessorAsString)]
 #[get = "pub"]
 #[repr(C)]
 pub struct BFEntity {
     vtable: u32,
-    padding: [u8; 0x104],
-    name: ZTBufferString,   // 0x108
+    #[offset(0x108)]
+    name: ZTBufferString,
     x_coord: i32,           // 0x114
     y_coord: i32,           // 0x118   
     z_coord: i32,           // 0x11c
     height_above_terrain: u32, // 0x120
-    padding4: [u8; 0x4],    // ----- padding: 4 bytes
-    inner_class_ptr: u32,   // 0x128
+    #[offset(0x128)]
+    inner_class_ptr: u32,
     rotation: i32,          // 0x12c
-    padding5: [u8; 0x14],    // ----- padding: 28 bytes
-    unknown_flag1: u8,    // 0x13c // isRemoved
+    #[offset(0x13c)]
+    unknown_flag1: u8,    // isRemoved
     unknown_flag2: u8,    // 0x13d // isRemovedUndo
     unknown_flag3: u8,    // 0x13e
     visible: u8,    // 0x13f 
@@ -191,7 +198,7 @@ impl BFEntity {
 
     fn vtable_get_footprint(&self) -> IVec3 {
         let function_address = get_from_memory::<u32>(self.vtable + 0x94);
-        let get_footprint_fn = unsafe { std::mem::transmute::<u32, extern "thiscall" fn(this: &BFEntity, param_1: &mut IVec3, param_2: u32) -> u32>(function_address) };
+        let get_footprint_fn = unsafe { std::mem::transmute::<usize, extern "thiscall" fn(this: &BFEntity, param_1: &mut IVec3, param_2: u32) -> u32>(function_address as usize) };
         let mut result_footprint = IVec3::default();
         let footprint_ptr = get_footprint_fn(self, &mut result_footprint, 0);
         get_from_memory::<IVec3>(footprint_ptr)
@@ -227,6 +234,70 @@ impl ZTEntity {
     }
 }
 
+/// An entity in the world, exposed to Lua
+///
+/// Fields are those of [`BFEntity`] plus `name` and `class`, read from memory on every access.
+/// Accessing an entity that has been removed from the world raises a Lua error.
+/// Only display flags can be set, moving an entity needs the game to update its tiles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ZTEntityRef(u32);
+
+const ENTITY_SETTABLE_FIELDS: &[&str] = &["visible", "draw_dithered"];
+
+impl ZTEntityRef {
+    pub fn new(zt_entity_ptr: u32) -> Self {
+        ZTEntityRef(zt_entity_ptr)
+    }
+
+    /// The entity, if it's still in the world
+    fn entity(&self) -> mlua::Result<&'static mut BFEntity> {
+        if !read_zt_world_mgr_from_global().contains_entity(self.0) {
+            return Err(userdata::stale_handle("ZTEntity", self.0));
+        }
+        Ok(map_from_memory(self.0))
+    }
+
+    /// The entity's class, without checking it's still in the world, for detours passed a live entity
+    pub fn class(&self) -> ZTEntityClass {
+        ZTEntityClass::from(map_from_memory::<BFEntity>(self.0).vtable)
+    }
+}
+
+impl LuaType for ZTEntityRef {
+    fn lua_type() -> String {
+        "ZTEntity".to_string()
+    }
+}
+
+impl UserData for ZTEntityRef {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("type", |_, this, ()| {
+            let entity_type_ptr = this.entity()?.inner_class_ptr;
+            Ok((entity_type_ptr != 0).then(|| BFEntityTypeRef::new(entity_type_ptr)))
+        });
+        methods.add_method("is_member", |_, this, member: String| {
+            this.entity()?;
+            Ok(read_zt_entity_from_memory(this.0).is_member(member))
+        });
+        methods.add_meta_method(MetaMethod::Index, |lua, this, name: String| {
+            let entity = this.entity()?;
+            match name.as_str() {
+                "name" => entity.name.to_string().into_lua(lua),
+                "class" => format!("{:?}", this.class()).into_lua(lua),
+                _ => userdata::get_field(lua, "ZTEntity", entity, &name),
+            }
+        });
+        methods.add_meta_method(MetaMethod::NewIndex, |_, this, (name, value): (String, mlua::Value)| {
+            userdata::set_field("ZTEntity", this.entity()?, Some(ENTITY_SETTABLE_FIELDS), &name, value)
+        });
+        methods.add_meta_method(MetaMethod::ToString, |_, this, ()| {
+            this.entity()?;
+            Ok(read_zt_entity_from_memory(this.0).to_string())
+        });
+        methods.add_meta_method(MetaMethod::Eq, |_, this, other: UserDataRef<ZTEntityRef>| Ok(*this == *other));
+    }
+}
+
 impl fmt::Display for ZTEntity {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -237,19 +308,20 @@ impl fmt::Display for ZTEntity {
     }
 }
 
+#[game_struct]
 #[derive(Debug)]
 #[repr(C)]
 pub struct ZTWorldMgr {
-    padding_1: [u8; 0x34],
+    #[offset(0x34)]
     map_x_size: u32,
     map_y_size: u32,
-    padding_2: [u8; 0x4],
+    #[offset(0x40)]
     tile_array: u32,
-    padding_3: [u8; 0x3c],
+    #[offset(0x80)]
     entity_array_start: u32,
     entity_array_end: u32,
     entity_array_buffer_end: u32,
-    padding_4: [u8; 0xc],
+    #[offset(0x98)]
     entity_type_array_start: u32,
     entity_type_array_end: u32,
     entity_type_array_buffer_end: u32,
@@ -300,6 +372,26 @@ impl ZTWorldMgr {
         self.entity_array_end
     }
 
+    /// Number of entities in the world
+    pub fn entity_count(&self) -> usize {
+        ((self.entity_array_end - self.entity_array_start) / 4) as usize
+    }
+
+    /// Addresses of every entity in the world
+    pub fn entity_ptrs(&self) -> Vec<u32> {
+        (self.entity_array_start..self.entity_array_end).step_by(4).map(get_from_memory::<u32>).collect()
+    }
+
+    /// Whether `entity_ptr` is one of the entities in the world
+    pub fn contains_entity(&self, entity_ptr: u32) -> bool {
+        (self.entity_array_start..self.entity_array_end).step_by(4).any(|ptr| get_from_memory::<u32>(ptr) == entity_ptr)
+    }
+
+    /// Whether `entity_type_ptr` is one of the loaded entity types
+    pub fn contains_entity_type(&self, entity_type_ptr: u32) -> bool {
+        (self.entity_type_array_start..self.entity_type_array_end).step_by(4).any(|ptr| get_from_memory::<u32>(ptr) == entity_type_ptr)
+    }
+
     pub fn get_neighbour(&self, bftile: &BFTile, direction: Direction) -> Option<BFTile> {
         let x_offset: i32 = match direction {
             Direction::West => 0,
@@ -382,6 +474,7 @@ impl ZTWorldMgr {
     }
 }
 
+#[cfg(target_os = "windows")]
 #[detour_mod]
 pub mod hooks_ztworldmgr {
     use crate::util::save_to_memory;
@@ -460,8 +553,14 @@ pub mod hooks_ztworldmgr {
 }
 
 pub fn init() {
+    register_lua_functions();
+    #[cfg(target_os = "windows")]
+    unsafe { hooks_ztworldmgr::init_detours().unwrap() };
+}
+
+pub(crate) fn register_lua_functions() {
     // list_entities() - no args
-    lua_fn!("list_entities", "Lists all entities in the world", "list_entities()", || {
+    lua_fn!("list_entities", "Lists all entities in the world", "list_entities()", ReadWorld, || {
         match command_get_zt_world_mgr_entities(vec![]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
@@ -469,7 +568,7 @@ pub fn init() {
     });
 
     // list_entities_2() - no args
-    lua_fn!("list_entities_2", "Lists all entities in the world (alternate format)", "list_entities_2()", || {
+    lua_fn!("list_entities_2", "Lists all entities in the world (alternate format)", "list_entities_2()", ReadWorld, || {
         match command_get_zt_world_mgr_entities_2(vec![]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
@@ -477,7 +576,7 @@ pub fn init() {
     });
 
     // list_types() - no args
-    lua_fn!("list_types", "Lists all entity types in the world", "list_types()", || {
+    lua_fn!("list_types", "Lists all entity types in the world", "list_types()", ReadWorld, || {
         match command_get_zt_world_mgr_types(vec![]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
@@ -485,7 +584,7 @@ pub fn init() {
     });
 
     // get_zt_world_mgr() - no args
-    lua_fn!("get_zt_world_mgr", "Returns world manager details", "get_zt_world_mgr()", || {
+    lua_fn!("get_zt_world_mgr", "Returns world manager details", "get_zt_world_mgr()", DebugMemory, || {
         match command_get_zt_world_mgr(vec![]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
@@ -493,7 +592,7 @@ pub fn init() {
     });
 
     // get_types_summary() - no args
-    lua_fn!("get_types_summary", "Returns summary of all entity types", "get_types_summary()", || {
+    lua_fn!("get_types_summary", "Returns summary of all entity types", "get_types_summary()", ReadWorld, || {
         match command_zt_world_mgr_types_summary(vec![]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
@@ -501,7 +600,7 @@ pub fn init() {
     });
 
     // get_entity_vtable_entry(offset) - single string arg
-    lua_fn!("get_entity_vtable_entry", "Returns unique entity vtable entries at offset", "get_entity_vtable_entry(offset)", |offset: String| {
+    lua_fn!("get_entity_vtable_entry", "Returns unique entity vtable entries at offset", "get_entity_vtable_entry(offset)", DebugMemory, |offset: String| {
         match command_get_entity_unique_vtable_entries(vec![&offset]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
@@ -509,14 +608,34 @@ pub fn init() {
     });
 
     // get_entity_type_vtable_entry(offset) - single string arg
-    lua_fn!("get_entity_type_vtable_entry", "Returns unique entity type vtable entries at offset", "get_entity_type_vtable_entry(offset)", |offset: String| {
+    lua_fn!("get_entity_type_vtable_entry", "Returns unique entity type vtable entries at offset", "get_entity_type_vtable_entry(offset)", DebugMemory, |offset: String| {
         match command_get_entity_type_unique_vtable_entries(vec![&offset]) {
             Ok(result) => Ok((Some(result), None::<String>)),
             Err(e) => Ok((None::<String>, Some(e.to_string())))
         }
     });
 
-    unsafe { hooks_ztworldmgr::init_detours().unwrap() };
+    // world.entities([class]) - iterator over entity userdata
+    add_lua_function(
+        "world.entities",
+        "Iterates over entities in the world, optionally only those of a class such as \"Animal\"",
+        "for entity in world.entities([class]) do ... end",
+        Capability::ReadWorld,
+        LuaTypes::new().param::<Option<String>>("class").returns_lua(&["fun(): ZTEntity?"]),
+        |lua| {
+            lua.create_function(|lua, class: Option<String>| {
+                let entities = read_zt_world_mgr_from_global()
+                    .entity_ptrs()
+                    .into_iter()
+                    .map(ZTEntityRef::new)
+                    .filter(|entity| class.as_ref().is_none_or(|class| format!("{:?}", entity.class()).eq_ignore_ascii_case(class)))
+                    .collect();
+                userdata::iterator(lua, entities)
+            })
+            .unwrap()
+        },
+    )
+    .unwrap();
 }
 
 pub fn read_zt_entity_from_memory(zt_entity_ptr: u32) -> ZTEntity {
@@ -533,7 +652,7 @@ pub fn read_zt_entity_from_memory(zt_entity_ptr: u32) -> ZTEntity {
 }
 
 pub fn read_zt_world_mgr_from_global() -> ZTWorldMgr {
-    let zt_world_mgr_ptr = get_from_memory::<u32>(GLOBAL_ZTWORLDMGR_ADDRESS);
+    let zt_world_mgr_ptr = get_from_memory::<u32>(GLOBAL_ZTWORLDMGR_ADDRESS.get());
     get_from_memory::<ZTWorldMgr>(zt_world_mgr_ptr)
 }
 
@@ -756,4 +875,89 @@ pub fn get_entity_type_by_id(id: u32) -> u32 {
 
 // struct BFMap {
 //     padding: [u8; 0x5c],
-// }
\ No newline at end of file
+// }
+#[cfg(test)]
+mod tests {
+    use std::{
+        mem::{offset_of, size_of},
+        sync::Arc,
+    };
+
+    use super::*;
+    use crate::game_memory::{bytes_of, with_memory, FakeMemory};
+
+    /// A `size` x `size` tile array where each tile knows its position
+    fn fake_world(memory: &FakeMemory, size: u32) -> u32 {
+        let tile_array = memory.alloc((size * size * 0x8c) as usize);
+        for y in 0..size {
+            for x in 0..size {
+                memory.write(tile_array + (y * size + x) * 0x8c + 0x34, IVec3 { x: x as i32, y: y as i32, z: 0 });
+            }
+        }
+        let world_mgr = memory.alloc(size_of::<ZTWorldMgr>());
+        memory.write(world_mgr + offset_of!(ZTWorldMgr, map_x_size) as u32, [size, size]);
+        memory.write(world_mgr + offset_of!(ZTWorldMgr, tile_array) as u32, tile_array);
+        world_mgr
+    }
+
+    #[test]
+    fn test_get_neighbour() {
+        let memory = Arc::new(FakeMemory::new());
+        let world_mgr = fake_world(&memory, 3);
+        with_memory(memory, || {
+            let world_mgr = get_from_memory::<ZTWorldMgr>(world_mgr);
+            let centre = world_mgr.get_tile_from_pos(IVec3 { x: 1, y: 1, z: 0 }).unwrap();
+            let position = |direction| world_mgr.get_neighbour(&centre, direction).map(|tile| (tile.pos.x, tile.pos.y));
+            assert_eq!(position(Direction::North), Some((2, 1)));
+            assert_eq!(position(Direction::NorthEast), Some((2, 2)));
+            assert_eq!(position(Direction::West), Some((1, 0)));
+            assert_eq!(position(Direction::SouthWest), Some((0, 0)));
+
+            let corner = world_mgr.get_tile_from_pos(IVec3 { x: 0, y: 0, z: 0 }).unwrap();
+            assert!(world_mgr.get_neighbour(&corner, Direction::South).is_none());
+            assert!(world_mgr.get_neighbour(&corner, Direction::West).is_none());
+        });
+    }
+
+    #[test]
+    fn test_entity_ptrs() {
+        let memory = Arc::new(FakeMemory::new());
+        let world_mgr = fake_world(&memory, 1);
+        let entities = memory.alloc_value([0x1000u32, 0x2000, 0x3000]);
+        memory.write(world_mgr + offset_of!(ZTWorldMgr, entity_array_start) as u32, [entities, entities + 12, entities + 12]);
+        with_memory(memory, || {
+            assert_eq!(get_from_memory::<ZTWorldMgr>(world_mgr).entity_ptrs(), vec![0x1000, 0x2000, 0x3000]);
+            assert_eq!(get_from_memory::<ZTWorldMgr>(world_mgr).entity_count(), 3);
+        });
+    }
+
+    #[test]
+    fn test_removed_entity_raises_lua_error() {
+        openzt_detour::game_build::set_detected(&openzt_detour::game_build::KNOWN_BUILDS[0]);
+        let memory = Arc::new(FakeMemory::new());
+        let world_mgr = fake_world(&memory, 1);
+        memory.map(GLOBAL_ZTWORLDMGR_ADDRESS.get(), bytes_of(&world_mgr));
+        let entity_type = memory.alloc(0x200);
+        let entity = memory.alloc(size_of::<BFEntity>());
+        memory.write(entity + offset_of!(BFEntity, inner_class_ptr) as u32, entity_type);
+        let entities = memory.alloc_value([entity]);
+        memory.write(world_mgr + offset_of!(ZTWorldMgr, entity_array_start) as u32, [entities, entities + 4, entities + 4]);
+
+        with_memory(memory.clone(), || {
+            let lua = mlua::Lua::new();
+            lua.globals().set("entity", ZTEntityRef::new(entity)).unwrap();
+            assert!(lua.load("return entity.visible").eval::<mlua::Value>().is_ok());
+
+            // Entity types are checked against the world's list too, which is empty here
+            let err = lua.load("return entity:type().codename").eval::<mlua::Value>().unwrap_err();
+            assert!(err.to_string().contains(&format!("BFEntityType at {:#x} no longer exists", entity_type)), "{}", err);
+
+            // The game removes the entity while Lua still holds it
+            memory.write(world_mgr + offset_of!(ZTWorldMgr, entity_array_end) as u32, entities);
+            for code in ["return entity.visible", "return entity.name", "return tostring(entity)", "return entity:type()"] {
+                let err = lua.load(code).eval::<mlua::Value>().unwrap_err();
+                assert!(err.to_string().contains(&format!("ZTEntity at {:#x} no longer exists", entity)), "{}: {}", code, err);
+            }
+        });
+    }
+}